@@ -1,7 +1,9 @@
 //! Unicode Bidirectional Algorithm implementation
 
-use unicode_bidi::BidiInfo as UnicodeBidiInfo;
+use editor_core::selection::Affinity;
+use unicode_bidi::{ BidiInfo as UnicodeBidiInfo, Level };
 use serde::{ Deserialize, Serialize };
+use std::ops::Range;
 
 /// Text direction
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -38,39 +40,78 @@ pub struct BidiInfo {
     text: String,
     /// Base direction of the paragraph
     base_direction: Direction,
+    /// Paragraph level given by the caller, `None` to detect it (rules P2-P3)
+    default_level: Option<Level>,
 }
 
 impl BidiInfo {
     /// Create bidi info from text
+    ///
+    /// With a default direction the paragraph is resolved at that direction;
+    /// without one it takes the direction of its first strong character, or
+    /// left-to-right if it has none.
     pub fn new(text: &str, default_direction: Option<Direction>) -> Self {
-        let info = UnicodeBidiInfo::new(text, None);
-
-        // Determine base direction
-        let base_direction = if let Some(dir) = default_direction {
-            dir
-        } else {
-            // Auto-detect based on first strong directional character
-            if !info.paragraphs.is_empty() && info.paragraphs[0].level.is_ltr() {
-                Direction::LeftToRight
-            } else {
-                Direction::RightToLeft
+        let default_level = default_direction.map(|dir| {
+            if dir.is_ltr() { Level::ltr() } else { Level::rtl() }
+        });
+        let info = UnicodeBidiInfo::new(text, default_level);
+
+        // Empty text has no paragraphs to resolve, so fall back on the default
+        let base_direction = default_direction.unwrap_or_else(|| {
+            match info.paragraphs.first() {
+                Some(para) if para.level.is_rtl() => Direction::RightToLeft,
+                _ => Direction::LeftToRight,
             }
-        };
+        });
 
         Self {
             text: text.to_string(),
             base_direction,
+            default_level,
         }
     }
 
+    /// Run the Unicode algorithm at the paragraph's level
+    fn unicode_info(&self) -> UnicodeBidiInfo<'_> {
+        UnicodeBidiInfo::new(&self.text, self.default_level)
+    }
+
     /// Get the base direction
     pub fn base_direction(&self) -> Direction {
         self.base_direction
     }
 
+    /// Get the resolved paragraph embedding level
+    pub fn paragraph_level(&self) -> u8 {
+        let info = self.unicode_info();
+        let default = if self.base_direction.is_rtl() { 1 } else { 0 };
+        info.paragraphs.first().map_or(default, |para| para.level.number())
+    }
+
+    /// Resolved embedding level of each character, laid out as one line
+    pub fn levels(&self) -> Vec<u8> {
+        let info = self.unicode_info();
+        let Some(para) = info.paragraphs.first() else {
+            return Vec::new();
+        };
+        info.reordered_levels_per_char(para, para.range.clone())
+            .into_iter()
+            .map(|level| level.number())
+            .collect()
+    }
+
+    /// Character indices in visual order, left to right
+    pub fn visual_order(&self) -> Vec<usize> {
+        let info = self.unicode_info();
+        let Some(para) = info.paragraphs.first() else {
+            return Vec::new();
+        };
+        UnicodeBidiInfo::reorder_visual(&info.reordered_levels_per_char(para, para.range.clone()))
+    }
+
     /// Get the visual runs for display
     pub fn visual_runs(&self, paragraph_range: std::ops::Range<usize>) -> Vec<VisualRun> {
-        let info = UnicodeBidiInfo::new(&self.text, None);
+        let info = self.unicode_info();
 
         if info.paragraphs.is_empty() {
             return Vec::new();
@@ -124,7 +165,7 @@ impl BidiInfo {
 
     /// Convert logical position to visual position
     pub fn logical_to_visual(&self, logical_pos: usize, text_len: usize) -> usize {
-        let info = UnicodeBidiInfo::new(&self.text, None);
+        let info = self.unicode_info();
 
         if info.paragraphs.is_empty() {
             return logical_pos;
@@ -152,7 +193,7 @@ impl BidiInfo {
 
     /// Convert visual position to logical position
     pub fn visual_to_logical(&self, visual_pos: usize, text_len: usize) -> usize {
-        let info = UnicodeBidiInfo::new(&self.text, None);
+        let info = self.unicode_info();
 
         if info.paragraphs.is_empty() {
             return visual_pos;
@@ -204,6 +245,21 @@ impl BidiParagraph {
         self.bidi_info.base_direction()
     }
 
+    /// Get the resolved paragraph embedding level
+    pub fn paragraph_level(&self) -> u8 {
+        self.bidi_info.paragraph_level()
+    }
+
+    /// Resolved embedding level of each character
+    pub fn levels(&self) -> Vec<u8> {
+        self.bidi_info.levels()
+    }
+
+    /// Character indices in visual order, left to right
+    pub fn visual_order(&self) -> Vec<usize> {
+        self.bidi_info.visual_order()
+    }
+
     /// Get visual runs for rendering
     pub fn visual_runs(&self) -> Vec<VisualRun> {
         self.bidi_info.visual_runs(0..self.text.len())
@@ -218,6 +274,71 @@ impl BidiParagraph {
     pub fn visual_to_logical(&self, visual_pos: usize) -> usize {
         self.bidi_info.visual_to_logical(visual_pos, self.text.len())
     }
+
+    /// Visual character ranges covered by a logical selection, left to right
+    ///
+    /// Indices are in characters, as in [`visual_order`](Self::visual_order).
+    /// A selection spanning runs of both directions can cover several
+    /// separate visual ranges; one within a single run covers one range.
+    pub fn visual_selection_rects(
+        &self,
+        logical_start: usize,
+        logical_end: usize
+    ) -> Vec<Range<usize>> {
+        let selected = logical_start..logical_end;
+        let mut rects: Vec<Range<usize>> = Vec::new();
+        for (visual, logical) in self.visual_order().into_iter().enumerate() {
+            if !selected.contains(&logical) {
+                continue;
+            }
+            match rects.last_mut() {
+                Some(rect) if rect.end == visual => {
+                    rect.end += 1;
+                }
+                _ => rects.push(visual..visual + 1),
+            }
+        }
+        rects
+    }
+
+    /// Visual caret position for a logical caret position
+    ///
+    /// Positions are gaps between characters, in characters: visual position
+    /// `x` is left of the character drawn at visual index `x`. Where runs of
+    /// different directions meet, one logical position has two visual
+    /// positions; `Upstream` keeps the caret against the character before it,
+    /// `Downstream` against the character after it.
+    pub fn visual_caret_position(&self, logical_pos: usize, affinity: Affinity) -> usize {
+        let levels = self.levels();
+        if levels.is_empty() {
+            return 0;
+        }
+        let logical_pos = logical_pos.min(levels.len());
+
+        let mut visual_index = vec![0; levels.len()];
+        for (visual, logical) in self.visual_order().into_iter().enumerate() {
+            visual_index[logical] = visual;
+        }
+
+        // Only one side exists at the ends of the paragraph
+        let upstream = match affinity {
+            Affinity::Upstream => logical_pos > 0,
+            Affinity::Downstream => logical_pos == levels.len(),
+        };
+        let (index, trailing_edge) = if upstream {
+            (logical_pos - 1, true)
+        } else {
+            (logical_pos, false)
+        };
+
+        // The trailing edge of a left-to-right character is its right side
+        let is_ltr = levels[index].is_multiple_of(2);
+        if is_ltr == trailing_edge {
+            visual_index[index] + 1
+        } else {
+            visual_index[index]
+        }
+    }
 }
 
 #[cfg(test)]
@@ -240,10 +361,68 @@ mod tests {
         assert!(para.base_direction().is_rtl());
     }
 
+    #[test]
+    fn test_empty_text_keeps_default_direction() {
+        let para = BidiParagraph::new(String::new(), Some(Direction::RightToLeft));
+        assert!(para.base_direction().is_rtl());
+        assert_eq!(para.paragraph_level(), 1);
+
+        let para = BidiParagraph::new(String::new(), None);
+        assert!(para.base_direction().is_ltr());
+    }
+
     #[test]
     fn test_mixed_text() {
         let para = BidiParagraph::new("Hello שלום World".to_string(), None);
         let runs = para.visual_runs();
         assert!(runs.len() > 1); // Should have multiple runs
     }
+
+    #[test]
+    fn test_visual_selection_rects() {
+        // Visual order: a b c ' ' ג ב א ' ' d e f
+        let para = BidiParagraph::new("abc אבג def".to_string(), None);
+
+        // Across the boundary: "bc " stays in place, א is drawn after ג and ב
+        assert_eq!(para.visual_selection_rects(1, 5), vec![1..4, 6..7]);
+        assert_eq!(para.visual_selection_rects(5, 9), vec![4..6, 7..9]);
+
+        // Within the RTL run: one range, reversed
+        assert_eq!(para.visual_selection_rects(4, 7), vec![4..7]);
+        assert_eq!(para.visual_selection_rects(4, 5), vec![6..7]);
+
+        assert_eq!(para.visual_selection_rects(0, 11), vec![0..11]);
+        assert!(para.visual_selection_rects(3, 3).is_empty());
+    }
+
+    #[test]
+    fn test_visual_caret_affinity() {
+        // Visual order: a b c ' ' ג ב א ' ' d e f
+        let para = BidiParagraph::new("abc אבג def".to_string(), None);
+
+        // Between ' ' and א: after the space, or right of א
+        assert_eq!(para.visual_caret_position(4, Affinity::Upstream), 4);
+        assert_eq!(para.visual_caret_position(4, Affinity::Downstream), 7);
+
+        // Between ג and ' ': left of ג, or before the space
+        assert_eq!(para.visual_caret_position(7, Affinity::Upstream), 4);
+        assert_eq!(para.visual_caret_position(7, Affinity::Downstream), 7);
+
+        // Inside a run both affinities agree
+        for pos in [2, 5] {
+            assert_eq!(
+                para.visual_caret_position(pos, Affinity::Upstream),
+                para.visual_caret_position(pos, Affinity::Downstream)
+            );
+        }
+        assert_eq!(para.visual_caret_position(5, Affinity::Downstream), 6);
+
+        // Paragraph ends have one side only
+        assert_eq!(para.visual_caret_position(0, Affinity::Upstream), 0);
+        assert_eq!(para.visual_caret_position(11, Affinity::Downstream), 11);
+
+        let rtl = BidiParagraph::new("אבג".to_string(), None);
+        assert_eq!(rtl.visual_caret_position(0, Affinity::Downstream), 3);
+        assert_eq!(rtl.visual_caret_position(3, Affinity::Upstream), 0);
+    }
 }
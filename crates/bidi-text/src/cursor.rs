@@ -24,6 +24,40 @@ pub struct TextPosition {
     pub column: usize,
 }
 
+/// Folded line regions, as seen by vertical cursor movement
+///
+/// Each fold is `(header, end)`: the header line stays visible while lines
+/// `header + 1..=end` are hidden.
+#[derive(Debug, Clone, Default)]
+pub struct FoldModel {
+    folds: Vec<(usize, usize)>,
+}
+
+impl FoldModel {
+    pub fn new(folds: Vec<(usize, usize)>) -> Self {
+        Self { folds }
+    }
+
+    /// Folded regions as `(header, end)` pairs
+    pub fn folds(&self) -> &[(usize, usize)] {
+        &self.folds
+    }
+
+    /// Check if a line is hidden inside a fold
+    pub fn is_hidden(&self, line: usize) -> bool {
+        self.hiding_fold(line).is_some()
+    }
+
+    /// Outermost fold hiding a line, if any
+    fn hiding_fold(&self, line: usize) -> Option<(usize, usize)> {
+        self.folds
+            .iter()
+            .filter(|(header, end)| line > *header && line <= *end)
+            .min_by_key(|(header, _)| *header)
+            .copied()
+    }
+}
+
 /// Cursor movement in bidirectional text
 pub struct CursorMovement;
 
@@ -39,34 +73,10 @@ impl CursorMovement {
             return Ok(0);
         }
 
-        let graphemes: Vec<&str> = text.graphemes(true).collect();
-        let grapheme_count = graphemes.len();
-
         match direction {
-            MovementDirection::Left => {
-                if logical_pos == 0 {
-                    return Ok(0);
-                }
-
-                // Move by grapheme clusters
-                let grapheme_pos = Self::char_to_grapheme_pos(text, logical_pos);
-                if grapheme_pos == 0 {
-                    Ok(0)
-                } else {
-                    let new_grapheme_pos = grapheme_pos - 1;
-                    Ok(Self::grapheme_to_char_pos(text, new_grapheme_pos))
-                }
-            }
+            MovementDirection::Left => Ok(Self::prev_grapheme_boundary(text, logical_pos)),
 
-            MovementDirection::Right => {
-                let grapheme_pos = Self::char_to_grapheme_pos(text, logical_pos);
-                if grapheme_pos >= grapheme_count {
-                    Ok(text.len())
-                } else {
-                    let new_grapheme_pos = grapheme_pos + 1;
-                    Ok(Self::grapheme_to_char_pos(text, new_grapheme_pos))
-                }
-            }
+            MovementDirection::Right => Ok(Self::next_grapheme_boundary(text, logical_pos)),
 
             MovementDirection::Home => {
                 // Move to start (respecting indentation on first press)
@@ -105,16 +115,41 @@ impl CursorMovement {
 
     /// Move cursor in logical direction (for navigation like Ctrl+Left/Right)
     pub fn move_logical(text: &str, logical_pos: usize, forward: bool) -> usize {
-        let graphemes: Vec<&str> = text.graphemes(true).collect();
-        let grapheme_pos = Self::char_to_grapheme_pos(text, logical_pos);
-        
-        let new_grapheme_pos = if forward {
-            (grapheme_pos + 1).min(graphemes.len())
+        if forward {
+            Self::next_grapheme_boundary(text, logical_pos)
         } else {
-            grapheme_pos.saturating_sub(1)
-        };
-        
-        Self::grapheme_to_char_pos(text, new_grapheme_pos)
+            Self::prev_grapheme_boundary(text, logical_pos)
+        }
+    }
+
+    /// Closest grapheme cluster boundary before `pos`, in characters
+    ///
+    /// Deleting backwards from `pos` to here removes a whole cluster, such as
+    /// a flag, an emoji ZWJ sequence or a Hangul syllable written as jamo.
+    pub fn prev_grapheme_boundary(text: &str, pos: usize) -> usize {
+        Self::grapheme_boundaries(text)
+            .take_while(|boundary| *boundary < pos)
+            .last()
+            .unwrap_or(0)
+    }
+
+    /// Closest grapheme cluster boundary after `pos`, in characters
+    ///
+    /// Positions at or past the end of the text give the end.
+    pub fn next_grapheme_boundary(text: &str, pos: usize) -> usize {
+        Self::grapheme_boundaries(text)
+            .find(|boundary| *boundary > pos)
+            .unwrap_or_else(|| text.chars().count())
+    }
+
+    /// Character offsets of the grapheme cluster boundaries, including 0
+    fn grapheme_boundaries(text: &str) -> impl Iterator<Item = usize> + '_ {
+        std::iter::once(0).chain(
+            text.graphemes(true).scan(0, |end, grapheme| {
+                *end += grapheme.chars().count();
+                Some(*end)
+            })
+        )
     }
 
     /// Convert character position to grapheme position
@@ -222,6 +257,52 @@ impl CursorMovement {
         }
     }
 
+    /// Move cursor vertically, skipping lines hidden inside folds
+    ///
+    /// Fold headers are visible and can be landed on; their interiors are
+    /// stepped over. With no visible line in the direction of movement the
+    /// cursor goes to the start or end of the current line.
+    pub fn move_vertical_folded(
+        fold: &FoldModel,
+        lines: &[String],
+        line: usize,
+        column: usize,
+        direction: MovementDirection,
+        sticky: Option<usize>
+    ) -> TextPosition {
+        let desired_column = sticky.unwrap_or(column);
+
+        let target = match direction {
+            MovementDirection::Up => {
+                let mut target = line.checked_sub(1);
+                while let Some((header, _)) = target.and_then(|t| fold.hiding_fold(t)) {
+                    target = Some(header);
+                }
+                target
+            }
+            MovementDirection::Down => {
+                let mut target = Some(line + 1).filter(|t| *t < lines.len());
+                while let Some((_, end)) = target.and_then(|t| fold.hiding_fold(t)) {
+                    target = Some(end + 1).filter(|t| *t < lines.len());
+                }
+                target
+            }
+            _ => None,
+        };
+
+        match target {
+            Some(target) =>
+                TextPosition {
+                    line: target,
+                    column: Self::adjust_column_for_line(&lines[target], desired_column),
+                },
+            None if direction == MovementDirection::Down && line < lines.len() =>
+                TextPosition { line, column: lines[line].graphemes(true).count() },
+            None if direction == MovementDirection::Up => TextPosition { line, column: 0 },
+            None => TextPosition { line, column },
+        }
+    }
+
     /// Adjust column to fit within target line
     fn adjust_column_for_line(line: &str, desired_column: usize) -> usize {
         let line_length = line.graphemes(true).count();
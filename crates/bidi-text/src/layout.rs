@@ -2,6 +2,9 @@
 
 use crate::algorithm::{ Direction, VisualRun as BidiVisualRun };
 use serde::{ Deserialize, Serialize };
+use std::ops::Range;
+use unicode_bidi::{ bidi_class, BidiClass, BidiInfo as UnicodeBidiInfo, Level };
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Represents a visual run of text with rendering information
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -75,12 +78,75 @@ impl VisualLine {
     }
 }
 
+/// ARABIC-INDIC DIGIT ZERO; the other digits follow it
+const ARABIC_INDIC_ZERO: u32 = 0x0660;
+
+/// How European digits are displayed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DigitSubstitution {
+    /// Always display European digits
+    #[default]
+    None,
+    /// Always display Arabic-Indic digits
+    National,
+    /// Display Arabic-Indic digits after Arabic text
+    Contextual,
+}
+
+/// Display form of `text` with digits substituted
+///
+/// Under `Contextual`, a digit is substituted when the closest strong
+/// character before it is Arabic. Only the displayed text changes; each
+/// digit stays one character, so character offsets are unaffected.
+pub fn substitute_digits(text: &str, mode: DigitSubstitution) -> String {
+    substitute_digits_in(text, 0..text.len(), mode)
+}
+
+/// Display form of `paragraph[range]`, taking context from the whole paragraph
+fn substitute_digits_in(paragraph: &str, range: Range<usize>, mode: DigitSubstitution) -> String {
+    if mode == DigitSubstitution::None {
+        return paragraph[range].to_string();
+    }
+
+    let mut after_arabic = false;
+    let mut display = String::with_capacity(range.len());
+    for (offset, c) in paragraph[..range.end].char_indices() {
+        match bidi_class(c) {
+            BidiClass::AL => {
+                after_arabic = true;
+            }
+            BidiClass::L | BidiClass::R => {
+                after_arabic = false;
+            }
+            _ => {}
+        }
+        if offset < range.start {
+            continue;
+        }
+
+        let substitute = match mode {
+            DigitSubstitution::None => false,
+            DigitSubstitution::National => true,
+            DigitSubstitution::Contextual => after_arabic,
+        };
+        if substitute && c.is_ascii_digit() {
+            let digit = (c as u32) - ('0' as u32);
+            display.push(char::from_u32(ARABIC_INDIC_ZERO + digit).unwrap_or(c));
+        } else {
+            display.push(c);
+        }
+    }
+    display
+}
+
 /// Layout engine for bidirectional text
 pub struct BidiLayoutEngine {
     /// Line height in pixels
     line_height: f32,
     /// Font size in pixels
     font_size: f32,
+    /// How digits in laid out runs are displayed
+    digit_substitution: DigitSubstitution,
 }
 
 impl BidiLayoutEngine {
@@ -88,9 +154,16 @@ impl BidiLayoutEngine {
         Self {
             line_height,
             font_size,
+            digit_substitution: DigitSubstitution::None,
         }
     }
 
+    /// Display digits in laid out runs according to `mode`
+    pub fn with_digit_substitution(mut self, mode: DigitSubstitution) -> Self {
+        self.digit_substitution = mode;
+        self
+    }
+
     /// Calculate visual layout for a line of text
     pub fn layout_line(
         &self,
@@ -102,12 +175,12 @@ impl BidiLayoutEngine {
 
         let mut x_offset = 0.0;
         for run in bidi_runs {
-            let run_text = &text[run.logical_range.clone()];
+            let run_text = substitute_digits_in(text, run.logical_range, self.digit_substitution);
 
             // Simple width calculation (would use proper text shaping in real impl)
             let width = (run_text.len() as f32) * self.font_size * 0.6;
 
-            let mut visual_run = VisualRun::new(run_text.to_string(), run.direction);
+            let mut visual_run = VisualRun::new(run_text, run.direction);
             visual_run.x_offset = x_offset;
             visual_run.width = width;
 
@@ -118,6 +191,126 @@ impl BidiLayoutEngine {
         visual_line.calculate_width();
         visual_line
     }
+
+    /// Lay out a whole document into wrapped, visually ordered lines
+    ///
+    /// Each `\n`-separated line is its own bidi paragraph, resolved with the
+    /// `base` direction or, if `None`, the paragraph's first strong character.
+    /// Paragraphs wrap greedily to `max_width`, breaking after whitespace when
+    /// possible and between graphemes otherwise; whitespace at a break hangs
+    /// past the edge. `measure` gives the pixel width of a piece of text.
+    ///
+    /// Lines are returned in document order, with `char_range` in characters
+    /// of the logical line and runs in left-to-right display order.
+    pub fn layout_document(
+        &self,
+        text: &str,
+        max_width: f32,
+        base: Option<Direction>,
+        measure: impl Fn(&str) -> f32
+    ) -> Vec<VisualLine> {
+        let level = base.map(|direction| {
+            if direction.is_rtl() { Level::rtl() } else { Level::ltr() }
+        });
+
+        let mut lines = Vec::new();
+        for (logical_line, paragraph) in text.split('\n').enumerate() {
+            let paragraph = paragraph.strip_suffix('\r').unwrap_or(paragraph);
+            let info = UnicodeBidiInfo::new(paragraph, level);
+
+            for (index, range) in wrap(paragraph, max_width, &measure).into_iter().enumerate() {
+                let char_start = paragraph[..range.start].chars().count();
+                let char_end = char_start + paragraph[range.clone()].chars().count();
+                let mut line = VisualLine::new(logical_line, index, char_start..char_end);
+                line.pixel_width = measure(paragraph[range.clone()].trim_end());
+                line.baseline_y = (lines.len() as f32) * self.line_height + self.font_size;
+
+                if let Some(para) = info.paragraphs.first() {
+                    let (levels, runs) = info.visual_runs(para, range);
+                    let mut x_offset = 0.0;
+                    for run in runs {
+                        let direction = if levels[run.start].is_rtl() {
+                            Direction::RightToLeft
+                        } else {
+                            Direction::LeftToRight
+                        };
+                        let run_text = substitute_digits_in(
+                            paragraph,
+                            run,
+                            self.digit_substitution
+                        );
+
+                        let mut visual_run = VisualRun::new(run_text, direction);
+                        visual_run.x_offset = x_offset;
+                        visual_run.width = measure(&visual_run.text);
+                        x_offset += visual_run.width;
+                        line.bidi_runs.push(visual_run);
+                    }
+                }
+
+                lines.push(line);
+            }
+        }
+        lines
+    }
+}
+
+/// Split a paragraph into byte ranges no wider than `max_width`
+///
+/// A single grapheme wider than `max_width` still gets a line of its own.
+/// An empty paragraph yields one empty range.
+fn wrap(paragraph: &str, max_width: f32, measure: &impl Fn(&str) -> f32) -> Vec<Range<usize>> {
+    let graphemes: Vec<(usize, &str)> = paragraph.grapheme_indices(true).collect();
+    let widths: Vec<(f32, bool)> = graphemes
+        .iter()
+        .map(|(_, grapheme)| (measure(grapheme), grapheme.chars().all(char::is_whitespace)))
+        .collect();
+    let offset = |index: usize| {
+        graphemes.get(index).map_or(paragraph.len(), |(offset, _)| *offset)
+    };
+
+    wrap_graphemes(&widths, max_width)
+        .into_iter()
+        .map(|line| offset(line.start)..offset(line.end))
+        .collect()
+}
+
+/// Greedily wrap a line of graphemes to `max_width`
+///
+/// Each item is a grapheme's width and whether it is whitespace. Lines
+/// break after whitespace when possible and between graphemes otherwise;
+/// whitespace at a break hangs past the edge, and a grapheme wider than
+/// `max_width` still gets a line of its own. Returns the grapheme range of
+/// each line, or one empty range for no graphemes.
+pub fn wrap_graphemes(graphemes: &[(f32, bool)], max_width: f32) -> Vec<Range<usize>> {
+    let mut lines = Vec::new();
+    let mut line_start = 0;
+    let mut line_width = 0.0;
+    let mut last_break = None;
+
+    for (index, &(width, whitespace)) in graphemes.iter().enumerate() {
+        if whitespace {
+            line_width += width;
+            last_break = Some(index + 1);
+            continue;
+        }
+
+        if line_width + width > max_width && index > line_start {
+            let at = last_break.filter(|at| *at > line_start).unwrap_or(index);
+            lines.push(line_start..at);
+            line_start = at;
+            line_width = graphemes[at..index]
+                .iter()
+                .map(|(width, _)| width)
+                .sum();
+            last_break = None;
+        }
+
+        line_width += width;
+    }
+
+    lines.push(line_start..graphemes.len());
+    lines
 }
 
 #[cfg(test)]
@@ -135,4 +328,80 @@ mod tests {
         assert!(visual_line.pixel_width > 0.0);
         assert_eq!(visual_line.bidi_runs.len(), 1);
     }
+
+    #[test]
+    fn test_layout_document_mixed_paragraphs() {
+        let engine = BidiLayoutEngine::new(14.0, 20.0);
+        let text = "Hello שלום world\nשלום עולם abc";
+
+        // 10px per character: the first paragraph needs 160px and wraps
+        let lines = engine.layout_document(text, 140.0, None, |s| (s.chars().count() as f32) * 10.0);
+
+        assert_eq!(lines.len(), 3);
+        let positions: Vec<(usize, usize)> = lines
+            .iter()
+            .map(|line| (line.logical_line, line.visual_line_index))
+            .collect();
+        assert_eq!(positions, vec![(0, 0), (0, 1), (1, 0)]);
+        assert_eq!(lines[0].char_range, 0..11);
+        assert_eq!(lines[1].char_range, 11..16);
+        assert!(lines[1].baseline_y > lines[0].baseline_y);
+
+        // LTR paragraph: Latin run, then the Hebrew word
+        let directions: Vec<Direction> = lines[0].bidi_runs
+            .iter()
+            .map(|run| run.direction)
+            .collect();
+        assert_eq!(directions[..2], [Direction::LeftToRight, Direction::RightToLeft]);
+        assert_eq!(lines[0].bidi_runs[1].text, "שלום");
+        assert!(lines[1].bidi_runs.iter().all(|run| run.direction.is_ltr()));
+
+        // RTL paragraph: the embedded Latin run is displayed leftmost
+        let runs = &lines[2].bidi_runs;
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].text, "abc");
+        assert_eq!(runs[0].direction, Direction::LeftToRight);
+        assert_eq!(runs[1].direction, Direction::RightToLeft);
+        assert!(runs[0].x_offset < runs[1].x_offset);
+    }
+
+    #[test]
+    fn test_layout_document_base_direction() {
+        let engine = BidiLayoutEngine::new(14.0, 20.0);
+        let lines = engine.layout_document("abc\n", 100.0, Some(Direction::RightToLeft), |s| {
+            (s.len() as f32) * 8.0
+        });
+
+        // A trailing newline leaves an empty final line
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].bidi_runs.is_empty());
+        assert_eq!(lines[0].bidi_runs[0].direction, Direction::LeftToRight);
+        assert_eq!(lines[0].pixel_width, 24.0);
+    }
+
+    #[test]
+    fn test_contextual_digit_substitution() {
+        let mode = DigitSubstitution::Contextual;
+
+        // After Arabic text digits are substituted, after Latin text they are not
+        assert_eq!(substitute_digits("العدد 42", mode), "العدد ٤٢");
+        assert_eq!(substitute_digits("count 42", mode), "count 42");
+        assert_eq!(substitute_digits("abc 1 عربي 2 def 3", mode), "abc 1 عربي ٢ def 3");
+        assert_eq!(substitute_digits("12", mode), "12");
+
+        assert_eq!(substitute_digits("count 42", DigitSubstitution::National), "count ٤٢");
+        assert_eq!(substitute_digits("العدد 42", DigitSubstitution::None), "العدد 42");
+
+        // Runs take their context from the whole paragraph
+        let engine = BidiLayoutEngine::new(14.0, 20.0).with_digit_substitution(mode);
+        let lines = engine.layout_document("abc العدد 42", 500.0, None, |s| {
+            (s.chars().count() as f32) * 10.0
+        });
+        let texts: Vec<&str> = lines[0].bidi_runs
+            .iter()
+            .map(|run| run.text.as_str())
+            .collect();
+        assert_eq!(texts.concat().chars().filter(|c| *c == '٤' || *c == '٢').count(), 2);
+        assert!(!texts.concat().contains("42"));
+    }
 }
@@ -5,8 +5,15 @@ pub mod layout;
 pub mod cursor;
 
 pub use algorithm::{ BidiParagraph, Direction, BidiInfo };
-pub use layout::{ VisualRun, VisualLine, BidiLayoutEngine };
-pub use cursor::{ CursorMovement, MovementDirection, TextPosition };
+pub use layout::{
+    VisualRun,
+    VisualLine,
+    BidiLayoutEngine,
+    DigitSubstitution,
+    substitute_digits,
+    wrap_graphemes,
+};
+pub use cursor::{ CursorMovement, FoldModel, MovementDirection, TextPosition };
 
 /// Common error types for bidi text processing
 #[derive(Debug, thiserror::Error)]
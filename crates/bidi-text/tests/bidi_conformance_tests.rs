@@ -0,0 +1,122 @@
+//! Conformance tests against cases in the format of BidiCharacterTest.txt
+
+use bidi_text::{ BidiParagraph, Direction };
+
+/// Hand-written cases, one group per rule
+const RULE_CASES: &str = include_str!("data/bidi_rule_cases.txt");
+
+/// Where the Unicode Character Database's BidiCharacterTest.txt goes, as
+/// published with its header and unchanged
+const UCD_TEST_FILE: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/tests/data/BidiCharacterTest.txt"
+);
+
+/// One line of the fixture
+struct Case {
+    line: usize,
+    text: String,
+    direction: Option<Direction>,
+    paragraph_level: u8,
+    /// Expected level of each character, `None` for characters removed by X9
+    levels: Vec<Option<u8>>,
+    order: Vec<usize>,
+}
+
+fn parse_cases(data: &str) -> Vec<Case> {
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|(index, line)| {
+            let fields: Vec<&str> = line.split(';').collect();
+            assert_eq!(fields.len(), 5, "line {}: expected 5 fields", index + 1);
+
+            let text = fields[0]
+                .split_whitespace()
+                .map(|hex| char::from_u32(u32::from_str_radix(hex, 16).unwrap()).unwrap())
+                .collect();
+            let direction = match fields[1] {
+                "0" => Some(Direction::LeftToRight),
+                "1" => Some(Direction::RightToLeft),
+                _ => None,
+            };
+            let levels = fields[3]
+                .split_whitespace()
+                .map(|level| level.parse().ok())
+                .collect();
+            let order = fields[4]
+                .split_whitespace()
+                .map(|index| index.parse().unwrap())
+                .collect();
+
+            Case {
+                line: index + 1,
+                text,
+                direction,
+                paragraph_level: fields[2].parse().unwrap(),
+                levels,
+                order,
+            }
+        })
+        .collect()
+}
+
+/// Differences between the paragraph's resolution and the expected one
+fn check(case: &Case) -> Vec<String> {
+    let para = BidiParagraph::new(case.text.clone(), case.direction);
+    let mut failures = Vec::new();
+
+    if para.paragraph_level() != case.paragraph_level {
+        failures.push(
+            format!("paragraph level {}, expected {}", para.paragraph_level(), case.paragraph_level)
+        );
+    }
+
+    let levels: Vec<Option<u8>> = para
+        .levels()
+        .into_iter()
+        .zip(&case.levels)
+        .map(|(level, expected)| expected.map(|_| level))
+        .collect();
+    if levels != case.levels {
+        failures.push(format!("levels {:?}, expected {:?}", levels, case.levels));
+    }
+
+    let order: Vec<usize> = para
+        .visual_order()
+        .into_iter()
+        .filter(|index| case.levels.get(*index).is_some_and(Option::is_some))
+        .collect();
+    if order != case.order {
+        failures.push(format!("order {:?}, expected {:?}", order, case.order));
+    }
+
+    failures
+}
+
+/// Check every case, panicking with a list of the failures
+fn check_all(cases: &[Case]) {
+    let failures: Vec<String> = cases
+        .iter()
+        .flat_map(|case| {
+            check(case)
+                .into_iter()
+                .map(move |failure| format!("line {}: {}", case.line, failure))
+        })
+        .collect();
+    assert!(failures.is_empty(), "{} failures:\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+fn test_bidi_rule_cases() {
+    let cases = parse_cases(RULE_CASES);
+    assert!(cases.len() > 40);
+    check_all(&cases);
+}
+
+#[test]
+#[ignore = "needs the UCD BidiCharacterTest.txt in tests/data"]
+fn test_ucd_bidi_character_test() {
+    let data = std::fs::read_to_string(UCD_TEST_FILE).expect("BidiCharacterTest.txt is missing");
+    check_all(&parse_cases(&data));
+}
@@ -1,6 +1,6 @@
 //! Tests for bidirectional cursor movement
 
-use bidi_text::{ BidiParagraph, CursorMovement, MovementDirection };
+use bidi_text::{ BidiParagraph, CursorMovement, FoldModel, MovementDirection, TextPosition };
 
 #[test]
 fn test_ltr_movement() {
@@ -153,3 +153,108 @@ fn test_logical_movement() {
     let pos = CursorMovement::move_logical(text, text.len(), true);
     assert_eq!(pos, text.len());
 }
+
+#[test]
+fn test_vertical_movement_skips_folded_lines() {
+    let lines: Vec<String> = vec![
+        "intro",
+        "before fold",
+        "#let f() = {",
+        "  body",
+        "}",
+        "after fold",
+        "end"
+    ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let fold = FoldModel::new(vec![(2, 4)]);
+
+    // Down from the line before the fold lands on its header
+    let pos = CursorMovement::move_vertical_folded(
+        &fold,
+        &lines,
+        1,
+        3,
+        MovementDirection::Down,
+        None
+    );
+    assert_eq!(pos, TextPosition { line: 2, column: 3 });
+
+    // The next Down skips the folded interior
+    let pos = CursorMovement::move_vertical_folded(
+        &fold,
+        &lines,
+        2,
+        3,
+        MovementDirection::Down,
+        None
+    );
+    assert_eq!(pos, TextPosition { line: 5, column: 3 });
+
+    // Up from below the fold returns to the header
+    let pos = CursorMovement::move_vertical_folded(
+        &fold,
+        &lines,
+        5,
+        8,
+        MovementDirection::Up,
+        Some(10)
+    );
+    assert_eq!(pos, TextPosition { line: 2, column: 10 });
+}
+
+#[test]
+fn test_vertical_movement_fold_at_end() {
+    let lines: Vec<String> = vec!["a", "#block[", "  b", "]"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let fold = FoldModel::new(vec![(1, 3)]);
+
+    // No visible line below the header: move to its end
+    let pos = CursorMovement::move_vertical_folded(
+        &fold,
+        &lines,
+        1,
+        0,
+        MovementDirection::Down,
+        None
+    );
+    assert_eq!(pos, TextPosition { line: 1, column: 7 });
+}
+
+#[test]
+fn test_movement_steps_over_grapheme_clusters() {
+    // 🇮🇱 is two regional indicators; the family emoji is five characters
+    // joined by ZWJ; the Hangul syllable is written as three jamo
+    let text = "a🇮🇱👨\u{200d}👩\u{200d}👧\u{1112}\u{1161}\u{11ab}b";
+    let boundaries = [0, 1, 3, 8, 11, 12];
+    let para = BidiParagraph::new(text.to_string(), None);
+
+    for step in boundaries.windows(2) {
+        let right = CursorMovement::move_visual(&para, step[0], MovementDirection::Right).unwrap();
+        assert_eq!(right, step[1]);
+        let left = CursorMovement::move_visual(&para, step[1], MovementDirection::Left).unwrap();
+        assert_eq!(left, step[0]);
+    }
+
+    // Positions inside a cluster snap to its edges
+    assert_eq!(CursorMovement::prev_grapheme_boundary(text, 2), 1);
+    assert_eq!(CursorMovement::next_grapheme_boundary(text, 2), 3);
+    assert_eq!(CursorMovement::next_grapheme_boundary(text, 5), 8);
+
+    // Deleting backwards from after the flag removes both indicators
+    let start = CursorMovement::prev_grapheme_boundary(text, 3);
+    let remaining: String = text
+        .chars()
+        .enumerate()
+        .filter(|(index, _)| !(start..3).contains(index))
+        .map(|(_, c)| c)
+        .collect();
+    assert!(remaining.starts_with("a👨"));
+
+    assert_eq!(CursorMovement::prev_grapheme_boundary(text, 0), 0);
+    assert_eq!(CursorMovement::next_grapheme_boundary(text, 12), 12);
+    assert_eq!(CursorMovement::move_logical(text, 3, true), 8);
+}
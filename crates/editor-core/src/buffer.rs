@@ -1,16 +1,23 @@
 //! Text buffer implementation using rope data structure
 
 pub mod word_boundaries;
+mod backup;
+#[cfg(feature = "syntax")]
+mod syntax;
 
 use crate::{ EditorError, Result, Version };
-use crate::selection::Position;
-use crate::operations::{ EditOperation, OperationType, UndoHistory };
-use ropey::Rope;
+use crate::selection::{ Position, Selection };
+use crate::operations::{ EditOperation, OperationType, UndoHistory, UndoLink };
+use crate::diff::diff_slices;
+use ropey::{ Rope, RopeSlice };
 use serde::{ Deserialize, Serialize };
+use std::borrow::Cow;
+use std::ops::Range;
 use std::path::PathBuf;
 use unicode_segmentation::UnicodeSegmentation;
 
-pub use word_boundaries::WordBoundaryFinder;
+pub use word_boundaries::{ is_word_grapheme, WordBoundaryFinder };
+pub use backup::{ backup_original, recover_backups, untitled_backup_dir };
 
 /// Unique identifier for a buffer
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -67,6 +74,7 @@ impl LineEnding {
 /// Immutable snapshot of a buffer at a point in time
 #[derive(Clone)]
 pub struct BufferSnapshot {
+    buffer_id: BufferId,
     rope: Rope,
     version: Version,
 }
@@ -80,6 +88,11 @@ impl BufferSnapshot {
         self.version
     }
 
+    /// Id of the buffer the snapshot was taken from
+    pub fn buffer_id(&self) -> BufferId {
+        self.buffer_id
+    }
+
     pub fn len_lines(&self) -> usize {
         self.rope.len_lines()
     }
@@ -91,6 +104,187 @@ impl BufferSnapshot {
     pub fn line(&self, line_idx: usize) -> Option<String> {
         if line_idx < self.len_lines() { Some(self.rope.line(line_idx).to_string()) } else { None }
     }
+
+    /// Changes that turn this snapshot into `other`
+    ///
+    /// Lines are diffed first, then each changed region is narrowed to the
+    /// graphemes that differ. Changes are in ascending order with positions in
+    /// this snapshot; applying them from last to first (or all at once with
+    /// [`Buffer::replace_ranges`]) yields `other`. Snapshots of the same
+    /// buffer at the same version are assumed identical.
+    pub fn diff(&self, other: &BufferSnapshot) -> Vec<TextChange> {
+        if (self.buffer_id, self.version) == (other.buffer_id, other.version) {
+            return Vec::new();
+        }
+
+        let old_lines: Vec<_> = self.rope.lines().collect();
+        let new_lines: Vec<_> = other.rope.lines().collect();
+
+        diff_slices(&old_lines, &new_lines)
+            .into_iter()
+            .filter_map(|hunk| {
+                let old_start = self.rope.line_to_char(hunk.old.start);
+                let new_start = other.rope.line_to_char(hunk.new.start);
+                let old_text = self.rope.slice(old_start..self.rope.line_to_char(hunk.old.end));
+                let new_text = other.rope.slice(new_start..other.rope.line_to_char(hunk.new.end));
+                let old_text = old_text.to_string();
+                let new_text = new_text.to_string();
+
+                let (prefix, suffix) = common_grapheme_affixes(&old_text, &new_text);
+                let removed = &old_text[prefix..old_text.len() - suffix];
+                let inserted = &new_text[prefix..new_text.len() - suffix];
+
+                let start = old_start + old_text[..prefix].chars().count();
+                let start_pos = position_of_char(&self.rope, start);
+                let end_pos = position_of_char(&self.rope, start + removed.chars().count());
+
+                match (removed.is_empty(), inserted.is_empty()) {
+                    (true, true) => None,
+                    (true, false) =>
+                        Some(TextChange::Insert {
+                            at: start_pos,
+                            text: inserted.to_string(),
+                        }),
+                    (false, true) => Some(TextChange::Delete { range: start_pos..end_pos }),
+                    (false, false) =>
+                        Some(TextChange::Replace {
+                            range: start_pos..end_pos,
+                            text: inserted.to_string(),
+                        }),
+                }
+            })
+            .collect()
+    }
+}
+
+/// A change between two snapshots, positioned in the older one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextChange {
+    Insert {
+        at: Position,
+        text: String,
+    },
+    Delete {
+        range: Range<Position>,
+    },
+    Replace {
+        range: Range<Position>,
+        text: String,
+    },
+}
+
+impl TextChange {
+    /// Range replaced by this change; empty for insertions
+    pub fn range(&self) -> Range<Position> {
+        match self {
+            TextChange::Insert { at, .. } => *at..*at,
+            TextChange::Delete { range } | TextChange::Replace { range, .. } => range.clone(),
+        }
+    }
+
+    /// Text inserted by this change; empty for deletions
+    pub fn text(&self) -> &str {
+        match self {
+            TextChange::Insert { text, .. } | TextChange::Replace { text, .. } => text,
+            TextChange::Delete { .. } => "",
+        }
+    }
+}
+
+/// A replacement of a range with text, e.g. from a language server
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    pub range: Range<Position>,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new(range: Range<Position>, text: impl Into<String>) -> Self {
+        Self { range, text: text.into() }
+    }
+}
+
+/// An edit applied to a buffer, as reported to subscribers
+///
+/// Positions and byte offsets before the edit describe the replaced text;
+/// those after it describe the inserted text, which starts at the same place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BufferChange {
+    /// Replaced range, in positions before the edit
+    pub old_range: Range<Position>,
+    /// Inserted range, in positions after the edit
+    pub new_range: Range<Position>,
+    /// Replaced range, in byte offsets before the edit
+    pub old_byte_range: Range<usize>,
+    /// Inserted range, in byte offsets after the edit
+    pub new_byte_range: Range<usize>,
+    /// Buffer version once the edit is applied
+    pub version: Version,
+}
+
+impl BufferChange {
+    /// Where a position from before the change is once it is applied
+    ///
+    /// Positions before the replaced range stay put, positions inside it move
+    /// to the end of the inserted text and positions after it shift with it.
+    pub fn map_position(&self, position: Position) -> Position {
+        let (old, new) = (&self.old_range, &self.new_range);
+        if position <= old.start {
+            position
+        } else if position < old.end {
+            new.end
+        } else if position.line == old.end.line {
+            Position::new(new.end.line, new.end.column + position.column - old.end.column)
+        } else {
+            Position::new(position.line + new.end.line - old.end.line, position.column)
+        }
+    }
+}
+
+/// Identifies a change subscription so it can be removed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(u64);
+
+/// Callback run for every change to a buffer
+pub type ChangeCallback = Box<dyn Fn(&BufferChange) + Send + Sync>;
+
+/// Byte lengths of the common grapheme prefix and suffix of two strings
+///
+/// The suffix never overlaps the prefix in either string.
+fn common_grapheme_affixes(a: &str, b: &str) -> (usize, usize) {
+    let prefix: usize = a
+        .graphemes(true)
+        .zip(b.graphemes(true))
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len())
+        .sum();
+    let suffix: usize = a[prefix..]
+        .graphemes(true)
+        .rev()
+        .zip(b[prefix..].graphemes(true).rev())
+        .take_while(|(x, y)| x == y)
+        .map(|(x, _)| x.len())
+        .sum();
+    (prefix, suffix)
+}
+
+/// Position of a character index, with the column in graphemes
+fn position_of_char(rope: &Rope, idx: usize) -> Position {
+    let line = rope.char_to_line(idx);
+    let char_offset = idx - rope.line_to_char(line);
+    let content = rope.line(line).to_string();
+
+    let mut chars_counted = 0;
+    let mut column = 0;
+    for grapheme in content.graphemes(true) {
+        if chars_counted >= char_offset {
+            break;
+        }
+        chars_counted += grapheme.chars().count();
+        column += 1;
+    }
+
+    Position::new(line, column)
 }
 
 /// Metrics about the buffer
@@ -100,8 +294,30 @@ pub struct BufferMetrics {
     pub total_chars: usize,
     pub total_bytes: usize,
     pub longest_line_length: usize,
+    /// Widest line in display columns, with tabs expanded to tab stops
+    pub longest_line_display_columns: usize,
+    /// Longest line in bytes, excluding the line ending
+    pub longest_line_bytes: usize,
 }
 
+/// Prose statistics about the buffer's text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct DocumentStats {
+    /// Words, not counting Typst keywords and function names after `#`
+    pub words: usize,
+    pub chars: usize,
+    pub chars_without_whitespace: usize,
+    pub lines: usize,
+    /// Runs of non-blank lines separated by blank lines
+    pub paragraphs: usize,
+}
+
+/// Tab width used when computing display columns for metrics
+const METRICS_TAB_SIZE: usize = 4;
+
+/// UTF-8 byte order mark
+const BOM: char = '\u{FEFF}';
+
 /// The main text buffer
 pub struct Buffer {
     id: BufferId,
@@ -109,9 +325,14 @@ pub struct Buffer {
     version: Version,
     file_path: Option<PathBuf>,
     line_ending: LineEnding,
+    /// Whether saving prepends a UTF-8 byte order mark
+    write_bom: bool,
     dirty: bool,
     read_only: bool,
     undo_history: UndoHistory,
+    /// Change callbacks, in subscription order
+    subscribers: Vec<(SubscriptionId, ChangeCallback)>,
+    next_subscription: u64,
 }
 
 impl Buffer {
@@ -123,9 +344,12 @@ impl Buffer {
             version: Version::new(),
             file_path: None,
             line_ending: LineEnding::Lf,
+            write_bom: false,
             dirty: false,
             read_only: false,
             undo_history: UndoHistory::new(),
+            subscribers: Vec::new(),
+            next_subscription: 0,
         }
     }
 
@@ -138,25 +362,38 @@ impl Buffer {
             version: Version::new(),
             file_path: None,
             line_ending,
+            write_bom: false,
             dirty: false,
             read_only: false,
             undo_history: UndoHistory::new(),
+            subscribers: Vec::new(),
+            next_subscription: 0,
         }
     }
 
     /// Create a buffer from a file path
+    ///
+    /// A leading byte order mark is stripped from the content and remembered
+    /// so that saving writes it back.
     pub fn from_file(id: BufferId, path: PathBuf) -> std::io::Result<Self> {
         let content = std::fs::read_to_string(&path)?;
-        let line_ending = LineEnding::detect(&content);
+        let (content, write_bom) = match content.strip_prefix(BOM) {
+            Some(stripped) => (stripped, true),
+            None => (content.as_str(), false),
+        };
+        let line_ending = LineEnding::detect(content);
         Ok(Self {
             id,
-            rope: Rope::from_str(&content),
+            rope: Rope::from_str(content),
             version: Version::new(),
             file_path: Some(path),
             line_ending,
+            write_bom,
             dirty: false,
             read_only: false,
             undo_history: UndoHistory::new(),
+            subscribers: Vec::new(),
+            next_subscription: 0,
         })
     }
 
@@ -232,6 +469,16 @@ impl Buffer {
         }
     }
 
+    /// Iterate over lines `start..end` without allocating
+    ///
+    /// `end` is clamped to the line count. Each slice includes its line
+    /// ending.
+    pub fn lines_in_range(&self, start: usize, end: usize) -> impl Iterator<Item = RopeSlice<'_>> {
+        let end = end.min(self.len_lines());
+        let start = start.min(end);
+        self.rope.lines_at(start).take(end - start)
+    }
+
     /// Get line ending style
     pub fn line_ending(&self) -> LineEnding {
         self.line_ending
@@ -243,6 +490,62 @@ impl Buffer {
         self.dirty = true;
     }
 
+    /// Rewrite every line terminator to `target` as one undoable edit
+    ///
+    /// Handles mixed `\n` and `\r\n` endings. A lone `\r` only counts as a
+    /// terminator in text without any `\n`, so stray carriage returns in LF
+    /// or CRLF text are left alone. Also sets the buffer's line ending, so
+    /// saving writes the new style.
+    pub fn convert_line_endings(&mut self, target: LineEnding) -> Result<()> {
+        if self.read_only {
+            return Err(EditorError::BufferError("Buffer is read-only".to_string()));
+        }
+
+        let cr_is_terminator = self.rope.chars().all(|c| c != '\n');
+        let mut edits = Vec::new();
+
+        for (line_idx, line) in self.rope.lines().enumerate() {
+            let line: Cow<str> = line.into();
+            let ending = if line.ends_with("\r\n") {
+                "\r\n"
+            } else if line.ends_with('\n') {
+                "\n"
+            } else if line.ends_with('\r') && cr_is_terminator {
+                "\r"
+            } else {
+                continue;
+            };
+            if ending == target.as_str() {
+                continue;
+            }
+
+            let column = line[..line.len() - ending.len()].graphemes(true).count();
+            let start = Position::new(line_idx, column);
+            let end = Position::new(line_idx, column + 1);
+            edits.push((start..end, target.as_str().to_string()));
+        }
+
+        self.replace_ranges(edits)?;
+        if self.line_ending != target {
+            self.line_ending = target;
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Check whether saving writes a UTF-8 byte order mark
+    pub fn write_bom(&self) -> bool {
+        self.write_bom
+    }
+
+    /// Set whether saving writes a UTF-8 byte order mark
+    pub fn set_write_bom(&mut self, write_bom: bool) {
+        if self.write_bom != write_bom {
+            self.write_bom = write_bom;
+            self.dirty = true;
+        }
+    }
+
     /// Convert position to character index
     pub fn position_to_char_idx(&self, pos: Position) -> Result<usize> {
         if pos.line >= self.len_lines() {
@@ -253,10 +556,10 @@ impl Buffer {
         }
 
         let line_start = self.rope.line_to_char(pos.line);
-        let line = self.rope.line(pos.line);
+        let line: Cow<str> = self.rope.line(pos.line).into();
 
         // Count grapheme clusters to respect unicode properly
-        let graphemes: Vec<&str> = line.as_str().unwrap_or("").graphemes(true).collect();
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
 
         if pos.column > graphemes.len() {
             return Err(EditorError::InvalidPosition {
@@ -273,6 +576,12 @@ impl Buffer {
         Ok(line_start + column_offset)
     }
 
+    /// Convert position to byte index
+    pub fn position_to_byte_idx(&self, pos: Position) -> Result<usize> {
+        let char_idx = self.position_to_char_idx(pos)?;
+        Ok(self.rope.char_to_byte(char_idx))
+    }
+
     /// Convert character index to position
     pub fn char_idx_to_position(&self, idx: usize) -> Result<Position> {
         if idx > self.len_chars() {
@@ -282,24 +591,7 @@ impl Buffer {
             });
         }
 
-        let line = self.rope.char_to_line(idx);
-        let line_start = self.rope.line_to_char(line);
-        let line_content = self.rope.line(line);
-
-        let char_offset = idx - line_start;
-        let graphemes: Vec<&str> = line_content.as_str().unwrap_or("").graphemes(true).collect();
-
-        let mut chars_counted = 0;
-        let mut column = 0;
-        for grapheme in graphemes {
-            if chars_counted >= char_offset {
-                break;
-            }
-            chars_counted += grapheme.chars().count();
-            column += 1;
-        }
-
-        Ok(Position::new(line, column))
+        Ok(position_of_char(&self.rope, idx))
     }
 
     /// Insert text at a position
@@ -311,21 +603,14 @@ impl Buffer {
         let char_idx = self.position_to_char_idx(pos)?;
 
         // Calculate cursor position after insertion
-        let lines_added = text.matches('\n').count();
-        let cursor_after = if lines_added > 0 {
-            let last_line_len = text.lines().last().unwrap_or("").len();
-            Position::new(pos.line + lines_added, last_line_len)
-        } else {
-            Position::new(pos.line, pos.column + text.len())
-        };
+        let cursor_after = end_of_text(pos, text);
 
         // Record operation for undo
         let operation = EditOperation::insert(pos, text.to_string(), cursor_after);
         self.undo_history.record_operation(operation);
 
-        self.rope.insert(char_idx, text);
-        self.version = self.version.next();
-        self.dirty = true;
+        let change = self.splice(char_idx..char_idx, text);
+        self.finish_edit(vec![change]);
         Ok(())
     }
 
@@ -352,9 +637,8 @@ impl Buffer {
         let operation = EditOperation::delete(start, end, deleted_text.clone(), start);
         self.undo_history.record_operation(operation);
 
-        self.rope.remove(start_idx..end_idx);
-        self.version = self.version.next();
-        self.dirty = true;
+        let change = self.splice(start_idx..end_idx, "");
+        self.finish_edit(vec![change]);
         Ok(deleted_text)
     }
 
@@ -378,13 +662,7 @@ impl Buffer {
         let deleted_text = self.rope.slice(start_idx..end_idx).to_string();
 
         // Calculate cursor position after replacement
-        let lines_added = text.matches('\n').count();
-        let cursor_after = if lines_added > 0 {
-            let last_line_len = text.lines().last().unwrap_or("").len();
-            Position::new(start.line + lines_added, last_line_len)
-        } else {
-            Position::new(start.line, start.column + text.len())
-        };
+        let cursor_after = end_of_text(start, text);
 
         // Record operation for undo (as a single atomic operation)
         let operation = EditOperation::replace(
@@ -396,38 +674,597 @@ impl Buffer {
         );
         self.undo_history.record_operation(operation);
 
-        self.rope.remove(start_idx..end_idx);
-        self.rope.insert(start_idx, text);
-        self.version = self.version.next();
-        self.dirty = true;
+        let change = self.splice(start_idx..end_idx, text);
+        self.finish_edit(vec![change]);
         Ok(deleted_text)
     }
 
+    /// Replace several ranges in one atomic edit
+    ///
+    /// Ranges are validated up front; if any are inverted or overlap, an error
+    /// is returned and the buffer is left untouched. Edits are applied from the
+    /// end of the buffer backwards so earlier positions stay valid, and the
+    /// whole batch is recorded as a single undo group.
+    pub fn replace_ranges(&mut self, edits: Vec<(Range<Position>, String)>) -> Result<()> {
+        if self.read_only {
+            return Err(EditorError::BufferError("Buffer is read-only".to_string()));
+        }
+
+        let mut resolved = Vec::with_capacity(edits.len());
+        for (range, text) in edits {
+            let start_idx = self.position_to_char_idx(range.start)?;
+            let end_idx = self.position_to_char_idx(range.end)?;
+
+            if start_idx > end_idx {
+                return Err(
+                    EditorError::InvalidRange(
+                        format!(
+                            "Start position {:?} is after end position {:?}",
+                            range.start,
+                            range.end
+                        )
+                    )
+                );
+            }
+
+            resolved.push((start_idx..end_idx, range, text));
+        }
+
+        resolved.sort_by_key(|(chars, _, _)| chars.start);
+
+        for pair in resolved.windows(2) {
+            let (prev, next) = (&pair[0], &pair[1]);
+            if next.0.start < prev.0.end || next.0.start == prev.0.start {
+                return Err(
+                    EditorError::InvalidRange(
+                        format!("Range {:?} overlaps range {:?}", prev.1, next.1)
+                    )
+                );
+            }
+        }
+
+        if resolved.is_empty() {
+            return Ok(());
+        }
+
+        let mut operations = Vec::with_capacity(resolved.len());
+        let mut changes = Vec::with_capacity(resolved.len());
+        for (chars, range, text) in resolved.into_iter().rev() {
+            let deleted_text = self.rope.slice(chars.clone()).to_string();
+
+            let cursor_after = end_of_text(range.start, &text);
+
+            changes.push(self.splice(chars, &text));
+
+            operations.push(
+                EditOperation::replace(range.start, range.end, deleted_text, text, cursor_after)
+            );
+        }
+
+        self.undo_history.record_group(operations);
+        self.finish_edit(changes);
+        Ok(())
+    }
+
+    /// Apply edits computed against version `base_version` of the buffer
+    ///
+    /// Edits from an asynchronous source, such as a language server, may
+    /// arrive after the buffer has changed; their positions then no longer
+    /// describe the current text. Such edits are rejected with
+    /// [`EditorError::VersionMismatch`] and the buffer is left untouched.
+    /// Otherwise the edits are applied as in [`Buffer::replace_ranges`].
+    pub fn apply_versioned_edits(
+        &mut self,
+        base_version: Version,
+        edits: &[TextEdit]
+    ) -> Result<()> {
+        if self.version != base_version {
+            return Err(EditorError::VersionMismatch {
+                expected: base_version.value(),
+                actual: self.version.value(),
+            });
+        }
+
+        self.replace_ranges(
+            edits
+                .iter()
+                .map(|edit| (edit.range.clone(), edit.text.clone()))
+                .collect()
+        )
+    }
+
+    /// Wrap a selection in opening and closing delimiters
+    ///
+    /// The wrap is recorded as a single undoable edit. Returns the selection
+    /// adjusted to cover the original text inside the delimiters.
+    pub fn wrap_selection(
+        &mut self,
+        selection: &Selection,
+        open: &str,
+        close: &str
+    ) -> Result<Selection> {
+        let (start, end) = selection.range();
+
+        let start_idx = self.position_to_char_idx(start)?;
+        let end_idx = self.position_to_char_idx(end)?;
+        let inner = self.rope.slice(start_idx..end_idx).to_string();
+
+        self.replace(start, end, &format!("{}{}{}", open, inner, close))?;
+
+        // Only text on the start line shifts right by the opening delimiter
+        let open_len = open.graphemes(true).count();
+        let new_start = Position::new(start.line, start.column + open_len);
+        let new_end = if end.line == start.line {
+            Position::new(end.line, end.column + open_len)
+        } else {
+            end
+        };
+
+        Ok(
+            if selection.is_forward() {
+                Selection::new(new_start, new_end)
+            } else {
+                Selection::new(new_end, new_start)
+            }
+        )
+    }
+
+    /// Wrap a selection in Typst math delimiters
+    ///
+    /// Selections within a line become inline math (`$x$`); selections spanning
+    /// lines become display math (`$ x $`).
+    pub fn wrap_in_math(&mut self, selection: &Selection) -> Result<Selection> {
+        let (start, end) = selection.range();
+        if start.line == end.line {
+            self.wrap_selection(selection, "$", "$")
+        } else {
+            self.wrap_selection(selection, "$ ", " $")
+        }
+    }
+
+    /// Open a blank line above or below a line
+    ///
+    /// The new line inherits the indentation of `line`, re-expressed with the
+    /// given indent style. Opening below a line that ends in an opening
+    /// bracket adds one extra level. Returns the cursor position at the end of
+    /// the new line's indentation.
+    pub fn open_line(
+        &mut self,
+        line: usize,
+        above: bool,
+        tab_size: usize,
+        insert_spaces: bool
+    ) -> Result<Position> {
+        let line_text = self.line(line)?;
+        let content = line_text.trim_end_matches(['\n', '\r']);
+
+        let tab_size = tab_size.max(1);
+        let mut width = indent_width(content, tab_size);
+
+        if !above && content.trim_end().ends_with(['{', '[', '(']) {
+            width += tab_size;
+        }
+
+        let indent = indent_string(width, tab_size, insert_spaces);
+        let indent_len = indent.graphemes(true).count();
+        let newline = self.line_ending.as_str().to_string();
+
+        if above {
+            self.insert(Position::new(line, 0), &format!("{}{}", indent, newline))?;
+            Ok(Position::new(line, indent_len))
+        } else {
+            let line_end = Position::new(line, content.graphemes(true).count());
+            self.insert(line_end, &format!("{}{}", newline, indent))?;
+            Ok(Position::new(line + 1, indent_len))
+        }
+    }
+
+    /// Break the line at `pos`, indenting the new line
+    ///
+    /// The new line inherits the indentation of the line being broken, plus
+    /// one level after an opening bracket. If the cursor sits between a
+    /// bracket pair, the closing bracket moves to a line of its own at the
+    /// original indentation. Returns the cursor position on the new line.
+    pub fn newline_with_indent(
+        &mut self,
+        pos: Position,
+        tab_size: usize,
+        use_spaces: bool
+    ) -> Result<Position> {
+        let line_text = self.line(pos.line)?;
+        let content = line_text.trim_end_matches(['\n', '\r']);
+        let split = content
+            .grapheme_indices(true)
+            .nth(pos.column)
+            .map_or(content.len(), |(offset, _)| offset);
+        let (before, after) = content.split_at(split);
+
+        let tab_size = tab_size.max(1);
+        let width = indent_width(before, tab_size);
+        let opening = before.trim_end().chars().last().and_then(closing_bracket);
+
+        let newline = self.line_ending.as_str();
+        let mut text = format!("{}{}", newline, indent_string(width, tab_size, use_spaces));
+        if let Some(close) = opening {
+            text.push_str(&indent_string(tab_size, tab_size, use_spaces));
+            if after.starts_with(close) {
+                text.push_str(newline);
+                text.push_str(&indent_string(width, tab_size, use_spaces));
+            }
+        }
+
+        let cursor_line = text.split(newline).nth(1).unwrap_or_default();
+        let cursor = Position::new(pos.line + 1, cursor_line.graphemes(true).count());
+        self.insert(pos, &text)?;
+        Ok(cursor)
+    }
+
+    /// Rewrite the indentation of a range of lines in the given indent style
+    ///
+    /// Each line keeps its indentation width. The reindent is recorded as its
+    /// own undo step and never merges with surrounding typing.
+    pub fn reindent_lines(
+        &mut self,
+        lines: Range<usize>,
+        tab_size: usize,
+        insert_spaces: bool
+    ) -> Result<()> {
+        let tab_size = tab_size.max(1);
+        let mut edits = Vec::new();
+
+        for line in lines {
+            let line_text = self.line(line)?;
+            let leading: String = line_text
+                .chars()
+                .take_while(|c| *c == ' ' || *c == '\t')
+                .collect();
+            let indent = indent_string(indent_width(&leading, tab_size), tab_size, insert_spaces);
+
+            if indent != leading {
+                let end = Position::new(line, leading.graphemes(true).count());
+                edits.push((Position::new(line, 0)..end, indent));
+            }
+        }
+
+        self.replace_ranges(edits)
+    }
+
+    /// Indent lines `start_line..=end_line` by one level
+    ///
+    /// Empty lines are left alone so indenting never adds trailing
+    /// whitespace. The indent is recorded as one undo step.
+    pub fn indent_lines(
+        &mut self,
+        start_line: usize,
+        end_line: usize,
+        use_spaces: bool,
+        tab_size: usize
+    ) -> Result<()> {
+        let tab_size = tab_size.max(1);
+        let level = if use_spaces { " ".repeat(tab_size) } else { "\t".to_string() };
+        let mut edits = Vec::new();
+
+        for line in start_line..=end_line {
+            let line_text = self.line(line)?;
+            if !line_text.trim_end_matches(['\n', '\r']).is_empty() {
+                let start = Position::new(line, 0);
+                edits.push((start..start, level.clone()));
+            }
+        }
+
+        self.replace_ranges(edits)
+    }
+
+    /// Outdent lines `start_line..=end_line` by one level
+    ///
+    /// Removes leading whitespace up to one indentation level: a tab, up to
+    /// `tab_size` spaces, or spaces followed by the tab that completes the
+    /// level. Lines without leading whitespace are left alone. The outdent is
+    /// recorded as one undo step.
+    pub fn outdent_lines(
+        &mut self,
+        start_line: usize,
+        end_line: usize,
+        tab_size: usize
+    ) -> Result<()> {
+        let tab_size = tab_size.max(1);
+        let mut edits = Vec::new();
+
+        for line in start_line..=end_line {
+            let line_text = self.line(line)?;
+            let mut width = 0;
+            let mut removed = 0;
+            for c in line_text.chars() {
+                if width >= tab_size {
+                    break;
+                }
+                match c {
+                    ' ' => width += 1,
+                    '\t' => width = tab_size,
+                    _ => break,
+                }
+                removed += 1;
+            }
+
+            if removed > 0 {
+                edits.push((Position::new(line, 0)..Position::new(line, removed), String::new()));
+            }
+        }
+
+        self.replace_ranges(edits)
+    }
+
+    /// Toggle `//` comments on lines `start_line..=end_line`
+    ///
+    /// Comments go after each line's indentation. If every non-blank line is
+    /// already commented, the comments are removed instead, along with one
+    /// space after `//`. Blank lines are left alone. The toggle is recorded as
+    /// one undo step.
+    pub fn toggle_line_comment(&mut self, start_line: usize, end_line: usize) -> Result<()> {
+        // Indentation width and text after it of each non-blank line
+        let mut lines = Vec::new();
+        for line in start_line..=end_line {
+            let line_text = self.line(line)?;
+            let content = line_text.trim_end_matches(['\n', '\r']);
+            let rest = content.trim_start_matches([' ', '\t']);
+            if !rest.is_empty() {
+                lines.push((line, content.len() - rest.len(), rest.to_string()));
+            }
+        }
+
+        let uncomment =
+            !lines.is_empty() && lines.iter().all(|(_, _, rest)| rest.starts_with("//"));
+        let edits = lines
+            .into_iter()
+            .map(|(line, indent, rest)| {
+                let start = Position::new(line, indent);
+                if uncomment {
+                    let removed = if rest.starts_with("// ") { 3 } else { 2 };
+                    (start..Position::new(line, indent + removed), String::new())
+                } else {
+                    (start..start, "// ".to_string())
+                }
+            })
+            .collect();
+
+        self.replace_ranges(edits)
+    }
+
+    /// Toggle a `/* */` comment around the text from `start` to `end`
+    ///
+    /// If the text, ignoring surrounding whitespace, is already a block
+    /// comment, its delimiters are removed; otherwise it is wrapped in one.
+    /// The toggle is one undo step. Returns the range of the resulting text,
+    /// including any delimiters.
+    pub fn toggle_block_comment(
+        &mut self,
+        start: Position,
+        end: Position
+    ) -> Result<Range<Position>> {
+        let (start, end) = if start <= end { (start, end) } else { (end, start) };
+        let start_idx = self.position_to_char_idx(start)?;
+        let end_idx = self.position_to_char_idx(end)?;
+        let text = self.rope.slice(start_idx..end_idx).to_string();
+
+        let leading = text.len() - text.trim_start().len();
+        let trailing = text.len() - text.trim_end().len();
+        let inner = text.trim();
+        if inner.len() >= 4 && inner.starts_with("/*") && inner.ends_with("*/") {
+            let open_start = end_of_text(start, &text[..leading]);
+            let open_end = end_of_text(open_start, "/*");
+            let close_start = end_of_text(start, &text[..text.len() - trailing - 2]);
+            let close_end = end_of_text(close_start, "*/");
+            self.replace_ranges(
+                vec![
+                    (open_start..open_end, String::new()),
+                    (close_start..close_end, String::new())
+                ]
+            )?;
+
+            let uncommented = format!(
+                "{}{}{}",
+                &text[..leading],
+                &inner[2..inner.len() - 2],
+                &text[text.len() - trailing..]
+            );
+            Ok(start..end_of_text(start, &uncommented))
+        } else {
+            let commented = format!("/*{}*/", text);
+            let commented_end = end_of_text(start, &commented);
+            self.replace_ranges(vec![(start..end, commented)])?;
+            Ok(start..commented_end)
+        }
+    }
+
+    /// Paste text at a position
+    ///
+    /// Unlike [`Buffer::insert`], the paste is always its own undo step.
+    /// Returns the cursor position after the pasted text.
+    pub fn paste(&mut self, pos: Position, text: &str) -> Result<Position> {
+        if self.read_only {
+            return Err(EditorError::BufferError("Buffer is read-only".to_string()));
+        }
+
+        let char_idx = self.position_to_char_idx(pos)?;
+        let cursor_after = end_of_text(pos, text);
+
+        let operation = EditOperation::insert(pos, text.to_string(), cursor_after);
+        self.undo_history.record_isolated(operation);
+
+        let change = self.splice(char_idx..char_idx, text);
+        self.finish_edit(vec![change]);
+        Ok(cursor_after)
+    }
+
+    /// Replace the whole buffer with formatter output
+    ///
+    /// Recorded as a single undo step that never merges with surrounding
+    /// typing. Does nothing if the text is unchanged.
+    pub fn apply_formatting(&mut self, formatted: &str) -> Result<()> {
+        if self.read_only {
+            return Err(EditorError::BufferError("Buffer is read-only".to_string()));
+        }
+
+        let original = self.text();
+        if original == formatted {
+            return Ok(());
+        }
+
+        let start = Position::zero();
+        let end = self.char_idx_to_position(self.len_chars())?;
+        let cursor_after = end_of_text(start, formatted);
+
+        let operation = EditOperation::replace(
+            start,
+            end,
+            original,
+            formatted.to_string(),
+            cursor_after
+        );
+        self.undo_history.record_isolated(operation);
+
+        let change = self.splice(0..self.len_chars(), formatted);
+        self.finish_edit(vec![change]);
+        Ok(())
+    }
+
+    /// Strip trailing whitespace from every line as one undoable edit
+    ///
+    /// Returns whether anything changed.
+    pub fn trim_trailing_whitespace(&mut self) -> bool {
+        self.trim_trailing_whitespace_except(&[])
+    }
+
+    /// Strip trailing whitespace, leaving lines where a selection or cursor
+    /// touches the trailing whitespace alone
+    ///
+    /// Line endings are kept as they are. Returns whether anything changed.
+    pub fn trim_trailing_whitespace_except(&mut self, selections: &[Selection]) -> bool {
+        let mut edits = Vec::new();
+
+        for (line_idx, line) in self.rope.lines().enumerate() {
+            let line = line.to_string();
+            let content = line.trim_end_matches(['\n', '\r']);
+            let trimmed = content.trim_end();
+            if trimmed.len() == content.len() {
+                continue;
+            }
+
+            let start = Position::new(line_idx, trimmed.graphemes(true).count());
+            let end = Position::new(line_idx, content.graphemes(true).count());
+            let in_use = selections.iter().any(|selection| {
+                let (sel_start, sel_end) = selection.range();
+                sel_start <= end && sel_end >= start
+            });
+            if !in_use {
+                edits.push((start..end, String::new()));
+            }
+        }
+
+        !edits.is_empty() && self.replace_ranges(edits).is_ok()
+    }
+
+    /// Make the buffer end in exactly one line ending when `enabled`
+    ///
+    /// Extra blank lines at the end are removed, and the buffer's
+    /// [`LineEnding`] is used for the final newline. Empty buffers are left
+    /// empty. The change is one undoable edit.
+    pub fn ensure_final_newline(&mut self, enabled: bool) {
+        if !enabled || self.read_only || self.is_empty() {
+            return;
+        }
+
+        let len = self.len_chars();
+        let trailing = self.rope
+            .chars_at(len)
+            .reversed()
+            .take_while(|c| matches!(c, '\n' | '\r'))
+            .count();
+        let ending = self.line_ending.as_str();
+        if self.rope.slice(len - trailing..) == ending || trailing == len {
+            return;
+        }
+
+        let start = position_of_char(&self.rope, len - trailing);
+        let end = position_of_char(&self.rope, len);
+        let _ = self.replace_ranges(vec![(start..end, ending.to_string())]);
+    }
+
     /// Save buffer to file
+    ///
+    /// Fails if the buffer has no path; use [`Buffer::save_with`] or
+    /// [`Buffer::save_or`] to supply one.
     pub fn save(&mut self) -> std::io::Result<()> {
+        self.save_with(|_| None)
+    }
+
+    /// Save buffer to file, resolving a path first if none is set
+    ///
+    /// `resolve` is only called for pathless buffers, e.g. to prompt the user
+    /// or derive a name. Returning `None` cancels the save with a `NotFound`
+    /// error. A resolved path becomes the buffer's path.
+    pub fn save_with(
+        &mut self,
+        resolve: impl FnOnce(&Buffer) -> Option<PathBuf>
+    ) -> std::io::Result<()> {
         if let Some(path) = &self.file_path {
-            let content = self.text();
-            std::fs::write(path, content)?;
+            std::fs::write(path, self.file_content())?;
             self.dirty = false;
+            self.discard_saved_backup(self.backup_path());
             Ok(())
         } else {
-            //TODO: should implement default fallback save path logic here using `self.save_as()`
-            Err(std::io::Error::new(std::io::ErrorKind::NotFound, "No file path set for buffer"))
+            match resolve(self) {
+                Some(path) => self.save_as(path),
+                None =>
+                    Err(
+                        std::io::Error::new(
+                            std::io::ErrorKind::NotFound,
+                            "No file path set for buffer"
+                        )
+                    ),
+            }
         }
     }
 
+    /// Save buffer to file, using `default_path` if no path is set
+    pub fn save_or(&mut self, default_path: PathBuf) -> std::io::Result<()> {
+        self.save_with(|_| Some(default_path))
+    }
+
     /// Save buffer to a specific file
     pub fn save_as(&mut self, path: PathBuf) -> std::io::Result<()> {
-        let content = self.text();
-        std::fs::write(&path, content)?;
+        std::fs::write(&path, self.file_content())?;
+        let previous_backup = self.backup_path();
         self.file_path = Some(path);
         self.dirty = false;
+        self.discard_saved_backup(previous_backup);
         Ok(())
     }
 
+    /// Remove the backup of changes that were just saved
+    ///
+    /// The save itself succeeded, so failing to remove the backup is only
+    /// logged; a leftover backup is older than the file and never recovered.
+    fn discard_saved_backup(&self, backup: PathBuf) {
+        if let Err(err) = backup::remove_if_exists(&backup) {
+            tracing::warn!("Failed to remove backup {}: {}", backup.display(), err);
+        }
+    }
+
+    /// Content as written to disk, with a byte order mark if enabled
+    fn file_content(&self) -> String {
+        let mut content = String::with_capacity(self.len_bytes() + BOM.len_utf8());
+        if self.write_bom {
+            content.push(BOM);
+        }
+        content.extend(self.rope.chunks());
+        content
+    }
+
     /// Create an immutable snapshot
     pub fn snapshot(&self) -> BufferSnapshot {
         BufferSnapshot {
+            buffer_id: self.id,
             rope: self.rope.clone(),
             version: self.version,
         }
@@ -435,22 +1272,133 @@ impl Buffer {
 
     /// Get buffer metrics
     pub fn metrics(&self) -> BufferMetrics {
-        let longest_line = (0..self.len_lines())
-            .map(|i| self.rope.line(i).len_chars())
-            .max()
-            .unwrap_or(0);
+        self.metrics_with_tab_size(METRICS_TAB_SIZE)
+    }
+
+    /// Get buffer metrics, expanding tabs to the given width
+    pub fn metrics_with_tab_size(&self, tab_size: usize) -> BufferMetrics {
+        let tab_size = tab_size.max(1);
+        let mut longest_line = 0;
+        let mut longest_display = 0;
+        let mut longest_bytes = 0;
+
+        for line in self.rope.lines() {
+            longest_line = longest_line.max(line.len_chars());
+
+            let content = line.to_string();
+            let content = content.trim_end_matches(['\n', '\r']);
+            longest_bytes = longest_bytes.max(content.len());
+
+            let columns = content.graphemes(true).fold(0, |column, grapheme| {
+                if grapheme == "\t" { (column / tab_size + 1) * tab_size } else { column + 1 }
+            });
+            longest_display = longest_display.max(columns);
+        }
 
         BufferMetrics {
             total_lines: self.len_lines(),
             total_chars: self.len_chars(),
             total_bytes: self.len_bytes(),
             longest_line_length: longest_line,
+            longest_line_display_columns: longest_display,
+            longest_line_bytes: longest_bytes,
+        }
+    }
+
+    /// Get word, character and paragraph counts
+    pub fn statistics(&self) -> DocumentStats {
+        let text = self.text();
+
+        let mut words = 0;
+        let mut previous = "";
+        for segment in text.split_word_bounds() {
+            if previous != "#" && segment.chars().any(char::is_alphanumeric) {
+                words += 1;
+            }
+            previous = segment;
+        }
+
+        let mut paragraphs = 0;
+        let mut in_paragraph = false;
+        for line in text.lines() {
+            let blank = line.trim().is_empty();
+            if !blank && !in_paragraph {
+                paragraphs += 1;
+            }
+            in_paragraph = !blank;
+        }
+
+        DocumentStats {
+            words,
+            chars: self.len_chars(),
+            chars_without_whitespace: text.chars().filter(|c| !c.is_whitespace()).count(),
+            lines: self.len_lines(),
+            paragraphs,
+        }
+    }
+
+    /// Run `callback` after every change to the text
+    ///
+    /// Each edit reports one change, so a replace is never split into a
+    /// delete and an insert. Batch edits such as [`Buffer::replace_ranges`],
+    /// and undo or redo of a batch, report one change per range in the order
+    /// they are applied, so every change's positions hold at the moment it is
+    /// reported. Callbacks run while the buffer is borrowed and must not try
+    /// to access it.
+    pub fn subscribe(&mut self, callback: ChangeCallback) -> SubscriptionId {
+        let id = SubscriptionId(self.next_subscription);
+        self.next_subscription += 1;
+        self.subscribers.push((id, callback));
+        id
+    }
+
+    /// Stop a subscription; returns false if it was already removed
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let before = self.subscribers.len();
+        self.subscribers.retain(|(subscription, _)| *subscription != id);
+        self.subscribers.len() != before
+    }
+
+    /// Replace a char range of the rope, describing the change
+    ///
+    /// The change carries the current version until [`Buffer::finish_edit`]
+    /// stamps it.
+    fn splice(&mut self, chars: Range<usize>, text: &str) -> BufferChange {
+        let start = position_of_char(&self.rope, chars.start);
+        let old_end = position_of_char(&self.rope, chars.end);
+        let start_byte = self.rope.char_to_byte(chars.start);
+        let old_end_byte = self.rope.char_to_byte(chars.end);
+
+        self.rope.remove(chars.clone());
+        self.rope.insert(chars.start, text);
+
+        let new_end = position_of_char(&self.rope, chars.start + text.chars().count());
+        BufferChange {
+            old_range: start..old_end,
+            new_range: start..new_end,
+            old_byte_range: start_byte..old_end_byte,
+            new_byte_range: start_byte..start_byte + text.len(),
+            version: self.version,
+        }
+    }
+
+    /// Bump the version after an edit and report its changes
+    fn finish_edit(&mut self, changes: Vec<BufferChange>) {
+        self.version = self.version.next();
+        self.dirty = true;
+
+        for mut change in changes {
+            change.version = self.version;
+            for (_, callback) in &self.subscribers {
+                callback(&change);
+            }
         }
     }
 
     /// Undo the last operation
     pub fn undo(&mut self) -> Result<Position> {
         let group = self.undo_history.undo().ok_or(EditorError::UndoHistoryExhausted)?;
+        let mut changes = Vec::with_capacity(group.operations.len());
 
         // Apply operations in reverse
         for operation in group.operations.iter().rev() {
@@ -458,24 +1406,18 @@ impl Buffer {
                 OperationType::Insert => {
                     // Undo insert by deleting
                     if let Some(text) = &operation.inserted_text {
-                        let end_pos = if text.contains('\n') {
-                            let lines = text.matches('\n').count();
-                            let last_line_len = text.lines().last().unwrap_or("").len();
-                            Position::new(operation.start.line + lines, last_line_len)
-                        } else {
-                            Position::new(operation.start.line, operation.start.column + text.len())
-                        };
+                        let end_pos = end_of_text(operation.start, text);
 
                         let start_idx = self.position_to_char_idx(operation.start)?;
                         let end_idx = self.position_to_char_idx(end_pos)?;
-                        self.rope.remove(start_idx..end_idx);
+                        changes.push(self.splice(start_idx..end_idx, ""));
                     }
                 }
                 OperationType::Delete => {
                     // Undo delete by inserting
                     if let Some(text) = &operation.deleted_text {
                         let char_idx = self.position_to_char_idx(operation.start)?;
-                        self.rope.insert(char_idx, text);
+                        changes.push(self.splice(char_idx..char_idx, text));
                     }
                 }
                 OperationType::Replace => {
@@ -486,28 +1428,17 @@ impl Buffer {
                             &operation.deleted_text,
                         )
                     {
-                        let end_pos = if inserted.contains('\n') {
-                            let lines = inserted.matches('\n').count();
-                            let last_line_len = inserted.lines().last().unwrap_or("").len();
-                            Position::new(operation.start.line + lines, last_line_len)
-                        } else {
-                            Position::new(
-                                operation.start.line,
-                                operation.start.column + inserted.len()
-                            )
-                        };
+                        let end_pos = end_of_text(operation.start, inserted);
 
                         let start_idx = self.position_to_char_idx(operation.start)?;
                         let end_idx = self.position_to_char_idx(end_pos)?;
-                        self.rope.remove(start_idx..end_idx);
-                        self.rope.insert(start_idx, deleted);
+                        changes.push(self.splice(start_idx..end_idx, deleted));
                     }
                 }
             }
         }
 
-        self.version = self.version.next();
-        self.dirty = true;
+        self.finish_edit(changes);
 
         // Return cursor position from first operation
         Ok(
@@ -521,6 +1452,7 @@ impl Buffer {
     /// Redo the last undone operation
     pub fn redo(&mut self) -> Result<Position> {
         let group = self.undo_history.redo().ok_or(EditorError::RedoHistoryExhausted)?;
+        let mut changes = Vec::with_capacity(group.operations.len());
 
         // Apply operations in forward order
         for operation in &group.operations {
@@ -528,29 +1460,27 @@ impl Buffer {
                 OperationType::Insert => {
                     if let Some(text) = &operation.inserted_text {
                         let char_idx = self.position_to_char_idx(operation.start)?;
-                        self.rope.insert(char_idx, text);
+                        changes.push(self.splice(char_idx..char_idx, text));
                     }
                 }
                 OperationType::Delete => {
                     if let Some(end) = operation.end {
                         let start_idx = self.position_to_char_idx(operation.start)?;
                         let end_idx = self.position_to_char_idx(end)?;
-                        self.rope.remove(start_idx..end_idx);
+                        changes.push(self.splice(start_idx..end_idx, ""));
                     }
                 }
                 OperationType::Replace => {
                     if let (Some(end), Some(inserted)) = (operation.end, &operation.inserted_text) {
                         let start_idx = self.position_to_char_idx(operation.start)?;
                         let end_idx = self.position_to_char_idx(end)?;
-                        self.rope.remove(start_idx..end_idx);
-                        self.rope.insert(start_idx, inserted);
+                        changes.push(self.splice(start_idx..end_idx, inserted));
                     }
                 }
             }
         }
 
-        self.version = self.version.next();
-        self.dirty = true;
+        self.finish_edit(changes);
 
         // Return cursor position from last operation
         Ok(
@@ -571,6 +1501,29 @@ impl Buffer {
         self.undo_history.can_redo()
     }
 
+    /// Link the last undo group to groups recorded in other buffers
+    ///
+    /// Used for edits that span several buffers, such as a rename. Returns
+    /// false if there is nothing to link.
+    pub fn link_last_undo(&mut self, link: UndoLink) -> bool {
+        self.undo_history.link_last_group(link)
+    }
+
+    /// Link of the undo group the next [`Buffer::undo`] reverts, if any
+    pub fn undo_link(&self) -> Option<UndoLink> {
+        self.undo_history.undo_link()
+    }
+
+    /// Link of the undo group the next [`Buffer::redo`] reapplies, if any
+    pub fn redo_link(&self) -> Option<UndoLink> {
+        self.undo_history.redo_link()
+    }
+
+    /// Whether the undo or redo history holds a group with `link`
+    pub fn has_undo_link(&self, link: UndoLink) -> bool {
+        self.undo_history.has_link(link)
+    }
+
     /// Create a boundary in the undo history (force new undo group)
     pub fn create_undo_boundary(&mut self) {
         self.undo_history.create_boundary();
@@ -636,26 +1589,32 @@ impl Buffer {
     }
 
     /// Get word boundaries in a line
-    fn word_boundaries(&self, line_idx: usize) -> Result<Vec<usize>> {
+    ///
+    /// `in_math` tells whether a byte offset in the buffer is inside math.
+    /// There, identifiers are runs of letters and numbers runs of digits, so
+    /// `_`, `^`, `\` and operators separate words.
+    fn word_boundaries(
+        &self,
+        line_idx: usize,
+        in_math: &dyn Fn(usize) -> bool
+    ) -> Result<Vec<usize>> {
         let line = self.line(line_idx)?;
         let mut boundaries = vec![0];
 
         let graphemes: Vec<&str> = line.graphemes(true).collect();
-        let mut in_word = false;
+        let mut offset = self.rope.line_to_byte(line_idx);
+        let mut prev_class = None;
 
         for (i, grapheme) in graphemes.iter().enumerate() {
-            let is_word_char = grapheme.chars().all(|c| (c.is_alphanumeric() || c == '_'));
-
-            if is_word_char && !in_word {
-                boundaries.push(i);
-                in_word = true;
-            } else if !is_word_char && in_word {
+            let class = word_class(grapheme, in_math(offset));
+            if class != prev_class && (class.is_some() || prev_class.is_some()) {
                 boundaries.push(i);
-                in_word = false;
             }
+            prev_class = class;
+            offset += grapheme.len();
         }
 
-        if in_word {
+        if prev_class.is_some() {
             boundaries.push(graphemes.len());
         }
 
@@ -664,7 +1623,17 @@ impl Buffer {
 
     /// Find next word boundary
     pub fn next_word_boundary(&self, pos: Position) -> Result<Position> {
-        let boundaries = self.word_boundaries(pos.line)?;
+        self.next_word_boundary_with(pos, |_| false)
+    }
+
+    /// Find next word boundary, using math word rules where `in_math` holds
+    /// for a byte offset
+    pub fn next_word_boundary_with(
+        &self,
+        pos: Position,
+        in_math: impl Fn(usize) -> bool
+    ) -> Result<Position> {
+        let boundaries = self.word_boundaries(pos.line, &in_math)?;
 
         for &boundary in &boundaries {
             if boundary > pos.column {
@@ -682,7 +1651,17 @@ impl Buffer {
 
     /// Find previous word boundary
     pub fn prev_word_boundary(&self, pos: Position) -> Result<Position> {
-        let boundaries = self.word_boundaries(pos.line)?;
+        self.prev_word_boundary_with(pos, |_| false)
+    }
+
+    /// Find previous word boundary, using math word rules where `in_math`
+    /// holds for a byte offset
+    pub fn prev_word_boundary_with(
+        &self,
+        pos: Position,
+        in_math: impl Fn(usize) -> bool
+    ) -> Result<Position> {
+        let boundaries = self.word_boundaries(pos.line, &in_math)?;
 
         for &boundary in boundaries.iter().rev() {
             if boundary < pos.column {
@@ -701,6 +1680,69 @@ impl Buffer {
     }
 }
 
+/// Kind of word a grapheme belongs to, or `None` between words
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WordClass {
+    Word,
+    MathIdent,
+    MathNumber,
+}
+
+fn word_class(grapheme: &str, in_math: bool) -> Option<WordClass> {
+    if in_math {
+        if grapheme.chars().all(char::is_alphabetic) {
+            Some(WordClass::MathIdent)
+        } else if grapheme.chars().all(|c| c.is_ascii_digit()) {
+            Some(WordClass::MathNumber)
+        } else {
+            None
+        }
+    } else if is_word_grapheme(grapheme) {
+        Some(WordClass::Word)
+    } else {
+        None
+    }
+}
+
+/// Width of the leading whitespace of `text`, counting tabs as `tab_size`
+fn indent_width(text: &str, tab_size: usize) -> usize {
+    text.chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .map(|c| if c == '\t' { tab_size } else { 1 })
+        .sum()
+}
+
+/// Closing bracket matching an opening bracket that adds an indent level
+fn closing_bracket(open: char) -> Option<char> {
+    match open {
+        '{' => Some('}'),
+        '[' => Some(']'),
+        '(' => Some(')'),
+        _ => None,
+    }
+}
+
+/// Indentation of the given width in the requested style
+fn indent_string(width: usize, tab_size: usize, insert_spaces: bool) -> String {
+    if insert_spaces {
+        " ".repeat(width)
+    } else {
+        format!("{}{}", "\t".repeat(width / tab_size), " ".repeat(width % tab_size))
+    }
+}
+
+/// Position just past `text` when it is inserted at `start`
+pub(crate) fn end_of_text(start: Position, text: &str) -> Position {
+    match text.rsplit_once('\n') {
+        Some((before, last_line)) =>
+            Position::new(
+                start.line + before.matches('\n').count() + 1,
+                last_line.graphemes(true).count()
+            ),
+        None => Position::new(start.line, start.column + text.graphemes(true).count()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -0,0 +1,100 @@
+//! Autosave backups of unsaved changes
+
+use super::Buffer;
+use std::path::{ Path, PathBuf };
+
+/// Extension of backup files
+const BACKUP_EXTENSION: &str = "autosave";
+
+impl Buffer {
+    /// Path the buffer's backup is written to
+    ///
+    /// `notes.typ` is backed up to a hidden `.notes.typ.autosave` next to it;
+    /// untitled buffers use a file named after their ID in the temp directory.
+    pub fn backup_path(&self) -> PathBuf {
+        match &self.file_path {
+            Some(path) => {
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                path.with_file_name(format!(".{}.{}", name, BACKUP_EXTENSION))
+            }
+            None =>
+                untitled_backup_dir().join(format!("untitled-{}.{}", self.id.0, BACKUP_EXTENSION)),
+        }
+    }
+
+    /// Write unsaved changes to the backup file
+    ///
+    /// Does nothing unless the buffer is dirty. The backup is written to a
+    /// temporary file first and renamed into place, so a crash mid-write
+    /// never leaves a truncated backup. Returns the backup path.
+    pub fn write_backup(&self) -> std::io::Result<PathBuf> {
+        let path = self.backup_path();
+        if !self.dirty {
+            return Ok(path);
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let partial = path.with_extension(format!("{}.partial", BACKUP_EXTENSION));
+        std::fs::write(&partial, self.file_content())?;
+        std::fs::rename(&partial, &path)?;
+        Ok(path)
+    }
+
+    /// Remove the backup file, if there is one
+    pub fn discard_backup(&self) -> std::io::Result<()> {
+        remove_if_exists(&self.backup_path())
+    }
+}
+
+/// Find backups in `dir` worth offering for recovery
+///
+/// A backup is kept if the file it belongs to is gone or was last modified
+/// before the backup was written. Backups older than their file are stale,
+/// since the file was saved since, and are skipped so they never overwrite
+/// newer content. Results are sorted by path.
+pub fn recover_backups(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut backups: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == BACKUP_EXTENSION))
+        .filter(|backup| {
+            let Some(original) = backup_original(backup) else {
+                // Untitled backups have no file to be newer than
+                return true;
+            };
+            let modified = |path: &Path| std::fs::metadata(path).and_then(|meta| meta.modified());
+            match (modified(backup), modified(&original)) {
+                (Ok(backup), Ok(original)) => backup > original,
+                (Ok(_), Err(_)) => true,
+                (Err(_), _) => false,
+            }
+        })
+        .collect();
+    backups.sort();
+    backups
+}
+
+/// File a backup belongs to, or `None` for backups of untitled buffers
+pub fn backup_original(backup: &Path) -> Option<PathBuf> {
+    let name = backup.file_name()?.to_str()?;
+    let original = name.strip_prefix('.')?.strip_suffix(&format!(".{}", BACKUP_EXTENSION))?;
+    Some(backup.with_file_name(original))
+}
+
+/// Directory holding backups of untitled buffers
+pub fn untitled_backup_dir() -> PathBuf {
+    std::env::temp_dir().join("typst-editor-autosave")
+}
+
+pub(super) fn remove_if_exists(path: &Path) -> std::io::Result<()> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,103 @@
+//! Syntax-tree aware queries over buffer content
+
+use super::Buffer;
+use crate::selection::Position;
+use std::ops::Range;
+use typst_syntax::{ LinkedNode, SyntaxKind };
+
+impl Buffer {
+    /// Get the range of the Nth argument of the function call around a position
+    ///
+    /// Uses the innermost call whose argument list contains `pos`. The range
+    /// excludes surrounding whitespace and separating commas.
+    pub fn select_argument(&self, pos: Position, index: usize) -> Option<Range<Position>> {
+        let text = self.text();
+        let offset = self.position_to_byte_idx(pos).ok()?;
+        let root = typst_syntax::parse(&text);
+
+        let args = Self::innermost_args(LinkedNode::new(&root), offset)?;
+        let argument = args
+            .children()
+            .filter(|child| {
+                !child.kind().is_trivia() &&
+                    !matches!(
+                        child.kind(),
+                        SyntaxKind::LeftParen | SyntaxKind::RightParen | SyntaxKind::Comma
+                    )
+            })
+            .nth(index)?;
+
+        let start = self.byte_idx_to_position(argument.offset())?;
+        let end = self.byte_idx_to_position(argument.offset() + argument.len())?;
+        Some(start..end)
+    }
+
+    /// Find the deepest argument list of a function call containing `offset`
+    fn innermost_args(root: LinkedNode, offset: usize) -> Option<LinkedNode> {
+        let mut node = root;
+        let mut innermost = None;
+
+        loop {
+            if node.kind() == SyntaxKind::Args {
+                innermost = Some(node.clone());
+            }
+
+            let child = node.children().find(|child| {
+                child.offset() <= offset && offset < child.offset() + child.len()
+            });
+            match child {
+                Some(child) => {
+                    node = child;
+                }
+                None => {
+                    return innermost;
+                }
+            }
+        }
+    }
+
+    /// Convert a byte index to a position
+    fn byte_idx_to_position(&self, byte_idx: usize) -> Option<Position> {
+        if byte_idx > self.len_bytes() {
+            return None;
+        }
+        self.char_idx_to_position(self.rope.byte_to_char(byte_idx)).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ Buffer, BufferId, Position };
+
+    #[test]
+    fn test_select_argument() {
+        let buffer = Buffer::from_text(BufferId::new(1), "#f(a, b + c, d)");
+
+        let range = buffer.select_argument(Position::new(0, 3), 1).unwrap();
+        assert_eq!(range, Position::new(0, 6)..Position::new(0, 11));
+
+        let range = buffer.select_argument(Position::new(0, 3), 2).unwrap();
+        assert_eq!(range, Position::new(0, 13)..Position::new(0, 14));
+
+        assert_eq!(buffer.select_argument(Position::new(0, 3), 3), None);
+    }
+
+    #[test]
+    fn test_select_argument_innermost_call() {
+        let buffer = Buffer::from_text(BufferId::new(1), "#f(a, g(x, y), z)");
+
+        // Cursor inside the nested call selects its arguments
+        let range = buffer.select_argument(Position::new(0, 8), 1).unwrap();
+        assert_eq!(range, Position::new(0, 11)..Position::new(0, 12));
+
+        // Outside the nested call selects from the outer call
+        let range = buffer.select_argument(Position::new(0, 4), 1).unwrap();
+        assert_eq!(range, Position::new(0, 6)..Position::new(0, 13));
+    }
+
+    #[test]
+    fn test_select_argument_outside_call() {
+        let buffer = Buffer::from_text(BufferId::new(1), "plain text");
+        assert_eq!(buffer.select_argument(Position::new(0, 2), 0), None);
+    }
+}
@@ -24,6 +24,14 @@ pub struct Boundary {
     pub boundary_type: BoundaryType,
 }
 
+/// Whether a grapheme is part of a word for word motions outside math
+///
+/// Word motion and vim motions share this rule, so `'` and `-` separate
+/// words for both.
+pub fn is_word_grapheme(grapheme: &str) -> bool {
+    grapheme.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
 /// Word boundary finder using UAX #29
 pub struct WordBoundaryFinder {
     graphemes: Vec<String>,
@@ -112,7 +120,7 @@ impl WordBoundaryFinder {
     }
 
     /// Check if a grapheme is a word character
-    fn is_word_char(grapheme: &str) -> bool {
+    pub(crate) fn is_word_char(grapheme: &str) -> bool {
         grapheme.chars().all(|c| { c.is_alphanumeric() || c == '_' || c == '\'' || c == '-' })
     }
 
@@ -0,0 +1,600 @@
+//! Clipboard access, multi-cursor copy and paste, and clipboard history
+
+use crate::buffer::end_of_text;
+use crate::selection::{ Position, Selection, SelectionSet };
+use crate::{ Buffer, EditorError, Result };
+use std::collections::VecDeque;
+use std::ops::Range;
+
+/// A clipboard holding plain text
+pub trait Clipboard {
+    /// Get the clipboard's text; empty if it holds none
+    fn get_text(&mut self) -> Result<String>;
+
+    fn set_text(&mut self, text: &str) -> Result<()>;
+}
+
+/// In-memory clipboard, for tests and headless use
+#[derive(Debug, Clone, Default)]
+pub struct MockClipboard {
+    text: String,
+}
+
+impl MockClipboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Clipboard for MockClipboard {
+    fn get_text(&mut self) -> Result<String> {
+        Ok(self.text.clone())
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<()> {
+        self.text = text.to_string();
+        Ok(())
+    }
+}
+
+/// The operating system's clipboard
+#[cfg(feature = "system-clipboard")]
+pub struct SystemClipboard {
+    inner: arboard::Clipboard,
+}
+
+#[cfg(feature = "system-clipboard")]
+impl SystemClipboard {
+    pub fn new() -> Result<Self> {
+        let inner = arboard::Clipboard
+            ::new()
+            .map_err(|err| EditorError::ClipboardError(err.to_string()))?;
+        Ok(Self { inner })
+    }
+}
+
+#[cfg(feature = "system-clipboard")]
+impl Clipboard for SystemClipboard {
+    fn get_text(&mut self) -> Result<String> {
+        match self.inner.get_text() {
+            Ok(text) => Ok(text),
+            Err(arboard::Error::ContentNotAvailable) => Ok(String::new()),
+            Err(err) => Err(EditorError::ClipboardError(err.to_string())),
+        }
+    }
+
+    fn set_text(&mut self, text: &str) -> Result<()> {
+        self.inner.set_text(text).map_err(|err| EditorError::ClipboardError(err.to_string()))
+    }
+}
+
+/// Text copied from the selections of one editor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CopiedText {
+    /// Text of each selection, in document order
+    pub entries: Vec<String>,
+    /// Whether whole lines were copied from empty selections
+    pub whole_lines: bool,
+}
+
+impl CopiedText {
+    /// Copy the selected text, or the cursors' lines if nothing is selected
+    ///
+    /// Copied lines always end with a newline.
+    pub fn from_selections(buffer: &Buffer, selections: &SelectionSet) -> Result<Self> {
+        let mut sorted: Vec<&Selection> = selections.selections().iter().collect();
+        sorted.sort_by_key(|selection| selection.to_range().start);
+        let whole_lines = sorted.iter().all(|selection| selection.is_collapsed());
+
+        let text = buffer.text();
+        let mut entries = Vec::with_capacity(sorted.len());
+        for selection in sorted {
+            let entry = if whole_lines {
+                let mut line = buffer.line(selection.cursor.position.line)?;
+                if !line.ends_with('\n') {
+                    line.push('\n');
+                }
+                line
+            } else {
+                let range = selection.to_range();
+                let start = buffer.position_to_byte_idx(range.start)?;
+                let end = buffer.position_to_byte_idx(range.end)?;
+                text[start..end].to_string()
+            };
+            entries.push(entry);
+        }
+        Ok(Self { entries, whole_lines })
+    }
+
+    /// Text put on the clipboard: entries one per line
+    pub fn joined(&self) -> String {
+        if self.whole_lines { self.entries.concat() } else { self.entries.join("\n") }
+    }
+}
+
+/// Copy and paste between an editor's selections and a clipboard
+///
+/// Other applications only see plain text. The entries of the last copy are
+/// remembered, so while the clipboard still holds that text, pasting with as
+/// many cursors as there were selections puts one entry at each cursor, and
+/// lines copied from empty selections are pasted as new lines above the
+/// cursors. Every copy and cut is also recorded in a [`ClipboardRing`].
+pub struct EditorClipboard<C: Clipboard> {
+    clipboard: C,
+    last_copy: Option<CopiedText>,
+    ring: ClipboardRing,
+}
+
+impl<C: Clipboard> EditorClipboard<C> {
+    pub fn new(clipboard: C) -> Self {
+        Self { clipboard, last_copy: None, ring: ClipboardRing::default() }
+    }
+
+    pub fn clipboard(&mut self) -> &mut C {
+        &mut self.clipboard
+    }
+
+    /// Text of recent copies and cuts, newest first
+    pub fn ring(&self) -> &ClipboardRing {
+        &self.ring
+    }
+
+    pub fn ring_mut(&mut self) -> &mut ClipboardRing {
+        &mut self.ring
+    }
+
+    /// Copy the selections, or the cursors' lines if nothing is selected
+    pub fn copy(&mut self, buffer: &Buffer, selections: &SelectionSet) -> Result<()> {
+        let copied = CopiedText::from_selections(buffer, selections)?;
+        let text = copied.joined();
+        self.clipboard.set_text(&text)?;
+        self.ring.push(text);
+        self.last_copy = Some(copied);
+        Ok(())
+    }
+
+    /// Copy, then delete the copied text or lines as one undo step
+    pub fn cut(&mut self, buffer: &mut Buffer, selections: &mut SelectionSet) -> Result<()> {
+        self.copy(buffer, selections)?;
+        let whole_lines = self.last_copy.as_ref().is_some_and(|copied| copied.whole_lines);
+
+        let cut_lines: Vec<usize> = selections
+            .selections()
+            .iter()
+            .map(|selection| selection.cursor.position.line)
+            .collect();
+        let last_line = buffer.len_lines().saturating_sub(1);
+        let edits = selections
+            .selections()
+            .iter()
+            .map(|selection| {
+                let range = selection.to_range();
+                if !whole_lines {
+                    return Ok(Edit { range: range.start..range.end, text: String::new() });
+                }
+                let line = selection.cursor.position.line;
+                let line_end = Position::new(line, grapheme_count(&buffer.line(line)?));
+                let range = if line < last_line {
+                    Position::new(line, 0)..Position::new(line + 1, 0)
+                } else if line > 0 && !cut_lines.contains(&(line - 1)) {
+                    // The last line has no newline of its own; take the one before it
+                    let previous = buffer.line(line - 1)?;
+                    Position::new(line - 1, grapheme_count(&previous))..line_end
+                } else {
+                    Position::new(line, 0)..line_end
+                };
+                Ok(Edit { range, text: String::new() })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let placed = apply_edits(buffer, edits)?;
+        for (selection, range) in selections.selections_mut().iter_mut().zip(placed) {
+            *selection = Selection::collapsed(range.start);
+        }
+        selections.merge_overlapping();
+        Ok(())
+    }
+
+    /// Paste the clipboard's text at every selection as one undo step
+    ///
+    /// Selected text is replaced and cursors end up after the pasted text.
+    /// Lines copied from empty selections go above empty selections, whose
+    /// cursors keep their column.
+    pub fn paste(&mut self, buffer: &mut Buffer, selections: &mut SelectionSet) -> Result<()> {
+        let text = self.clipboard.get_text()?;
+        let copied = match &self.last_copy {
+            Some(copied) if copied.joined() == text => copied.clone(),
+            _ => CopiedText { entries: vec![text], whole_lines: false },
+        };
+
+        // Entries are distributed to the cursors in document order
+        let count = selections.selections().len();
+        let mut order: Vec<usize> = (0..count).collect();
+        order.sort_by_key(|index| selections.selections()[*index].to_range().start);
+        let mut entries = vec![copied.joined(); count];
+        if count > 1 && copied.entries.len() == count {
+            for (index, entry) in order.into_iter().zip(&copied.entries) {
+                entries[index] = entry.clone();
+            }
+        }
+
+        let line_pastes: Vec<bool> = selections
+            .selections()
+            .iter()
+            .map(|selection| copied.whole_lines && selection.is_collapsed())
+            .collect();
+        let edits = selections
+            .selections()
+            .iter()
+            .zip(entries)
+            .zip(&line_pastes)
+            .map(|((selection, text), line_paste)| {
+                let range = selection.to_range();
+                if *line_paste {
+                    let line_start = Position::new(range.start.line, 0);
+                    Edit { range: line_start..line_start, text }
+                } else {
+                    Edit { range: range.start..range.end, text }
+                }
+            })
+            .collect();
+
+        let placed = apply_edits(buffer, edits)?;
+        let updates = placed.into_iter().zip(line_pastes);
+        let selections_mut = selections.selections_mut();
+        for (selection, (range, line_paste)) in selections_mut.iter_mut().zip(updates) {
+            let position = if line_paste {
+                Position::new(range.end.line, selection.cursor.position.column)
+            } else {
+                range.end
+            };
+            *selection = Selection::collapsed(position);
+        }
+        selections.merge_overlapping();
+        Ok(())
+    }
+}
+
+/// Replacement of a range by text
+struct Edit {
+    range: Range<Position>,
+    text: String,
+}
+
+/// Apply edits as one undo group, returning where each edit's text ended up
+///
+/// Edits at the same empty range are combined, in order. The returned ranges
+/// are in the same order as `edits`.
+fn apply_edits(buffer: &mut Buffer, edits: Vec<Edit>) -> Result<Vec<Range<Position>>> {
+    let mut order: Vec<usize> = (0..edits.len()).collect();
+    order.sort_by_key(|index| (edits[*index].range.start, edits[*index].range.end, *index));
+
+    // Insertions at the start of another edit are folded into it
+    let mut merged: Vec<(Range<Position>, String, Vec<usize>)> = Vec::new();
+    for index in order {
+        let edit = &edits[index];
+        match merged.last_mut() {
+            Some((range, text, members)) if range.start == edit.range.start => {
+                if range.start == range.end {
+                    text.push_str(&edit.text);
+                    *range = edit.range.clone();
+                } else if *range != edit.range {
+                    return Err(
+                        EditorError::InvalidRange(
+                            format!("Range {:?} overlaps range {:?}", range, edit.range)
+                        )
+                    );
+                }
+                members.push(index);
+            }
+            _ => merged.push((edit.range.clone(), edit.text.clone(), vec![index])),
+        }
+    }
+
+    // Where each merged edit lands once earlier edits have been applied
+    let mut placed = vec![Position::zero()..Position::zero(); edits.len()];
+    let mut line_delta = 0isize;
+    let mut last_end: Option<(Position, Position)> = None;
+    for (range, text, members) in &merged {
+        let line = range.start.line.saturating_add_signed(line_delta);
+        let column = match last_end {
+            Some((old_end, new_end)) if old_end.line == range.start.line =>
+                new_end.column + (range.start.column - old_end.column),
+            _ => range.start.column,
+        };
+        let start = Position::new(line, column);
+        let end = end_of_text(start, text);
+
+        // Members of a merged insertion each end after their own text
+        let mut member_start = start;
+        for index in members {
+            let member_end = end_of_text(member_start, &edits[*index].text);
+            placed[*index] = start..member_end;
+            member_start = member_end;
+        }
+
+        let removed_lines = (range.end.line - range.start.line) as isize;
+        let added_lines = (end.line - start.line) as isize;
+        line_delta += added_lines - removed_lines;
+        last_end = Some((range.end, end));
+    }
+
+    buffer.replace_ranges(
+        merged
+            .into_iter()
+            .map(|(range, text, _)| (range, text))
+            .collect()
+    )?;
+    Ok(placed)
+}
+
+fn grapheme_count(text: &str) -> usize {
+    use unicode_segmentation::UnicodeSegmentation;
+    text.trim_end_matches(['\r', '\n']).graphemes(true).count()
+}
+
+/// Ring of recently copied strings, newest first
+#[derive(Debug, Clone)]
+pub struct ClipboardRing {
+    entries: VecDeque<String>,
+    capacity: usize,
+    /// Index of the entry that paste currently uses
+    cursor: usize,
+}
+
+impl ClipboardRing {
+    /// Create a ring holding at most `capacity` entries
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity: capacity.max(1),
+            cursor: 0,
+        }
+    }
+
+    /// Record copied or cut text
+    ///
+    /// Consecutive duplicates are ignored, and the oldest entry is dropped once
+    /// the ring is full. Pushing resets cycling to the newest entry.
+    pub fn push(&mut self, text: String) {
+        self.cursor = 0;
+
+        if self.entries.front() == Some(&text) {
+            return;
+        }
+
+        self.entries.push_front(text);
+        self.entries.truncate(self.capacity);
+    }
+
+    /// Get the entry paste would currently insert
+    pub fn current(&self) -> Option<&str> {
+        self.entries.get(self.cursor).map(|s| s.as_str())
+    }
+
+    /// Move to the next older entry, wrapping back to the newest
+    pub fn cycle(&mut self) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+
+        self.cursor = (self.cursor + 1) % self.entries.len();
+        self.current()
+    }
+
+    /// Get all entries, newest first
+    pub fn entries(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|s| s.as_str())
+    }
+
+    /// Get number of entries
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check if the ring is empty
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Get maximum number of entries
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Remove all entries
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.cursor = 0;
+    }
+}
+
+impl Default for ClipboardRing {
+    fn default() -> Self {
+        Self::new(10)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BufferId;
+
+    fn cursors(positions: &[(usize, usize)]) -> SelectionSet {
+        let (line, column) = positions[0];
+        let mut set = SelectionSet::new(Selection::collapsed(Position::new(line, column)));
+        for &(line, column) in &positions[1..] {
+            set.add_selection(Selection::collapsed(Position::new(line, column)));
+        }
+        set
+    }
+
+    fn selected(start: (usize, usize), end: (usize, usize)) -> Selection {
+        Selection::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+    }
+
+    fn cursor_positions(set: &SelectionSet) -> Vec<Position> {
+        set.selections()
+            .iter()
+            .map(|selection| selection.cursor.position)
+            .collect()
+    }
+
+    #[test]
+    fn test_single_selection_paste() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "hello world");
+        let mut clipboard = EditorClipboard::new(MockClipboard::new());
+
+        let selection = SelectionSet::new(selected((0, 6), (0, 11)));
+        clipboard.copy(&buffer, &selection).unwrap();
+        assert_eq!(clipboard.clipboard().get_text().unwrap(), "world");
+
+        let mut set = SelectionSet::new(selected((0, 0), (0, 5)));
+        clipboard.paste(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "world world");
+        assert_eq!(cursor_positions(&set), vec![Position::new(0, 5)]);
+
+        // Text copied elsewhere is pasted as is
+        clipboard.clipboard().set_text("big\n").unwrap();
+        clipboard.paste(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "worldbig\n world");
+        assert_eq!(cursor_positions(&set), vec![Position::new(1, 0)]);
+    }
+
+    #[test]
+    fn test_multi_selection_paste() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "one two three\nx\ny\nz");
+        let mut clipboard = EditorClipboard::new(MockClipboard::new());
+
+        // The primary selection comes last in the document
+        let mut selection = SelectionSet::new(selected((0, 8), (0, 13)));
+        selection.add_selection(selected((0, 0), (0, 3)));
+        selection.add_selection(selected((0, 4), (0, 7)));
+        clipboard.copy(&buffer, &selection).unwrap();
+        assert_eq!(clipboard.clipboard().get_text().unwrap(), "one\ntwo\nthree");
+
+        // One entry per cursor, in document order
+        let mut set = cursors(&[(3, 1), (1, 1), (2, 1)]);
+        clipboard.paste(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "one two three\nxone\nytwo\nzthree");
+        assert_eq!(
+            cursor_positions(&set),
+            vec![Position::new(1, 4), Position::new(2, 4), Position::new(3, 6)]
+        );
+        buffer.undo().unwrap();
+        assert_eq!(buffer.text(), "one two three\nx\ny\nz");
+
+        // With a different number of cursors, each gets the whole text
+        let mut set = cursors(&[(1, 1), (2, 1)]);
+        clipboard.paste(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "one two three\nxone\ntwo\nthree\nyone\ntwo\nthree\nz");
+    }
+
+    #[test]
+    fn test_line_copy_paste() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "first\nsecond\nthird");
+        let mut clipboard = EditorClipboard::new(MockClipboard::new());
+
+        clipboard.copy(&buffer, &cursors(&[(2, 3)])).unwrap();
+        assert_eq!(clipboard.clipboard().get_text().unwrap(), "third\n");
+
+        // Pasted above the cursor's line, keeping the cursor's column
+        let mut set = cursors(&[(0, 2)]);
+        clipboard.paste(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "third\nfirst\nsecond\nthird");
+        assert_eq!(cursor_positions(&set), vec![Position::new(1, 2)]);
+
+        // Several cursors copy one line each
+        clipboard.copy(&buffer, &cursors(&[(1, 0), (2, 0)])).unwrap();
+        let mut set = cursors(&[(0, 0), (3, 1)]);
+        clipboard.paste(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "first\nthird\nfirst\nsecond\nsecond\nthird");
+        assert_eq!(cursor_positions(&set), vec![Position::new(1, 0), Position::new(5, 1)]);
+    }
+
+    #[test]
+    fn test_cut() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "first\nsecond\nthird");
+        let mut clipboard = EditorClipboard::new(MockClipboard::new());
+
+        let mut set = cursors(&[(1, 2)]);
+        clipboard.cut(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "first\nthird");
+        assert_eq!(cursor_positions(&set), vec![Position::new(1, 0)]);
+
+        // The last line takes the newline before it
+        let mut set = cursors(&[(1, 0)]);
+        clipboard.cut(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "first");
+        assert_eq!(clipboard.clipboard().get_text().unwrap(), "third\n");
+
+        let mut set = SelectionSet::new(selected((0, 1), (0, 3)));
+        clipboard.cut(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "fst");
+        assert_eq!(clipboard.clipboard().get_text().unwrap(), "ir");
+    }
+
+    #[test]
+    fn test_copy_and_cut_feed_the_ring() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "one two");
+        let mut clipboard = EditorClipboard::new(MockClipboard::new());
+
+        clipboard.copy(&buffer, &SelectionSet::new(selected((0, 0), (0, 3)))).unwrap();
+        clipboard.copy(&buffer, &SelectionSet::new(selected((0, 4), (0, 7)))).unwrap();
+        assert_eq!(clipboard.ring().current(), Some("two"));
+        assert_eq!(clipboard.ring_mut().cycle(), Some("one"));
+
+        let mut set = SelectionSet::new(selected((0, 3), (0, 7)));
+        clipboard.cut(&mut buffer, &mut set).unwrap();
+        assert_eq!(buffer.text(), "one");
+        assert_eq!(clipboard.ring().entries().collect::<Vec<_>>(), [" two", "two", "one"]);
+    }
+
+    #[test]
+    fn test_push_and_cycle() {
+        let mut ring = ClipboardRing::new(5);
+        ring.push("one".to_string());
+        ring.push("two".to_string());
+        ring.push("three".to_string());
+
+        assert_eq!(ring.current(), Some("three"));
+        assert_eq!(ring.cycle(), Some("two"));
+        assert_eq!(ring.cycle(), Some("one"));
+        // Wraps back to the newest entry
+        assert_eq!(ring.cycle(), Some("three"));
+
+        // A new copy resets cycling
+        ring.cycle();
+        ring.push("four".to_string());
+        assert_eq!(ring.current(), Some("four"));
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut ring = ClipboardRing::new(3);
+        for text in ["a", "b", "c", "d", "e"] {
+            ring.push(text.to_string());
+        }
+
+        assert_eq!(ring.len(), 3);
+        assert_eq!(ring.entries().collect::<Vec<_>>(), vec!["e", "d", "c"]);
+    }
+
+    #[test]
+    fn test_consecutive_duplicates() {
+        let mut ring = ClipboardRing::new(5);
+        ring.push("a".to_string());
+        ring.push("a".to_string());
+        ring.push("b".to_string());
+        ring.push("a".to_string());
+
+        assert_eq!(ring.entries().collect::<Vec<_>>(), vec!["a", "b", "a"]);
+    }
+
+    #[test]
+    fn test_cycle_empty() {
+        let mut ring = ClipboardRing::default();
+        assert_eq!(ring.cycle(), None);
+        assert_eq!(ring.current(), None);
+    }
+}
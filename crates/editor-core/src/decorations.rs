@@ -0,0 +1,77 @@
+//! Editor decorations shared by the UI and language tooling
+//!
+//! These are plain data, so protocol crates can produce them without
+//! depending on the UI that draws them.
+
+use std::ops::Range;
+
+/// Inline decoration (squiggles, code lens, hints)
+#[derive(Debug, Clone)]
+pub struct InlineDecoration {
+    pub range: Range<usize>,
+    pub kind: InlineDecorationKind,
+}
+
+/// Type of inline decoration
+#[derive(Debug, Clone)]
+pub enum InlineDecorationKind {
+    /// Error squiggle (red wavy underline)
+    ErrorSquiggle,
+    /// Warning squiggle (yellow wavy underline)
+    WarningSquiggle,
+    /// Info squiggle (blue wavy underline)
+    InfoSquiggle,
+    /// Hint squiggle (gray wavy underline)
+    HintSquiggle,
+    /// Code lens above the line
+    CodeLens(String),
+    /// Inline hint (parameter name, type hint)
+    InlineHint(String),
+    /// Matching bracket highlight
+    MatchingBracket,
+    /// Uncommitted IME composition, drawn underlined at the range start
+    /// with a caret `cursor` bytes into the text
+    Preedit {
+        text: String,
+        cursor: usize,
+    },
+}
+
+/// Gutter decoration (line numbers, icons, etc.)
+#[derive(Debug, Clone)]
+pub struct GutterDecoration {
+    pub line: usize,
+    pub kind: GutterDecorationKind,
+}
+
+/// Type of gutter decoration
+#[derive(Debug, Clone)]
+pub enum GutterDecorationKind {
+    /// Line number (always present)
+    LineNumber,
+    /// Folding marker (expand/collapse)
+    FoldingMarker {
+        folded: bool,
+    },
+    /// Diagnostic marker
+    Diagnostic(DiagnosticSeverity),
+    /// Git diff indicator
+    GitDiff(GitDiffKind),
+}
+
+/// Diagnostic severity level
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiagnosticSeverity {
+    Error,
+    Warning,
+    Info,
+    Hint,
+}
+
+/// Git diff kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitDiffKind {
+    Added,
+    Modified,
+    Deleted,
+}
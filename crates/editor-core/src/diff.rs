@@ -0,0 +1,279 @@
+//! Sequence diffing using Myers' O(ND) algorithm in linear space
+
+use std::ops::Range;
+
+/// A changed region between two sequences
+///
+/// An empty `old` range is an insertion at `old.start`, an empty `new` range
+/// a deletion, and two non-empty ranges a replacement.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffHunk {
+    /// Range in the old sequence
+    pub old: Range<usize>,
+    /// Range in the new sequence
+    pub new: Range<usize>,
+}
+
+/// Compute the changed regions between two sequences
+///
+/// Hunks are in order and never adjacent; everything between them is equal
+/// in both sequences. The common prefix and suffix are trimmed before the
+/// diff runs, so small edits to long sequences stay cheap.
+pub fn diff_slices<T: PartialEq>(old: &[T], new: &[T]) -> Vec<DiffHunk> {
+    let prefix = old
+        .iter()
+        .zip(new)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut hunks = Vec::new();
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for (x, y) in matching_pairs(a, b).into_iter().chain(std::iter::once((a.len(), b.len()))) {
+        if x > old_pos || y > new_pos {
+            hunks.push(DiffHunk {
+                old: prefix + old_pos..prefix + x,
+                new: prefix + new_pos..prefix + y,
+            });
+        }
+        old_pos = x + 1;
+        new_pos = y + 1;
+    }
+    hunks
+}
+
+/// Pairs of equal elements on a shortest edit path, in ascending order
+///
+/// Uses the linear-space variant of Myers' algorithm: the middle snake of a
+/// shortest path splits the problem in two, which are solved recursively, so
+/// memory stays O(N + M) however different the sequences are.
+fn matching_pairs<T: PartialEq>(a: &[T], b: &[T]) -> Vec<(usize, usize)> {
+    let max_d = ((a.len() + b.len()).div_ceil(2) + 1) as isize;
+    let mut myers = Myers {
+        a,
+        b,
+        forward: vec![0; 2 * (max_d as usize) + 3],
+        backward: vec![0; 2 * (max_d as usize) + 3],
+        offset: max_d + 1,
+        pairs: Vec::new(),
+    };
+    myers.collect(0..a.len(), 0..b.len());
+    myers.pairs
+}
+
+/// State of a linear-space Myers diff
+struct Myers<'a, T> {
+    a: &'a [T],
+    b: &'a [T],
+    /// Furthest x reached on each diagonal k = x - y, searching forward
+    forward: Vec<isize>,
+    /// Furthest x reached on each diagonal, searching back from the ends
+    backward: Vec<isize>,
+    /// Index of diagonal 0 in the frontiers
+    offset: isize,
+    /// Matching pairs found so far, in ascending order
+    pairs: Vec<(usize, usize)>,
+}
+
+impl<T: PartialEq> Myers<'_, T> {
+    /// Collect the matching pairs between two subranges
+    fn collect(&mut self, mut old: Range<usize>, mut new: Range<usize>) {
+        while !old.is_empty() && !new.is_empty() && self.a[old.start] == self.b[new.start] {
+            self.pairs.push((old.start, new.start));
+            old.start += 1;
+            new.start += 1;
+        }
+        let mut suffix = 0;
+        while
+            suffix < old.len() &&
+            suffix < new.len() &&
+            self.a[old.end - 1 - suffix] == self.b[new.end - 1 - suffix]
+        {
+            suffix += 1;
+        }
+        old.end -= suffix;
+        new.end -= suffix;
+
+        if !old.is_empty() && !new.is_empty() {
+            let (x, y) = self.middle_snake(old.clone(), new.clone());
+            self.collect(old.start..x, new.start..y);
+            self.collect(x..old.end, y..new.end);
+        }
+
+        for i in 0..suffix {
+            self.pairs.push((old.end + i, new.end + i));
+        }
+    }
+
+    /// Start of the middle snake of a shortest path between two subranges
+    ///
+    /// The ranges must be non-empty and differ in their first and last
+    /// elements.
+    fn middle_snake(&mut self, old: Range<usize>, new: Range<usize>) -> (usize, usize) {
+        let n = old.len() as isize;
+        let m = new.len() as isize;
+        let delta = n - m;
+        let odd = delta.rem_euclid(2) == 1;
+        let max_d = (n + m + 1).div_euclid(2) + 1;
+        let at = |k: isize| (k + self.offset) as usize;
+        let a = |x: isize| &self.a[old.start + (x as usize)];
+        let b = |y: isize| &self.b[new.start + (y as usize)];
+
+        self.forward[at(1)] = 0;
+        self.backward[at(1)] = 0;
+        for d in 0..max_d {
+            for k in (-d..=d).rev().step_by(2) {
+                let forward = &self.forward;
+                let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                    forward[at(k + 1)]
+                } else {
+                    forward[at(k - 1)] + 1
+                };
+                let (snake_x, snake_y) = (x, x - k);
+                let mut y = snake_y;
+                while x < n && y >= 0 && y < m && a(x) == b(y) {
+                    x += 1;
+                    y += 1;
+                }
+                self.forward[at(k)] = x;
+
+                let overlaps = odd && (k - delta).abs() < d;
+                if overlaps && x + self.backward[at(delta - k)] >= n {
+                    return (old.start + (snake_x as usize), new.start + (snake_y as usize));
+                }
+            }
+
+            for k in (-d..=d).rev().step_by(2) {
+                let backward = &self.backward;
+                let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                    backward[at(k + 1)]
+                } else {
+                    backward[at(k - 1)] + 1
+                };
+                let mut y = x - k;
+                while x < n && y >= 0 && y < m && a(n - x - 1) == b(m - y - 1) {
+                    x += 1;
+                    y += 1;
+                }
+                self.backward[at(k)] = x;
+
+                let overlaps = !odd && (k - delta).abs() <= d;
+                if overlaps && x + self.forward[at(delta - k)] >= n {
+                    return (old.start + ((n - x) as usize), new.start + ((m - y) as usize));
+                }
+            }
+        }
+
+        // Unreachable for non-empty ranges; replacing everything is still valid
+        (old.end, new.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical_sequences() {
+        assert!(diff_slices(&[1, 2, 3], &[1, 2, 3]).is_empty());
+        assert!(diff_slices::<u8>(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn test_insert_delete_replace() {
+        let old = ["a", "b", "c", "d"];
+        let new = ["a", "x", "c", "d", "e"];
+
+        assert_eq!(diff_slices(&old, &new), vec![
+            DiffHunk { old: 1..2, new: 1..2 },
+            DiffHunk { old: 4..4, new: 4..5 }
+        ]);
+        assert_eq!(diff_slices(&new, &["a", "x"]), vec![DiffHunk { old: 2..5, new: 2..2 }]);
+    }
+
+    #[test]
+    fn test_shortest_edit_script() {
+        // Classic example from Myers' paper: D = 5
+        let old: Vec<char> = "abcabba".chars().collect();
+        let new: Vec<char> = "cbabac".chars().collect();
+        let hunks = diff_slices(&old, &new);
+
+        let edits: usize = hunks
+            .iter()
+            .map(|hunk| hunk.old.len() + hunk.new.len())
+            .sum();
+        assert_eq!(edits, 5);
+
+        // Applying the hunks to old yields new
+        let mut result = Vec::new();
+        let mut pos = 0;
+        for hunk in &hunks {
+            result.extend_from_slice(&old[pos..hunk.old.start]);
+            result.extend_from_slice(&new[hunk.new.clone()]);
+            pos = hunk.old.end;
+        }
+        result.extend_from_slice(&old[pos..]);
+        assert_eq!(result, new);
+    }
+
+    /// Length of the longest common subsequence, by dynamic programming
+    fn lcs_len<T: PartialEq>(a: &[T], b: &[T]) -> usize {
+        let mut row = vec![0; b.len() + 1];
+        for x in a {
+            let mut diagonal = 0;
+            for (j, y) in b.iter().enumerate() {
+                let above = row[j + 1];
+                row[j + 1] = if x == y { diagonal + 1 } else { above.max(row[j]) };
+                diagonal = above;
+            }
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn test_edit_scripts_are_minimal() {
+        // Deterministic pseudo-random sequences over a small alphabet
+        let mut seed = 7u32;
+        let mut next = move || {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (seed >> 16) % 4
+        };
+
+        for _ in 0..200 {
+            let old: Vec<u32> = (0..next() * 5).map(|_| next()).collect();
+            let new: Vec<u32> = (0..next() * 5).map(|_| next()).collect();
+            let hunks = diff_slices(&old, &new);
+
+            let edits: usize = hunks
+                .iter()
+                .map(|hunk| hunk.old.len() + hunk.new.len())
+                .sum();
+            assert_eq!(edits, old.len() + new.len() - 2 * lcs_len(&old, &new));
+
+            let mut result = Vec::new();
+            let mut pos = 0;
+            for hunk in &hunks {
+                result.extend_from_slice(&old[pos..hunk.old.start]);
+                result.extend_from_slice(&new[hunk.new.clone()]);
+                pos = hunk.old.end;
+            }
+            result.extend_from_slice(&old[pos..]);
+            assert_eq!(result, new);
+        }
+    }
+
+    #[test]
+    fn test_completely_different_sequences() {
+        let old: Vec<usize> = (0..5000).collect();
+        let new: Vec<usize> = (5000..10000).collect();
+        assert_eq!(diff_slices(&old, &new), vec![DiffHunk { old: 0..5000, new: 0..5000 }]);
+    }
+}
@@ -6,11 +6,44 @@
 pub mod buffer;
 pub mod selection;
 pub mod operations;
+pub mod clipboard;
+pub mod decorations;
+pub mod diff;
+pub mod registry;
+pub mod signature;
+pub mod snippets;
 
 // Re-export commonly used types
-pub use buffer::{ Buffer, BufferId, BufferSnapshot, LineEnding };
-pub use selection::{ Selection, Cursor, Position, Affinity, SelectionSet, Granularity };
-pub use operations::{ EditOperation, OperationType, UndoHistory };
+pub use buffer::{
+    recover_backups,
+    Buffer,
+    BufferChange,
+    BufferId,
+    BufferSnapshot,
+    ChangeCallback,
+    DocumentStats,
+    LineEnding,
+    SubscriptionId,
+    TextChange,
+    TextEdit,
+};
+pub use selection::{
+    Selection,
+    Cursor,
+    Position,
+    Affinity,
+    SelectionSet,
+    Granularity,
+    TextRange,
+};
+pub use operations::{ EditOperation, OperationType, UndoHistory, UndoLink };
+pub use clipboard::{ Clipboard, ClipboardRing, CopiedText, EditorClipboard, MockClipboard };
+#[cfg(feature = "system-clipboard")]
+pub use clipboard::SystemClipboard;
+pub use diff::{ diff_slices, DiffHunk };
+pub use registry::{ BufferHandle, BufferRegistry };
+pub use snippets::{ Placeholder, Snippet, SnippetSession, TabStop };
+pub use ropey::RopeSlice;
 
 /// Version number for tracking buffer changes
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -53,6 +86,16 @@ pub enum EditorError {
 
     #[error("Redo history exhausted")]
     RedoHistoryExhausted,
+
+    #[error("Clipboard error: {0}")] ClipboardError(String),
+
+    #[error("Invalid snippet: {0}")] InvalidSnippet(String),
+
+    #[error("Buffer is at version {actual}, but the edits were made against version {expected}")]
+    VersionMismatch {
+        expected: u64,
+        actual: u64,
+    },
 }
 
 pub type Result<T> = std::result::Result<T, EditorError>;
@@ -2,6 +2,7 @@
 
 use crate::selection::Position;
 use serde::{ Deserialize, Serialize };
+use std::sync::atomic::{ AtomicU64, Ordering };
 use std::time::{ SystemTime, UNIX_EPOCH };
 
 /// Type of edit operation
@@ -114,11 +115,34 @@ fn current_timestamp() -> u64 {
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
 }
 
+/// Ties undo groups in several buffers into one edit
+///
+/// Groups that share a link were recorded together, e.g. by a rename across
+/// files, and are meant to be undone and redone together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct UndoLink(u64);
+
+impl UndoLink {
+    /// A link no other call has returned
+    pub fn new() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        Self(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for UndoLink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Group of operations that should be undone/redone together
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OperationGroup {
     pub operations: Vec<EditOperation>,
     pub timestamp: u64,
+    /// Set when the group belongs to an edit spanning several buffers
+    pub link: Option<UndoLink>,
 }
 
 impl OperationGroup {
@@ -127,6 +151,7 @@ impl OperationGroup {
         Self {
             operations: vec![operation],
             timestamp,
+            link: None,
         }
     }
 
@@ -202,6 +227,70 @@ impl UndoHistory {
         self.enforce_limits();
     }
 
+    /// Record several operations as a single undo group
+    ///
+    /// The group is closed immediately so later edits never merge into it.
+    pub fn record_group(&mut self, operations: Vec<EditOperation>) {
+        let mut operations = operations.into_iter();
+        let Some(first) = operations.next() else {
+            return;
+        };
+
+        self.redo_stack.clear();
+        self.create_boundary();
+
+        let mut group = OperationGroup::new(first);
+        for operation in operations {
+            group.add_operation(operation);
+        }
+        self.undo_stack.push(group);
+
+        self.enforce_limits();
+    }
+
+    /// Record an operation in its own undo group
+    ///
+    /// Structural edits (paste, reindent, formatting) use this so they never
+    /// coalesce with surrounding typing.
+    pub fn record_isolated(&mut self, operation: EditOperation) {
+        self.record_group(vec![operation]);
+    }
+
+    /// Link the last closed undo group to groups in other buffers
+    ///
+    /// Returns false if there is no closed group to link.
+    pub fn link_last_group(&mut self, link: UndoLink) -> bool {
+        self.create_boundary();
+        match self.undo_stack.last_mut() {
+            Some(group) => {
+                group.link = Some(link);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Link of the group the next undo reverts
+    pub fn undo_link(&self) -> Option<UndoLink> {
+        if self.current_group.is_some() {
+            return None;
+        }
+        self.undo_stack.last().and_then(|group| group.link)
+    }
+
+    /// Link of the group the next redo reapplies
+    pub fn redo_link(&self) -> Option<UndoLink> {
+        self.redo_stack.last().and_then(|group| group.link)
+    }
+
+    /// Whether any group that can be undone or redone carries `link`
+    pub fn has_link(&self, link: UndoLink) -> bool {
+        self.undo_stack
+            .iter()
+            .chain(&self.redo_stack)
+            .any(|group| group.link == Some(link))
+    }
+
     /// Force a boundary in the undo history
     pub fn create_boundary(&mut self) {
         if let Some(group) = self.current_group.take() {
@@ -302,4 +391,34 @@ mod tests {
         assert!(history.can_undo());
         assert!(!history.can_redo());
     }
+
+    #[test]
+    fn test_undo_link_follows_group() {
+        let mut history = UndoHistory::new();
+        let link = UndoLink::new();
+        assert!(!history.link_last_group(link));
+
+        let op = |column: usize, text: &str| {
+            EditOperation::insert(
+                Position::new(0, column),
+                text.to_string(),
+                Position::new(0, column + text.len())
+            )
+        };
+        history.record_group(vec![op(0, "rename")]);
+        assert!(history.link_last_group(link));
+        assert_eq!(history.undo_link(), Some(link));
+
+        // Typing after the linked group hides it until it is undone
+        history.record_operation(op(6, "x"));
+        assert_eq!(history.undo_link(), None);
+        history.undo();
+        assert_eq!(history.undo_link(), Some(link));
+
+        history.undo();
+        assert_eq!(history.undo_link(), None);
+        assert_eq!(history.redo_link(), Some(link));
+        assert!(history.has_link(link));
+        assert!(!history.has_link(UndoLink::new()));
+    }
 }
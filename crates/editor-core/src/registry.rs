@@ -0,0 +1,138 @@
+//! Shared, thread-safe storage of open buffers
+
+use crate::{ Buffer, BufferId, BufferSnapshot };
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::sync::atomic::{ AtomicU64, Ordering };
+use std::sync::{ Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard };
+
+/// Shared handle to a buffer
+///
+/// Hold its lock only for the duration of one read or edit.
+pub type BufferHandle = Arc<RwLock<Buffer>>;
+
+/// Open buffers keyed by ID
+///
+/// The registry's own lock is never held while a buffer is locked, so
+/// looking up buffers while editing one, or taking a snapshot while another
+/// thread edits, cannot deadlock.
+pub struct BufferRegistry {
+    buffers: RwLock<HashMap<BufferId, BufferHandle>>,
+    next_id: AtomicU64,
+}
+
+impl BufferRegistry {
+    pub fn new() -> Self {
+        Self {
+            buffers: RwLock::new(HashMap::new()),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Create a buffer from text and return its ID
+    pub fn create(&self, text: &str) -> BufferId {
+        let id = self.next_id();
+        self.insert(Buffer::from_text(id, text));
+        id
+    }
+
+    /// Load a file into a new buffer and return its ID
+    pub fn open(&self, path: PathBuf) -> std::io::Result<BufferId> {
+        let buffer = Buffer::from_file(self.next_id(), path)?;
+        let id = buffer.id();
+        self.insert(buffer);
+        Ok(id)
+    }
+
+    /// Get a handle to a buffer
+    pub fn get(&self, id: BufferId) -> Option<BufferHandle> {
+        self.map().get(&id).cloned()
+    }
+
+    /// Remove a buffer, returning its handle
+    ///
+    /// Clones of the handle stay valid; the buffer is dropped with the last
+    /// one.
+    pub fn remove(&self, id: BufferId) -> Option<BufferHandle> {
+        self.map_mut().remove(&id)
+    }
+
+    /// Snapshot of a buffer's current content
+    ///
+    /// Waits for an edit in progress on that buffer to finish.
+    pub fn snapshot(&self, id: BufferId) -> Option<BufferSnapshot> {
+        // Release the registry lock before locking the buffer
+        let handle = self.get(id)?;
+        let buffer = read_buffer(&handle);
+        Some(buffer.snapshot())
+    }
+
+    /// ID of the buffer showing a file, if one is open
+    ///
+    /// Paths are compared as given and, when both exist, canonicalized. Reads
+    /// every buffer, so do not call this while holding a buffer's write lock.
+    pub fn find_by_path(&self, path: &Path) -> Option<BufferId> {
+        let canonical = path.canonicalize().ok();
+        let handles: Vec<(BufferId, BufferHandle)> = self
+            .map()
+            .iter()
+            .map(|(id, handle)| (*id, handle.clone()))
+            .collect();
+
+        handles.into_iter().find_map(|(id, handle)| {
+            let buffer = read_buffer(&handle);
+            let file = buffer.file_path()?;
+            let same_file = canonical.is_some() && file.canonicalize().ok() == canonical;
+            (file == path || same_file).then_some(id)
+        })
+    }
+
+    /// IDs of all open buffers, in no particular order
+    pub fn ids(&self) -> Vec<BufferId> {
+        self.map().keys().copied().collect()
+    }
+
+    /// Get number of open buffers
+    pub fn len(&self) -> usize {
+        self.map().len()
+    }
+
+    /// Check if no buffers are open
+    pub fn is_empty(&self) -> bool {
+        self.map().is_empty()
+    }
+
+    fn next_id(&self) -> BufferId {
+        BufferId::new(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn insert(&self, buffer: Buffer) {
+        self.map_mut().insert(buffer.id(), Arc::new(RwLock::new(buffer)));
+    }
+
+    // A panic while a lock was held leaves the map itself consistent, so
+    // poisoning is ignored
+    fn map(&self) -> RwLockReadGuard<'_, HashMap<BufferId, BufferHandle>> {
+        self.buffers.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn map_mut(&self) -> RwLockWriteGuard<'_, HashMap<BufferId, BufferHandle>> {
+        self.buffers.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for BufferRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Lock a buffer for reading, even if a writer panicked
+pub fn read_buffer(handle: &BufferHandle) -> RwLockReadGuard<'_, Buffer> {
+    handle.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Lock a buffer for writing, even if a writer panicked
+pub fn write_buffer(handle: &BufferHandle) -> RwLockWriteGuard<'_, Buffer> {
+    handle.write().unwrap_or_else(PoisonError::into_inner)
+}
@@ -1,6 +1,9 @@
 //! Selection and cursor management
 
+use crate::buffer::{ Buffer, WordBoundaryFinder };
+use crate::Result;
 use serde::{ Deserialize, Serialize };
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Represents a position in the text buffer
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -19,6 +22,13 @@ impl Position {
     pub fn zero() -> Self {
         Self { line: 0, column: 0 }
     }
+
+    /// Position `n` columns to the right, or left if negative
+    ///
+    /// The column saturates at 0; the line is unchanged.
+    pub fn offset_by_columns(&self, n: isize) -> Position {
+        Self { line: self.line, column: self.column.saturating_add_signed(n) }
+    }
 }
 
 impl PartialOrd for Position {
@@ -36,6 +46,48 @@ impl Ord for Position {
     }
 }
 
+/// A range of positions, with `start <= end`
+///
+/// The range is half-open: it contains `start` but not `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TextRange {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl TextRange {
+    /// Range between two positions, in either order
+    pub fn new(a: Position, b: Position) -> Self {
+        Self { start: a.min(b), end: a.max(b) }
+    }
+
+    /// Empty range at a position
+    pub fn collapsed(position: Position) -> Self {
+        Self { start: position, end: position }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    pub fn contains(&self, pos: Position) -> bool {
+        self.start <= pos && pos < self.end
+    }
+
+    /// Check if the ranges share at least one position
+    ///
+    /// Ranges that only touch, and empty ranges, do not intersect.
+    pub fn intersects(&self, other: &TextRange) -> bool {
+        !self.is_empty() && !other.is_empty() && self.start < other.end && other.start < self.end
+    }
+}
+
+impl From<(Position, Position)> for TextRange {
+    fn from((a, b): (Position, Position)) -> Self {
+        Self::new(a, b)
+    }
+}
+
 /// Cursor affinity for bidirectional text
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Affinity {
@@ -133,6 +185,11 @@ impl Selection {
         }
     }
 
+    /// Returns the selected range
+    pub fn to_range(&self) -> TextRange {
+        TextRange::new(self.anchor, self.cursor.position)
+    }
+
     /// Returns true if the cursor is at the end of the selection
     pub fn is_forward(&self) -> bool {
         self.cursor.position >= self.anchor
@@ -168,6 +225,11 @@ impl SelectionSet {
         &self.selections
     }
 
+    /// Selections for updating in place; the primary stays the same
+    pub fn selections_mut(&mut self) -> &mut [Selection] {
+        &mut self.selections
+    }
+
     pub fn add_selection(&mut self, selection: Selection) {
         self.selections.push(selection);
     }
@@ -184,44 +246,119 @@ impl SelectionSet {
     }
 
     /// Merge overlapping selections
+    ///
+    /// Merged selections are normalized to forward direction; selections that
+    /// are not merged keep their direction. If the primary selection takes part
+    /// in a merge, the merged selection becomes the primary.
     pub fn merge_overlapping(&mut self) {
         if self.selections.len() <= 1 {
             return;
         }
 
-        // Sort selections by range start
+        // Sort selections by range start, breaking ties by index so the result is deterministic
         let mut sorted_indices: Vec<usize> = (0..self.selections.len()).collect();
-        sorted_indices.sort_by_key(|&i| self.selections[i].range().0);
+        sorted_indices.sort_by_key(|&i| (self.selections[i].to_range().start, i));
 
         let mut merged = Vec::new();
+        let mut new_primary = 0;
         let mut current = self.selections[sorted_indices[0]].clone();
+        let mut current_has_primary = sorted_indices[0] == self.primary;
 
         for &idx in sorted_indices.iter().skip(1) {
             let sel = &self.selections[idx];
-            let current_range = current.range();
-            let sel_range = sel.range();
-
-            if sel_range.0 <= current_range.1 {
-                // Overlapping or adjacent - merge
-                let new_end = current_range.1.max(sel_range.1);
-                current = Selection::new(current_range.0, new_end);
+            let current_range = current.to_range();
+            let sel_range = sel.to_range();
+
+            if sel_range.start <= current_range.end {
+                // Overlapping or adjacent - merge into a forward selection
+                let new_end = current_range.end.max(sel_range.end);
+                current = Selection::new(current_range.start, new_end);
+                current_has_primary |= idx == self.primary;
             } else {
                 // No overlap - push current and start new
-                merged.push(current.clone());
+                if current_has_primary {
+                    new_primary = merged.len();
+                }
+                merged.push(current);
                 current = sel.clone();
+                current_has_primary = idx == self.primary;
             }
         }
+        if current_has_primary {
+            new_primary = merged.len();
+        }
         merged.push(current);
 
-        // Find new primary index
-        let old_primary = &self.selections[self.primary];
-        self.primary = merged
-            .iter()
-            .position(|s| s.range() == old_primary.range())
-            .unwrap_or(0);
-
+        self.primary = new_primary;
         self.selections = merged;
     }
+
+    /// Add a selection on the next occurrence of the primary selection's text
+    ///
+    /// The search starts at the end of the most recently added selection and
+    /// wraps around the buffer; occurrences that are already selected are
+    /// skipped. A collapsed primary selection is first expanded to the word
+    /// under the cursor. Returns whether a selection was added.
+    pub fn select_next_occurrence(&mut self, buffer: &Buffer) -> Result<bool> {
+        if self.primary().is_collapsed() {
+            match word_range_at(buffer, self.primary().cursor.position)? {
+                Some((start, end)) => {
+                    *self.primary_mut() = Selection::new(start, end);
+                }
+                None => {
+                    return Ok(false);
+                }
+            }
+        }
+
+        let text = buffer.text();
+        let (start, end) = self.primary().range();
+        let needle = &text[buffer.position_to_byte_idx(start)?..buffer.position_to_byte_idx(end)?];
+
+        let mut selected = Vec::with_capacity(self.selections.len());
+        for selection in &self.selections {
+            let (start, end) = selection.range();
+            selected.push(buffer.position_to_byte_idx(start)?..buffer.position_to_byte_idx(end)?);
+        }
+
+        let from = selected.last().map_or(0, |range| range.end);
+        let after = text[from..].match_indices(needle).map(|(offset, _)| from + offset);
+        let before = text[..from].match_indices(needle).map(|(offset, _)| offset);
+
+        let Some(found) = after
+            .chain(before)
+            .map(|offset| offset..offset + needle.len())
+            .find(|range| !selected.contains(range)) else {
+            return Ok(false);
+        };
+
+        let start = buffer.char_idx_to_position(text[..found.start].chars().count())?;
+        let end = buffer.char_idx_to_position(text[..found.end].chars().count())?;
+        self.selections.push(Selection::new(start, end));
+        Ok(true)
+    }
+}
+
+/// Range of the word containing `pos`, or ending at it
+fn word_range_at(buffer: &Buffer, pos: Position) -> Result<Option<(Position, Position)>> {
+    let line = buffer.line(pos.line)?;
+    let line = line.trim_end_matches(['\n', '\r']);
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+    let finder = WordBoundaryFinder::new(line);
+
+    let column = [Some(pos.column), pos.column.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .find(|column| graphemes.get(*column).is_some_and(|g| WordBoundaryFinder::is_word_char(g)));
+
+    Ok(
+        column.map(|column| {
+            (
+                Position::new(pos.line, finder.word_start_at(column)),
+                Position::new(pos.line, finder.word_end_at(column)),
+            )
+        })
+    )
 }
 
 impl Default for SelectionSet {
@@ -0,0 +1,47 @@
+//! Signature help shown while typing the arguments of a call
+
+use crate::selection::Position;
+
+/// Parameter hints popup
+#[derive(Debug, Clone)]
+pub struct ParameterHints {
+    /// Hints position
+    pub position: Position,
+    /// Signatures
+    pub signatures: Vec<SignatureInfo>,
+    /// Active signature index
+    pub active_signature: usize,
+    /// Active parameter index
+    pub active_parameter: usize,
+}
+
+impl ParameterHints {
+    pub fn new(position: Position, signatures: Vec<SignatureInfo>) -> Self {
+        Self {
+            position,
+            signatures,
+            active_signature: 0,
+            active_parameter: 0,
+        }
+    }
+
+    /// Get active signature
+    pub fn get_active_signature(&self) -> Option<&SignatureInfo> {
+        self.signatures.get(self.active_signature)
+    }
+}
+
+/// Signature information
+#[derive(Debug, Clone)]
+pub struct SignatureInfo {
+    pub label: String,
+    pub parameters: Vec<ParameterInfo>,
+    pub documentation: Option<String>,
+}
+
+/// Parameter information
+#[derive(Debug, Clone)]
+pub struct ParameterInfo {
+    pub label: String,
+    pub documentation: Option<String>,
+}
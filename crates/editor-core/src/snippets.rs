@@ -0,0 +1,338 @@
+//! Snippet templates with tab stops
+//!
+//! Snippet bodies use the usual placeholder syntax: `$1`, `$2`, ... are tab
+//! stops visited in order, `${1:default}` gives a stop default text and `$0`
+//! is where the cursor ends up. A `\` escapes `$`, `}` and itself.
+
+use crate::buffer::end_of_text;
+use crate::selection::{ Position, Selection, SelectionSet };
+use crate::{ Buffer, EditorError, Result };
+use std::iter::Peekable;
+use std::ops::Range;
+use std::str::CharIndices;
+
+/// A placeholder in a snippet's text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placeholder {
+    pub index: usize,
+    /// Byte range of the placeholder's text in the snippet's text
+    pub range: Range<usize>,
+}
+
+/// A parsed snippet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    /// Text with placeholders replaced by their default text
+    pub text: String,
+    /// Placeholders in the order they appear
+    pub placeholders: Vec<Placeholder>,
+}
+
+impl Snippet {
+    /// Parse a snippet body
+    ///
+    /// Defaults may contain other placeholders. A placeholder used again
+    /// without a default mirrors the default given elsewhere.
+    pub fn parse(body: &str) -> Result<Self> {
+        let mut parser = Parser {
+            chars: body.char_indices().peekable(),
+            text: String::new(),
+            parsed: Vec::new(),
+        };
+        parser.parse_until_close(None)?;
+        let Parser { mut text, mut parsed, .. } = parser;
+
+        // Fill in mirrors of placeholders whose default is given elsewhere
+        for i in 0..parsed.len() {
+            if parsed[i].has_default {
+                continue;
+            }
+            let index = parsed[i].placeholder.index;
+            let Some(mirrored) = parsed
+                .iter()
+                .find(|other| other.has_default && other.placeholder.index == index)
+                .map(|other| text[other.placeholder.range.clone()].to_string()) else {
+                continue;
+            };
+
+            let at = parsed[i].placeholder.range.start;
+            text.insert_str(at, &mirrored);
+            let ancestors = ancestors(&parsed, i);
+            for (j, other) in parsed.iter_mut().enumerate() {
+                let range = &mut other.placeholder.range;
+                if j == i || ancestors.contains(&j) {
+                    range.end += mirrored.len();
+                } else if j > i && range.start >= at {
+                    range.start += mirrored.len();
+                    range.end += mirrored.len();
+                }
+            }
+        }
+
+        let placeholders = parsed
+            .into_iter()
+            .map(|parsed| parsed.placeholder)
+            .collect();
+        Ok(Self { text, placeholders })
+    }
+
+    /// Insert the snippet at `at` and start visiting its tab stops
+    ///
+    /// Lines after the first are indented like the line at `at`.
+    pub fn expand(&self, buffer: &mut Buffer, at: Position) -> Result<SnippetSession> {
+        let line = buffer.line(at.line)?;
+        let indent: String = line
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+
+        // Byte offsets in the indented text of each byte in the snippet's text
+        let mut text = String::with_capacity(self.text.len());
+        let mut offsets = Vec::with_capacity(self.text.len() + 1);
+        for (offset, c) in self.text.char_indices() {
+            offsets.resize(offset + 1, text.len());
+            text.push(c);
+            if c == '\n' {
+                text.push_str(&indent);
+            }
+        }
+        offsets.resize(self.text.len() + 1, text.len());
+
+        buffer.insert(at, &text)?;
+
+        let position_at = |offset: usize| end_of_text(at, &text[..offsets[offset]]);
+        let mut stops: Vec<TabStop> = Vec::new();
+        for placeholder in &self.placeholders {
+            let range = position_at(placeholder.range.start)..position_at(placeholder.range.end);
+            match stops.iter_mut().find(|stop| stop.index == placeholder.index) {
+                Some(stop) => stop.ranges.push(range),
+                None => stops.push(TabStop { index: placeholder.index, ranges: vec![range] }),
+            }
+        }
+
+        // Stops are visited in order with `$0`, or the end of the snippet, last
+        stops.sort_by_key(|stop| (stop.index == 0, stop.index));
+        if stops.last().is_none_or(|stop| stop.index != 0) {
+            let end = position_at(self.text.len());
+            stops.push(TabStop { index: 0, ranges: vec![end..end] });
+        }
+
+        Ok(SnippetSession { stops, active: 0 })
+    }
+}
+
+/// A placeholder found while parsing
+struct ParsedPlaceholder {
+    placeholder: Placeholder,
+    has_default: bool,
+    /// Position in the parsed placeholders of the enclosing placeholder
+    parent: Option<usize>,
+}
+
+struct Parser<'a> {
+    chars: Peekable<CharIndices<'a>>,
+    text: String,
+    /// Placeholders in order of their start
+    parsed: Vec<ParsedPlaceholder>,
+}
+
+impl Parser<'_> {
+    /// Parse text up to the `}` closing the placeholder `parent`, or the end
+    fn parse_until_close(&mut self, parent: Option<usize>) -> Result<()> {
+        while let Some((offset, c)) = self.chars.next() {
+            match c {
+                '\\' => {
+                    match self.chars.next_if(|(_, next)| matches!(next, '$' | '}' | '\\')) {
+                        Some((_, escaped)) => self.text.push(escaped),
+                        None => self.text.push('\\'),
+                    }
+                }
+                '}' if parent.is_some() => {
+                    return Ok(());
+                }
+                '$' => self.parse_placeholder(offset, parent)?,
+                _ => self.text.push(c),
+            }
+        }
+        match parent {
+            Some(parent) => {
+                let offset = self.parsed[parent].placeholder.range.start;
+                Err(invalid(offset, "unclosed placeholder"))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Parse a placeholder after its `$`
+    fn parse_placeholder(&mut self, offset: usize, parent: Option<usize>) -> Result<()> {
+        let braced = self.chars.next_if(|(_, next)| *next == '{').is_some();
+        let mut digits = String::new();
+        while let Some((_, digit)) = self.chars.next_if(|(_, next)| next.is_ascii_digit()) {
+            digits.push(digit);
+        }
+        let Ok(index) = digits.parse::<usize>() else {
+            if braced {
+                return Err(invalid(offset, "expected a tab stop number"));
+            }
+            self.text.push('$');
+            return Ok(());
+        };
+
+        let start = self.text.len();
+        let position = self.parsed.len();
+        self.parsed.push(ParsedPlaceholder {
+            placeholder: Placeholder { index, range: start..start },
+            has_default: false,
+            parent,
+        });
+        if braced {
+            match self.chars.next() {
+                Some((_, '}')) => {}
+                Some((_, ':')) => {
+                    self.parsed[position].has_default = true;
+                    self.parse_until_close(Some(position))?;
+                }
+                _ => {
+                    return Err(invalid(offset, "expected `:` or `}`"));
+                }
+            }
+        }
+        self.parsed[position].placeholder.range.end = self.text.len();
+        Ok(())
+    }
+}
+
+/// Positions of the placeholders enclosing the placeholder at `position`
+fn ancestors(parsed: &[ParsedPlaceholder], position: usize) -> Vec<usize> {
+    let mut ancestors = Vec::new();
+    let mut current = parsed[position].parent;
+    while let Some(parent) = current {
+        ancestors.push(parent);
+        current = parsed[parent].parent;
+    }
+    ancestors
+}
+
+fn invalid(offset: usize, message: &str) -> EditorError {
+    EditorError::InvalidSnippet(format!("{message} at byte {offset}"))
+}
+
+/// A tab stop of an expanded snippet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TabStop {
+    pub index: usize,
+    /// Ranges of the stop's placeholder and its mirrors, in document order
+    pub ranges: Vec<Range<Position>>,
+}
+
+/// Tab stops of a snippet being filled in
+///
+/// The editor moves between stops with Tab and Shift-Tab, selecting every
+/// range of the active stop, and routes typing in a stop through
+/// [`SnippetSession::replace_active`] so mirrors update together.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetSession {
+    stops: Vec<TabStop>,
+    active: usize,
+}
+
+impl SnippetSession {
+    /// Tab stops in visiting order; the last is `$0`
+    pub fn tab_stops(&self) -> &[TabStop] {
+        &self.stops
+    }
+
+    pub fn active(&self) -> &TabStop {
+        &self.stops[self.active]
+    }
+
+    /// Whether the active stop is the final `$0`
+    pub fn is_finished(&self) -> bool {
+        self.active + 1 == self.stops.len()
+    }
+
+    /// Move to the next stop, or return `None` if already at the last
+    pub fn next_stop(&mut self) -> Option<&TabStop> {
+        if self.is_finished() {
+            return None;
+        }
+        self.active += 1;
+        Some(self.active())
+    }
+
+    /// Move to the previous stop, or return `None` if already at the first
+    pub fn prev_stop(&mut self) -> Option<&TabStop> {
+        self.active = self.active.checked_sub(1)?;
+        Some(self.active())
+    }
+
+    /// Selections covering every range of the active stop
+    pub fn selections(&self) -> SelectionSet {
+        let mut ranges = self.active().ranges.iter();
+        let first = ranges.next().expect("tab stops have at least one range");
+        let mut set = SelectionSet::new(Selection::new(first.start, first.end));
+        for range in ranges {
+            set.add_selection(Selection::new(range.start, range.end));
+        }
+        set
+    }
+
+    /// Replace the active stop's text, and that of its mirrors, as one edit
+    pub fn replace_active(&mut self, buffer: &mut Buffer, text: &str) -> Result<()> {
+        let edits: Vec<Range<Position>> = self.active().ranges.clone();
+        buffer.replace_ranges(
+            edits
+                .iter()
+                .map(|range| (range.clone(), text.to_string()))
+                .collect()
+        )?;
+
+        // Map every range through the edits, last first so earlier edits'
+        // positions stay valid
+        for edit in edits.iter().rev() {
+            let new_end = end_of_text(edit.start, text);
+            for stop in &mut self.stops {
+                for range in &mut stop.ranges {
+                    if range == edit {
+                        *range = edit.start..new_end;
+                        continue;
+                    }
+                    range.start = map_start(range.start, edit, new_end);
+                    range.end = map_end(range.end, edit, new_end);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Position of a range's start after `edit` is replaced by text ending at `new_end`
+fn map_start(pos: Position, edit: &Range<Position>, new_end: Position) -> Position {
+    if pos < edit.start {
+        pos
+    } else if pos >= edit.end {
+        shift(pos, edit.end, new_end)
+    } else {
+        edit.start
+    }
+}
+
+/// Position of a range's end after `edit` is replaced by text ending at `new_end`
+fn map_end(pos: Position, edit: &Range<Position>, new_end: Position) -> Position {
+    if pos <= edit.start {
+        pos
+    } else if pos >= edit.end {
+        shift(pos, edit.end, new_end)
+    } else {
+        new_end
+    }
+}
+
+/// Move a position after `old_end` along with it to `new_end`
+fn shift(pos: Position, old_end: Position, new_end: Position) -> Position {
+    if pos.line == old_end.line {
+        Position::new(new_end.line, new_end.column + (pos.column - old_end.column))
+    } else {
+        Position::new(pos.line - old_end.line + new_end.line, pos.column)
+    }
+}
@@ -1,6 +1,8 @@
 //! Comprehensive tests for the text buffer implementation
 
-use editor_core::{ Buffer, BufferId, Position, LineEnding };
+use editor_core::{ Buffer, BufferChange, BufferId, Position, LineEnding, Selection, TextChange };
+use editor_core::{ EditorError, TextEdit };
+use std::path::PathBuf;
 
 #[test]
 fn test_buffer_creation_and_basic_operations() {
@@ -129,6 +131,50 @@ fn test_word_boundaries() {
     assert!(prev.column < 10);
 }
 
+#[test]
+fn test_word_boundaries_in_math() {
+    let buffer = Buffer::from_text(BufferId::new(1), "x_1 $x_12 + alpha^2$");
+    let in_math = |offset: usize| (5..19).contains(&offset);
+    let stops = |forward: bool| {
+        let mut pos = if forward { Position::zero() } else { Position::new(0, 20) };
+        let mut stops = Vec::new();
+        loop {
+            let next = if forward {
+                buffer.next_word_boundary_with(pos, in_math).unwrap()
+            } else {
+                buffer.prev_word_boundary_with(pos, in_math).unwrap()
+            };
+            if next == pos {
+                return stops;
+            }
+            stops.push(next.column);
+            pos = next;
+        }
+    };
+
+    // Outside math `x_1` is one word; inside, `_` and `^` split identifiers
+    // from numbers
+    assert_eq!(stops(true), vec![3, 5, 6, 7, 9, 12, 17, 18, 19]);
+    assert_eq!(stops(false), vec![19, 18, 17, 12, 9, 7, 6, 5, 3, 0]);
+    assert_eq!(buffer.next_word_boundary(Position::new(0, 5)).unwrap(), Position::new(0, 9));
+}
+
+#[test]
+fn test_word_boundaries_stop_at_apostrophe_and_hyphen() {
+    let buffer = Buffer::from_text(BufferId::new(1), "don't-stop");
+    let mut pos = Position::zero();
+    let mut stops = Vec::new();
+    loop {
+        let next = buffer.next_word_boundary(pos).unwrap();
+        if next == pos {
+            break;
+        }
+        stops.push(next.column);
+        pos = next;
+    }
+    assert_eq!(stops, vec![3, 4, 5, 6, 10]);
+}
+
 #[test]
 fn test_unicode_grapheme_handling() {
     // Test with combining characters (e + combining acute accent = é)
@@ -169,6 +215,162 @@ fn test_buffer_metrics() {
     assert!(metrics.total_bytes > 0);
 }
 
+#[test]
+fn test_buffer_metrics_expands_tabs() {
+    let buffer = Buffer::from_text(BufferId::new(1), "short\n\tab\tcé\nend");
+    let metrics = buffer.metrics_with_tab_size(4);
+
+    // "\tab\tcé": tab to 4, "ab" to 6, tab to 8, "cé" to 10
+    assert_eq!(metrics.longest_line_display_columns, 10);
+    assert!(metrics.longest_line_display_columns > "\tab\tcé".chars().count());
+    assert_eq!(metrics.longest_line_bytes, "\tab\tcé".len());
+}
+
+#[test]
+fn test_buffer_statistics() {
+    let text = concat!(
+        "= Introduction\n\n",
+        "The quick, brown fox\njumps over *the* lazy dog.\n\n\n",
+        "#set text(size: 11pt)\nIt's done!\n"
+    );
+    let buffer = Buffer::from_text(BufferId::new(1), text);
+    let stats = buffer.statistics();
+
+    // "set" after `#` is markup; "text", "size" and "11pt" still count
+    assert_eq!(stats.words, 1 + 9 + 3 + 2);
+    assert_eq!(stats.paragraphs, 3);
+    assert_eq!(stats.chars, text.chars().count());
+    let visible = text.chars().filter(|c| !c.is_whitespace()).count();
+    assert_eq!(stats.chars_without_whitespace, visible);
+    assert_eq!(stats.lines, buffer.len_lines());
+}
+
+#[test]
+fn test_buffer_statistics_empty() {
+    let stats = Buffer::new(BufferId::new(1)).statistics();
+    assert_eq!(stats.words, 0);
+    assert_eq!(stats.paragraphs, 0);
+    assert_eq!(stats.chars_without_whitespace, 0);
+}
+
+#[test]
+fn test_trim_trailing_whitespace_single_undo() {
+    let original = "one  \r\ntwo\t\r\n   \r\nfour \t";
+    let mut buffer = Buffer::from_text(BufferId::new(1), original);
+
+    assert!(buffer.trim_trailing_whitespace());
+    buffer.ensure_final_newline(true);
+    assert_eq!(buffer.text(), "one\r\ntwo\r\n\r\nfour\r\n");
+    assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+    assert!(!buffer.trim_trailing_whitespace());
+
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "one\r\ntwo\r\n\r\nfour");
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), original);
+}
+
+#[test]
+fn test_trim_trailing_whitespace_skips_selected_lines() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "a  \nb  \nc  ");
+    let typing = Selection::collapsed(Position::new(1, 3));
+
+    assert!(buffer.trim_trailing_whitespace_except(&[typing]));
+    assert_eq!(buffer.text(), "a\nb  \nc");
+}
+
+#[test]
+fn test_ensure_final_newline() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "text\n\n\n");
+    buffer.ensure_final_newline(false);
+    assert_eq!(buffer.text(), "text\n\n\n");
+
+    buffer.ensure_final_newline(true);
+    assert_eq!(buffer.text(), "text\n");
+    assert!(!buffer.can_redo());
+
+    let version = buffer.version();
+    buffer.ensure_final_newline(true);
+    assert_eq!(buffer.version(), version);
+}
+
+#[test]
+fn test_convert_line_endings_round_trip() {
+    let crlf = "first\r\nsecond\r\n\r\nlast\r\n";
+    let mut buffer = Buffer::from_text(BufferId::new(1), crlf);
+    let version = buffer.version().value();
+
+    buffer.convert_line_endings(LineEnding::Lf).unwrap();
+    assert_eq!(buffer.text().as_bytes(), b"first\nsecond\n\nlast\n");
+    assert_eq!(buffer.line_ending(), LineEnding::Lf);
+    assert_eq!(buffer.version().value(), version + 1);
+
+    buffer.convert_line_endings(LineEnding::Crlf).unwrap();
+    assert_eq!(buffer.text().as_bytes(), crlf.as_bytes());
+    assert_eq!(buffer.line_ending(), LineEnding::Crlf);
+    assert_eq!(buffer.version().value(), version + 2);
+
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "first\nsecond\n\nlast\n");
+}
+
+#[test]
+fn test_convert_mixed_line_endings_keeps_lone_cr() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "a\r\nb\nprogress\r50%\nc");
+    buffer.convert_line_endings(LineEnding::Crlf).unwrap();
+    assert_eq!(buffer.text(), "a\r\nb\r\nprogress\r50%\r\nc");
+
+    let mut classic = Buffer::from_text(BufferId::new(2), "a\rb\r");
+    classic.convert_line_endings(LineEnding::Lf).unwrap();
+    assert_eq!(classic.text(), "a\nb\n");
+}
+
+#[test]
+fn test_lines_in_range() {
+    let text: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+    let buffer = Buffer::from_text(BufferId::new(1), &text);
+
+    let lines: Vec<String> = buffer
+        .lines_in_range(500, 550)
+        .map(|line| line.to_string())
+        .collect();
+    assert_eq!(lines.len(), 50);
+    assert_eq!(lines[0], "line 500\n");
+    assert_eq!(lines[49], "line 549\n");
+
+    // The final empty line after the trailing newline is included
+    let tail: Vec<String> = buffer
+        .lines_in_range(998, 5000)
+        .map(|line| line.to_string())
+        .collect();
+    assert_eq!(tail, vec!["line 998\n", "line 999\n", ""]);
+    assert_eq!(buffer.lines_in_range(2000, 3000).count(), 0);
+}
+
+#[test]
+fn test_indent_lines_with_spaces() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "a\n  b\n\nc\nd");
+    buffer.indent_lines(0, 3, true, 4).unwrap();
+    assert_eq!(buffer.text(), "    a\n      b\n\n    c\nd");
+
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "a\n  b\n\nc\nd");
+
+    buffer.indent_lines(0, 1, false, 4).unwrap();
+    assert_eq!(buffer.text(), "\ta\n\t  b\n\nc\nd");
+}
+
+#[test]
+fn test_outdent_lines() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "\t\ta\n\tb\nc\n      d\n  \te");
+    buffer.outdent_lines(0, 4, 4).unwrap();
+    assert_eq!(buffer.text(), "\ta\nb\nc\n  d\ne");
+
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "\t\ta\n\tb\nc\n      d\n  \te");
+    assert!(buffer.outdent_lines(0, 9, 4).is_err());
+}
+
 #[test]
 fn test_position_conversion() {
     let buffer = Buffer::from_text(BufferId::new(1), "Hello\nWorld\n");
@@ -262,3 +464,535 @@ fn test_large_buffer_operations() {
     let metrics = buffer.metrics();
     assert!(metrics.total_lines >= 100);
 }
+
+#[test]
+fn test_wrap_inline_selection_in_math() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "area is pi r^2 here");
+
+    let selection = Selection::new(Position::new(0, 8), Position::new(0, 14));
+    let wrapped = buffer.wrap_in_math(&selection).unwrap();
+
+    assert_eq!(buffer.text(), "area is $pi r^2$ here");
+    assert_eq!(wrapped.range(), (Position::new(0, 9), Position::new(0, 15)));
+
+    // The wrap is a single undo step
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "area is pi r^2 here");
+}
+
+#[test]
+fn test_wrap_multiline_selection_in_block_math() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "a + b\n= c\n");
+
+    let selection = Selection::new(Position::new(0, 0), Position::new(1, 3));
+    let wrapped = buffer.wrap_in_math(&selection).unwrap();
+
+    assert_eq!(buffer.text(), "$ a + b\n= c $\n");
+    assert_eq!(wrapped.range(), (Position::new(0, 2), Position::new(1, 3)));
+}
+
+#[test]
+fn test_replace_ranges_single_undo() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "let foo = 1\nfoo + 2\nbar(foo)");
+
+    buffer
+        .replace_ranges(
+            vec![
+                (Position::new(1, 0)..Position::new(1, 3), "value".to_string()),
+                (Position::new(0, 4)..Position::new(0, 7), "value".to_string()),
+                (Position::new(2, 4)..Position::new(2, 7), "value".to_string())
+            ]
+        )
+        .unwrap();
+    assert_eq!(buffer.text(), "let value = 1\nvalue + 2\nbar(value)");
+
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "let foo = 1\nfoo + 2\nbar(foo)");
+    assert!(!buffer.can_undo());
+
+    buffer.redo().unwrap();
+    assert_eq!(buffer.text(), "let value = 1\nvalue + 2\nbar(value)");
+}
+
+#[test]
+fn test_replace_ranges_rejects_overlap() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "Hello World");
+
+    let result = buffer.replace_ranges(
+        vec![
+            (Position::new(0, 6)..Position::new(0, 11), "Typst".to_string()),
+            (Position::new(0, 0)..Position::new(0, 8), "Bye".to_string())
+        ]
+    );
+    assert!(result.is_err());
+    assert_eq!(buffer.text(), "Hello World");
+    assert!(!buffer.can_undo());
+}
+
+#[test]
+fn test_versioned_edits_at_current_version() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "#let x = 1\n#x");
+    let version = buffer.version();
+
+    let edits = [
+        TextEdit::new(Position::new(0, 5)..Position::new(0, 6), "value"),
+        TextEdit::new(Position::new(1, 1)..Position::new(1, 2), "value"),
+    ];
+    buffer.apply_versioned_edits(version, &edits).unwrap();
+    assert_eq!(buffer.text(), "#let value = 1\n#value");
+
+    // All edits are undone together
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "#let x = 1\n#x");
+}
+
+#[test]
+fn test_versioned_edits_reject_stale_version() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "#let x = 1");
+    let stale = buffer.version();
+    buffer.insert(Position::new(0, 0), "// x\n").unwrap();
+
+    let edits = [TextEdit::new(Position::new(0, 5)..Position::new(0, 6), "value")];
+    let result = buffer.apply_versioned_edits(stale, &edits);
+    assert!(matches!(
+        result,
+        Err(EditorError::VersionMismatch { expected, actual })
+            if expected == stale.value() && actual == buffer.version().value()
+    ));
+    assert_eq!(buffer.text(), "// x\n#let x = 1");
+}
+
+#[test]
+fn test_open_line_below_inherits_indentation() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "#if x {\n    foo\n}");
+
+    let cursor = buffer.open_line(1, false, 4, true).unwrap();
+    assert_eq!(buffer.text(), "#if x {\n    foo\n    \n}");
+    assert_eq!(cursor, Position::new(2, 4));
+
+    // An opening bracket adds a level, expressed with tabs when requested
+    let cursor = buffer.open_line(0, false, 4, false).unwrap();
+    assert_eq!(buffer.text(), "#if x {\n\t\n    foo\n    \n}");
+    assert_eq!(cursor, Position::new(1, 1));
+}
+
+#[test]
+fn test_open_line_above_inserts_before() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "first\n  second");
+
+    let cursor = buffer.open_line(1, true, 4, true).unwrap();
+    assert_eq!(buffer.text(), "first\n  \n  second");
+    assert_eq!(cursor, Position::new(1, 2));
+
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "first\n  second");
+}
+
+#[test]
+fn test_newline_inherits_indentation() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "  - item text");
+
+    let cursor = buffer.newline_with_indent(Position::new(0, 8), 4, true).unwrap();
+    assert_eq!(buffer.text(), "  - item\n   text");
+    assert_eq!(cursor, Position::new(1, 2));
+
+    // Breaking inside the indentation keeps only the part before the cursor
+    let mut buffer = Buffer::from_text(BufferId::new(1), "\t\tx");
+    let cursor = buffer.newline_with_indent(Position::new(0, 1), 4, false).unwrap();
+    assert_eq!(buffer.text(), "\t\n\t\tx");
+    assert_eq!(cursor, Position::new(1, 1));
+}
+
+#[test]
+fn test_newline_expands_brackets() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "  #let f(x) = {}");
+
+    // Between a bracket pair, the closing bracket moves to its own line
+    let cursor = buffer.newline_with_indent(Position::new(0, 15), 2, true).unwrap();
+    assert_eq!(buffer.text(), "  #let f(x) = {\n    \n  }");
+    assert_eq!(cursor, Position::new(1, 4));
+
+    // After an unclosed bracket, only the level is added
+    let mut buffer = Buffer::from_text(BufferId::new(1), "#table[ ");
+    let cursor = buffer.newline_with_indent(Position::new(0, 8), 4, false).unwrap();
+    assert_eq!(buffer.text(), "#table[ \n\t");
+    assert_eq!(cursor, Position::new(1, 1));
+}
+
+#[test]
+fn test_isolated_reindent_is_own_undo_step() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "\tfoo");
+
+    buffer.insert(Position::new(0, 4), "a").unwrap();
+    buffer.reindent_lines(0..1, 4, true).unwrap();
+    assert_eq!(buffer.text(), "    fooa");
+    buffer.insert(Position::new(0, 8), "b").unwrap();
+
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "    fooa");
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "\tfooa");
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "\tfoo");
+}
+
+#[test]
+fn test_paste_does_not_merge_with_typing() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "");
+
+    // Adjacent typing would normally coalesce into one undo step
+    buffer.insert(Position::new(0, 0), "a").unwrap();
+    let cursor = buffer.paste(Position::new(0, 1), "xy").unwrap();
+    assert_eq!(cursor, Position::new(0, 3));
+    buffer.insert(cursor, "b").unwrap();
+    assert_eq!(buffer.text(), "axyb");
+
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "axy");
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "a");
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "");
+}
+
+#[test]
+fn test_apply_formatting_single_undo() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "#let  x=1\n");
+
+    buffer.insert(Position::new(1, 0), "y").unwrap();
+    buffer.apply_formatting("#let x = 1\ny").unwrap();
+    assert_eq!(buffer.text(), "#let x = 1\ny");
+
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "#let  x=1\ny");
+}
+
+#[test]
+fn test_save_or_uses_default_for_pathless_buffer() {
+    let dir = tempfile::tempdir().unwrap();
+    let default_path = dir.path().join("default.typ");
+    let mut buffer = Buffer::from_text(BufferId::new(1), "= Draft");
+    buffer.insert(Position::new(0, 7), "!").unwrap();
+
+    assert!(buffer.save().is_err());
+    buffer.save_or(default_path.clone()).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&default_path).unwrap(), "= Draft!");
+    assert_eq!(buffer.file_path(), Some(&default_path));
+    assert!(!buffer.is_dirty());
+}
+
+#[test]
+fn test_save_or_ignores_default_when_path_set() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("own.typ");
+    let default_path = dir.path().join("unused.typ");
+    let mut buffer = Buffer::from_text(BufferId::new(1), "= Notes");
+    buffer.set_file_path(path.clone());
+
+    buffer.save_or(default_path.clone()).unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "= Notes");
+    assert!(!default_path.exists());
+    assert_eq!(buffer.file_path(), Some(&path));
+}
+
+#[test]
+fn test_bom_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("bom.typ");
+    std::fs::write(&path, "\u{FEFF}= Title\n").unwrap();
+
+    let mut buffer = Buffer::from_file(BufferId::new(1), path.clone()).unwrap();
+    assert!(buffer.write_bom());
+    assert_eq!(buffer.text(), "= Title\n");
+
+    buffer.insert(Position::new(1, 0), "Body").unwrap();
+    buffer.save().unwrap();
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "\u{FEFF}= Title\nBody");
+}
+
+#[test]
+fn test_no_bom_stays_bom_free() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("no-bom.typ");
+    std::fs::write(&path, "= Title\n").unwrap();
+
+    let mut buffer = Buffer::from_file(BufferId::new(1), path.clone()).unwrap();
+    assert!(!buffer.write_bom());
+
+    buffer.insert(Position::new(1, 0), "Body").unwrap();
+    buffer.save().unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), b"= Title\nBody");
+
+    // Enabling the flag adds the mark on the next save
+    buffer.set_write_bom(true);
+    assert!(buffer.is_dirty());
+    buffer.save().unwrap();
+    assert!(std::fs::read(&path).unwrap().starts_with(b"\xEF\xBB\xBF"));
+}
+
+/// Apply snapshot changes to a copy of `old` and return the result
+fn apply_changes(old: &str, changes: &[TextChange]) -> String {
+    let mut buffer = Buffer::from_text(BufferId::new(2), old);
+    buffer
+        .replace_ranges(
+            changes
+                .iter()
+                .map(|change| (change.range(), change.text().to_string()))
+                .collect()
+        )
+        .unwrap();
+    buffer.text()
+}
+
+#[test]
+fn test_snapshot_diff_insertion() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "= Title\nSome text\n");
+    let before = buffer.snapshot();
+    assert!(before.diff(&buffer.snapshot()).is_empty());
+
+    buffer.insert(Position::new(1, 5), "more ").unwrap();
+    let changes = before.diff(&buffer.snapshot());
+
+    assert_eq!(changes, vec![TextChange::Insert {
+        at: Position::new(1, 5),
+        text: "more ".to_string(),
+    }]);
+    assert_eq!(apply_changes(&before.text(), &changes), buffer.text());
+}
+
+#[test]
+fn test_snapshot_diff_deletion() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "one\ntwo\nthree\n");
+    let before = buffer.snapshot();
+
+    buffer.delete(Position::new(0, 2), Position::new(1, 1)).unwrap();
+    let changes = before.diff(&buffer.snapshot());
+
+    assert_eq!(changes, vec![TextChange::Delete {
+        range: Position::new(0, 2)..Position::new(1, 1),
+    }]);
+    assert_eq!(apply_changes(&before.text(), &changes), buffer.text());
+}
+
+#[test]
+fn test_snapshot_diff_multiline_replacement() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "a\nb\nc\nd\ne\nf\n");
+    let before = buffer.snapshot();
+
+    buffer.replace(Position::new(1, 0), Position::new(3, 1), "x\ny").unwrap();
+    buffer.insert(Position::new(4, 1), "!").unwrap();
+    let after = buffer.snapshot();
+    let changes = before.diff(&after);
+
+    assert_eq!(changes.len(), 2);
+    assert!(matches!(changes[0], TextChange::Replace { .. }));
+    assert_eq!(apply_changes(&before.text(), &changes), after.text());
+
+    // The reverse diff undoes the edit
+    assert_eq!(apply_changes(&after.text(), &after.diff(&before)), before.text());
+}
+
+#[test]
+fn test_snapshot_diff_across_buffers() {
+    // Fresh buffers share a version, so only the id tells them apart
+    let first = Buffer::from_text(BufferId::new(1), "alpha\n").snapshot();
+    let second = Buffer::from_text(BufferId::new(2), "beta\n").snapshot();
+    assert_eq!(first.version(), second.version());
+
+    let changes = first.diff(&second);
+    assert!(!changes.is_empty());
+    assert_eq!(apply_changes(&first.text(), &changes), second.text());
+}
+
+/// Subscribe to a buffer, collecting every change it reports
+fn record_changes(buffer: &mut Buffer) -> std::sync::Arc<std::sync::Mutex<Vec<BufferChange>>> {
+    let changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let sink = changes.clone();
+    buffer.subscribe(Box::new(move |change| sink.lock().unwrap().push(change.clone())));
+    changes
+}
+
+#[test]
+fn test_change_callbacks_for_insert_delete_and_replace() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "= Über\nbody\n");
+    let changes = record_changes(&mut buffer);
+
+    buffer.insert(Position::new(0, 6), "sicht").unwrap();
+    buffer.delete(Position::new(0, 6), Position::new(1, 2)).unwrap();
+    buffer.replace(Position::new(0, 2), Position::new(0, 6), "Intro\n").unwrap();
+
+    let changes = changes.lock().unwrap();
+    assert_eq!(changes.len(), 3);
+
+    // Insert: empty old range; "Ü" is two bytes
+    assert_eq!(changes[0].old_range, Position::new(0, 6)..Position::new(0, 6));
+    assert_eq!(changes[0].new_range, Position::new(0, 6)..Position::new(0, 11));
+    assert_eq!(changes[0].old_byte_range, 7..7);
+    assert_eq!(changes[0].new_byte_range, 7..12);
+
+    // Delete across a line break: empty new range
+    assert_eq!(changes[1].old_range, Position::new(0, 6)..Position::new(1, 2));
+    assert_eq!(changes[1].new_range, Position::new(0, 6)..Position::new(0, 6));
+    assert_eq!(changes[1].old_byte_range, 7..15);
+
+    // Replace is one change, not a delete and an insert
+    assert_eq!(changes[2].old_range, Position::new(0, 2)..Position::new(0, 6));
+    assert_eq!(changes[2].new_range, Position::new(0, 2)..Position::new(1, 0));
+    assert_eq!(changes[2].new_byte_range, 2..8);
+
+    assert_eq!(changes[2].version, buffer.version());
+    assert!(changes[0].version < changes[1].version);
+    assert_eq!(buffer.text(), "= Intro\ndy\n");
+}
+
+#[test]
+fn test_change_maps_positions() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "one two\nthree\n");
+    let changes = record_changes(&mut buffer);
+    buffer.replace(Position::new(0, 4), Position::new(0, 7), "2\nand").unwrap();
+
+    let change = &changes.lock().unwrap()[0];
+    assert_eq!(change.map_position(Position::new(0, 4)), Position::new(0, 4));
+    assert_eq!(change.map_position(Position::new(0, 5)), Position::new(1, 3));
+    assert_eq!(change.map_position(Position::new(0, 7)), Position::new(1, 3));
+    assert_eq!(change.map_position(Position::new(1, 2)), Position::new(2, 2));
+}
+
+#[test]
+fn test_unsubscribe_and_batch_changes() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "a b c");
+    let changes = record_changes(&mut buffer);
+    let ignored = buffer.subscribe(Box::new(|_| panic!("unsubscribed callback ran")));
+    assert!(buffer.unsubscribe(ignored));
+    assert!(!buffer.unsubscribe(ignored));
+
+    buffer.replace_ranges(vec![
+        (Position::new(0, 0)..Position::new(0, 1), "x".to_string()),
+        (Position::new(0, 4)..Position::new(0, 5), "zz".to_string()),
+    ]).unwrap();
+    buffer.undo().unwrap();
+
+    let changes = changes.lock().unwrap();
+    let old_ranges: Vec<_> = changes.iter().map(|change| change.old_range.clone()).collect();
+    assert_eq!(old_ranges, [
+        // The batch, applied from the end backwards
+        Position::new(0, 4)..Position::new(0, 5),
+        Position::new(0, 0)..Position::new(0, 1),
+        // Undo reverts it front to back
+        Position::new(0, 0)..Position::new(0, 1),
+        Position::new(0, 4)..Position::new(0, 6),
+    ]);
+    assert_eq!(changes[1].version, changes[0].version);
+    assert_eq!(buffer.text(), "a b c");
+}
+
+#[test]
+fn test_backup_written_when_dirty_and_removed_on_save() {
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path().to_path_buf();
+    let path = dir.join("notes.typ");
+    std::fs::write(&path, "= Notes\n").unwrap();
+    let mut buffer = Buffer::from_file(BufferId::new(1), path.clone()).unwrap();
+
+    // Clean buffers have nothing to back up
+    let backup = buffer.write_backup().unwrap();
+    assert_eq!(backup, dir.join(".notes.typ.autosave"));
+    assert!(!backup.exists());
+
+    buffer.insert(Position::new(1, 0), "Unsaved\n").unwrap();
+    buffer.write_backup().unwrap();
+    assert_eq!(std::fs::read_to_string(&backup).unwrap(), "= Notes\nUnsaved\n");
+    assert_eq!(editor_core::recover_backups(&dir), vec![backup.clone()]);
+
+    buffer.save().unwrap();
+    assert!(!backup.exists());
+    assert!(editor_core::recover_backups(&dir).is_empty());
+
+    // Untitled buffers back up to the temp directory until saved under a name
+    let mut untitled = Buffer::new(BufferId::new(900_000 + std::process::id() as u64));
+    untitled.insert(Position::new(0, 0), "draft").unwrap();
+    let untitled_backup = untitled.write_backup().unwrap();
+    assert!(untitled_backup.starts_with(editor_core::buffer::untitled_backup_dir()));
+    untitled.save_as(dir.join("draft.typ")).unwrap();
+    assert!(!untitled_backup.exists());
+}
+
+#[test]
+fn test_stale_backups_are_not_recovered() {
+    use std::time::{ Duration, SystemTime };
+
+    let temp = tempfile::tempdir().unwrap();
+    let dir = temp.path().to_path_buf();
+    let set_modified = |path: &PathBuf, time: SystemTime| {
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+    };
+    let earlier = SystemTime::now() - Duration::from_secs(60);
+
+    // Crashed before saving: the backup is newer than the file
+    std::fs::write(dir.join("crashed.typ"), "old").unwrap();
+    std::fs::write(dir.join(".crashed.typ.autosave"), "new").unwrap();
+    set_modified(&dir.join("crashed.typ"), earlier);
+
+    // Saved from elsewhere after the backup: the file is newer
+    std::fs::write(dir.join("saved.typ"), "newest").unwrap();
+    std::fs::write(dir.join(".saved.typ.autosave"), "stale").unwrap();
+    set_modified(&dir.join(".saved.typ.autosave"), earlier);
+
+    // The file itself is gone
+    std::fs::write(dir.join(".deleted.typ.autosave"), "only copy").unwrap();
+
+    let backups = editor_core::recover_backups(&dir);
+    assert_eq!(backups, vec![dir.join(".crashed.typ.autosave"), dir.join(".deleted.typ.autosave")]);
+    assert_eq!(
+        editor_core::buffer::backup_original(&backups[0]),
+        Some(dir.join("crashed.typ"))
+    );
+}
+
+#[test]
+fn test_toggle_line_comment_on_mixed_block() {
+    let text = "#let x = 1\n  // note\n\n  #x\n";
+    let mut buffer = Buffer::from_text(BufferId::new(1), text);
+
+    // Not every line is commented, so all are commented after their indentation
+    buffer.toggle_line_comment(0, 3).unwrap();
+    assert_eq!(buffer.text(), "// #let x = 1\n  // // note\n\n  // #x\n");
+
+    // Blank lines do not stop the block counting as commented
+    buffer.toggle_line_comment(0, 3).unwrap();
+    assert_eq!(buffer.text(), text);
+
+    buffer.toggle_line_comment(1, 1).unwrap();
+    assert_eq!(buffer.text(), "#let x = 1\n  note\n\n  #x\n");
+
+    // Each toggle is one undo step
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), text);
+
+    // Only blank lines: nothing to toggle
+    buffer.toggle_line_comment(2, 2).unwrap();
+    assert_eq!(buffer.text(), text);
+}
+
+#[test]
+fn test_toggle_block_comment() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "a *b*\nc d");
+
+    let range = buffer.toggle_block_comment(Position::new(0, 2), Position::new(1, 1)).unwrap();
+    assert_eq!(buffer.text(), "a /**b*\nc*/ d");
+    assert_eq!(range, Position::new(0, 2)..Position::new(1, 3));
+
+    let range = buffer.toggle_block_comment(range.start, range.end).unwrap();
+    assert_eq!(buffer.text(), "a *b*\nc d");
+    assert_eq!(range, Position::new(0, 2)..Position::new(1, 1));
+
+    // Whitespace around a selected comment is ignored
+    let mut buffer = Buffer::from_text(BufferId::new(1), "x /* y */ z");
+    let range = buffer.toggle_block_comment(Position::new(0, 1), Position::new(0, 10)).unwrap();
+    assert_eq!(buffer.text(), "x  y  z");
+    assert_eq!(range, Position::new(0, 1)..Position::new(0, 6));
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "x /* y */ z");
+}
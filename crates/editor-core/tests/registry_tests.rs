@@ -0,0 +1,61 @@
+//! Tests for the shared buffer registry
+
+use editor_core::registry::{ read_buffer, write_buffer };
+use editor_core::{ BufferRegistry, Position };
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn test_handles_share_one_buffer() {
+    let registry = BufferRegistry::new();
+    let id = registry.create("Hello");
+    assert_eq!(registry.len(), 1);
+
+    let ui = registry.get(id).unwrap();
+    let lsp = ui.clone();
+    write_buffer(&ui).insert(Position::new(0, 5), " World").unwrap();
+    assert_eq!(read_buffer(&lsp).text(), "Hello World");
+
+    // A snapshot can be taken while another handle is held for reading
+    let reading = read_buffer(&lsp);
+    let snapshot = registry.snapshot(id).unwrap();
+    assert_eq!(snapshot.text(), "Hello World");
+    drop(reading);
+
+    // Removed buffers stay alive through existing handles
+    assert!(registry.remove(id).is_some());
+    assert!(registry.get(id).is_none());
+    assert!(registry.snapshot(id).is_none());
+    assert_eq!(read_buffer(&ui).text(), "Hello World");
+}
+
+#[test]
+fn test_snapshot_while_editing_on_another_thread() {
+    let registry = Arc::new(BufferRegistry::new());
+    let id = registry.create("");
+    let other = registry.create("other");
+
+    let editor = {
+        let registry = Arc::clone(&registry);
+        thread::spawn(move || {
+            for _ in 0..100 {
+                let handle = registry.get(id).unwrap();
+                let mut buffer = write_buffer(&handle);
+                let end = Position::new(0, buffer.len_chars());
+                buffer.insert(end, "a").unwrap();
+                // Looking up other buffers while holding a write lock is fine
+                assert!(registry.get(other).is_some());
+            }
+        })
+    };
+
+    // Snapshots see whole edits only
+    for _ in 0..100 {
+        let snapshot = registry.snapshot(id).unwrap();
+        assert!(snapshot.text().chars().all(|c| c == 'a'));
+    }
+    editor.join().unwrap();
+
+    assert_eq!(registry.snapshot(id).unwrap().text().len(), 100);
+    assert_eq!(registry.snapshot(other).unwrap().text(), "other");
+}
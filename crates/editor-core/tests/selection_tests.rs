@@ -1,6 +1,7 @@
 //! Tests for selection and cursor management
 
-use editor_core::{ Position, Selection, SelectionSet, Cursor, Affinity, Granularity };
+use editor_core::{ Buffer, BufferId, Position, Selection, SelectionSet, Cursor, Affinity, Granularity };
+use editor_core::TextRange;
 
 #[test]
 fn test_position_ordering() {
@@ -148,3 +149,134 @@ fn test_selection_set_default() {
     assert_eq!(set.selections().len(), 1);
     assert_eq!(set.primary().cursor.position, Position::zero());
 }
+
+#[test]
+fn test_merge_keeps_primary_and_normalizes_direction() {
+    // Primary is a backward selection that overlaps a secondary one
+    let mut set = SelectionSet::new(Selection::new(Position::new(0, 8), Position::new(0, 2)));
+    set.add_selection(Selection::new(Position::new(2, 0), Position::new(2, 4)));
+    set.add_selection(Selection::new(Position::new(0, 5), Position::new(0, 12)));
+
+    set.merge_overlapping();
+
+    assert_eq!(set.selections().len(), 2);
+    let primary = set.primary();
+    assert_eq!(primary.range(), (Position::new(0, 2), Position::new(0, 12)));
+    assert!(primary.is_forward());
+}
+
+#[test]
+fn test_merge_primary_not_first_after_sort() {
+    let mut set = SelectionSet::new(Selection::new(Position::new(3, 0), Position::new(3, 5)));
+    set.add_selection(Selection::new(Position::new(0, 0), Position::new(0, 3)));
+    set.add_selection(Selection::new(Position::new(3, 2), Position::new(4, 1)));
+
+    set.merge_overlapping();
+
+    assert_eq!(set.selections().len(), 2);
+    assert_eq!(set.primary().range(), (Position::new(3, 0), Position::new(4, 1)));
+    // The unmerged selection is untouched
+    assert_eq!(set.selections()[0].range(), (Position::new(0, 0), Position::new(0, 3)));
+}
+
+#[test]
+fn test_select_next_occurrence_across_lines() {
+    let buffer = Buffer::from_text(BufferId::new(1), "let item = 1\nitem + item\n#item");
+    let mut selections = SelectionSet::new(Selection::collapsed(Position::new(0, 5)));
+
+    // The first call expands to the word and adds the next occurrence
+    assert!(selections.select_next_occurrence(&buffer).unwrap());
+    let ranges: Vec<(Position, Position)> = selections
+        .selections()
+        .iter()
+        .map(|s| s.range())
+        .collect();
+    assert_eq!(ranges, vec![
+        (Position::new(0, 4), Position::new(0, 8)),
+        (Position::new(1, 0), Position::new(1, 4))
+    ]);
+
+    assert!(selections.select_next_occurrence(&buffer).unwrap());
+    assert_eq!(selections.selections().len(), 3);
+    assert_eq!(selections.selections()[2].range(), (Position::new(1, 7), Position::new(1, 11)));
+
+    assert!(selections.select_next_occurrence(&buffer).unwrap());
+    assert_eq!(selections.selections().len(), 4);
+    assert_eq!(selections.selections()[3].range(), (Position::new(2, 1), Position::new(2, 5)));
+
+    // Every occurrence is selected; wrapping around finds nothing new
+    assert!(!selections.select_next_occurrence(&buffer).unwrap());
+    assert_eq!(selections.selections().len(), 4);
+}
+
+#[test]
+fn test_select_next_occurrence_wraps_around() {
+    let buffer = Buffer::from_text(BufferId::new(1), "ab x ab x ab");
+    let mut selections = SelectionSet::new(
+        Selection::new(Position::new(0, 5), Position::new(0, 7))
+    );
+
+    assert!(selections.select_next_occurrence(&buffer).unwrap());
+    assert_eq!(selections.selections()[1].range(), (Position::new(0, 10), Position::new(0, 12)));
+
+    assert!(selections.select_next_occurrence(&buffer).unwrap());
+    assert_eq!(selections.selections()[2].range(), (Position::new(0, 0), Position::new(0, 2)));
+}
+
+#[test]
+fn test_select_next_occurrence_needs_a_word() {
+    let buffer = Buffer::from_text(BufferId::new(1), "a  b");
+    let mut selections = SelectionSet::new(Selection::collapsed(Position::new(0, 2)));
+
+    assert!(!selections.select_next_occurrence(&buffer).unwrap());
+    assert!(selections.primary().is_collapsed());
+}
+
+#[test]
+fn test_text_range_normalizes_and_contains() {
+    let range = TextRange::new(Position::new(2, 4), Position::new(1, 3));
+    assert_eq!(range.start, Position::new(1, 3));
+    assert_eq!(range.end, Position::new(2, 4));
+
+    assert!(range.contains(Position::new(1, 3)));
+    assert!(range.contains(Position::new(1, 80)));
+    assert!(!range.contains(Position::new(2, 4)));
+    assert!(!range.contains(Position::new(0, 9)));
+    assert!(!range.is_empty());
+
+    let empty = TextRange::collapsed(Position::new(1, 5));
+    assert!(empty.is_empty());
+    assert!(!empty.contains(Position::new(1, 5)));
+
+    // Backward selections give the same range
+    let backward = Selection::new(Position::new(2, 4), Position::new(1, 3));
+    assert_eq!(backward.to_range(), range);
+    assert_eq!(TextRange::from(backward.range()), range);
+}
+
+#[test]
+fn test_text_range_intersection() {
+    let range = |a: (usize, usize), b: (usize, usize)| {
+        TextRange::new(Position::new(a.0, a.1), Position::new(b.0, b.1))
+    };
+    let first = range((0, 0), (1, 5));
+
+    let overlapping = range((1, 2), (3, 0));
+    assert!(first.intersects(&overlapping));
+    assert!(overlapping.intersects(&first));
+    assert!(first.intersects(&range((0, 3), (0, 4))));
+
+    // Disjoint and touching ranges do not intersect
+    assert!(!first.intersects(&range((2, 0), (2, 1))));
+    assert!(!first.intersects(&range((1, 5), (2, 0))));
+    assert!(!first.intersects(&TextRange::collapsed(Position::new(0, 2))));
+}
+
+#[test]
+fn test_offset_by_columns_clamps_at_zero() {
+    let pos = Position::new(3, 4);
+    assert_eq!(pos.offset_by_columns(2), Position::new(3, 6));
+    assert_eq!(pos.offset_by_columns(-4), Position::new(3, 0));
+    assert_eq!(pos.offset_by_columns(-10), Position::new(3, 0));
+    assert_eq!(pos.offset_by_columns(0), pos);
+}
@@ -0,0 +1,112 @@
+//! Tests for snippet parsing and expansion
+
+use editor_core::{ Buffer, BufferId, Position, Snippet };
+
+#[test]
+fn test_parse_default_and_mirror() {
+    let snippet = Snippet::parse("#let ${1:name} = $2\n$1$0 \\$x").unwrap();
+    assert_eq!(snippet.text, "#let name = \nname $x");
+
+    let placeholders: Vec<(usize, &str)> = snippet.placeholders
+        .iter()
+        .map(|placeholder| (placeholder.index, &snippet.text[placeholder.range.clone()]))
+        .collect();
+    assert_eq!(placeholders, vec![(1, "name"), (2, ""), (1, "name"), (0, "")]);
+
+    // Mirrors copy defaults that contain other placeholders
+    let nested = Snippet::parse("$1 ${1:a ${2:b}}").unwrap();
+    assert_eq!(nested.text, "a b a b");
+    let ranges: Vec<_> = nested.placeholders
+        .iter()
+        .map(|placeholder| placeholder.range.clone())
+        .collect();
+    assert_eq!(ranges, vec![0..3, 4..7, 6..7]);
+
+    assert!(Snippet::parse("${1:unclosed").is_err());
+    assert!(Snippet::parse("${x}").is_err());
+    assert_eq!(Snippet::parse("cost: $").unwrap().text, "cost: $");
+}
+
+#[test]
+fn test_expand_tab_stops() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "  fig\n");
+    buffer.delete(Position::new(0, 2), Position::new(0, 5)).unwrap();
+
+    let snippet = Snippet::parse(
+        "#figure(\n  ${1:image(\"$2\")},\n  caption: [${3:caption}],\n) <$4>$0"
+    ).unwrap();
+    let mut session = snippet.expand(&mut buffer, Position::new(0, 2)).unwrap();
+    assert_eq!(
+        buffer.text(),
+        "  #figure(\n    image(\"\"),\n    caption: [caption],\n  ) <>\n"
+    );
+
+    let stops: Vec<(usize, Position, Position)> = session
+        .tab_stops()
+        .iter()
+        .map(|stop| (stop.index, stop.ranges[0].start, stop.ranges[0].end))
+        .collect();
+    assert_eq!(
+        stops,
+        vec![
+            (1, Position::new(1, 4), Position::new(1, 13)),
+            (2, Position::new(1, 11), Position::new(1, 11)),
+            (3, Position::new(2, 14), Position::new(2, 21)),
+            (4, Position::new(3, 5), Position::new(3, 5)),
+            (0, Position::new(3, 6), Position::new(3, 6))
+        ]
+    );
+
+    // Tab and Shift-Tab move between stops, ending at `$0`
+    assert_eq!(session.active().index, 1);
+    assert_eq!(session.next_stop().unwrap().index, 2);
+    assert_eq!(session.next_stop().unwrap().index, 3);
+    assert_eq!(session.prev_stop().unwrap().index, 2);
+    session.next_stop();
+    session.next_stop();
+    assert_eq!(session.next_stop().unwrap().index, 0);
+    assert!(session.is_finished());
+    assert!(session.next_stop().is_none());
+}
+
+#[test]
+fn test_expand_without_final_stop() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "");
+    let session = Snippet::parse("*$1*").unwrap().expand(&mut buffer, Position::zero()).unwrap();
+
+    let last = session.tab_stops().last().unwrap();
+    assert_eq!(last.index, 0);
+    assert_eq!(last.ranges, vec![Position::new(0, 2)..Position::new(0, 2)]);
+}
+
+#[test]
+fn test_mirrors_update_together() {
+    let mut buffer = Buffer::from_text(BufferId::new(1), "");
+    let snippet = Snippet::parse("\\begin{${1:env}}\n$2\n\\end{$1}$0").unwrap();
+    let mut session = snippet.expand(&mut buffer, Position::zero()).unwrap();
+    assert_eq!(buffer.text(), "\\begin{env}\n\n\\end{env}");
+    assert_eq!(session.selections().selections().len(), 2);
+
+    session.replace_active(&mut buffer, "align").unwrap();
+    assert_eq!(buffer.text(), "\\begin{align}\n\n\\end{align}");
+    assert_eq!(
+        session.active().ranges,
+        vec![
+            Position::new(0, 7)..Position::new(0, 12),
+            Position::new(2, 5)..Position::new(2, 10)
+        ]
+    );
+
+    // Later stops move with the edit
+    session.next_stop();
+    session.replace_active(&mut buffer, "x\ny").unwrap();
+    assert_eq!(buffer.text(), "\\begin{align}\nx\ny\n\\end{align}");
+    assert_eq!(session.tab_stops()[0].ranges[1], Position::new(3, 5)..Position::new(3, 10));
+    let end = Position::new(3, 11);
+    assert_eq!(session.next_stop().unwrap().ranges, vec![end..end]);
+
+    // Both mirrors are undone together
+    buffer.undo().unwrap();
+    buffer.undo().unwrap();
+    assert_eq!(buffer.text(), "\\begin{env}\n\n\\end{env}");
+}
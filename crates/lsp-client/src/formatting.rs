@@ -0,0 +1,259 @@
+//! Applying server-provided text edits to buffers
+
+use crate::{ LspError, Result };
+use editor_core::{ Buffer, Position };
+use lsp_types::{ DocumentChangeOperation, DocumentChanges, OneOf, TextEdit, Url, WorkspaceEdit };
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Apply LSP text edits, such as the result of `textDocument/formatting`
+pub trait ApplyTextEdits {
+    /// Apply edits whose ranges refer to the current text, as one undo step
+    ///
+    /// Ranges are converted from UTF-16 columns to grapheme positions and
+    /// applied from the end of the buffer backwards, so earlier edits never
+    /// shift later ranges. Edits that start at the same position, at most one
+    /// of them a replace, keep their list order, as the LSP specification
+    /// requires. Overlapping edits are an error and leave the buffer
+    /// untouched; an empty list is a no-op.
+    fn apply_text_edits(&mut self, edits: &[TextEdit]) -> editor_core::Result<()>;
+}
+
+impl ApplyTextEdits for Buffer {
+    fn apply_text_edits(&mut self, edits: &[TextEdit]) -> editor_core::Result<()> {
+        let mut resolved: Vec<(Range<Position>, String)> = edits
+            .iter()
+            .map(|edit| {
+                let start = lsp_position_to_position(edit.range.start, self);
+                let end = lsp_position_to_position(edit.range.end, self);
+                (start..end, edit.new_text.clone())
+            })
+            .collect();
+        // Stable, so edits at one position keep their list order
+        resolved.sort_by_key(|(range, _)| range.start);
+
+        // Fold inserts into the edit before or after them at the same
+        // position, since the buffer rejects edits that start at the same place
+        let mut merged: Vec<(Range<Position>, String)> = Vec::with_capacity(resolved.len());
+        for (range, text) in resolved {
+            match merged.last_mut() {
+                Some((last, last_text)) if
+                    last.start == range.start &&
+                    (last.is_empty() || range.is_empty())
+                => {
+                    last.end = last.end.max(range.end);
+                    last_text.push_str(&text);
+                }
+                _ => merged.push((range, text)),
+            }
+        }
+
+        self.replace_ranges(merged)
+    }
+}
+
+/// Text edits of a workspace edit, grouped by document
+///
+/// `document_changes` takes precedence over `changes`, as the LSP
+/// specification requires. Documents keep the order the server sent them
+/// in; plain `changes` are ordered by URI. Create, rename and delete
+/// operations are not supported.
+pub fn workspace_edit_changes(edit: &WorkspaceEdit) -> Result<Vec<(Url, Vec<TextEdit>)>> {
+    let mut grouped: Vec<(Url, Vec<TextEdit>)> = Vec::new();
+    let mut push = |uri: &Url, edits: Vec<TextEdit>| {
+        match grouped.iter_mut().find(|(existing, _)| existing == uri) {
+            Some((_, existing)) => existing.extend(edits),
+            None => grouped.push((uri.clone(), edits)),
+        }
+    };
+
+    match &edit.document_changes {
+        Some(DocumentChanges::Edits(documents)) => {
+            for document in documents {
+                push(&document.text_document.uri, document_edits(&document.edits));
+            }
+        }
+        Some(DocumentChanges::Operations(operations)) => {
+            for operation in operations {
+                match operation {
+                    DocumentChangeOperation::Edit(document) => {
+                        push(&document.text_document.uri, document_edits(&document.edits));
+                    }
+                    DocumentChangeOperation::Op(_) => {
+                        return Err(
+                            LspError::ProtocolError(
+                                "Unsupported resource operation in workspace edit".to_string()
+                            )
+                        );
+                    }
+                }
+            }
+        }
+        None => {
+            let mut changes: Vec<_> = edit.changes.iter().flatten().collect();
+            changes.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+            for (uri, edits) in changes {
+                push(uri, edits.clone());
+            }
+        }
+    }
+
+    Ok(grouped)
+}
+
+/// Plain text edits of a document edit, dropping change annotations
+fn document_edits(edits: &[OneOf<TextEdit, lsp_types::AnnotatedTextEdit>]) -> Vec<TextEdit> {
+    edits
+        .iter()
+        .map(|edit| {
+            match edit {
+                OneOf::Left(edit) => edit.clone(),
+                OneOf::Right(annotated) => annotated.text_edit.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Convert an LSP (line, UTF-16 column) position to a buffer position
+///
+/// Lines past the end clamp to the end of the buffer and columns past the end
+/// of a line clamp to the end of that line.
+pub fn lsp_position_to_position(position: lsp_types::Position, buffer: &Buffer) -> Position {
+    let line_idx = position.line as usize;
+    let last_line = buffer.len_lines().saturating_sub(1);
+    let (line_idx, target) = if line_idx > last_line {
+        (last_line, usize::MAX)
+    } else {
+        (line_idx, position.character as usize)
+    };
+
+    let line = buffer.line(line_idx).unwrap_or_default();
+    let content = line.trim_end_matches(['\n', '\r']);
+
+    let mut utf16_offset = 0;
+    let mut column = 0;
+    for grapheme in content.graphemes(true) {
+        if utf16_offset >= target {
+            break;
+        }
+        utf16_offset += grapheme.encode_utf16().count();
+        column += 1;
+    }
+
+    Position::new(line_idx, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::BufferId;
+
+    fn edit(start: (u32, u32), end: (u32, u32), text: &str) -> TextEdit {
+        TextEdit {
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(start.0, start.1),
+                lsp_types::Position::new(end.0, end.1)
+            ),
+            new_text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_apply_formatting_edits_as_one_undo() {
+        let original = "#let  x=1\n#if x {\n[a]}\n";
+        let mut buffer = Buffer::from_text(BufferId::new(1), original);
+
+        // Edits arrive unordered and several touch the same line
+        let edits = [
+            edit((2, 0), (2, 0), "  "),
+            edit((0, 5), (0, 6), ""),
+            edit((0, 7), (0, 8), " = "),
+            edit((2, 3), (2, 3), "\n"),
+            edit((2, 3), (2, 3), ""),
+        ];
+        buffer.apply_text_edits(&edits).unwrap();
+        assert_eq!(buffer.text(), "#let x = 1\n#if x {\n  [a]\n}\n");
+
+        buffer.undo().unwrap();
+        assert_eq!(buffer.text(), original);
+    }
+
+    #[test]
+    fn test_apply_text_edits_utf16_and_edge_cases() {
+        // "😀" is two UTF-16 code units but one grapheme
+        let mut buffer = Buffer::from_text(BufferId::new(1), "😀ab\ncd");
+        let version = buffer.version();
+
+        buffer.apply_text_edits(&[]).unwrap();
+        assert_eq!(buffer.version(), version);
+
+        // Adjacent edits and inserts at one position in list order
+        let edits = [
+            edit((0, 2), (0, 3), "A"),
+            edit((0, 3), (0, 4), "B"),
+            edit((1, 0), (1, 0), "1"),
+            edit((1, 0), (1, 0), "2"),
+        ];
+        buffer.apply_text_edits(&edits).unwrap();
+        assert_eq!(buffer.text(), "😀AB\n12cd");
+
+        // A replace and inserts at its start, in either order
+        let edits = [
+            edit((0, 2), (0, 3), "C"),
+            edit((0, 2), (0, 2), "D"),
+            edit((1, 0), (1, 0), "0"),
+            edit((1, 0), (1, 2), "E"),
+        ];
+        buffer.apply_text_edits(&edits).unwrap();
+        assert_eq!(buffer.text(), "😀CDB
+0Ecd");
+
+        let overlapping = [edit((0, 0), (0, 3), "x"), edit((0, 2), (0, 4), "y")];
+        assert!(buffer.apply_text_edits(&overlapping).is_err());
+        assert_eq!(buffer.text(), "😀CDB\n0Ecd");
+    }
+
+    #[test]
+    fn test_workspace_edit_changes_prefers_document_changes() {
+        use lsp_types::{ OptionalVersionedTextDocumentIdentifier, TextDocumentEdit };
+        use std::collections::HashMap;
+
+        let main = Url::parse("file:///main.typ").unwrap();
+        let other = Url::parse("file:///other.typ").unwrap();
+        let document_edit = |uri: &Url, text: &str| TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: uri.clone(),
+                version: Some(1),
+            },
+            edits: vec![OneOf::Left(edit((0, 0), (0, 1), text))],
+        };
+
+        let mut edit = WorkspaceEdit {
+            changes: Some(HashMap::from([(main.clone(), vec![edit((0, 0), (0, 1), "ignored")])])),
+            document_changes: Some(
+                DocumentChanges::Edits(
+                    vec![
+                        document_edit(&other, "a"),
+                        document_edit(&main, "b"),
+                        document_edit(&other, "c")
+                    ]
+                )
+            ),
+            change_annotations: None,
+        };
+
+        let grouped = workspace_edit_changes(&edit).unwrap();
+        assert_eq!(grouped.len(), 2);
+        assert_eq!(grouped[0].0, other);
+        let texts: Vec<&str> = grouped[0].1
+            .iter()
+            .map(|edit| edit.new_text.as_str())
+            .collect();
+        assert_eq!(texts, ["a", "c"]);
+
+        edit.document_changes = None;
+        let grouped = workspace_edit_changes(&edit).unwrap();
+        assert_eq!(grouped[0].0, main);
+        assert_eq!(grouped[0].1[0].new_text, "ignored");
+    }
+}
@@ -3,10 +3,15 @@
 pub mod protocol;
 pub mod requests;
 pub mod notifications;
+pub mod formatting;
+pub mod signature_help;
+pub mod symbols;
 
 pub use protocol::{ LspClient, LspMessage };
 pub use requests::RequestManager;
 pub use notifications::NotificationHandler;
+pub use formatting::{ ApplyTextEdits, workspace_edit_changes };
+pub use symbols::SymbolEntry;
 
 /// LSP errors
 #[derive(Debug, thiserror::Error)]
@@ -1,7 +1,18 @@
 //! LSP notification handling
 
+use crate::formatting::lsp_position_to_position;
+use editor_core::Buffer;
+use editor_core::decorations::{
+    DiagnosticSeverity as DecorationSeverity,
+    GutterDecoration,
+    GutterDecorationKind,
+    InlineDecoration,
+    InlineDecorationKind,
+};
 use lsp_types::*;
 use serde_json::Value;
+use std::ops::Range;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Notification handler trait
 pub trait NotificationHandler: Send + Sync {
@@ -70,3 +81,145 @@ impl Notification {
         }
     }
 }
+
+/// Map published diagnostics to inline squiggle decorations
+///
+/// Ranges are converted from LSP UTF-16 positions to grapheme offsets into the
+/// buffer, whose line offsets are counted once for all diagnostics.
+/// Overlapping diagnostics each get their own decoration.
+pub fn diagnostics_to_decorations(
+    params: &PublishDiagnosticsParams,
+    buffer: &Buffer
+) -> Vec<InlineDecoration> {
+    let line_starts = line_start_offsets(buffer);
+    params.diagnostics
+        .iter()
+        .map(|diagnostic| InlineDecoration {
+            range: lsp_range_to_grapheme_range(&diagnostic.range, buffer, &line_starts),
+            kind: squiggle_kind(diagnostic.severity),
+        })
+        .collect()
+}
+
+/// Map published diagnostics to gutter markers
+pub fn diagnostics_to_gutter_decorations(
+    params: &PublishDiagnosticsParams
+) -> Vec<GutterDecoration> {
+    params.diagnostics
+        .iter()
+        .map(|diagnostic| GutterDecoration {
+            line: diagnostic.range.start.line as usize,
+            kind: GutterDecorationKind::Diagnostic(decoration_severity(diagnostic.severity)),
+        })
+        .collect()
+}
+
+/// Convert an LSP severity to a decoration severity (missing severity is an error)
+fn decoration_severity(severity: Option<lsp_types::DiagnosticSeverity>) -> DecorationSeverity {
+    match severity {
+        Some(lsp_types::DiagnosticSeverity::WARNING) => DecorationSeverity::Warning,
+        Some(lsp_types::DiagnosticSeverity::INFORMATION) => DecorationSeverity::Info,
+        Some(lsp_types::DiagnosticSeverity::HINT) => DecorationSeverity::Hint,
+        _ => DecorationSeverity::Error,
+    }
+}
+
+/// Choose the squiggle matching an LSP severity
+fn squiggle_kind(severity: Option<lsp_types::DiagnosticSeverity>) -> InlineDecorationKind {
+    match decoration_severity(severity) {
+        DecorationSeverity::Error => InlineDecorationKind::ErrorSquiggle,
+        DecorationSeverity::Warning => InlineDecorationKind::WarningSquiggle,
+        DecorationSeverity::Info => InlineDecorationKind::InfoSquiggle,
+        DecorationSeverity::Hint => InlineDecorationKind::HintSquiggle,
+    }
+}
+
+/// Convert an LSP range to a grapheme range over the whole buffer
+fn lsp_range_to_grapheme_range(
+    range: &lsp_types::Range,
+    buffer: &Buffer,
+    line_starts: &[usize]
+) -> Range<usize> {
+    let start = lsp_position_to_grapheme_offset(range.start, buffer, line_starts);
+    let end = lsp_position_to_grapheme_offset(range.end, buffer, line_starts);
+    start..end.max(start)
+}
+
+/// Convert an LSP (line, UTF-16 column) position to a grapheme offset
+///
+/// `line_starts` holds the grapheme offset of every line of the buffer.
+fn lsp_position_to_grapheme_offset(
+    position: lsp_types::Position,
+    buffer: &Buffer,
+    line_starts: &[usize]
+) -> usize {
+    let position = lsp_position_to_position(position, buffer);
+    line_starts[position.line] + position.column
+}
+
+/// Grapheme offset of the start of every line of a buffer
+fn line_start_offsets(buffer: &Buffer) -> Vec<usize> {
+    let mut starts = Vec::with_capacity(buffer.len_lines());
+    let mut offset = 0;
+    for line in 0..buffer.len_lines() {
+        starts.push(offset);
+        offset += buffer
+            .line(line)
+            .map(|line| line.graphemes(true).count())
+            .unwrap_or_default();
+    }
+    starts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::BufferId;
+
+    fn diagnostic(
+        start: (u32, u32),
+        end: (u32, u32),
+        severity: lsp_types::DiagnosticSeverity
+    ) -> lsp_types::Diagnostic {
+        lsp_types::Diagnostic {
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(start.0, start.1),
+                lsp_types::Position::new(end.0, end.1)
+            ),
+            severity: Some(severity),
+            message: "test".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_diagnostics_to_decorations() {
+        // "😀" is two UTF-16 code units but a single grapheme
+        let buffer = Buffer::from_text(BufferId::new(1), "#let x = 😀y\nfoo bar\n");
+        let params = PublishDiagnosticsParams {
+            uri: Url::parse("file:///main.typ").unwrap(),
+            diagnostics: vec![
+                diagnostic((0, 9), (0, 12), lsp_types::DiagnosticSeverity::ERROR),
+                diagnostic((0, 11), (1, 3), lsp_types::DiagnosticSeverity::WARNING)
+            ],
+            version: None,
+        };
+
+        let inline = diagnostics_to_decorations(&params, &buffer);
+        assert_eq!(inline.len(), 2);
+        assert!(matches!(inline[0].kind, InlineDecorationKind::ErrorSquiggle));
+        assert_eq!(inline[0].range, 9..11);
+        assert!(matches!(inline[1].kind, InlineDecorationKind::WarningSquiggle));
+        assert_eq!(inline[1].range, 10..15);
+
+        let gutter = diagnostics_to_gutter_decorations(&params);
+        assert_eq!(gutter.len(), 2);
+        assert!(
+            matches!(gutter[0].kind, GutterDecorationKind::Diagnostic(DecorationSeverity::Error))
+        );
+        assert!(
+            matches!(gutter[1].kind, GutterDecorationKind::Diagnostic(DecorationSeverity::Warning))
+        );
+        assert_eq!(gutter[1].line, 0);
+    }
+}
@@ -1,10 +1,17 @@
 //! LSP protocol implementation
 
+use crate::symbols::{ symbol_tree, SymbolEntry };
 use crate::{ LspError, Result };
 use lsp_types::*;
 use serde_json::Value;
+use std::collections::VecDeque;
+use editor_core::signature::ParameterHints;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
+/// How long to wait for the response to a request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
 #[derive(Debug, Clone)]
 pub struct ResponseError {
     pub code: i32,
@@ -52,6 +59,8 @@ pub struct LspClient {
     next_id: i64,
     /// Server capabilities
     capabilities: Option<ServerCapabilities>,
+    /// Messages received while waiting for a response, in arrival order
+    deferred: VecDeque<LspMessage>,
 }
 
 impl LspClient {
@@ -64,6 +73,7 @@ impl LspClient {
             message_rx,
             next_id: 1,
             capabilities: None,
+            deferred: VecDeque::new(),
         }
     }
 
@@ -156,7 +166,173 @@ impl LspClient {
 
     /// Receive a message
     pub async fn receive_message(&mut self) -> Option<LspMessage> {
-        self.message_rx.recv().await
+        match self.deferred.pop_front() {
+            Some(message) => Some(message),
+            None => self.message_rx.recv().await,
+        }
+    }
+
+    /// Wait for the response to a request
+    ///
+    /// Other messages that arrive first are kept for
+    /// [`receive_message`](Self::receive_message).
+    async fn wait_for_response(&mut self, id: i64) -> Result<Option<Value>> {
+        let mut skipped = Vec::new();
+        let outcome = loop {
+            let message = tokio::time::timeout(REQUEST_TIMEOUT, self.message_rx.recv()).await;
+            match message {
+                Err(_) => {
+                    break Err(LspError::Timeout);
+                }
+                Ok(None) => {
+                    break Err(LspError::ConnectionError("Message channel closed".to_string()));
+                }
+                Ok(Some(LspMessage::Response { id: response_id, result, error })) if
+                    response_id == id
+                => {
+                    break match error {
+                        Some(error) =>
+                            Err(
+                                LspError::ProtocolError(
+                                    format!("{} (code {})", error.message, error.code)
+                                )
+                            ),
+                        None => Ok(result),
+                    };
+                }
+                Ok(Some(message)) => skipped.push(message),
+            }
+        };
+
+        self.deferred.extend(skipped);
+        outcome
+    }
+
+    /// Ask the server to format a whole document
+    ///
+    /// Returns the server's edits, which refer to the document as last sent
+    /// to the server. A server that has nothing to change may return no edits.
+    pub async fn format_document(
+        &mut self,
+        uri: Url,
+        tab_size: usize,
+        insert_spaces: bool
+    ) -> Result<Vec<TextEdit>> {
+        let params = DocumentFormattingParams {
+            text_document: TextDocumentIdentifier { uri },
+            options: FormattingOptions {
+                tab_size: tab_size as u32,
+                insert_spaces,
+                ..Default::default()
+            },
+            work_done_progress_params: Default::default(),
+        };
+        let params = serde_json
+            ::to_value(params)
+            .map_err(|e| LspError::ProtocolError(e.to_string()))?;
+
+        let id = self.send_request("textDocument/formatting".to_string(), params).await?;
+        match self.wait_for_response(id).await? {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(result) =>
+                serde_json::from_value(result).map_err(|e| LspError::ProtocolError(e.to_string())),
+        }
+    }
+
+    /// Ask the server to rename the symbol at a position
+    ///
+    /// Returns the edits to apply across the workspace; a server that finds
+    /// nothing to rename may return an empty edit.
+    pub async fn rename(
+        &mut self,
+        uri: Url,
+        position: Position,
+        new_name: String
+    ) -> Result<WorkspaceEdit> {
+        let params = RenameParams {
+            text_document_position: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            new_name,
+            work_done_progress_params: Default::default(),
+        };
+        let params = serde_json
+            ::to_value(params)
+            .map_err(|e| LspError::ProtocolError(e.to_string()))?;
+
+        let id = self.send_request("textDocument/rename".to_string(), params).await?;
+        match self.wait_for_response(id).await? {
+            None | Some(Value::Null) => Ok(WorkspaceEdit::default()),
+            Some(result) =>
+                serde_json::from_value(result).map_err(|e| LspError::ProtocolError(e.to_string())),
+        }
+    }
+
+    /// Ask the server for the signature of the call around a position
+    ///
+    /// Returns `Ok(None)` when there is nothing to show, so the hints should
+    /// be hidden. `buffer` holds the document's text as last sent to the
+    /// server; the hints are placed at `position` converted into it.
+    pub async fn signature_help(
+        &mut self,
+        uri: Url,
+        position: Position,
+        buffer: &editor_core::Buffer
+    ) -> Result<Option<ParameterHints>> {
+        let params = SignatureHelpParams {
+            context: None,
+            text_document_position_params: TextDocumentPositionParams {
+                text_document: TextDocumentIdentifier { uri },
+                position,
+            },
+            work_done_progress_params: Default::default(),
+        };
+        let params = serde_json
+            ::to_value(params)
+            .map_err(|e| LspError::ProtocolError(e.to_string()))?;
+
+        let id = self.send_request("textDocument/signatureHelp".to_string(), params).await?;
+        let help: SignatureHelp = match self.wait_for_response(id).await? {
+            None | Some(Value::Null) => {
+                return Ok(None);
+            }
+            Some(result) =>
+                serde_json::from_value(result).map_err(|e| LspError::ProtocolError(e.to_string()))?,
+        };
+
+        let at = crate::formatting::lsp_position_to_position(position, buffer);
+        Ok(crate::signature_help::parameter_hints(help, at))
+    }
+
+    /// Ask the server for the symbols of a document, as an outline tree
+    ///
+    /// `buffer` holds the document's text as last sent to the server; it is
+    /// needed to turn the server's UTF-16 ranges into grapheme positions.
+    pub async fn document_symbols(
+        &mut self,
+        uri: Url,
+        buffer: &editor_core::Buffer
+    ) -> Result<Vec<SymbolEntry>> {
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier { uri },
+            work_done_progress_params: Default::default(),
+            partial_result_params: Default::default(),
+        };
+        let params = serde_json
+            ::to_value(params)
+            .map_err(|e| LspError::ProtocolError(e.to_string()))?;
+
+        let id = self.send_request("textDocument/documentSymbol".to_string(), params).await?;
+        match self.wait_for_response(id).await? {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(result) => {
+                let response: DocumentSymbolResponse = serde_json
+                    ::from_value(result)
+                    .map_err(|e| LspError::ProtocolError(e.to_string()))?;
+                Ok(symbol_tree(response, buffer))
+            }
+        }
     }
 
     /// Get server capabilities
@@ -189,3 +365,139 @@ impl Default for LspClient {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    async fn running_client() -> LspClient {
+        let mut client = LspClient::new();
+        client.start().await.unwrap();
+        client
+    }
+
+    #[tokio::test]
+    async fn test_format_document_returns_server_edits() {
+        let mut client = running_client().await;
+        let uri = Url::parse("file:///main.typ").unwrap();
+
+        // Queue a notification and the server's reply to the first request
+        let log = LspMessage::Notification {
+            method: "window/logMessage".to_string(),
+            params: json!({}),
+        };
+        client.message_tx.send(log).await.unwrap();
+        client.message_tx
+            .send(LspMessage::Response {
+                id: 1,
+                result: Some(
+                    json!([{
+                        "range": {
+                            "start": { "line": 0, "character": 4 },
+                            "end": { "line": 0, "character": 6 }
+                        },
+                        "newText": " "
+                    }])
+                ),
+                error: None,
+            }).await
+            .unwrap();
+
+        let edits = client.format_document(uri, 2, true).await.unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(edits[0].new_text, " ");
+        assert_eq!(edits[0].range.end, lsp_types::Position::new(0, 6));
+
+        // Messages that arrived before the response are not lost
+        let deferred = client.receive_message().await;
+        assert!(
+            matches!(
+                deferred,
+                Some(LspMessage::Notification { method, .. }) if method == "window/logMessage"
+            )
+        );
+        assert!(matches!(client.receive_message().await, Some(LspMessage::Request { id: 1, .. })));
+    }
+
+    #[tokio::test]
+    async fn test_format_document_error_and_null() {
+        let mut client = running_client().await;
+        let uri = Url::parse("file:///main.typ").unwrap();
+
+        client.message_tx
+            .send(LspMessage::Response { id: 1, result: Some(Value::Null), error: None }).await
+            .unwrap();
+        assert!(client.format_document(uri.clone(), 4, false).await.unwrap().is_empty());
+
+        client.message_tx
+            .send(LspMessage::Response {
+                id: 2,
+                result: None,
+                error: Some(ResponseError { code: -32603, message: "parse error".to_string() }),
+            }).await
+            .unwrap();
+        let error = client.format_document(uri, 4, false).await.unwrap_err();
+        assert!(
+            matches!(error, LspError::ProtocolError(message) if message.contains("parse error"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signature_help_keeps_active_parameter() {
+        let mut client = running_client().await;
+        let uri = Url::parse("file:///main.typ").unwrap();
+
+        client.message_tx
+            .send(LspMessage::Response {
+                id: 1,
+                result: Some(
+                    json!({
+                        "signatures": [{
+                            "label": "heading(level: int, body: content)",
+                            "documentation": "A section heading.",
+                            "parameters": [
+                                { "label": [8, 18] },
+                                {
+                                    "label": "body",
+                                    "documentation": {
+                                        "kind": "markdown",
+                                        "value": "The heading's title."
+                                    }
+                                }
+                            ]
+                        }],
+                        "activeSignature": 0,
+                        "activeParameter": 1
+                    })
+                ),
+                error: None,
+            }).await
+            .unwrap();
+
+        // The emoji is two UTF-16 units but one grapheme
+        let buffer = editor_core::Buffer::from_text(
+            editor_core::BufferId::new(1),
+            "= Intro\n\n#let x = 1\n#heading(\"😀\", "
+        );
+        let position = Position::new(3, 15);
+        let hints = client.signature_help(uri.clone(), position, &buffer).await.unwrap().unwrap();
+        assert_eq!(hints.position, editor_core::Position::new(3, 14));
+        assert_eq!(hints.active_signature, 0);
+        assert_eq!(hints.active_parameter, 1);
+
+        let signature = hints.get_active_signature().unwrap();
+        assert_eq!(signature.label, "heading(level: int, body: content)");
+        assert_eq!(signature.documentation.as_deref(), Some("A section heading."));
+        assert_eq!(signature.parameters.len(), 2);
+        assert_eq!(signature.parameters[0].label, "level: int");
+        assert_eq!(signature.parameters[1].label, "body");
+        assert_eq!(signature.parameters[1].documentation.as_deref(), Some("The heading's title."));
+
+        // Nothing to show hides the hints
+        client.message_tx
+            .send(LspMessage::Response { id: 2, result: Some(Value::Null), error: None }).await
+            .unwrap();
+        assert!(client.signature_help(uri, position, &buffer).await.unwrap().is_none());
+    }
+}
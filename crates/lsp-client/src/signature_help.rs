@@ -0,0 +1,70 @@
+//! Converting signature help into parameter hints
+
+use lsp_types::{ Documentation, ParameterLabel, SignatureHelp, SignatureInformation };
+use editor_core::signature::{ ParameterHints, ParameterInfo, SignatureInfo };
+
+/// Convert a signature help response into parameter hints at a position
+///
+/// The active parameter of the active signature comes from the signature
+/// itself if the server set it there, otherwise from the response. Returns
+/// `None` if there are no signatures to show.
+pub fn parameter_hints(
+    help: SignatureHelp,
+    position: editor_core::Position
+) -> Option<ParameterHints> {
+    if help.signatures.is_empty() {
+        return None;
+    }
+
+    let active_signature = (help.active_signature.unwrap_or(0) as usize).min(
+        help.signatures.len() - 1
+    );
+    let active_parameter = help.signatures[active_signature].active_parameter
+        .or(help.active_parameter)
+        .unwrap_or(0) as usize;
+
+    let mut hints = ParameterHints::new(
+        position,
+        help.signatures.into_iter().map(signature_info).collect()
+    );
+    hints.active_signature = active_signature;
+    hints.active_parameter = active_parameter;
+    Some(hints)
+}
+
+fn signature_info(signature: SignatureInformation) -> SignatureInfo {
+    let parameters = signature.parameters
+        .unwrap_or_default()
+        .into_iter()
+        .map(|parameter| ParameterInfo {
+            label: parameter_label(&signature.label, parameter.label),
+            documentation: parameter.documentation.map(documentation_text),
+        })
+        .collect();
+
+    SignatureInfo {
+        label: signature.label,
+        parameters,
+        documentation: signature.documentation.map(documentation_text),
+    }
+}
+
+/// Parameter label text; offsets are UTF-16 offsets into the signature label
+fn parameter_label(signature_label: &str, label: ParameterLabel) -> String {
+    match label {
+        ParameterLabel::Simple(label) => label,
+        ParameterLabel::LabelOffsets([start, end]) => {
+            let units: Vec<u16> = signature_label.encode_utf16().collect();
+            let end = (end as usize).min(units.len());
+            let start = (start as usize).min(end);
+            String::from_utf16_lossy(&units[start..end])
+        }
+    }
+}
+
+fn documentation_text(documentation: Documentation) -> String {
+    match documentation {
+        Documentation::String(text) => text,
+        Documentation::MarkupContent(content) => content.value,
+    }
+}
@@ -0,0 +1,239 @@
+//! Normalizing document symbols into an outline tree
+
+use crate::formatting::lsp_position_to_position;
+use editor_core::{ Buffer, Position };
+use lsp_types::{ DocumentSymbol, DocumentSymbolResponse, SymbolInformation, SymbolKind };
+use std::ops::Range;
+
+/// A symbol in the document outline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolEntry {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Full extent of the symbol, in grapheme positions
+    pub range: Range<Position>,
+    /// Symbols nested inside this one, in document order
+    pub children: Vec<SymbolEntry>,
+}
+
+/// Build the symbol tree from either response shape
+///
+/// Hierarchical responses keep their nesting. Flat responses are nested by
+/// `containerName`: a symbol becomes a child of the first other symbol with
+/// that name, and stays top-level if there is none.
+pub fn symbol_tree(response: DocumentSymbolResponse, buffer: &Buffer) -> Vec<SymbolEntry> {
+    match response {
+        DocumentSymbolResponse::Nested(symbols) => {
+            symbols
+                .into_iter()
+                .map(|symbol| nested_entry(symbol, buffer))
+                .collect()
+        }
+        DocumentSymbolResponse::Flat(symbols) => flat_tree(symbols, buffer),
+    }
+}
+
+fn nested_entry(symbol: DocumentSymbol, buffer: &Buffer) -> SymbolEntry {
+    SymbolEntry {
+        name: symbol.name,
+        kind: symbol.kind,
+        range: resolve_range(symbol.range, buffer),
+        children: symbol.children
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| nested_entry(child, buffer))
+            .collect(),
+    }
+}
+
+fn flat_tree(symbols: Vec<SymbolInformation>, buffer: &Buffer) -> Vec<SymbolEntry> {
+    let parents: Vec<Option<usize>> = symbols
+        .iter()
+        .enumerate()
+        .map(|(index, symbol)| {
+            let container = symbol.container_name.as_deref()?;
+            symbols
+                .iter()
+                .enumerate()
+                .position(|(other, candidate)| other != index && candidate.name == container)
+        })
+        .collect();
+
+    // Containers that name each other would never be reached from a root
+    let in_cycle = |index: usize| {
+        let mut current = parents[index];
+        for _ in 0..symbols.len() {
+            match current {
+                Some(parent) if parent == index => {
+                    return true;
+                }
+                Some(parent) => {
+                    current = parents[parent];
+                }
+                None => {
+                    return false;
+                }
+            }
+        }
+        false
+    };
+    let parents: Vec<Option<usize>> = (0..symbols.len())
+        .map(|index| parents[index].filter(|_| !in_cycle(index)))
+        .collect();
+
+    let mut entries: Vec<Option<SymbolEntry>> = symbols
+        .into_iter()
+        .map(|symbol| {
+            Some(SymbolEntry {
+                name: symbol.name,
+                kind: symbol.kind,
+                range: resolve_range(symbol.location.range, buffer),
+                children: Vec::new(),
+            })
+        })
+        .collect();
+
+    // Attach the deepest symbols first, so children are complete before
+    // their parent is moved
+    let depth = |mut index: usize| {
+        let mut depth = 0;
+        while let Some(parent) = parents[index] {
+            depth += 1;
+            index = parent;
+        }
+        depth
+    };
+    let mut order: Vec<usize> = (0..entries.len())
+        .filter(|&index| parents[index].is_some())
+        .collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(depth(index)));
+    for index in order {
+        if let (Some(entry), Some(parent)) = (entries[index].take(), parents[index]) {
+            if let Some(parent) = entries[parent].as_mut() {
+                parent.children.push(entry);
+            }
+        }
+    }
+
+    let mut roots: Vec<SymbolEntry> = entries.into_iter().flatten().collect();
+    sort_by_position(&mut roots);
+    roots
+}
+
+/// Put symbols and their children in document order
+fn sort_by_position(entries: &mut [SymbolEntry]) {
+    entries.sort_by_key(|entry| entry.range.start);
+    for entry in entries {
+        sort_by_position(&mut entry.children);
+    }
+}
+
+fn resolve_range(range: lsp_types::Range, buffer: &Buffer) -> Range<Position> {
+    lsp_position_to_position(range.start, buffer)..lsp_position_to_position(range.end, buffer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::BufferId;
+    use serde_json::json;
+
+    const SOURCE: &str = "= Über\n== Détails\n#let x = 1\n= End\n";
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> serde_json::Value {
+        json!({
+            "start": { "line": start.0, "character": start.1 },
+            "end": { "line": end.0, "character": end.1 }
+        })
+    }
+
+    fn names(entries: &[SymbolEntry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_hierarchical_symbols_keep_nesting() {
+        let buffer = Buffer::from_text(BufferId::new(1), SOURCE);
+        let response: DocumentSymbolResponse = serde_json
+            ::from_value(
+                json!([
+                    {
+                        "name": "Über",
+                        "kind": 3,
+                        "range": range((0, 0), (2, 10)),
+                        "selectionRange": range((0, 2), (0, 6)),
+                        "children": [{
+                            "name": "Détails",
+                            "kind": 3,
+                            "range": range((1, 0), (2, 10)),
+                            "selectionRange": range((1, 3), (1, 10)),
+                            "children": [{
+                                "name": "x",
+                                "kind": 13,
+                                "range": range((2, 5), (2, 6)),
+                                "selectionRange": range((2, 5), (2, 6))
+                            }]
+                        }]
+                    },
+                    {
+                        "name": "End",
+                        "kind": 3,
+                        "range": range((3, 0), (3, 5)),
+                        "selectionRange": range((3, 2), (3, 5))
+                    }
+                ])
+            )
+            .unwrap();
+
+        let tree = symbol_tree(response, &buffer);
+        assert_eq!(names(&tree), ["Über", "End"]);
+        assert_eq!(names(&tree[0].children), ["Détails"]);
+        assert_eq!(names(&tree[0].children[0].children), ["x"]);
+        assert_eq!(tree[0].children[0].children[0].kind, SymbolKind::VARIABLE);
+        assert_eq!(tree[0].range, Position::new(0, 0)..Position::new(2, 10));
+        assert!(tree[1].children.is_empty());
+    }
+
+    #[test]
+    fn test_flat_symbols_grouped_by_container() {
+        // "😀" is two UTF-16 code units but one grapheme
+        let buffer = Buffer::from_text(BufferId::new(1), "= 😀 Intro\n== Part\n#let x = 1\n");
+        let location = |start: (u32, u32), end: (u32, u32)| {
+            json!({ "uri": "file:///main.typ", "range": range(start, end) })
+        };
+        let response: DocumentSymbolResponse = serde_json
+            ::from_value(
+                json!([
+                    {
+                        "name": "x",
+                        "kind": 13,
+                        "location": location((2, 5), (2, 6)),
+                        "containerName": "Part"
+                    },
+                    {
+                        "name": "Part",
+                        "kind": 3,
+                        "location": location((1, 0), (2, 10)),
+                        "containerName": "😀 Intro"
+                    },
+                    { "name": "😀 Intro", "kind": 3, "location": location((0, 0), (0, 10)) },
+                    {
+                        "name": "orphan",
+                        "kind": 13,
+                        "location": location((2, 0), (2, 1)),
+                        "containerName": "missing"
+                    }
+                ])
+            )
+            .unwrap();
+
+        let tree = symbol_tree(response, &buffer);
+        assert_eq!(names(&tree), ["😀 Intro", "orphan"]);
+        assert_eq!(names(&tree[0].children), ["Part"]);
+        assert_eq!(names(&tree[0].children[0].children), ["x"]);
+        assert_eq!(tree[0].range, Position::new(0, 0)..Position::new(0, 9));
+    }
+}
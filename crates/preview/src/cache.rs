@@ -0,0 +1,168 @@
+//! Cache of rendered pages
+
+use crate::renderer::{ RenderFormat, RenderedPage };
+use std::collections::{ BTreeMap, HashMap };
+
+/// Ratio between the zooms of neighboring buckets
+///
+/// Zooms within about 2.5% of each other share a bucket, and so a render.
+pub const ZOOM_BUCKET_RATIO: f32 = 1.05;
+
+/// Bucket that a zoom level is rendered at
+pub fn zoom_bucket(zoom: f32) -> i32 {
+    (zoom.ln() / ZOOM_BUCKET_RATIO.ln()).round() as i32
+}
+
+/// Zoom level that pages in a bucket are rendered at
+pub fn bucket_zoom(bucket: i32) -> f32 {
+    ZOOM_BUCKET_RATIO.powi(bucket)
+}
+
+/// Identifies a render: page, zoom bucket, format and document version
+pub type CacheKey = (usize, i32, RenderFormat, u64);
+
+/// How often the cache was consulted successfully
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Rendered pages, evicting the least recently used beyond a memory budget
+///
+/// Each entry is charged its bytes plus [`PageCache::ENTRY_OVERHEAD`], so
+/// even empty renders count towards the budget.
+#[derive(Debug)]
+pub struct PageCache {
+    /// Renders with the tick of their last use
+    entries: HashMap<CacheKey, (RenderedPage, u64)>,
+    /// Keys by the tick of their last use, least recent first
+    recency: BTreeMap<u64, CacheKey>,
+    /// Bytes currently charged to the budget
+    used: usize,
+    budget: usize,
+    /// Incremented on every access to order entries by recency
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl PageCache {
+    /// Bytes charged per entry on top of its rendered bytes
+    pub const ENTRY_OVERHEAD: usize = 256;
+
+    /// Default memory budget
+    pub const DEFAULT_BUDGET: usize = 64 * 1024 * 1024;
+
+    pub fn new(budget: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            used: 0,
+            budget,
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// Get a cached render, marking it as recently used
+    pub fn get(&mut self, key: CacheKey) -> Option<RenderedPage> {
+        self.clock += 1;
+        match self.entries.get_mut(&key) {
+            Some((page, last_used)) => {
+                self.recency.remove(last_used);
+                self.recency.insert(self.clock, key);
+                *last_used = self.clock;
+                self.stats.hits += 1;
+                Some(page.clone())
+            }
+            None => {
+                self.stats.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Cache a render, evicting older ones to stay within the budget
+    ///
+    /// A render larger than the whole budget is not cached.
+    pub fn insert(&mut self, key: CacheKey, page: RenderedPage) {
+        let size = Self::size_of(&page);
+        if size > self.budget {
+            return;
+        }
+
+        self.remove(key);
+        while self.used + size > self.budget && self.evict_oldest() {}
+
+        self.clock += 1;
+        self.used += size;
+        self.entries.insert(key, (page, self.clock));
+        self.recency.insert(self.clock, key);
+    }
+
+    /// Drop every entry
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.recency.clear();
+        self.used = 0;
+    }
+
+    /// Change the memory budget, evicting entries if it shrank
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        while self.used > self.budget && self.evict_oldest() {}
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Bytes currently charged to the budget
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn contains(&self, key: CacheKey) -> bool {
+        self.entries.contains_key(&key)
+    }
+
+    fn remove(&mut self, key: CacheKey) {
+        if let Some((page, last_used)) = self.entries.remove(&key) {
+            self.recency.remove(&last_used);
+            self.used -= Self::size_of(&page);
+        }
+    }
+
+    /// Remove the least recently used entry, returning whether there was one
+    fn evict_oldest(&mut self) -> bool {
+        match self.recency.first_key_value() {
+            Some((_, &key)) => {
+                self.remove(key);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn size_of(page: &RenderedPage) -> usize {
+        page.bytes.len() + Self::ENTRY_OVERHEAD
+    }
+}
+
+impl Default for PageCache {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_BUDGET)
+    }
+}
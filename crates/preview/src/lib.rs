@@ -1,12 +1,21 @@
 //! Preview rendering for compiled Typst documents
 
+pub mod cache;
 pub mod renderer;
 pub mod sync;
 pub mod viewport;
 
-pub use renderer::{ PreviewRenderer, RenderFormat };
+pub use renderer::{
+    PreviewRenderer,
+    RenderFormat,
+    RenderedPage,
+    RasterThreshold,
+    PreviewStyle,
+    PagePlacement,
+};
+pub use cache::{ CacheStats, PageCache };
 pub use sync::{ SourceMapping, SyncManager };
-pub use viewport::{ Viewport, ZoomLevel };
+pub use viewport::{ Viewport, ZoomLevel, PreviewRect, Size, PageLayout };
 
 /// Preview errors
 #[derive(Debug, thiserror::Error)]
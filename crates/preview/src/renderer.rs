@@ -1,22 +1,111 @@
 //! Preview rendering implementation
 
+use crate::cache::{ self, CacheStats, PageCache };
+use crate::viewport::{ PageLayout, Size };
 use crate::{ PreviewError, Result };
+use palette::Srgb;
+use std::cell::{ Cell, RefCell };
 use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Resolution of a page shown at 100% zoom
+pub const BASE_DPI: f32 = 96.0;
 
 /// Output format for rendering
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RenderFormat {
     Pdf,
     Svg,
     Png,
 }
 
+/// Styling for the chrome around rendered pages
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PreviewStyle {
+    /// Background color behind the pages
+    pub background: Srgb,
+    /// Draw a drop shadow under each page
+    pub page_shadow: bool,
+    /// Gap between pages, and around the first and last page, in pixels
+    pub page_gap: f32,
+}
+
+impl Default for PreviewStyle {
+    fn default() -> Self {
+        Self {
+            background: Srgb::new(0.5, 0.5, 0.5),
+            page_shadow: true,
+            page_gap: 16.0,
+        }
+    }
+}
+
+/// When pages switch from SVG to rasterized PNG
+///
+/// SVG stays crisp and cheap at normal sizes, but at high zoom its
+/// complexity becomes the bottleneck, so pages above the threshold are
+/// rasterized instead. Near the threshold the previous format is kept until
+/// the resolution moves `hysteresis` (a fraction of `dpi`) past it, so zooming
+/// back and forth around it does not switch formats on every step.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RasterThreshold {
+    /// Resolution above which pages are rasterized
+    pub dpi: f32,
+    pub hysteresis: f32,
+}
+
+impl Default for RasterThreshold {
+    fn default() -> Self {
+        Self {
+            dpi: 2.0 * BASE_DPI,
+            hysteresis: 0.1,
+        }
+    }
+}
+
+/// A rendered page
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedPage {
+    pub page: usize,
+    /// Either [`RenderFormat::Svg`] or [`RenderFormat::Png`]
+    pub format: RenderFormat,
+    /// Resolution the page was rendered for
+    pub dpi: f32,
+    /// Rendered data, shared with the page cache
+    pub bytes: Arc<[u8]>,
+}
+
+/// Placement of a single page in the scrollable preview content
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PagePlacement {
+    /// Page index
+    pub page: usize,
+    /// Horizontal offset, centering the page within the widest page
+    pub x: f32,
+    /// Vertical offset from the top of the content
+    pub y: f32,
+    /// Page width
+    pub width: f32,
+    /// Page height
+    pub height: f32,
+}
+
 /// Preview renderer
 pub struct PreviewRenderer {
     /// Current document path
     document: Option<PathBuf>,
     /// Render format
     format: RenderFormat,
+    /// Page chrome styling
+    style: PreviewStyle,
+    /// When pages are rasterized rather than rendered as SVG
+    raster_threshold: RasterThreshold,
+    /// Format of the last page rendered by `render_page`
+    last_page_format: Cell<Option<RenderFormat>>,
+    /// Incremented whenever the document is recompiled
+    document_version: u64,
+    /// Pages rendered for the current document version
+    cache: RefCell<PageCache>,
 }
 
 impl PreviewRenderer {
@@ -24,24 +113,75 @@ impl PreviewRenderer {
         Self {
             document: None,
             format,
+            style: PreviewStyle::default(),
+            raster_threshold: RasterThreshold::default(),
+            last_page_format: Cell::new(None),
+            document_version: 0,
+            cache: RefCell::new(PageCache::default()),
         }
     }
 
     /// Load a document for preview
     pub fn load_document(&mut self, path: PathBuf) -> Result<()> {
         self.document = Some(path);
+        self.invalidate();
         Ok(())
     }
 
-    /// Render a specific page
-    pub fn render_page(&self, _page: usize) -> Result<Vec<u8>> {
-        if self.document.is_none() {
-            return Err(PreviewError::DocumentNotLoaded);
+    /// Drop every cached page, after the document was recompiled
+    pub fn invalidate(&mut self) {
+        self.document_version += 1;
+        self.cache.get_mut().clear();
+    }
+
+    /// Version of the document pages are rendered from
+    pub fn document_version(&self) -> u64 {
+        self.document_version
+    }
+
+    /// Render a page for display at `zoom`, where 1.0 is 100%
+    ///
+    /// Pages are SVG up to the raster threshold and PNG above it; see
+    /// [`RasterThreshold`]. Zoom is rounded to a bucket (see
+    /// [`cache::zoom_bucket`]) and pages are rendered at the bucket's zoom, so
+    /// nearby zoom levels reuse the same cached render.
+    pub fn render_page(&self, page: usize, zoom: f32) -> Result<RenderedPage> {
+        if page >= self.page_count()? {
+            return Err(PreviewError::InvalidPage(page));
+        }
+        if !(zoom.is_finite() && zoom > 0.0) {
+            return Err(PreviewError::RenderingFailed(format!("Invalid zoom {}", zoom)));
+        }
+
+        // The format depends on the last page's, so it is decided before the
+        // lookup and is part of the key
+        let bucket = cache::zoom_bucket(zoom);
+        let dpi = cache::bucket_zoom(bucket) * BASE_DPI;
+        let format = self.page_format(dpi);
+        self.last_page_format.set(Some(format));
+
+        let key = (page, bucket, format, self.document_version);
+        if let Some(rendered) = self.cache.borrow_mut().get(key) {
+            return Ok(rendered);
         }
 
         // TODO: Implement actual rendering using pdfium or SVG
         // For now, return empty data
-        Ok(Vec::new())
+        let rendered = RenderedPage { page, format, dpi, bytes: Arc::from([]) };
+        self.cache.borrow_mut().insert(key, rendered.clone());
+        Ok(rendered)
+    }
+
+    /// Format for a page at `dpi`, given the format of the last page
+    fn page_format(&self, dpi: f32) -> RenderFormat {
+        let RasterThreshold { dpi: threshold, hysteresis } = self.raster_threshold;
+        let band = threshold * hysteresis.max(0.0);
+        let rasterize = match self.last_page_format.get() {
+            Some(RenderFormat::Png) => dpi >= threshold - band,
+            Some(_) => dpi > threshold + band,
+            None => dpi > threshold,
+        };
+        if rasterize { RenderFormat::Png } else { RenderFormat::Svg }
     }
 
     /// Get number of pages
@@ -63,6 +203,59 @@ impl PreviewRenderer {
     pub fn format(&self) -> RenderFormat {
         self.format
     }
+
+    /// Set page chrome styling
+    pub fn set_style(&mut self, style: PreviewStyle) {
+        self.style = style;
+    }
+
+    /// Get page chrome styling
+    pub fn style(&self) -> &PreviewStyle {
+        &self.style
+    }
+
+    /// Set when pages are rasterized
+    pub fn set_raster_threshold(&mut self, threshold: RasterThreshold) {
+        self.raster_threshold = threshold;
+    }
+
+    /// Get when pages are rasterized
+    pub fn raster_threshold(&self) -> RasterThreshold {
+        self.raster_threshold
+    }
+
+    /// Set the memory budget of the page cache, in bytes
+    pub fn set_cache_budget(&mut self, budget: usize) {
+        self.cache.get_mut().set_budget(budget);
+    }
+
+    /// Get the memory budget of the page cache, in bytes
+    pub fn cache_budget(&self) -> usize {
+        self.cache.borrow().budget()
+    }
+
+    /// Get how often renders were served from the page cache
+    pub fn cache_stats(&self) -> CacheStats {
+        self.cache.borrow().stats()
+    }
+
+    /// Stack pages vertically, separated by the style's page gap
+    pub fn page_layout(&self, page_sizes: &[(f32, f32)]) -> Vec<PagePlacement> {
+        self.stack_pages(page_sizes).placements()
+    }
+
+    /// Total content height for the given pages, including gaps
+    pub fn content_height(&self, page_sizes: &[(f32, f32)]) -> f32 {
+        self.stack_pages(page_sizes).total_height()
+    }
+
+    fn stack_pages(&self, page_sizes: &[(f32, f32)]) -> PageLayout {
+        let pages = page_sizes
+            .iter()
+            .map(|&(width, height)| Size::new(width, height))
+            .collect();
+        PageLayout::new(pages, self.style.page_gap)
+    }
 }
 
 impl Default for PreviewRenderer {
@@ -70,3 +263,145 @@ impl Default for PreviewRenderer {
         Self::new(RenderFormat::Pdf)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rendered_format(renderer: &PreviewRenderer, zoom: f32) -> RenderFormat {
+        renderer.render_page(0, zoom).unwrap().format
+    }
+
+    fn loaded_renderer() -> PreviewRenderer {
+        let mut renderer = PreviewRenderer::default();
+        renderer.load_document(PathBuf::from("main.pdf")).unwrap();
+        renderer.set_raster_threshold(RasterThreshold { dpi: 192.0, hysteresis: 0.1 });
+        renderer
+    }
+
+    #[test]
+    fn test_format_by_zoom() {
+        let renderer = loaded_renderer();
+        let page = renderer.render_page(0, 1.0).unwrap();
+        assert_eq!(page.format, RenderFormat::Svg);
+        assert_eq!(page.dpi, 96.0);
+
+        assert_eq!(rendered_format(&loaded_renderer(), 4.0), RenderFormat::Png);
+        // Without a previous page, the threshold itself decides
+        assert_eq!(rendered_format(&loaded_renderer(), 1.9), RenderFormat::Svg);
+        assert_eq!(rendered_format(&loaded_renderer(), 2.1), RenderFormat::Png);
+
+        assert!(matches!(renderer.render_page(1, 1.0), Err(PreviewError::InvalidPage(1))));
+        assert!(renderer.render_page(0, 0.0).is_err());
+        assert!(PreviewRenderer::default().render_page(0, 1.0).is_err());
+    }
+
+    #[test]
+    fn test_format_switch_is_hysteretic() {
+        // The threshold is 192 DPI (zoom 2.0), with a band from 1.8 to 2.2
+        let renderer = loaded_renderer();
+        assert_eq!(rendered_format(&renderer, 1.0), RenderFormat::Svg);
+        assert_eq!(rendered_format(&renderer, 2.1), RenderFormat::Svg);
+        assert_eq!(rendered_format(&renderer, 2.3), RenderFormat::Png);
+
+        // Back near the threshold, the page stays rasterized
+        assert_eq!(rendered_format(&renderer, 2.0), RenderFormat::Png);
+        assert_eq!(rendered_format(&renderer, 1.85), RenderFormat::Png);
+        assert_eq!(rendered_format(&renderer, 1.7), RenderFormat::Svg);
+        assert_eq!(rendered_format(&renderer, 2.15), RenderFormat::Svg);
+    }
+
+    #[test]
+    fn test_cached_render_keeps_hysteresis() {
+        let renderer = loaded_renderer();
+        assert_eq!(rendered_format(&renderer, 1.0), RenderFormat::Svg);
+        assert_eq!(rendered_format(&renderer, 2.1), RenderFormat::Svg);
+        assert_eq!(rendered_format(&renderer, 2.3), RenderFormat::Png);
+
+        // The SVG cached at 2.1 is not reused once pages are rasterized
+        assert_eq!(rendered_format(&renderer, 2.1), RenderFormat::Png);
+        assert_eq!(rendered_format(&renderer, 2.3), RenderFormat::Png);
+        assert_eq!(renderer.cache_stats(), CacheStats { hits: 1, misses: 4 });
+    }
+
+    #[test]
+    fn test_repeated_render_hits_cache() {
+        let renderer = loaded_renderer();
+        let first = renderer.render_page(0, 1.5).unwrap();
+        assert_eq!(renderer.cache_stats(), CacheStats { hits: 0, misses: 1 });
+
+        // A tiny zoom change falls in the same bucket
+        assert_eq!(renderer.render_page(0, 1.5).unwrap(), first);
+        assert_eq!(renderer.render_page(0, 1.51).unwrap(), first);
+        assert_eq!(renderer.cache_stats(), CacheStats { hits: 2, misses: 1 });
+
+        renderer.render_page(0, 3.0).unwrap();
+        assert_eq!(renderer.cache_stats().misses, 2);
+    }
+
+    #[test]
+    fn test_new_version_invalidates_cache() {
+        let mut renderer = loaded_renderer();
+        renderer.render_page(0, 1.0).unwrap();
+        let version = renderer.document_version();
+
+        renderer.invalidate();
+        assert_eq!(renderer.document_version(), version + 1);
+        renderer.render_page(0, 1.0).unwrap();
+        assert_eq!(renderer.cache_stats(), CacheStats { hits: 0, misses: 2 });
+        assert_eq!(renderer.cache.borrow().len(), 1);
+    }
+
+    #[test]
+    fn test_cache_evicts_least_recently_used() {
+        let page = |page| RenderedPage {
+            page,
+            format: RenderFormat::Png,
+            dpi: BASE_DPI,
+            bytes: Arc::from(vec![0; 1000]),
+        };
+        let key = |page| (page, 0, RenderFormat::Png, 1);
+        let size = 1000 + PageCache::ENTRY_OVERHEAD;
+        let mut cache = PageCache::new(2 * size);
+        cache.insert(key(0), page(0));
+        cache.insert(key(1), page(1));
+        cache.get(key(0)).unwrap();
+
+        cache.insert(key(2), page(2));
+        assert!(cache.contains(key(0)));
+        assert!(!cache.contains(key(1)));
+        assert_eq!(cache.used(), 2 * size);
+
+        // Shrinking the budget evicts too, and oversized renders are skipped
+        cache.set_budget(size);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.contains(key(2)));
+        cache.insert(key(3), RenderedPage { bytes: Arc::from(vec![0; 2000]), ..page(3) });
+        assert!(!cache.contains(key(3)));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_page_layout_with_gap() {
+        let mut renderer = PreviewRenderer::default();
+        renderer.set_style(PreviewStyle {
+            page_gap: 20.0,
+            ..PreviewStyle::default()
+        });
+
+        let sizes = [
+            (600.0, 800.0),
+            (600.0, 800.0),
+            (400.0, 500.0),
+        ];
+        let layout = renderer.page_layout(&sizes);
+
+        let offsets: Vec<f32> = layout
+            .iter()
+            .map(|placement| placement.y)
+            .collect();
+        assert_eq!(offsets, vec![20.0, 840.0, 1660.0]);
+        assert_eq!(layout[2].x, 120.0);
+        assert_eq!(renderer.content_height(&sizes), 2180.0);
+    }
+}
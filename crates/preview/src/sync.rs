@@ -1,8 +1,10 @@
 //! Source-preview synchronization
 
+use crate::viewport::PreviewRect;
 use serde::{ Deserialize, Serialize };
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::btree_map::Entry;
+use std::collections::{ BTreeMap, HashMap };
+use std::path::{ Path, PathBuf };
 
 /// Position in source code
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -19,20 +21,160 @@ pub struct PreviewPosition {
     pub y: f32,
 }
 
+/// A laid-out element and the source positions that produced it
+#[derive(Debug, Clone)]
+struct MappedElement {
+    bounds: PreviewRect,
+    sources: Vec<(PathBuf, SourcePosition)>,
+    /// Insertion order; later elements are drawn on top
+    order: usize,
+}
+
+impl MappedElement {
+    fn contains(&self, x: f32, y: f32) -> bool {
+        x >= self.bounds.x &&
+            x <= self.bounds.x + self.bounds.width &&
+            y >= self.bounds.y &&
+            y <= self.bounds.y + self.bounds.height
+    }
+
+    fn distance_to(&self, x: f32, y: f32) -> f32 {
+        let dx = (self.bounds.x - x).max(x - (self.bounds.x + self.bounds.width)).max(0.0);
+        let dy = (self.bounds.y - y).max(y - (self.bounds.y + self.bounds.height)).max(0.0);
+        dx.hypot(dy)
+    }
+
+    fn area(&self) -> f32 {
+        self.bounds.width * self.bounds.height
+    }
+
+    /// Orders smaller elements first, then the topmost of equal-sized ones
+    fn pick_order(&self, other: &Self) -> std::cmp::Ordering {
+        self.area()
+            .total_cmp(&other.area())
+            .then_with(|| other.order.cmp(&self.order))
+    }
+}
+
+/// Total-order key of an element's bounds, sorted by top edge first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct BoundsKey([u32; 4]);
+
+impl BoundsKey {
+    fn new(bounds: PreviewRect) -> Self {
+        Self([bounds.y, bounds.x, bounds.width, bounds.height].map(ordered_bits))
+    }
+
+    /// Smallest key with its top edge at `y`
+    fn first_at(y: f32) -> Self {
+        Self([ordered_bits(y), 0, 0, 0])
+    }
+
+    /// Largest key with its top edge at `y`
+    fn last_at(y: f32) -> Self {
+        Self([ordered_bits(y), u32::MAX, u32::MAX, u32::MAX])
+    }
+}
+
+/// Bits of a float that sort in the same order as its value
+fn ordered_bits(value: f32) -> u32 {
+    // Adding zero folds -0.0 into 0.0
+    let bits = (value + 0.0).to_bits();
+    if bits >> 31 == 1 { !bits } else { bits | (1 << 31) }
+}
+
+/// Elements of one page ordered by their top edge
+///
+/// A point can only fall inside elements whose top lies within the tallest
+/// element's height above it, so lookups visit just that range.
+#[derive(Debug, Clone, Default)]
+struct PageIndex {
+    elements: BTreeMap<BoundsKey, MappedElement>,
+    max_height: f32,
+}
+
+impl PageIndex {
+    fn insert(&mut self, file: PathBuf, source_pos: SourcePosition, bounds: PreviewRect) {
+        let order = self.elements.len();
+        match self.elements.entry(BoundsKey::new(bounds)) {
+            Entry::Occupied(mut entry) => entry.get_mut().sources.push((file, source_pos)),
+            Entry::Vacant(entry) => {
+                self.max_height = self.max_height.max(bounds.height);
+                entry.insert(MappedElement {
+                    bounds,
+                    sources: vec![(file, source_pos)],
+                    order,
+                });
+            }
+        }
+    }
+
+    /// Elements whose top edge lies in `top..=bottom`
+    fn tops_between(&self, top: f32, bottom: f32) -> impl Iterator<Item = &MappedElement> {
+        let (start, end) = (BoundsKey::first_at(top), BoundsKey::last_at(bottom));
+        // `BTreeMap::range` panics on a reversed range
+        let range = (start <= end).then(|| self.elements.range(start..=end));
+        range.into_iter().flatten().map(|(_, element)| element)
+    }
+
+    /// First element with its top edge at or below `y`
+    fn first_below(&self, y: f32) -> Option<&MappedElement> {
+        self.elements
+            .range(BoundsKey::first_at(y)..)
+            .next()
+            .map(|(_, element)| element)
+    }
+
+    fn first(&self) -> Option<&MappedElement> {
+        self.elements.values().next()
+    }
+
+    fn last(&self) -> Option<&MappedElement> {
+        self.elements.values().next_back()
+    }
+
+    /// Innermost element containing the point, or the nearest one within
+    /// `radius`
+    fn pick(&self, x: f32, y: f32, radius: f32) -> Option<&MappedElement> {
+        let containing = self
+            .tops_between(y - self.max_height, y)
+            .filter(|element| element.contains(x, y))
+            .min_by(|a, b| a.pick_order(b));
+        if containing.is_some() {
+            return containing;
+        }
+
+        self.tops_between(y - radius - self.max_height, y + radius)
+            .map(|element| (element.distance_to(x, y), element))
+            .filter(|(distance, _)| *distance <= radius)
+            .min_by(|(a_distance, a), (b_distance, b)| {
+                a_distance.total_cmp(b_distance).then_with(|| a.pick_order(b))
+            })
+            .map(|(_, element)| element)
+    }
+}
+
 /// Source mapping between source and preview
 #[derive(Clone)]
 pub struct SourceMapping {
     /// Map from source positions to preview positions
     source_to_preview: HashMap<(PathBuf, SourcePosition), Vec<PreviewPosition>>,
-    /// Map from preview positions to source positions
-    preview_to_source: HashMap<(usize, (u32, u32)), Vec<(PathBuf, SourcePosition)>>,
+    /// First preview position of each mapped line, per file
+    lines: HashMap<PathBuf, BTreeMap<usize, PreviewPosition>>,
+    /// Spatial index of mapped elements per page
+    pages: BTreeMap<usize, PageIndex>,
 }
 
 impl SourceMapping {
+    /// Distance in points within which a click outside every element still
+    /// picks the nearest one
+    pub const PICK_RADIUS: f32 = 8.0;
+
     pub fn new() -> Self {
         Self {
             source_to_preview: HashMap::new(),
-            preview_to_source: HashMap::new(),
+            lines: HashMap::new(),
+            pages: BTreeMap::new(),
         }
     }
 
@@ -43,42 +185,113 @@ impl SourceMapping {
         source_pos: SourcePosition,
         preview_pos: PreviewPosition
     ) {
+        let bounds = PreviewRect::new(preview_pos.x, preview_pos.y, 0.0, 0.0);
+        self.add_element_mapping(file, source_pos, preview_pos.page, bounds);
+    }
+
+    /// Add a mapping for an element covering `bounds` on `page`
+    pub fn add_element_mapping(
+        &mut self,
+        file: PathBuf,
+        source_pos: SourcePosition,
+        page: usize,
+        bounds: PreviewRect
+    ) {
+        let preview_pos = PreviewPosition { page, x: bounds.x, y: bounds.y };
         self.source_to_preview
             .entry((file.clone(), source_pos))
-            .or_insert_with(Vec::new)
+            .or_default()
             .push(preview_pos);
 
-        let grid_pos = (((preview_pos.x as u32) / 10) * 10, ((preview_pos.y as u32) / 10) * 10);
-        self.preview_to_source
-            .entry((preview_pos.page, grid_pos))
-            .or_insert_with(Vec::new)
-            .push((file, source_pos));
+        self.lines
+            .entry(file.clone())
+            .or_default()
+            .entry(source_pos.line)
+            .or_insert(preview_pos);
+
+        self.pages.entry(page).or_default().insert(file, source_pos, bounds);
     }
 
     /// Find preview positions for a source position
     pub fn source_to_preview_lookup(
         &self,
-        file: &PathBuf,
+        file: &Path,
         pos: SourcePosition
     ) -> Option<&[PreviewPosition]> {
-        self.source_to_preview.get(&(file.clone(), pos)).map(|v| v.as_slice())
+        self.source_to_preview.get(&(file.to_path_buf(), pos)).map(|v| v.as_slice())
     }
 
     /// Find source positions for a preview position
+    ///
+    /// Returns the sources of the innermost element containing the point,
+    /// preferring the topmost of equally sized overlapping elements. Points
+    /// outside every element pick the nearest one within
+    /// [`PICK_RADIUS`](Self::PICK_RADIUS).
     pub fn preview_to_source_lookup(
         &self,
         page: usize,
         x: f32,
         y: f32
     ) -> Option<&[(PathBuf, SourcePosition)]> {
-        let grid_pos = (((x as u32) / 10) * 10, ((y as u32) / 10) * 10);
-        self.preview_to_source.get(&(page, grid_pos)).map(|v| v.as_slice())
+        self.pages
+            .get(&page)?
+            .pick(x, y, Self::PICK_RADIUS)
+            .map(|element| element.sources.as_slice())
+    }
+
+    /// Find the mapped line closest to `line` and its preview position
+    ///
+    /// Ties prefer the line after, so the result stays at or below `line`
+    /// where possible.
+    pub fn nearest_line_lookup(
+        &self,
+        file: &Path,
+        line: usize
+    ) -> Option<(usize, PreviewPosition)> {
+        let lines = self.lines.get(file)?;
+        let after = lines.range(line..).next();
+        let before = lines.range(..line).next_back();
+
+        match (after, before) {
+            (Some(after), Some(before)) if line - before.0 < after.0 - line => Some(before),
+            (Some(after), _) => Some(after),
+            (None, before) => before,
+        }.map(|(&line, &pos)| (line, pos))
+    }
+
+    /// Find the source position mapped closest to `y` on `page`
+    ///
+    /// Prefers the first mapping at or below `y`, then the closest one above
+    /// it. Pages without mappings fall back to the next mapped page, then
+    /// the previous one.
+    pub fn nearest_preview_lookup(
+        &self,
+        page: usize,
+        y: f32
+    ) -> Option<(&PathBuf, SourcePosition)> {
+        if let Some(index) = self.pages.get(&page) {
+            if let Some(element) = index.first_below(y).or_else(|| index.last()) {
+                let (file, pos) = element.sources.first()?;
+                return Some((file, *pos));
+            }
+        }
+
+        let next = self.pages
+            .range(page + 1..)
+            .find_map(|(_, index)| index.first());
+        let previous = self.pages
+            .range(..page)
+            .rev()
+            .find_map(|(_, index)| index.last());
+        let (file, pos) = next.or(previous)?.sources.first()?;
+        Some((file, *pos))
     }
 
     /// Clear all mappings
     pub fn clear(&mut self) {
         self.source_to_preview.clear();
-        self.preview_to_source.clear();
+        self.lines.clear();
+        self.pages.clear();
     }
 }
 
@@ -106,7 +319,7 @@ impl SyncManager {
     }
 
     /// Sync from source to preview
-    pub fn sync_to_preview(&self, file: &PathBuf, pos: SourcePosition) -> Option<PreviewPosition> {
+    pub fn sync_to_preview(&self, file: &Path, pos: SourcePosition) -> Option<PreviewPosition> {
         self.mapping
             .source_to_preview_lookup(file, pos)
             .and_then(|positions| positions.first())
@@ -120,6 +333,28 @@ impl SyncManager {
             .and_then(|positions| positions.first())
             .cloned()
     }
+
+    /// Preview page and y to scroll to when `top_line` is the topmost visible
+    /// source line
+    ///
+    /// Lines without a mapping use the closest mapped line instead.
+    pub fn sync_scroll_source_to_preview(
+        &self,
+        file: &Path,
+        top_line: usize
+    ) -> Option<(usize, f32)> {
+        self.mapping
+            .nearest_line_lookup(file, top_line)
+            .map(|(_, pos)| (pos.page, pos.y))
+    }
+
+    /// Source file and line to scroll to when `y` on `page` is the top of the
+    /// visible preview
+    pub fn sync_scroll_preview_to_source(&self, page: usize, y: f32) -> Option<(PathBuf, usize)> {
+        self.mapping
+            .nearest_preview_lookup(page, y)
+            .map(|(file, pos)| (file.clone(), pos.line))
+    }
 }
 
 impl Default for SyncManager {
@@ -127,3 +362,119 @@ impl Default for SyncManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sparse_manager(file: &Path) -> SyncManager {
+        let mut mapping = SourceMapping::new();
+        for (line, page, y) in [(2, 0, 100.0), (10, 0, 400.0), (30, 1, 50.0)] {
+            mapping.add_mapping(
+                file.to_path_buf(),
+                SourcePosition { line, column: 0 },
+                PreviewPosition { page, x: 72.0, y }
+            );
+        }
+        let mut manager = SyncManager::new();
+        manager.update_mapping(mapping);
+        manager
+    }
+
+    #[test]
+    fn test_scroll_source_to_preview_sparse() {
+        let file = PathBuf::from("main.typ");
+        let manager = sparse_manager(&file);
+
+        assert_eq!(manager.sync_scroll_source_to_preview(&file, 10), Some((0, 400.0)));
+        assert_eq!(manager.sync_scroll_source_to_preview(&file, 0), Some((0, 100.0)));
+        assert_eq!(manager.sync_scroll_source_to_preview(&file, 8), Some((0, 400.0)));
+        assert_eq!(manager.sync_scroll_source_to_preview(&file, 12), Some((0, 400.0)));
+        assert_eq!(manager.sync_scroll_source_to_preview(&file, 25), Some((1, 50.0)));
+        assert_eq!(manager.sync_scroll_source_to_preview(&file, 99), Some((1, 50.0)));
+        assert_eq!(
+            manager.sync_scroll_source_to_preview(&PathBuf::from("other.typ"), 10),
+            None
+        );
+    }
+
+    #[test]
+    fn test_preview_lookup_picks_innermost_element() {
+        let file = PathBuf::from("main.typ");
+        let pos = |line| SourcePosition { line, column: 0 };
+        let rect = PreviewRect::new;
+        let mut mapping = SourceMapping::new();
+        mapping.add_element_mapping(file.clone(), pos(1), 0, rect(0.0, 0.0, 500.0, 300.0));
+        mapping.add_element_mapping(file.clone(), pos(2), 0, rect(50.0, 50.0, 200.0, 100.0));
+        mapping.add_element_mapping(file.clone(), pos(3), 0, rect(60.0, 60.0, 40.0, 20.0));
+        // Same size as line 3 and added later, so drawn on top of it
+        mapping.add_element_mapping(file.clone(), pos(4), 0, rect(80.0, 60.0, 40.0, 20.0));
+
+        let line_at = |x, y| {
+            mapping.preview_to_source_lookup(0, x, y).map(|sources| sources[0].1.line)
+        };
+        assert_eq!(line_at(10.0, 10.0), Some(1));
+        assert_eq!(line_at(200.0, 120.0), Some(2));
+        assert_eq!(line_at(65.0, 70.0), Some(3));
+        assert_eq!(line_at(90.0, 70.0), Some(4));
+        assert_eq!(mapping.preview_to_source_lookup(1, 65.0, 70.0), None);
+    }
+
+    #[test]
+    fn test_page_index_orders_by_top_edge() {
+        let file = PathBuf::from("main.typ");
+        let pos = |line| SourcePosition { line, column: 0 };
+        let mut index = PageIndex::default();
+        for (line, y) in [(1, 40.0), (2, -20.0), (3, 0.0), (4, -0.0), (5, 10.5)] {
+            index.insert(file.clone(), pos(line), PreviewRect::new(0.0, y, 10.0, 10.0));
+        }
+
+        // -0.0 and 0.0 are the same bounds, so their sources merge
+        let tops: Vec<f32> = index.tops_between(-100.0, 100.0)
+            .map(|element| element.bounds.y)
+            .collect();
+        assert_eq!(tops, vec![-20.0, 0.0, 10.5, 40.0]);
+        assert_eq!(index.first_below(1.0).map(|element| element.bounds.y), Some(10.5));
+        assert_eq!(index.pick(5.0, 5.0, 0.0).map(|element| element.sources.len()), Some(2));
+        assert_eq!(index.tops_between(50.0, 10.0).count(), 0);
+    }
+
+    #[test]
+    fn test_preview_lookup_nearest_within_radius() {
+        let file = PathBuf::from("main.typ");
+        let mut mapping = SourceMapping::new();
+        mapping.add_element_mapping(
+            file.clone(),
+            SourcePosition { line: 1, column: 0 },
+            0,
+            PreviewRect::new(100.0, 100.0, 50.0, 10.0)
+        );
+        mapping.add_mapping(
+            file.clone(),
+            SourcePosition { line: 2, column: 4 },
+            PreviewPosition { page: 0, x: 300.0, y: 100.0 }
+        );
+
+        let line_at = |x, y| {
+            mapping.preview_to_source_lookup(0, x, y).map(|sources| sources[0].1.line)
+        };
+        // Just right of the box and just across a would-be grid boundary
+        assert_eq!(line_at(155.0, 105.0), Some(1));
+        assert_eq!(line_at(299.0, 99.0), Some(2));
+        assert_eq!(line_at(200.0, 105.0), None);
+        assert_eq!(line_at(125.0, 100.0 + 10.0 + SourceMapping::PICK_RADIUS + 1.0), None);
+    }
+
+    #[test]
+    fn test_scroll_preview_to_source_sparse() {
+        let file = PathBuf::from("main.typ");
+        let manager = sparse_manager(&file);
+
+        assert_eq!(manager.sync_scroll_preview_to_source(0, 0.0), Some((file.clone(), 2)));
+        assert_eq!(manager.sync_scroll_preview_to_source(0, 150.0), Some((file.clone(), 10)));
+        assert_eq!(manager.sync_scroll_preview_to_source(0, 600.0), Some((file.clone(), 10)));
+        assert_eq!(manager.sync_scroll_preview_to_source(1, 0.0), Some((file.clone(), 30)));
+        assert_eq!(manager.sync_scroll_preview_to_source(5, 0.0), Some((file, 30)));
+        assert_eq!(SyncManager::new().sync_scroll_preview_to_source(0, 0.0), None);
+    }
+}
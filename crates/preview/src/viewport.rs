@@ -1,5 +1,6 @@
 //! Viewport management for preview
 
+use crate::renderer::PagePlacement;
 use serde::{ Deserialize, Serialize };
 
 /// Zoom level for preview
@@ -16,6 +17,29 @@ pub enum ZoomLevel {
 }
 
 impl ZoomLevel {
+    /// Smallest custom zoom scale
+    pub const MIN_SCALE: f32 = 0.1;
+    /// Largest custom zoom scale
+    pub const MAX_SCALE: f32 = 8.0;
+
+    /// Scale factor for a page of `page_size` shown in a `viewport`, both as
+    /// (width, height)
+    ///
+    /// Custom scales are clamped to [`MIN_SCALE`](Self::MIN_SCALE)..=[`MAX_SCALE`](Self::MAX_SCALE).
+    pub fn resolve(&self, page_size: (f32, f32), viewport: (f32, f32)) -> f32 {
+        let (page_width, page_height) = page_size;
+        let (viewport_width, viewport_height) = viewport;
+        let width_scale = viewport_width / page_width.max(f32::EPSILON);
+        let height_scale = viewport_height / page_height.max(f32::EPSILON);
+
+        match self {
+            ZoomLevel::FitWidth => width_scale,
+            ZoomLevel::FitPage => width_scale.min(height_scale),
+            ZoomLevel::FitHeight => height_scale,
+            ZoomLevel::Custom(scale) => scale.clamp(Self::MIN_SCALE, Self::MAX_SCALE),
+        }
+    }
+
     /// Convert to scale factor
     pub fn to_scale(
         &self,
@@ -24,15 +48,23 @@ impl ZoomLevel {
         page_width: f32,
         page_height: f32
     ) -> f32 {
-        match self {
-            ZoomLevel::FitWidth => viewport_width / page_width,
-            ZoomLevel::FitPage => {
-                let width_scale = viewport_width / page_width;
-                let height_scale = viewport_height / page_height;
-                width_scale.min(height_scale)
+        self.resolve((page_width, page_height), (viewport_width, viewport_height))
+    }
+
+    /// Parse a zoom setting such as `"fit_width"`, `"fit_page"`,
+    /// `"fit_height"`, `"150%"` or `"1.5"`
+    pub fn from_setting(value: &str) -> Option<ZoomLevel> {
+        match value.trim() {
+            "fit_width" => Some(ZoomLevel::FitWidth),
+            "fit_page" => Some(ZoomLevel::FitPage),
+            "fit_height" => Some(ZoomLevel::FitHeight),
+            value => {
+                let scale = match value.strip_suffix('%') {
+                    Some(percent) => percent.trim().parse::<f32>().ok()? / 100.0,
+                    None => value.parse::<f32>().ok()?,
+                };
+                scale.is_finite().then_some(ZoomLevel::Custom(scale))
             }
-            ZoomLevel::FitHeight => viewport_height / page_height,
-            ZoomLevel::Custom(scale) => *scale,
         }
     }
 }
@@ -43,6 +75,138 @@ impl Default for ZoomLevel {
     }
 }
 
+/// Width and height pair
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Size<T> {
+    pub width: T,
+    pub height: T,
+}
+
+impl<T> Size<T> {
+    pub fn new(width: T, height: T) -> Self {
+        Self { width, height }
+    }
+}
+
+/// Rectangle in unscaled preview content coordinates
+///
+/// Content coordinates place pages as laid out by
+/// [`PreviewRenderer::page_layout`](crate::PreviewRenderer::page_layout).
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PreviewRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl PreviewRect {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self { x, y, width, height }
+    }
+
+    /// Smallest rectangle containing both rectangles
+    pub fn union(&self, other: &PreviewRect) -> PreviewRect {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        PreviewRect::new(x, y, right - x, bottom - y)
+    }
+}
+
+/// Vertical stack of pages for continuous scrolling
+///
+/// Pages are separated by `gap`, with one gap above the first page and one
+/// to the left of the widest page; narrower pages are centered on it.
+/// Offsets are in unscaled content coordinates; multiply by the zoom scale to
+/// get viewport scroll positions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageLayout {
+    pages: Vec<Size<f32>>,
+    gap: f32,
+    /// Top edge of each page
+    tops: Vec<f32>,
+    total_height: f32,
+}
+
+impl PageLayout {
+    pub fn new(pages: Vec<Size<f32>>, gap: f32) -> Self {
+        let mut tops = Vec::with_capacity(pages.len());
+        let mut y = gap;
+        for page in &pages {
+            tops.push(y);
+            y += page.height + gap;
+        }
+
+        Self {
+            pages,
+            gap,
+            tops,
+            total_height: y,
+        }
+    }
+
+    /// Number of pages
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Size of a page
+    pub fn page_size(&self, page: usize) -> Option<Size<f32>> {
+        self.pages.get(page).copied()
+    }
+
+    /// Height of the whole stack, including gaps
+    pub fn total_height(&self) -> f32 {
+        self.total_height
+    }
+
+    /// Page containing content offset `y` and the offset within that page
+    ///
+    /// Offsets in the gap below a page resolve to that page's bottom edge and
+    /// offsets above the first page to its top. Returns `(0, 0.0)` if there
+    /// are no pages.
+    pub fn page_at_scroll(&self, y: f32) -> (usize, f32) {
+        if self.pages.is_empty() {
+            return (0, 0.0);
+        }
+
+        let page = self.tops.partition_point(|top| *top <= y).saturating_sub(1);
+        let in_page = (y - self.tops[page]).clamp(0.0, self.pages[page].height);
+        (page, in_page)
+    }
+
+    /// Where each page is placed in the content
+    pub fn placements(&self) -> Vec<PagePlacement> {
+        let max_width = self.pages
+            .iter()
+            .map(|page| page.width)
+            .fold(0.0, f32::max);
+
+        self.pages
+            .iter()
+            .zip(&self.tops)
+            .enumerate()
+            .map(|(page, (size, &y))| PagePlacement {
+                page,
+                x: self.gap + (max_width - size.width) / 2.0,
+                y,
+                width: size.width,
+                height: size.height,
+            })
+            .collect()
+    }
+
+    /// Content offset of a page's top edge, clamped to the last page
+    pub fn scroll_for_page(&self, page: usize) -> f32 {
+        match self.tops.len() {
+            0 => 0.0,
+            len => self.tops[page.min(len - 1)],
+        }
+    }
+}
+
 /// Viewport for preview display
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Viewport {
@@ -70,6 +234,9 @@ impl Viewport {
     }
 
     /// Set viewport size
+    ///
+    /// Fit zoom modes follow the new size, since the scale is resolved from
+    /// the current size whenever it is queried.
     pub fn set_size(&mut self, width: f32, height: f32) {
         self.width = width;
         self.height = height;
@@ -96,7 +263,7 @@ impl Viewport {
     /// Zoom in (increase by 25%)
     pub fn zoom_in(&mut self) {
         if let ZoomLevel::Custom(scale) = self.zoom {
-            self.zoom = ZoomLevel::Custom((scale * 1.25).min(4.0));
+            self.zoom = ZoomLevel::Custom((scale * 1.25).min(ZoomLevel::MAX_SCALE));
         } else {
             self.zoom = ZoomLevel::Custom(1.25);
         }
@@ -105,12 +272,37 @@ impl Viewport {
     /// Zoom out (decrease by 25%)
     pub fn zoom_out(&mut self) {
         if let ZoomLevel::Custom(scale) = self.zoom {
-            self.zoom = ZoomLevel::Custom((scale / 1.25).max(0.1));
+            self.zoom = ZoomLevel::Custom((scale / 1.25).max(ZoomLevel::MIN_SCALE));
         } else {
             self.zoom = ZoomLevel::Custom(0.75);
         }
     }
 
+    /// Zoom and scroll so the given rectangles fill the viewport
+    ///
+    /// The bounding box of `rects` is scaled to fit inside the viewport minus
+    /// `padding` on every side, then centered. Does nothing if `rects` is empty.
+    pub fn fit_to_rects(&mut self, rects: &[PreviewRect], viewport_size: Size<f32>, padding: f32) {
+        let Some(bounds) = rects.iter().copied().reduce(|acc, rect| acc.union(&rect)) else {
+            return;
+        };
+
+        self.set_size(viewport_size.width, viewport_size.height);
+
+        let available_width = (viewport_size.width - 2.0 * padding).max(1.0);
+        let available_height = (viewport_size.height - 2.0 * padding).max(1.0);
+        let scale = (available_width / bounds.width.max(f32::EPSILON))
+            .min(available_height / bounds.height.max(f32::EPSILON))
+            .clamp(ZoomLevel::MIN_SCALE, ZoomLevel::MAX_SCALE);
+        self.zoom = ZoomLevel::Custom(scale);
+
+        let center_x = (bounds.x + bounds.width / 2.0) * scale;
+        let center_y = (bounds.y + bounds.height / 2.0) * scale;
+        self.scroll_x = center_x - viewport_size.width / 2.0;
+        self.scroll_y = center_y - viewport_size.height / 2.0;
+        self.clamp_scroll();
+    }
+
     /// Get current scale factor for given page dimensions
     pub fn current_scale(&self, page_width: f32, page_height: f32) -> f32 {
         self.zoom.to_scale(self.width, self.height, page_width, page_height)
@@ -150,6 +342,34 @@ mod tests {
         assert!((fit_page_scale - 1.142857).abs() < 0.001);
     }
 
+    #[test]
+    fn test_resolve_portrait_page_in_wide_viewport() {
+        let page = (600.0, 800.0);
+        let viewport = (1200.0, 400.0);
+
+        assert_eq!(ZoomLevel::FitWidth.resolve(page, viewport), 2.0);
+        assert_eq!(ZoomLevel::FitPage.resolve(page, viewport), 0.5);
+        assert_eq!(ZoomLevel::FitHeight.resolve(page, viewport), 0.5);
+        assert_eq!(ZoomLevel::Custom(1.5).resolve(page, viewport), 1.5);
+        assert_eq!(ZoomLevel::Custom(20.0).resolve(page, viewport), ZoomLevel::MAX_SCALE);
+        assert_eq!(ZoomLevel::Custom(0.0).resolve(page, viewport), ZoomLevel::MIN_SCALE);
+    }
+
+    #[test]
+    fn test_zoom_from_setting_and_resize() {
+        assert_eq!(ZoomLevel::from_setting("fit_width"), Some(ZoomLevel::FitWidth));
+        assert_eq!(ZoomLevel::from_setting("fit_page"), Some(ZoomLevel::FitPage));
+        assert_eq!(ZoomLevel::from_setting("150%"), Some(ZoomLevel::Custom(1.5)));
+        assert_eq!(ZoomLevel::from_setting("0.75"), Some(ZoomLevel::Custom(0.75)));
+        assert_eq!(ZoomLevel::from_setting("huge"), None);
+
+        let mut viewport = Viewport::new(1200.0, 400.0);
+        viewport.set_zoom(ZoomLevel::FitWidth);
+        assert_eq!(viewport.current_scale(600.0, 800.0), 2.0);
+        viewport.set_size(900.0, 400.0);
+        assert_eq!(viewport.current_scale(600.0, 800.0), 1.5);
+    }
+
     #[test]
     fn test_scrolling() {
         let mut viewport = Viewport::new(800.0, 600.0);
@@ -161,4 +381,51 @@ mod tests {
         assert_eq!(viewport.scroll_x, 120.0);
         assert_eq!(viewport.scroll_y, 80.0);
     }
+
+    #[test]
+    fn test_page_layout_variable_sizes() {
+        let layout = PageLayout::new(
+            vec![Size::new(600.0, 800.0), Size::new(800.0, 600.0), Size::new(600.0, 400.0)],
+            10.0
+        );
+
+        assert_eq!(layout.scroll_for_page(0), 10.0);
+        assert_eq!(layout.scroll_for_page(2), 1430.0);
+        assert_eq!(layout.scroll_for_page(9), 1430.0);
+        assert_eq!(layout.total_height(), 1840.0);
+
+        // Mid-document lands inside the second page
+        assert_eq!(layout.page_at_scroll(920.0), (1, 100.0));
+        assert_eq!(layout.page_at_scroll(1500.0), (2, 70.0));
+
+        // Gaps resolve to the edge of the neighbouring page
+        assert_eq!(layout.page_at_scroll(815.0), (0, 800.0));
+        assert_eq!(layout.page_at_scroll(0.0), (0, 0.0));
+
+        // Narrower pages are centered on the widest one
+        let xs: Vec<f32> = layout.placements()
+            .iter()
+            .map(|placement| placement.x)
+            .collect();
+        assert_eq!(xs, vec![110.0, 10.0, 110.0]);
+    }
+
+    #[test]
+    fn test_fit_to_single_rect() {
+        let mut viewport = Viewport::default();
+        let rect = PreviewRect::new(100.0, 200.0, 350.0, 125.0);
+
+        viewport.fit_to_rects(&[rect], Size::new(800.0, 600.0), 50.0);
+
+        // Width is the limiting side: (800 - 2 * 50) / 350
+        assert_eq!(viewport.zoom, ZoomLevel::Custom(2.0));
+
+        // The rect's center lands on the viewport's center
+        let screen_left = rect.x * 2.0 - viewport.scroll_x;
+        let screen_right = (rect.x + rect.width) * 2.0 - viewport.scroll_x;
+        let screen_center_y = (rect.y + rect.height / 2.0) * 2.0 - viewport.scroll_y;
+        assert_eq!(screen_left, 50.0);
+        assert_eq!(screen_right, 750.0);
+        assert_eq!(screen_center_y, 300.0);
+    }
 }
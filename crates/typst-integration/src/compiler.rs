@@ -1,10 +1,18 @@
 //! Typst compilation service
 
-use crate::diagnostics::{ Diagnostic, DiagnosticList };
+use crate::diagnostics::{ Diagnostic, DiagnosticFormat, DiagnosticList };
 use crate::world::SystemWorld;
 use crate::{ Result, TypstError };
+use std::collections::BTreeMap;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tokio_util::sync::CancellationToken;
+use typst::diag::SourceDiagnostic;
+use typst::foundations::{ Dict, Str, Value };
 
 /// Request for compilation
 #[derive(Debug, Clone)]
@@ -15,6 +23,90 @@ pub struct CompileRequest {
     pub main_file: PathBuf,
     /// Request ID for tracking
     pub id: u64,
+    /// Values available to the document as `sys.inputs`
+    pub inputs: BTreeMap<String, String>,
+    /// How diagnostic messages are written
+    pub diagnostic_format: DiagnosticFormat,
+}
+
+impl CompileRequest {
+    /// Start building a request to compile `main`
+    pub fn builder(main: PathBuf) -> CompileRequestBuilder {
+        CompileRequestBuilder {
+            main,
+            root: None,
+            id: 0,
+            inputs: BTreeMap::new(),
+            diagnostic_format: DiagnosticFormat::default(),
+        }
+    }
+}
+
+/// Builder for a [`CompileRequest`]
+#[derive(Debug, Clone)]
+pub struct CompileRequestBuilder {
+    main: PathBuf,
+    root: Option<PathBuf>,
+    id: u64,
+    inputs: BTreeMap<String, String>,
+    diagnostic_format: DiagnosticFormat,
+}
+
+impl CompileRequestBuilder {
+    /// Set the project root; a relative main file is resolved against it
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = Some(root.into());
+        self
+    }
+
+    /// Set the request ID
+    pub fn with_id(mut self, id: u64) -> Self {
+        self.id = id;
+        self
+    }
+
+    /// Add a value to `sys.inputs`, replacing any with the same key
+    pub fn with_input(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.inputs.insert(key.into(), value.into());
+        self
+    }
+
+    /// Set how diagnostic messages are written
+    pub fn diagnostic_format(mut self, format: DiagnosticFormat) -> Self {
+        self.diagnostic_format = format;
+        self
+    }
+
+    /// Build the request
+    ///
+    /// Without a root, the main file's directory is the root.
+    pub fn build(self) -> CompileRequest {
+        let (root, main_file) = match self.root {
+            Some(root) => {
+                let main_file = if self.main.is_absolute() {
+                    self.main
+                } else {
+                    root.join(&self.main)
+                };
+                (root, main_file)
+            }
+            None => {
+                let root = match self.main.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                    _ => PathBuf::from("."),
+                };
+                (root, self.main)
+            }
+        };
+
+        CompileRequest {
+            root,
+            main_file,
+            id: self.id,
+            inputs: self.inputs,
+            diagnostic_format: self.diagnostic_format,
+        }
+    }
 }
 
 /// Result of compilation
@@ -34,6 +126,8 @@ pub struct CompileResult {
 pub struct Compiler {
     request_tx: mpsc::Sender<CompileRequest>,
     result_rx: mpsc::Receiver<CompileResult>,
+    /// Token of the latest cancellable compile
+    active: Option<CancellationToken>,
 }
 
 impl Compiler {
@@ -45,7 +139,7 @@ impl Compiler {
         // Spawn compiler thread
         tokio::spawn(async move {
             while let Some(request) = request_rx.recv().await {
-                let result = Self::compile_internal(request).await;
+                let result = Self::compile_internal(request, None);
                 let _ = result_tx.send(result).await;
             }
         });
@@ -53,6 +147,7 @@ impl Compiler {
         Self {
             request_tx,
             result_rx,
+            active: None,
         }
     }
 
@@ -68,13 +163,65 @@ impl Compiler {
         self.result_rx.recv().await
     }
 
+    /// Compile, giving up with [`TypstError::Cancelled`] once `cancel` fires
+    ///
+    /// Starting a compile cancels the previous one. This returns as soon as
+    /// the token fires, and each compile uses its own world, so a cancelled
+    /// compile leaves nothing behind.
+    ///
+    /// Typst offers no way to interrupt a compile, so the token is only
+    /// checked before compiling and whenever Typst loads a file, which stops
+    /// documents split across includes or imports early. A large single file
+    /// is not interrupted: its compile finishes on a blocking thread and the
+    /// result is discarded.
+    pub async fn compile_cancellable(
+        &mut self,
+        request: CompileRequest,
+        cancel: CancellationToken
+    ) -> Result<CompileResult> {
+        if let Some(previous) = self.active.replace(cancel.clone()) {
+            previous.cancel();
+        }
+
+        let token = cancel.clone();
+        let compile = tokio::task::spawn_blocking(move || {
+            Self::compile_internal(request, Some(token))
+        });
+        tokio::select! {
+            biased;
+            _ = cancel.cancelled() => Err(TypstError::Cancelled),
+            result = compile => {
+                let result = result.map_err(|e| TypstError::CompilationFailed(e.to_string()))?;
+                // Errors from files refused after cancelling are not real
+                if cancel.is_cancelled() {
+                    return Err(TypstError::Cancelled);
+                }
+                Ok(result)
+            }
+        }
+    }
+
     /// Internal compilation implementation
-    async fn compile_internal(request: CompileRequest) -> CompileResult {
+    fn compile_internal(
+        request: CompileRequest,
+        cancel: Option<CancellationToken>
+    ) -> CompileResult {
         let mut diagnostics = DiagnosticList::new();
 
+        let inputs: Dict = request.inputs
+            .iter()
+            .map(|(key, value)| (Str::from(key.as_str()), Value::Str(Str::from(value.as_str()))))
+            .collect();
+
         // Create world
         let world = match SystemWorld::new(request.root.clone(), request.main_file.clone()) {
-            Ok(w) => w,
+            Ok(w) => {
+                let world = w.with_inputs(inputs);
+                match &cancel {
+                    Some(cancel) => world.with_cancellation(cancel.clone()),
+                    None => world,
+                }
+            }
             Err(e) => {
                 diagnostics.add(Diagnostic::error(format!("Failed to create world: {}", e)));
                 return CompileResult {
@@ -86,12 +233,35 @@ impl Compiler {
             }
         };
 
-        // Compile
-        let result = typst::compile(&world);
+        Self::compile_world(request, &world, cancel.as_ref())
+    }
+
+    /// Compile a request in a world whose file accesses fail once `cancel`
+    /// fires
+    ///
+    /// A cancelled compile reports only that it was cancelled, since errors
+    /// from refused files are not real.
+    fn compile_world(
+        request: CompileRequest,
+        world: &dyn typst::World,
+        cancel: Option<&CancellationToken>
+    ) -> CompileResult {
+        let cancelled = || cancel.is_some_and(CancellationToken::is_cancelled);
+        if cancelled() {
+            return Self::cancelled(request.id);
+        }
+
+        let result = typst::compile(world);
+        if cancelled() {
+            return Self::cancelled(request.id);
+        }
+
+        let mut diagnostics = DiagnosticList::new();
 
         // Handle warnings
         for warning in &result.warnings {
-            diagnostics.add(Diagnostic::warning(format!("{:?}", warning)));
+            let hints = Self::hints(warning, request.diagnostic_format);
+            diagnostics.add(Diagnostic::warning(warning.message.to_string()).with_hints(hints));
         }
 
         match result.output {
@@ -107,7 +277,8 @@ impl Compiler {
             Err(errors) => {
                 // Convert Typst errors to diagnostics
                 for error in errors {
-                    diagnostics.add(Diagnostic::error(format!("{:?}", error)));
+                    let hints = Self::hints(&error, request.diagnostic_format);
+                    diagnostics.add(Diagnostic::error(error.message.to_string()).with_hints(hints));
                 }
                 CompileResult {
                     id: request.id,
@@ -118,6 +289,29 @@ impl Compiler {
             }
         }
     }
+
+    /// Result of a compile given up because it was cancelled
+    fn cancelled(id: u64) -> CompileResult {
+        let mut diagnostics = DiagnosticList::new();
+        diagnostics.add(Diagnostic::error("compilation cancelled".to_string()));
+        CompileResult {
+            id,
+            success: false,
+            diagnostics,
+            document: None,
+        }
+    }
+
+    /// Hints of a Typst diagnostic, if the requested format keeps them
+    fn hints(diagnostic: &SourceDiagnostic, format: DiagnosticFormat) -> Vec<String> {
+        match format {
+            DiagnosticFormat::Human => diagnostic.hints
+                .iter()
+                .map(|hint| hint.to_string())
+                .collect(),
+            DiagnosticFormat::Short => Vec::new(),
+        }
+    }
 }
 
 impl Default for Compiler {
@@ -126,13 +320,366 @@ impl Default for Compiler {
     }
 }
 
+/// Quiet time after the last request before compiling
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// Longest a request waits to compile while requests keep arriving
+pub const DEFAULT_MAX_WAIT: Duration = Duration::from_secs(1);
+
+/// Compiles a request on a blocking thread, stopping early once cancelled
+pub type CompileFn = Arc<dyn Fn(CompileRequest, CancellationToken) -> CompileResult + Send + Sync>;
+
+/// Compiles the latest of rapidly arriving requests
+///
+/// Each request restarts the debounce timer, and only the latest request is
+/// compiled when it fires. While requests keep arriving, the latest one is
+/// still compiled once the oldest waiting request has waited `max_wait`.
+/// Starting a compile cancels the one in flight, whose result is dropped.
+pub struct CompileScheduler {
+    request_tx: mpsc::UnboundedSender<CompileRequest>,
+    result_rx: mpsc::Receiver<CompileResult>,
+}
+
+impl CompileScheduler {
+    /// Create a scheduler with the default timing
+    pub fn new() -> Self {
+        Self::with_timing(DEFAULT_DEBOUNCE, DEFAULT_MAX_WAIT)
+    }
+
+    /// Create a scheduler with the given debounce and maximum wait
+    pub fn with_timing(debounce: Duration, max_wait: Duration) -> Self {
+        let compile: CompileFn = Arc::new(|request, cancel| {
+            Compiler::compile_internal(request, Some(cancel))
+        });
+        Self::with_compile_fn(debounce, max_wait, compile)
+    }
+
+    /// Create a scheduler that compiles with `compile` instead of Typst
+    pub fn with_compile_fn(debounce: Duration, max_wait: Duration, compile: CompileFn) -> Self {
+        let (request_tx, request_rx) = mpsc::unbounded_channel();
+        let (result_tx, result_rx) = mpsc::channel(10);
+        tokio::spawn(Self::run(request_rx, result_tx, debounce, max_wait, compile));
+
+        Self {
+            request_tx,
+            result_rx,
+        }
+    }
+
+    /// Request a compile, superseding any request still waiting
+    pub fn request(&self, request: CompileRequest) {
+        // The task only stops once the scheduler is dropped
+        let _ = self.request_tx.send(request);
+    }
+
+    /// Receive the result of the next finished compile
+    pub async fn receive_result(&mut self) -> Option<CompileResult> {
+        self.result_rx.recv().await
+    }
+
+    async fn run(
+        mut request_rx: mpsc::UnboundedReceiver<CompileRequest>,
+        result_tx: mpsc::Sender<CompileResult>,
+        debounce: Duration,
+        max_wait: Duration,
+        compile: CompileFn
+    ) {
+        let mut pending: Option<CompileRequest> = None;
+        let mut waiting_since = Instant::now();
+        let mut deadline = Instant::now();
+        let mut in_flight: Option<(CancellationToken, JoinHandle<CompileResult>)> = None;
+
+        loop {
+            tokio::select! {
+                request = request_rx.recv() => {
+                    let Some(request) = request else {
+                        break;
+                    };
+                    let now = Instant::now();
+                    if pending.is_none() {
+                        waiting_since = now;
+                    }
+                    pending = Some(request);
+                    deadline = (now + debounce).min(waiting_since + max_wait);
+                }
+                _ = tokio::time::sleep_until(deadline), if pending.is_some() => {
+                    if let Some((cancel, _)) = in_flight.take() {
+                        cancel.cancel();
+                    }
+                    let request = pending.take().expect("guarded by the branch condition");
+                    let cancel = CancellationToken::new();
+                    let token = cancel.clone();
+                    let compile = compile.clone();
+                    let task = tokio::task::spawn_blocking(move || compile(request, token));
+                    in_flight = Some((cancel, task));
+                }
+                result = async { (&mut in_flight.as_mut().unwrap().1).await },
+                    if in_flight.is_some() => {
+                    in_flight = None;
+                    if let Ok(result) = result {
+                        if result_tx.send(result).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some((cancel, _)) = in_flight {
+            cancel.cancel();
+        }
+    }
+}
+
+impl Default for CompileScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::Path;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use typst::diag::FileResult;
+    use typst::foundations::{ Bytes, Datetime };
+    use typst::syntax::{ FileId, Source, VirtualPath };
+    use typst::text::{ Font, FontBook };
+    use typst::{ Library, World };
+    use typst_utils::LazyHash;
 
     #[tokio::test]
     async fn test_compiler_creation() {
         let _compiler = Compiler::new();
         // Compiler created successfully
     }
+
+    #[test]
+    fn test_builder_resolves_main_against_root() {
+        let request = CompileRequest::builder(PathBuf::from("chapters/main.typ"))
+            .with_root("/project")
+            .diagnostic_format(DiagnosticFormat::Short)
+            .build();
+        assert_eq!(request.root, Path::new("/project"));
+        assert_eq!(request.main_file, Path::new("/project/chapters/main.typ"));
+        assert_eq!(request.diagnostic_format, DiagnosticFormat::Short);
+
+        // Without a root, the main file's directory is used
+        let request = CompileRequest::builder(PathBuf::from("main.typ")).build();
+        assert_eq!(request.root, Path::new("."));
+        assert_eq!(request.main_file, Path::new("main.typ"));
+    }
+
+    async fn compile_with_inputs(source: &str) -> CompileResult {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.typ"), source).unwrap();
+
+        let request = CompileRequest::builder(PathBuf::from("main.typ"))
+            .with_root(dir.path())
+            .with_input("foo", "bar")
+            .with_input("count", "2")
+            .build();
+        let mut compiler = Compiler::new();
+        compiler.compile(request).await.unwrap();
+        compiler.receive_result().await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_inputs_reach_document() {
+        let result = compile_with_inputs(
+            "#assert.eq(sys.inputs.foo, \"bar\")\n#assert.eq(sys.inputs.count, \"2\")"
+        ).await;
+        assert!(result.success, "{:?}", result.diagnostics);
+
+        let result = compile_with_inputs("#panic(sys.inputs.foo)").await;
+        assert!(!result.success);
+        let message = &result.diagnostics.errors().next().unwrap().message;
+        assert!(message.contains("bar"), "{message}");
+    }
+
+    #[test]
+    fn test_cancelled_before_compiling() {
+        // A single large file is never interrupted, so it must not be started
+        let dir = tempfile::tempdir().unwrap();
+        let body = "#for i in range(200000) { let _ = calc.pow(i, 2) }\n";
+        std::fs::write(dir.path().join("main.typ"), body).unwrap();
+
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let request = CompileRequest::builder(PathBuf::from("main.typ"))
+            .with_root(dir.path())
+            .build();
+        let result = Compiler::compile_internal(request, Some(cancel));
+        assert!(!result.success);
+        assert_eq!(result.diagnostics.diagnostics[0].message, "compilation cancelled");
+    }
+
+    /// World that cancels its compile when Typst first asks for `trigger`
+    struct CancelOnLoad {
+        world: SystemWorld,
+        trigger: FileId,
+        cancel: CancellationToken,
+        /// Files loaded successfully, in order
+        loaded: Mutex<Vec<FileId>>,
+    }
+
+    impl World for CancelOnLoad {
+        fn library(&self) -> &LazyHash<Library> {
+            self.world.library()
+        }
+
+        fn book(&self) -> &LazyHash<FontBook> {
+            self.world.book()
+        }
+
+        fn main(&self) -> FileId {
+            self.world.main()
+        }
+
+        fn source(&self, id: FileId) -> FileResult<Source> {
+            if id == self.trigger {
+                self.cancel.cancel();
+            }
+            let source = self.world.source(id)?;
+            self.loaded.lock().unwrap().push(id);
+            Ok(source)
+        }
+
+        fn file(&self, id: FileId) -> FileResult<Bytes> {
+            self.world.file(id)
+        }
+
+        fn font(&self, index: usize) -> Option<Font> {
+            self.world.font(index)
+        }
+
+        fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+            self.world.today(offset)
+        }
+    }
+
+    #[test]
+    fn test_cancel_mid_compile() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut main = String::new();
+        for i in 0..10 {
+            std::fs::write(dir.path().join(format!("part{i}.typ")), format!("= Part {i}")).unwrap();
+            main.push_str(&format!("#include \"part{i}.typ\"\n"));
+        }
+        std::fs::write(dir.path().join("main.typ"), main).unwrap();
+
+        let request = CompileRequest::builder(PathBuf::from("main.typ"))
+            .with_root(dir.path())
+            .build();
+        let cancel = CancellationToken::new();
+        let part = |i: usize| FileId::new(None, VirtualPath::new(format!("part{i}.typ")));
+        let world = CancelOnLoad {
+            world: SystemWorld::new(request.root.clone(), request.main_file.clone())
+                .unwrap()
+                .with_cancellation(cancel.clone()),
+            trigger: part(3),
+            cancel: cancel.clone(),
+            loaded: Mutex::new(Vec::new()),
+        };
+
+        let result = Compiler::compile_world(request, &world, Some(&cancel));
+        assert!(!result.success);
+        assert_eq!(result.diagnostics.diagnostics.len(), 1);
+        assert_eq!(result.diagnostics.diagnostics[0].message, "compilation cancelled");
+
+        // Files after the cancelling one are refused
+        let loaded = world.loaded.lock().unwrap();
+        assert!(loaded.contains(&part(2)));
+        assert!((3..10).all(|i| !loaded.contains(&part(i))));
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_compile_returns_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.typ"), "= Title").unwrap();
+        let request = || {
+            CompileRequest::builder(PathBuf::from("main.typ"))
+                .with_root(dir.path())
+                .build()
+        };
+
+        let mut compiler = Compiler::new();
+        let cancel = CancellationToken::new();
+        cancel.cancel();
+        let result = compiler.compile_cancellable(request(), cancel).await;
+        assert!(matches!(result, Err(TypstError::Cancelled)));
+
+        // The next compile is unaffected
+        let result = compiler.compile_cancellable(request(), CancellationToken::new()).await;
+        assert!(result.unwrap().success);
+    }
+
+    #[tokio::test]
+    async fn test_new_compile_cancels_previous() {
+        let mut compiler = Compiler::new();
+        let first = CancellationToken::new();
+        compiler.active = Some(first.clone());
+
+        let request = CompileRequest::builder(PathBuf::from("missing.typ")).build();
+        let _ = compiler.compile_cancellable(request, CancellationToken::new()).await;
+        assert!(first.is_cancelled());
+    }
+
+    /// Collect results until none arrives for `quiet`
+    async fn drain(scheduler: &mut CompileScheduler, quiet: Duration) -> Vec<u64> {
+        let mut ids = Vec::new();
+        while let Ok(Some(result)) = tokio::time::timeout(quiet, scheduler.receive_result()).await {
+            ids.push(result.id);
+        }
+        ids
+    }
+
+    /// Scheduler whose compiles succeed at once without running Typst
+    fn instant_scheduler(debounce: Duration, max_wait: Duration) -> CompileScheduler {
+        let compile: CompileFn = Arc::new(|request, _| CompileResult {
+            id: request.id,
+            success: true,
+            diagnostics: DiagnosticList::new(),
+            document: None,
+        });
+        CompileScheduler::with_compile_fn(debounce, max_wait, compile)
+    }
+
+    fn request(id: u64) -> CompileRequest {
+        CompileRequest::builder(PathBuf::from("main.typ")).with_id(id).build()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_scheduler_coalesces_bursts() {
+        let mut scheduler = instant_scheduler(Duration::from_millis(100), Duration::from_secs(10));
+
+        // Two bursts, each compiled once with its latest request
+        for burst in [0, 10] {
+            for id in burst..burst + 5 {
+                scheduler.request(request(id));
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        assert_eq!(drain(&mut scheduler, Duration::from_millis(500)).await, vec![4, 14]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_scheduler_max_wait() {
+        let mut scheduler = instant_scheduler(
+            Duration::from_millis(100),
+            Duration::from_millis(250)
+        );
+
+        // Typing every 20ms never pauses for the debounce, yet the latest
+        // request compiles every 250ms, and the last once typing stops
+        for id in 0..40 {
+            scheduler.request(request(id));
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let ids = drain(&mut scheduler, Duration::from_millis(500)).await;
+        assert_eq!(ids, vec![12, 25, 38, 39]);
+    }
 }
@@ -1,7 +1,7 @@
 //! Diagnostic information from Typst compilation
 
 use serde::{ Deserialize, Serialize };
-use std::path::PathBuf;
+use std::path::{ Path, PathBuf };
 
 /// Severity level of a diagnostic
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -12,6 +12,16 @@ pub enum Severity {
     Hint,
 }
 
+/// How diagnostic messages are written
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiagnosticFormat {
+    /// The message, with hints kept alongside it
+    #[default]
+    Human,
+    /// The message only, without hints
+    Short,
+}
+
 /// Source location for a diagnostic
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SourceLocation {
@@ -33,6 +43,9 @@ pub struct Diagnostic {
     pub code: Option<String>,
     /// Related information (other locations)
     pub related: Vec<DiagnosticRelated>,
+    /// Suggestions on how to fix the problem
+    #[serde(default)]
+    pub hints: Vec<String>,
 }
 
 impl Diagnostic {
@@ -43,6 +56,7 @@ impl Diagnostic {
             location: None,
             code: None,
             related: Vec::new(),
+            hints: Vec::new(),
         }
     }
 
@@ -53,6 +67,7 @@ impl Diagnostic {
             location: None,
             code: None,
             related: Vec::new(),
+            hints: Vec::new(),
         }
     }
 
@@ -65,6 +80,34 @@ impl Diagnostic {
         self.code = Some(code);
         self
     }
+
+    pub fn with_hints(mut self, hints: Vec<String>) -> Self {
+        self.hints = hints;
+        self
+    }
+
+    /// Titles of quick fixes for the hints that suggest a change
+    ///
+    /// Hints that only point to further reading, such as "see ..." or "for
+    /// more information ...", are not actionable and produce no fix.
+    pub fn quick_fixes(&self) -> Vec<String> {
+        self.hints
+            .iter()
+            .map(|hint| hint.trim())
+            .filter(|hint| {
+                !hint.is_empty() &&
+                    !hint.starts_with("see ") &&
+                    !hint.starts_with("for more information")
+            })
+            .map(|hint| {
+                let mut chars = hint.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => String::new(),
+                }
+            })
+            .collect()
+    }
 }
 
 /// Related diagnostic information
@@ -74,6 +117,15 @@ pub struct DiagnosticRelated {
     pub message: String,
 }
 
+/// Number of diagnostics of each severity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DiagnosticCounts {
+    pub errors: usize,
+    pub warnings: usize,
+    pub infos: usize,
+    pub hints: usize,
+}
+
 /// Collection of diagnostics from a compilation
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DiagnosticList {
@@ -103,7 +155,123 @@ impl DiagnosticList {
         self.diagnostics.iter().filter(|d| d.severity == Severity::Warning)
     }
 
+    /// Count diagnostics by severity in a single pass
+    pub fn counts(&self) -> DiagnosticCounts {
+        let mut counts = DiagnosticCounts::default();
+        for diagnostic in &self.diagnostics {
+            match diagnostic.severity {
+                Severity::Error => {
+                    counts.errors += 1;
+                }
+                Severity::Warning => {
+                    counts.warnings += 1;
+                }
+                Severity::Info => {
+                    counts.infos += 1;
+                }
+                Severity::Hint => {
+                    counts.hints += 1;
+                }
+            }
+        }
+        counts
+    }
+
+    /// Diagnostics located in `path`; those without a location are excluded
+    pub fn filter_by_file(&self, path: &Path) -> Vec<&Diagnostic> {
+        self.diagnostics
+            .iter()
+            .filter(|d| d.location.as_ref().is_some_and(|location| location.file == path))
+            .collect()
+    }
+
+    /// Diagnostics ordered by file, line and column
+    ///
+    /// Diagnostics without a location come last, in their original order.
+    pub fn sorted_by_location(&self) -> Vec<&Diagnostic> {
+        let mut sorted: Vec<&Diagnostic> = self.diagnostics.iter().collect();
+        sorted.sort_by(|a, b| {
+            match (&a.location, &b.location) {
+                (Some(a), Some(b)) =>
+                    (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            }
+        });
+        sorted
+    }
+
     pub fn clear(&mut self) {
         self.diagnostics.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn located(diagnostic: Diagnostic, file: &str, line: usize) -> Diagnostic {
+        diagnostic.with_location(SourceLocation { file: PathBuf::from(file), line, column: 0 })
+    }
+
+    fn mixed_list() -> DiagnosticList {
+        let mut list = DiagnosticList::new();
+        list.add(located(Diagnostic::error("b2".to_string()), "b.typ", 2));
+        list.add(Diagnostic::warning("no location".to_string()));
+        list.add(located(Diagnostic::warning("a5".to_string()), "a.typ", 5));
+        list.add(Diagnostic { severity: Severity::Hint, ..Diagnostic::warning("a1".to_string()) });
+        list.add(located(Diagnostic::error("b1".to_string()), "b.typ", 1));
+        list.add(Diagnostic { severity: Severity::Info, ..Diagnostic::error("info".to_string()) });
+        list
+    }
+
+    fn messages(diagnostics: Vec<&Diagnostic>) -> Vec<&str> {
+        diagnostics
+            .into_iter()
+            .map(|d| d.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_counts() {
+        let counts = mixed_list().counts();
+        assert_eq!(counts, DiagnosticCounts { errors: 2, warnings: 2, infos: 1, hints: 1 });
+        assert_eq!(DiagnosticList::new().counts(), DiagnosticCounts::default());
+    }
+
+    #[test]
+    fn test_filter_and_sort_by_location() {
+        let list = mixed_list();
+        assert_eq!(messages(list.filter_by_file(Path::new("b.typ"))), vec!["b2", "b1"]);
+        assert_eq!(messages(list.filter_by_file(Path::new("a.typ"))), vec!["a5"]);
+        assert!(list.filter_by_file(Path::new("c.typ")).is_empty());
+
+        // Unlocated diagnostics keep their order after the located ones
+        assert_eq!(
+            messages(list.sorted_by_location()),
+            vec!["a5", "b1", "b2", "no location", "a1", "info"]
+        );
+    }
+
+    #[test]
+    fn test_hints_become_quick_fixes() {
+        let diagnostic = Diagnostic::error("unclosed delimiter".to_string()).with_hints(
+            vec!["try adding a closing `]`".to_string(), "did you mean `#list`?".to_string()]
+        );
+        assert_eq!(
+            diagnostic.quick_fixes(),
+            vec!["Try adding a closing `]`", "Did you mean `#list`?"]
+        );
+    }
+
+    #[test]
+    fn test_no_fixes_without_actionable_hints() {
+        assert!(Diagnostic::error("unknown variable".to_string()).quick_fixes().is_empty());
+
+        let diagnostic = Diagnostic::warning("deprecated".to_string()).with_hints(
+            vec!["see https://typst.app/docs for details".to_string()]
+        );
+        assert!(diagnostic.quick_fixes().is_empty());
+    }
+}
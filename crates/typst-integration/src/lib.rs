@@ -4,9 +4,22 @@ pub mod compiler;
 pub mod diagnostics;
 pub mod world;
 
-pub use compiler::{ CompileRequest, CompileResult, Compiler };
-pub use diagnostics::{ Diagnostic, Severity };
+pub use compiler::{
+    CompileFn,
+    CompileRequest,
+    CompileRequestBuilder,
+    CompileResult,
+    CompileScheduler,
+    Compiler,
+};
+pub use diagnostics::{
+    Diagnostic,
+    DiagnosticCounts,
+    DiagnosticFormat,
+    Severity,
+};
 pub use world::SystemWorld;
+pub use tokio_util::sync::CancellationToken;
 
 /// Common error types
 #[derive(Debug, thiserror::Error)]
@@ -18,6 +31,9 @@ pub enum TypstError {
     #[error("IO error: {0}")] IoError(#[from] std::io::Error),
 
     #[error("World error: {0}")] WorldError(String),
+
+    #[error("Compilation cancelled")]
+    Cancelled,
 }
 
 pub type Result<T> = std::result::Result<T, TypstError>;
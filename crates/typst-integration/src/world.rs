@@ -5,11 +5,12 @@ use std::collections::HashMap;
 use std::path::{ Path, PathBuf };
 use std::sync::{ Arc, Mutex };
 use typst::diag::{ FileError, FileResult };
-use typst::foundations::Bytes;
-use typst::syntax::{ FileId, Source };
+use typst::foundations::{ Bytes, Dict };
+use typst::syntax::{ FileId, Source, VirtualPath };
 use typst::text::{ Font, FontBook };
 use typst::Library;
 use chrono::{ Datelike, Local };
+use tokio_util::sync::CancellationToken;
 
 // Import LazyHash from typst-utils
 use typst_utils::LazyHash;
@@ -30,6 +31,8 @@ pub struct SystemWorld {
     sources: Arc<Mutex<HashMap<FileId, FileResult<Source>>>>,
     /// Binary file cache
     files: Arc<Mutex<HashMap<FileId, FileResult<Bytes>>>>,
+    /// Once cancelled, files are refused so compilation stops early
+    cancel: Option<CancellationToken>,
 }
 
 impl SystemWorld {
@@ -53,9 +56,32 @@ impl SystemWorld {
             fonts,
             sources: Arc::new(Mutex::new(HashMap::new())),
             files: Arc::new(Mutex::new(HashMap::new())),
+            cancel: None,
         })
     }
 
+    /// Make `inputs` available to documents as `sys.inputs`
+    pub fn with_inputs(mut self, inputs: Dict) -> Self {
+        self.library = LazyHash::new(Library::builder().with_inputs(inputs).build());
+        self
+    }
+
+    /// Refuse to load files once `cancel` fires
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Error for file accesses after cancellation
+    fn check_cancelled(&self) -> FileResult<()> {
+        match &self.cancel {
+            Some(cancel) if cancel.is_cancelled() => {
+                Err(FileError::Other(Some("compilation cancelled".into())))
+            }
+            _ => Ok(()),
+        }
+    }
+
     /// Get the main source file
     pub fn main_file(&self) -> &Path {
         &self.main
@@ -98,11 +124,15 @@ impl typst::World for SystemWorld {
     }
 
     fn main(&self) -> FileId {
-        // Get main file ID
-        FileId::new(None, typst::syntax::VirtualPath::new(&self.main))
+        // The main file is either a path under the root or relative to it
+        let vpath = VirtualPath::within_root(&self.main, &self.root)
+            .unwrap_or_else(|| VirtualPath::new(&self.main));
+        FileId::new(None, vpath)
     }
 
     fn source(&self, id: FileId) -> FileResult<Source> {
+        // Refused accesses are not cached, so the caches stay valid
+        self.check_cancelled()?;
         let mut sources = self.sources.lock().unwrap();
 
         if let Some(result) = sources.get(&id) {
@@ -115,6 +145,7 @@ impl typst::World for SystemWorld {
     }
 
     fn file(&self, id: FileId) -> FileResult<Bytes> {
+        self.check_cancelled()?;
         let mut files = self.files.lock().unwrap();
 
         if let Some(result) = files.get(&id) {
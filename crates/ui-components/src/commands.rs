@@ -0,0 +1,254 @@
+//! Command palette registry with fuzzy search
+
+use crate::input::Action;
+use std::collections::HashMap;
+
+/// Score of each matched character
+const MATCH_SCORE: i32 = 1;
+/// Bonus for a match right after the previous one
+const CONSECUTIVE_BONUS: i32 = 6;
+/// Bonus for a match at the start of a word
+const WORD_START_BONUS: i32 = 4;
+/// Penalty for each character skipped between two matches
+const GAP_PENALTY: i32 = 1;
+/// Most characters skipped before the first match that are penalized
+const MAX_LEADING_PENALTY: i32 = 3;
+
+/// A command shown in the palette
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Command {
+    pub id: String,
+    pub title: String,
+    pub action: Action,
+    /// Context in which the command applies, e.g. `"editorFocus"`
+    pub when: Option<String>,
+}
+
+/// Commands keyed by ID
+#[derive(Debug, Default)]
+pub struct CommandRegistry {
+    commands: HashMap<String, Command>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a command, replacing any with the same ID
+    pub fn register(
+        &mut self,
+        id: impl Into<String>,
+        title: impl Into<String>,
+        action: Action,
+        when: Option<String>
+    ) {
+        let id = id.into();
+        let command = Command { id: id.clone(), title: title.into(), action, when };
+        self.commands.insert(id, command);
+    }
+
+    /// Get a command by ID
+    pub fn get(&self, id: &str) -> Option<&Command> {
+        self.commands.get(id)
+    }
+
+    /// Commands whose title fuzzily matches `query`, best match first
+    ///
+    /// Every query character must appear in the title in order, ignoring
+    /// case. Runs of consecutive characters and matches at word starts rank
+    /// higher; characters skipped between matches rank lower. An empty query
+    /// matches every command.
+    pub fn search(&self, query: &str) -> Vec<&Command> {
+        let mut matches: Vec<(i32, &Command)> = self.commands
+            .values()
+            .filter_map(|command| fuzzy_score(query, &command.title).map(|score| (score, command)))
+            .collect();
+        matches.sort_by(|(a_score, a), (b_score, b)| {
+            b_score
+                .cmp(a_score)
+                .then_with(|| a.title.len().cmp(&b.title.len()))
+                .then_with(|| a.title.cmp(&b.title))
+        });
+        matches
+            .into_iter()
+            .map(|(_, command)| command)
+            .collect()
+    }
+
+    /// Action to execute for a command ID
+    pub fn resolve_id(&self, id: &str) -> Option<&Action> {
+        self.get(id).map(|command| &command.action)
+    }
+
+    /// Action to execute for `action`
+    ///
+    /// `Action::Custom` names a registered command and resolves to that
+    /// command's action, following chains of custom commands. Other actions
+    /// resolve to themselves. Returns `None` for unknown or cyclic commands.
+    pub fn resolve<'a>(&'a self, action: &'a Action) -> Option<&'a Action> {
+        let mut current = action;
+        for _ in 0..=self.commands.len() {
+            match current {
+                Action::Custom(id) => {
+                    current = self.resolve_id(id)?;
+                }
+                _ => {
+                    return Some(current);
+                }
+            }
+        }
+        None
+    }
+
+    /// Get number of registered commands
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Check if no commands are registered
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+/// Score of the best fuzzy match of `query` in `text`, if it matches
+pub fn fuzzy_score(query: &str, text: &str) -> Option<i32> {
+    let query: Vec<char> = query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    let text: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = text
+        .iter()
+        .map(|c| c.to_lowercase().next().unwrap_or(*c))
+        .collect();
+    if query.is_empty() {
+        return Some(0);
+    }
+
+    let word_start = |i: usize| {
+        i == 0 ||
+            !text[i - 1].is_alphanumeric() ||
+            (text[i - 1].is_lowercase() && text[i].is_uppercase())
+    };
+    let char_score = |i: usize| MATCH_SCORE + if word_start(i) { WORD_START_BONUS } else { 0 };
+
+    // best[i]: best score with the latest query character matched at text[i]
+    let mut best: Vec<Option<i32>> = (0..text.len())
+        .map(|i| {
+            (lower[i] == query[0]).then(|| {
+                char_score(i) - (i as i32).min(MAX_LEADING_PENALTY) * GAP_PENALTY
+            })
+        })
+        .collect();
+
+    for &c in &query[1..] {
+        // Best of best[prev] + prev * GAP_PENALTY over the non-adjacent
+        // predecessors seen so far, so a gap of any length costs one lookup
+        let mut gapped: Option<i32> = None;
+        let mut next = vec![None; text.len()];
+        for i in 0..text.len() {
+            if i >= 2 {
+                let score = best[i - 2].map(|score| score + ((i - 2) as i32) * GAP_PENALTY);
+                gapped = gapped.max(score);
+            }
+            if lower[i] != c {
+                continue;
+            }
+            let consecutive = i
+                .checked_sub(1)
+                .and_then(|prev| best[prev])
+                .map(|score| score + CONSECUTIVE_BONUS);
+            let gap = gapped.map(|score| score - ((i - 1) as i32) * GAP_PENALTY);
+            next[i] = consecutive.max(gap).map(|score| score + char_score(i));
+        }
+        best = next;
+    }
+
+    best.into_iter().flatten().max()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+        registry.register("file.save", "Save", Action::Save, None);
+        registry.register("view.select", "Select View", Action::Custom("view.pick".into()), None);
+        registry.register("edit.selectAll", "Select All", Action::SelectAll, None);
+        registry.register(
+            "edit.saveSelection",
+            "Save Selection",
+            Action::Custom("file.save".into()),
+            Some("editorHasSelection".into())
+        );
+        registry.register("edit.undo", "Undo", Action::Undo, None);
+        registry
+    }
+
+    fn titles<'a>(commands: &[&'a Command]) -> Vec<&'a str> {
+        commands
+            .iter()
+            .map(|command| command.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_search_ranking() {
+        let registry = registry();
+
+        let results = registry.search("sv");
+        assert_eq!(titles(&results), vec!["Save", "Save Selection", "Select View"]);
+
+        // Consecutive matches at the start beat those later in the title
+        let results = registry.search("sel");
+        assert_eq!(titles(&results), vec!["Select All", "Select View", "Save Selection"]);
+
+        // Matching ignores case; unmatched characters exclude a command
+        assert_eq!(titles(&registry.search("UNDO")), vec!["Undo"]);
+        assert!(registry.search("xyz").is_empty());
+        assert_eq!(registry.search("").len(), registry.len());
+    }
+
+    #[test]
+    fn test_fuzzy_score_gaps() {
+        // Consecutive matches beat gapped ones, and longer gaps cost more
+        let adjacent = fuzzy_score("ab", "xabx").unwrap();
+        let short_gap = fuzzy_score("ab", "xa-bx").unwrap();
+        let long_gap = fuzzy_score("ab", "xa---bx").unwrap();
+        assert!(adjacent > short_gap);
+        assert_eq!(short_gap - long_gap, 2 * GAP_PENALTY);
+
+        // The best alignment is found even when an earlier match is worse
+        assert_eq!(fuzzy_score("ab", "a-----ab"), fuzzy_score("ab", "------ab"));
+        assert_eq!(fuzzy_score("abc", "ab"), None);
+
+        // Long paths stay linear in the text length per query character
+        let long_path = "dir/".repeat(5000) + "main.typ";
+        assert!(fuzzy_score("main", &long_path).is_some());
+    }
+
+    #[test]
+    fn test_resolve_custom_actions() {
+        let registry = registry();
+        assert_eq!(registry.resolve_id("file.save"), Some(&Action::Save));
+
+        // Custom actions route through the registry
+        let custom = Action::Custom("edit.saveSelection".into());
+        assert_eq!(registry.resolve(&custom), Some(&Action::Save));
+        assert_eq!(registry.resolve(&Action::Undo), Some(&Action::Undo));
+
+        // Unknown and cyclic commands do not resolve
+        assert_eq!(registry.resolve(&Action::Custom("view.select".into())), None);
+        let mut cyclic = CommandRegistry::new();
+        cyclic.register("a", "A", Action::Custom("b".into()), None);
+        cyclic.register("b", "B", Action::Custom("a".into()), None);
+        assert_eq!(cyclic.resolve(&Action::Custom("a".into())), None);
+
+        let command = registry.get("edit.saveSelection").unwrap();
+        assert_eq!(command.when.as_deref(), Some("editorHasSelection"));
+    }
+}
@@ -0,0 +1,603 @@
+//! Offline completion from built-in knowledge of Typst and the document
+//!
+//! Covers standard library functions, labels for references and named math
+//! symbols.
+//!
+//! Used when no language server is running; results feed the
+//! [`AutocompletePopup`](crate::editor_view::overlays::AutocompletePopup).
+
+use crate::editor_view::overlays::{ CompletionItem, CompletionKind };
+
+/// A built-in function and its commonly used parameters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuiltinFunction {
+    pub name: &'static str,
+    /// Positional parameters first, then named ones as `name: default`
+    pub params: &'static [&'static str],
+    pub doc: &'static str,
+}
+
+macro_rules! builtin {
+    ($name:literal, [$($param:literal),*], $doc:literal) => {
+        BuiltinFunction { name: $name, params: &[$($param),*], doc: $doc }
+    };
+}
+
+/// Common functions of Typst's standard library, sorted by name
+pub const BUILTIN_FUNCTIONS: &[BuiltinFunction] = &[
+    builtin!("align", ["alignment", "body"], "Aligns content horizontally and vertically."),
+    builtin!("bibliography", ["sources", "title: auto", "style: \"ieee\""], "A bibliography."),
+    builtin!(
+        "block",
+        ["body", "width: auto", "height: auto", "inset: 0pt"],
+        "A block-level container."
+    ),
+    builtin!(
+        "box",
+        ["body", "width: auto", "height: auto", "baseline: 0pt"],
+        "An inline-level container."
+    ),
+    builtin!("circle", ["body", "radius: 0pt", "fill: none", "stroke: auto"], "A circle."),
+    builtin!(
+        "cite",
+        ["key", "supplement: none", "form: \"normal\""],
+        "Cites a bibliography entry."
+    ),
+    builtin!("colbreak", ["weak: false"], "Forces a column break."),
+    builtin!("columns", ["count", "body", "gutter: 4%"], "Separates a region into columns."),
+    builtin!("emph", ["body"], "Emphasizes content by toggling italics."),
+    builtin!("enum", ["children", "numbering: \"1.\"", "start: 1"], "A numbered list."),
+    builtin!(
+        "figure",
+        ["body", "caption: none", "kind: auto", "placement: none"],
+        "A figure with an optional caption."
+    ),
+    builtin!("footnote", ["body", "numbering: \"1\""], "A footnote."),
+    builtin!(
+        "grid",
+        ["children", "columns: ()", "rows: ()", "gutter: ()"],
+        "Arranges content in a grid."
+    ),
+    builtin!("h", ["amount", "weak: false"], "Inserts horizontal spacing."),
+    builtin!(
+        "heading",
+        ["body", "level: auto", "numbering: none", "outlined: true"],
+        "A section heading."
+    ),
+    builtin!(
+        "highlight",
+        ["body", "fill: rgb(\"#fffd11a1\")"],
+        "Highlights text with a background color."
+    ),
+    builtin!(
+        "image",
+        ["source", "width: auto", "height: auto", "fit: \"cover\""],
+        "A raster or vector graphic."
+    ),
+    builtin!(
+        "line",
+        ["start: (0pt, 0pt)", "end: none", "length: 0pt", "stroke: 1pt"],
+        "A line from one point to another."
+    ),
+    builtin!("linebreak", ["justify: false"], "Inserts a line break."),
+    builtin!("link", ["dest", "body"], "Links to a URL or a location in the document."),
+    builtin!("list", ["children", "marker: ([•], [‣], [–])", "tight: true"], "A bullet list."),
+    builtin!("lorem", ["words"], "Creates blind text."),
+    builtin!("lower", ["text"], "Converts text to lowercase."),
+    builtin!(
+        "numbering",
+        ["numbering", "numbers"],
+        "Applies a numbering to a sequence of numbers."
+    ),
+    builtin!(
+        "outline",
+        ["title: auto", "target: heading", "depth: none", "indent: auto"],
+        "A table of contents."
+    ),
+    builtin!(
+        "pad",
+        ["body", "left: 0pt", "right: 0pt", "top: 0pt", "bottom: 0pt", "rest: 0pt"],
+        "Adds spacing around content."
+    ),
+    builtin!(
+        "page",
+        ["body", "paper: \"a4\"", "margin: auto", "numbering: none"],
+        "Layouts its child onto one or multiple pages."
+    ),
+    builtin!("pagebreak", ["weak: false", "to: none"], "A manual page break."),
+    builtin!(
+        "par",
+        ["body", "leading: 0.65em", "justify: false", "first-line-indent: 0pt"],
+        "A paragraph."
+    ),
+    builtin!(
+        "place",
+        ["alignment", "body", "float: false", "dx: 0pt", "dy: 0pt"],
+        "Places content relative to its parent container."
+    ),
+    builtin!("quote", ["body", "block: false", "attribution: none"], "Displays a quote."),
+    builtin!(
+        "raw",
+        ["text", "lang: none", "block: false"],
+        "Raw text with optional syntax highlighting."
+    ),
+    builtin!(
+        "rect",
+        ["body", "width: auto", "height: auto", "fill: none", "stroke: auto"],
+        "A rectangle."
+    ),
+    builtin!(
+        "ref",
+        ["target", "supplement: auto"],
+        "A reference to a label or bibliography entry."
+    ),
+    builtin!("smallcaps", ["body"], "Displays text in small capitals."),
+    builtin!("stack", ["children", "dir: ttb", "spacing: none"], "Arranges content along an axis."),
+    builtin!("strike", ["body", "stroke: auto"], "Strikes through text."),
+    builtin!(
+        "strong",
+        ["body", "delta: 300"],
+        "Strongly emphasizes content by increasing the font weight."
+    ),
+    builtin!("sub", ["body"], "Renders text in subscript."),
+    builtin!("super", ["body"], "Renders text in superscript."),
+    builtin!(
+        "table",
+        ["children", "columns: ()", "rows: ()", "align: auto", "stroke: 1pt"],
+        "A table of items."
+    ),
+    builtin!(
+        "terms",
+        ["children", "separator: h(0.6em)", "tight: true"],
+        "A list of terms and their descriptions."
+    ),
+    builtin!(
+        "text",
+        ["body", "font: \"libertinus serif\"", "size: 11pt", "fill: luma(0%)", "lang: \"en\""],
+        "Customizes the look and layout of text."
+    ),
+    builtin!("underline", ["body", "stroke: auto", "offset: auto"], "Underlines text."),
+    builtin!("upper", ["text"], "Converts text to uppercase."),
+    builtin!("v", ["amount", "weak: false"], "Inserts vertical spacing."),
+];
+
+/// Keywords that start code expressions
+pub const KEYWORDS: &[&str] = &[
+    "auto",
+    "context",
+    "else",
+    "false",
+    "for",
+    "if",
+    "import",
+    "include",
+    "let",
+    "none",
+    "return",
+    "set",
+    "show",
+    "true",
+    "while",
+];
+
+/// Completes Typst's built-in functions and keywords without a server
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BuiltinCompleter;
+
+impl BuiltinCompleter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Functions and keywords starting with `prefix`, sorted by name
+    ///
+    /// In markup, code is only reachable after `#`, so nothing is offered
+    /// unless `prefix` starts with one; the `#` itself is not matched.
+    pub fn complete(&self, prefix: &str, in_code_mode: bool) -> Vec<CompletionItem> {
+        let prefix = if in_code_mode {
+            prefix
+        } else {
+            match prefix.strip_prefix('#') {
+                Some(prefix) => prefix,
+                None => {
+                    return Vec::new();
+                }
+            }
+        };
+
+        let functions = BUILTIN_FUNCTIONS
+            .iter()
+            .filter(|function| function.name.starts_with(prefix))
+            .map(|function| CompletionItem {
+                label: function.name.to_string(),
+                kind: CompletionKind::Function,
+                detail: Some(format!("{}({})", function.name, function.params.join(", "))),
+                documentation: Some(function.doc.to_string()),
+            });
+        let keywords = KEYWORDS
+            .iter()
+            .filter(|keyword| keyword.starts_with(prefix))
+            .map(|keyword| CompletionItem {
+                label: keyword.to_string(),
+                kind: CompletionKind::Keyword,
+                detail: None,
+                documentation: None,
+            });
+
+        let mut items: Vec<CompletionItem> = functions.chain(keywords).collect();
+        items.sort_by(|a, b| a.label.cmp(&b.label));
+        items
+    }
+}
+
+/// Completes references to the labels defined in a document
+#[derive(Debug, Clone, Default)]
+pub struct LabelCompleter {
+    labels: Vec<String>,
+}
+
+impl LabelCompleter {
+    /// Create a completer over label names, as found by
+    /// [`SyntaxHighlighter::collect_labels`](crate::syntax::SyntaxHighlighter::collect_labels)
+    pub fn new(labels: Vec<String>) -> Self {
+        Self { labels }
+    }
+
+    /// Labels starting with the text after `@`, in document order
+    ///
+    /// Nothing is offered unless `prefix` starts with `@`.
+    pub fn complete(&self, prefix: &str) -> Vec<CompletionItem> {
+        let Some(prefix) = prefix.strip_prefix('@') else {
+            return Vec::new();
+        };
+
+        self.labels
+            .iter()
+            .filter(|label| label.starts_with(prefix))
+            .map(|label| CompletionItem {
+                label: label.clone(),
+                kind: CompletionKind::Label,
+                detail: Some(format!("<{}>", label)),
+                documentation: None,
+            })
+            .collect()
+    }
+}
+
+/// Kind of a math symbol, for grouping in the symbol palette
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolCategory {
+    Greek,
+    Arrow,
+    Operator,
+    Relation,
+    Logic,
+    Misc,
+}
+
+/// Named math symbols: name, rendering and category
+pub const SYMBOLS: &[(&str, char, SymbolCategory)] = &[
+    // Greek
+    ("alpha", 'α', SymbolCategory::Greek),
+    ("beta", 'β', SymbolCategory::Greek),
+    ("gamma", 'γ', SymbolCategory::Greek),
+    ("delta", 'δ', SymbolCategory::Greek),
+    ("epsilon", 'ε', SymbolCategory::Greek),
+    ("epsilon.alt", 'ϵ', SymbolCategory::Greek),
+    ("zeta", 'ζ', SymbolCategory::Greek),
+    ("eta", 'η', SymbolCategory::Greek),
+    ("theta", 'θ', SymbolCategory::Greek),
+    ("theta.alt", 'ϑ', SymbolCategory::Greek),
+    ("iota", 'ι', SymbolCategory::Greek),
+    ("kappa", 'κ', SymbolCategory::Greek),
+    ("lambda", 'λ', SymbolCategory::Greek),
+    ("mu", 'μ', SymbolCategory::Greek),
+    ("nu", 'ν', SymbolCategory::Greek),
+    ("xi", 'ξ', SymbolCategory::Greek),
+    ("pi", 'π', SymbolCategory::Greek),
+    ("rho", 'ρ', SymbolCategory::Greek),
+    ("sigma", 'σ', SymbolCategory::Greek),
+    ("tau", 'τ', SymbolCategory::Greek),
+    ("upsilon", 'υ', SymbolCategory::Greek),
+    ("phi", 'φ', SymbolCategory::Greek),
+    ("phi.alt", 'ϕ', SymbolCategory::Greek),
+    ("chi", 'χ', SymbolCategory::Greek),
+    ("psi", 'ψ', SymbolCategory::Greek),
+    ("omega", 'ω', SymbolCategory::Greek),
+    ("Gamma", 'Γ', SymbolCategory::Greek),
+    ("Delta", 'Δ', SymbolCategory::Greek),
+    ("Theta", 'Θ', SymbolCategory::Greek),
+    ("Lambda", 'Λ', SymbolCategory::Greek),
+    ("Xi", 'Ξ', SymbolCategory::Greek),
+    ("Pi", 'Π', SymbolCategory::Greek),
+    ("Sigma", 'Σ', SymbolCategory::Greek),
+    ("Phi", 'Φ', SymbolCategory::Greek),
+    ("Psi", 'Ψ', SymbolCategory::Greek),
+    ("Omega", 'Ω', SymbolCategory::Greek),
+    // Arrow
+    ("arrow.r", '→', SymbolCategory::Arrow),
+    ("arrow.l", '←', SymbolCategory::Arrow),
+    ("arrow.t", '↑', SymbolCategory::Arrow),
+    ("arrow.b", '↓', SymbolCategory::Arrow),
+    ("arrow.l.r", '↔', SymbolCategory::Arrow),
+    ("arrow.r.double", '⇒', SymbolCategory::Arrow),
+    ("arrow.l.double", '⇐', SymbolCategory::Arrow),
+    ("arrow.l.r.double", '⇔', SymbolCategory::Arrow),
+    ("arrow.r.long", '⟶', SymbolCategory::Arrow),
+    ("arrow.r.bar", '↦', SymbolCategory::Arrow),
+    ("arrow.r.hook", '↪', SymbolCategory::Arrow),
+    ("arrow.r.squiggly", '⇝', SymbolCategory::Arrow),
+    // Operator
+    ("plus.minus", '±', SymbolCategory::Operator),
+    ("minus.plus", '∓', SymbolCategory::Operator),
+    ("times", '×', SymbolCategory::Operator),
+    ("div", '÷', SymbolCategory::Operator),
+    ("dot.op", '⋅', SymbolCategory::Operator),
+    ("ast.op", '∗', SymbolCategory::Operator),
+    ("star.op", '⋆', SymbolCategory::Operator),
+    ("compose", '∘', SymbolCategory::Operator),
+    ("sum", '∑', SymbolCategory::Operator),
+    ("product", '∏', SymbolCategory::Operator),
+    ("integral", '∫', SymbolCategory::Operator),
+    ("integral.double", '∬', SymbolCategory::Operator),
+    ("integral.cont", '∮', SymbolCategory::Operator),
+    ("partial", '∂', SymbolCategory::Operator),
+    ("nabla", '∇', SymbolCategory::Operator),
+    ("radical", '√', SymbolCategory::Operator),
+    ("union", '∪', SymbolCategory::Operator),
+    ("sect", '∩', SymbolCategory::Operator),
+    // Relation
+    ("eq.not", '≠', SymbolCategory::Relation),
+    ("lt.eq", '≤', SymbolCategory::Relation),
+    ("gt.eq", '≥', SymbolCategory::Relation),
+    ("approx", '≈', SymbolCategory::Relation),
+    ("equiv", '≡', SymbolCategory::Relation),
+    ("prop", '∝', SymbolCategory::Relation),
+    ("tilde.op", '∼', SymbolCategory::Relation),
+    ("ll", '≪', SymbolCategory::Relation),
+    ("gg", '≫', SymbolCategory::Relation),
+    ("in", '∈', SymbolCategory::Relation),
+    ("in.not", '∉', SymbolCategory::Relation),
+    ("subset", '⊂', SymbolCategory::Relation),
+    ("subset.eq", '⊆', SymbolCategory::Relation),
+    ("supset", '⊃', SymbolCategory::Relation),
+    ("supset.eq", '⊇', SymbolCategory::Relation),
+    ("parallel", '∥', SymbolCategory::Relation),
+    ("perp", '⟂', SymbolCategory::Relation),
+    // Logic
+    ("and", '∧', SymbolCategory::Logic),
+    ("or", '∨', SymbolCategory::Logic),
+    ("not", '¬', SymbolCategory::Logic),
+    ("forall", '∀', SymbolCategory::Logic),
+    ("exists", '∃', SymbolCategory::Logic),
+    ("exists.not", '∄', SymbolCategory::Logic),
+    ("top", '⊤', SymbolCategory::Logic),
+    ("bot", '⊥', SymbolCategory::Logic),
+    ("tack.r", '⊢', SymbolCategory::Logic),
+    ("therefore", '∴', SymbolCategory::Logic),
+    ("because", '∵', SymbolCategory::Logic),
+    // Misc
+    ("emptyset", '∅', SymbolCategory::Misc),
+    ("infinity", '∞', SymbolCategory::Misc),
+    ("aleph", 'ℵ', SymbolCategory::Misc),
+    ("ell", 'ℓ', SymbolCategory::Misc),
+    ("planck.reduce", 'ħ', SymbolCategory::Misc),
+    ("angle", '∠', SymbolCategory::Misc),
+    ("degree", '°', SymbolCategory::Misc),
+    ("prime", '′', SymbolCategory::Misc),
+    ("dots.h", '…', SymbolCategory::Misc),
+    ("dots.c", '⋯', SymbolCategory::Misc),
+    ("dots.v", '⋮', SymbolCategory::Misc),
+    ("dots.down", '⋱', SymbolCategory::Misc),
+];
+
+/// A symbol offered for completion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymbolCompletion {
+    pub name: &'static str,
+    pub symbol: char,
+    pub category: SymbolCategory,
+}
+
+impl From<SymbolCompletion> for CompletionItem {
+    fn from(completion: SymbolCompletion) -> Self {
+        CompletionItem {
+            label: completion.name.to_string(),
+            kind: CompletionKind::Constant,
+            detail: Some(completion.symbol.to_string()),
+            documentation: None,
+        }
+    }
+}
+
+/// Completes Typst's named math symbols
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SymbolCompleter;
+
+impl SymbolCompleter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Symbols whose name matches `prefix`
+    ///
+    /// Names starting with `prefix` come first. Then come names whose
+    /// dot-separated parts each start with the matching part of `prefix`, so
+    /// `arr.r` finds `arrow.r` and `arrow.r.long`. Shorter names rank higher
+    /// within each group.
+    pub fn complete(&self, prefix: &str) -> Vec<SymbolCompletion> {
+        let mut matches: Vec<(bool, SymbolCompletion)> = SYMBOLS
+            .iter()
+            .filter_map(|&(name, symbol, category)| {
+                let completion = SymbolCompletion { name, symbol, category };
+                if name.starts_with(prefix) {
+                    Some((false, completion))
+                } else {
+                    matches_parts(prefix, name).then_some((true, completion))
+                }
+            })
+            .collect();
+        matches.sort_by(|(a_fuzzy, a), (b_fuzzy, b)| {
+            a_fuzzy
+                .cmp(b_fuzzy)
+                .then_with(|| a.name.len().cmp(&b.name.len()))
+                .then_with(|| a.name.cmp(b.name))
+        });
+        matches
+            .into_iter()
+            .map(|(_, completion)| completion)
+            .collect()
+    }
+
+    /// The symbol with exactly this name
+    pub fn lookup(&self, name: &str) -> Option<char> {
+        SYMBOLS.iter()
+            .find(|(symbol_name, _, _)| *symbol_name == name)
+            .map(|(_, symbol, _)| *symbol)
+    }
+}
+
+/// Whether each dot-separated part of `query` starts the same part of `name`
+fn matches_parts(query: &str, name: &str) -> bool {
+    let mut name_parts = name.split('.');
+    query
+        .split('.')
+        .all(|part| name_parts.next().is_some_and(|name_part| name_part.starts_with(part)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(items: &[CompletionItem]) -> Vec<&str> {
+        items
+            .iter()
+            .map(|item| item.label.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_prefix_filtering() {
+        let completer = BuiltinCompleter::new();
+
+        let items = completer.complete("fi", true);
+        assert_eq!(labels(&items), vec!["figure"]);
+        assert_eq!(
+            items[0].detail.as_deref(),
+            Some("figure(body, caption: none, kind: auto, placement: none)")
+        );
+
+        // Functions and keywords are both offered, sorted by name
+        assert_eq!(labels(&completer.complete("s", true)), vec![
+            "set",
+            "show",
+            "smallcaps",
+            "stack",
+            "strike",
+            "strong",
+            "sub",
+            "super",
+        ]);
+        assert_eq!(completer.complete("", true).len(), BUILTIN_FUNCTIONS.len() + KEYWORDS.len());
+        assert!(completer.complete("xyz", true).is_empty());
+        assert!(completer.complete("Table", true).is_empty());
+    }
+
+    #[test]
+    fn test_markup_needs_hash() {
+        let completer = BuiltinCompleter::new();
+
+        assert!(completer.complete("tab", false).is_empty());
+        assert!(completer.complete("", false).is_empty());
+        assert_eq!(labels(&completer.complete("#tab", false)), vec!["table"]);
+        assert_eq!(labels(&completer.complete("#le", false)), vec!["let"]);
+        assert_eq!(
+            completer.complete("#", false).len(),
+            completer.complete("", true).len()
+        );
+    }
+
+    #[test]
+    fn test_label_completion() {
+        let completer = LabelCompleter::new(vec!["fig:b".into(), "fig:a".into(), "eq".into()]);
+
+        assert_eq!(labels(&completer.complete("@fig")), vec!["fig:b", "fig:a"]);
+        assert_eq!(completer.complete("@").len(), 3);
+        assert!(completer.complete("fig").is_empty());
+        assert!(completer.complete("@tab").is_empty());
+    }
+
+    #[test]
+    fn test_symbol_prefix_completion() {
+        let completer = SymbolCompleter::new();
+
+        let alph = completer.complete("alph");
+        assert_eq!(alph.len(), 1);
+        assert_eq!(alph[0].name, "alpha");
+        assert_eq!(alph[0].symbol, 'α');
+        assert_eq!(alph[0].category, SymbolCategory::Greek);
+
+        // Shorter names first
+        let names: Vec<&str> = completer
+            .complete("epsilon")
+            .iter()
+            .map(|completion| completion.name)
+            .collect();
+        assert_eq!(names, vec!["epsilon", "epsilon.alt"]);
+
+        assert_eq!(completer.lookup("integral"), Some('∫'));
+        assert_eq!(completer.lookup("integ"), None);
+    }
+
+    #[test]
+    fn test_symbol_fuzzy_dotted_names() {
+        let completer = SymbolCompleter::new();
+
+        let names: Vec<&str> = completer
+            .complete("arr.r")
+            .iter()
+            .map(|completion| completion.name)
+            .collect();
+        assert_eq!(names, vec![
+            "arrow.r",
+            "arrow.r.bar",
+            "arrow.r.hook",
+            "arrow.r.long",
+            "arrow.r.double",
+            "arrow.r.squiggly",
+        ]);
+
+        // Exact prefixes rank before part matches
+        let names: Vec<&str> = completer
+            .complete("in")
+            .iter()
+            .map(|completion| completion.name)
+            .collect();
+        assert_eq!(names, vec![
+            "in",
+            "in.not",
+            "infinity",
+            "integral",
+            "integral.cont",
+            "integral.double",
+        ]);
+
+        let item = CompletionItem::from(completer.complete("arrow.l.r.d")[0]);
+        assert_eq!(item.label, "arrow.l.r.double");
+        assert_eq!(item.detail.as_deref(), Some("⇔"));
+        assert!(completer.complete("arr.x").is_empty());
+    }
+
+    #[test]
+    fn test_table_is_sorted() {
+        assert!(BUILTIN_FUNCTIONS.windows(2).all(|pair| pair[0].name < pair[1].name));
+        assert!(KEYWORDS.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn test_symbol_names_are_unique() {
+        for (i, (name, _, _)) in SYMBOLS.iter().enumerate() {
+            assert!(SYMBOLS[i + 1..].iter().all(|(other, _, _)| other != name), "{name}");
+        }
+    }
+}
@@ -2,22 +2,47 @@
 //!
 //! Phase 3.5: Decorations and Annotations
 
+use editor_core::{ Buffer, Position, SelectionSet };
 use palette::Srgb;
 use std::ops::Range;
 
+pub use editor_core::decorations::{
+    DiagnosticSeverity,
+    GitDiffKind,
+    GutterDecoration,
+    GutterDecorationKind,
+    InlineDecoration,
+    InlineDecorationKind,
+};
+
 /// Decoration manager
 pub struct DecorationManager {
     inline_decorations: Vec<InlineDecoration>,
     gutter_decorations: Vec<GutterDecoration>,
     highlight_ranges: Vec<HighlightRange>,
+    /// Byte ranges of the current search matches
+    search_matches: Vec<Range<usize>>,
+    /// Index of the active match in `search_matches`
+    current_match: Option<usize>,
+    /// Color of the active match's highlight
+    current_match_color: Srgb,
+    /// Symbol whose occurrences are highlighted
+    occurrence_symbol: Option<String>,
 }
 
+/// Default color for the active search match
+const CURRENT_MATCH_COLOR: Srgb = Srgb::new(0.95, 0.6, 0.1);
+
 impl DecorationManager {
     pub fn new() -> Self {
         Self {
             inline_decorations: Vec::new(),
             gutter_decorations: Vec::new(),
             highlight_ranges: Vec::new(),
+            search_matches: Vec::new(),
+            current_match: None,
+            current_match_color: CURRENT_MATCH_COLOR,
+            occurrence_symbol: None,
         }
     }
 
@@ -36,11 +61,166 @@ impl DecorationManager {
         self.highlight_ranges.push(range);
     }
 
+    /// Replace selection highlights with one byte range per selection
+    ///
+    /// Highlights span whole selections rather than individual lines; the
+    /// renderer clips them to the visible lines.
+    pub fn set_selection_highlights(&mut self, selections: &SelectionSet, buffer: &Buffer) {
+        self.highlight_ranges.retain(|h| !matches!(h.kind, HighlightKind::Selection));
+
+        for selection in selections.selections() {
+            if selection.is_collapsed() {
+                continue;
+            }
+
+            let (start, end) = selection.range();
+            if
+                let (Ok(start), Ok(end)) = (
+                    buffer.position_to_byte_idx(start),
+                    buffer.position_to_byte_idx(end),
+                )
+            {
+                self.highlight_ranges.push(HighlightRange {
+                    range: start..end,
+                    kind: HighlightKind::Selection,
+                });
+            }
+        }
+    }
+
+    /// Replace search highlights with one per match
+    ///
+    /// Matches are `(start, end)` position pairs; ones that fall outside the
+    /// buffer are skipped. Other highlights are left alone, and no match is
+    /// current afterwards.
+    pub fn set_search_results(&mut self, matches: &[(Position, Position)], buffer: &Buffer) {
+        self.remove_search_highlights();
+
+        self.search_matches = matches
+            .iter()
+            .filter_map(|&(start, end)| {
+                let start = buffer.position_to_byte_idx(start).ok()?;
+                let end = buffer.position_to_byte_idx(end).ok()?;
+                Some(start..end)
+            })
+            .collect();
+        self.current_match = None;
+
+        self.push_search_highlights();
+    }
+
+    /// Mark one search match as the active one
+    ///
+    /// The active match is drawn with its own color instead of the search
+    /// result highlight. Out-of-range indices clear the active match.
+    pub fn set_current_match(&mut self, index: Option<usize>) {
+        self.remove_search_highlights();
+        self.current_match = index.filter(|&index| index < self.search_matches.len());
+        self.push_search_highlights();
+    }
+
+    /// Index of the active search match
+    pub fn current_match(&self) -> Option<usize> {
+        self.current_match
+    }
+
+    /// Byte ranges of the search matches
+    pub fn search_matches(&self) -> &[Range<usize>] {
+        &self.search_matches
+    }
+
+    /// Set the color of the active search match
+    pub fn set_current_match_color(&mut self, color: Srgb) {
+        self.remove_search_highlights();
+        self.current_match_color = color;
+        self.push_search_highlights();
+    }
+
+    /// Remove search highlights, leaving all other highlights in place
+    pub fn clear_search(&mut self) {
+        self.remove_search_highlights();
+        self.search_matches.clear();
+        self.current_match = None;
+    }
+
+    fn push_search_highlights(&mut self) {
+        for (index, range) in self.search_matches.iter().enumerate() {
+            let kind = if self.current_match == Some(index) {
+                HighlightKind::Custom(self.current_match_color)
+            } else {
+                HighlightKind::SearchResult
+            };
+            self.highlight_ranges.push(HighlightRange { range: range.clone(), kind });
+        }
+    }
+
+    fn remove_search_highlights(&mut self) {
+        let current = self.current_match.map(|index| self.search_matches[index].clone());
+        let color = self.current_match_color;
+        let mut current_removed = false;
+
+        self.highlight_ranges.retain(|highlight| {
+            match highlight.kind {
+                HighlightKind::SearchResult => false,
+                HighlightKind::Custom(c) if
+                    !current_removed &&
+                    c == color &&
+                    current.as_ref() == Some(&highlight.range)
+                => {
+                    current_removed = true;
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    /// Replace occurrence highlights with the reads and writes of `symbol`
+    ///
+    /// Ranges come from
+    /// [`SyntaxHighlighter::occurrences`](crate::syntax::SyntaxHighlighter::occurrences).
+    pub fn highlight_occurrences(
+        &mut self,
+        symbol: &str,
+        read_ranges: &[Range<usize>],
+        write_ranges: &[Range<usize>]
+    ) {
+        self.clear_occurrences();
+        self.occurrence_symbol = Some(symbol.to_string());
+
+        let reads = read_ranges.iter().map(|range| (range, HighlightKind::ReadOccurrence));
+        let writes = write_ranges.iter().map(|range| (range, HighlightKind::WriteOccurrence));
+        for (range, kind) in reads.chain(writes) {
+            self.highlight_ranges.push(HighlightRange { range: range.clone(), kind });
+        }
+    }
+
+    /// Symbol whose occurrences are highlighted
+    pub fn occurrence_symbol(&self) -> Option<&str> {
+        self.occurrence_symbol.as_deref()
+    }
+
+    /// Remove occurrence highlights, leaving all other highlights in place
+    pub fn clear_occurrences(&mut self) {
+        self.occurrence_symbol = None;
+        self.highlight_ranges.retain(|h| {
+            !matches!(h.kind, HighlightKind::ReadOccurrence | HighlightKind::WriteOccurrence)
+        });
+    }
+
+    /// Get all highlight ranges
+    pub fn highlights(&self) -> &[HighlightRange] {
+        &self.highlight_ranges
+    }
+
     /// Clear all decorations
     pub fn clear(&mut self) {
         self.inline_decorations.clear();
         self.gutter_decorations.clear();
         self.highlight_ranges.clear();
+        self.search_matches.clear();
+        self.current_match = None;
+        self.occurrence_symbol = None;
     }
 }
 
@@ -50,71 +230,6 @@ impl Default for DecorationManager {
     }
 }
 
-/// Inline decoration (squiggles, code lens, hints)
-#[derive(Debug, Clone)]
-pub struct InlineDecoration {
-    pub range: Range<usize>,
-    pub kind: InlineDecorationKind,
-}
-
-/// Type of inline decoration
-#[derive(Debug, Clone)]
-pub enum InlineDecorationKind {
-    /// Error squiggle (red wavy underline)
-    ErrorSquiggle,
-    /// Warning squiggle (yellow wavy underline)
-    WarningSquiggle,
-    /// Info squiggle (blue wavy underline)
-    InfoSquiggle,
-    /// Hint squiggle (gray wavy underline)
-    HintSquiggle,
-    /// Code lens above the line
-    CodeLens(String),
-    /// Inline hint (parameter name, type hint)
-    InlineHint(String),
-    /// Matching bracket highlight
-    MatchingBracket,
-}
-
-/// Gutter decoration (line numbers, icons, etc.)
-#[derive(Debug, Clone)]
-pub struct GutterDecoration {
-    pub line: usize,
-    pub kind: GutterDecorationKind,
-}
-
-/// Type of gutter decoration
-#[derive(Debug, Clone)]
-pub enum GutterDecorationKind {
-    /// Line number (always present)
-    LineNumber,
-    /// Folding marker (expand/collapse)
-    FoldingMarker {
-        folded: bool,
-    },
-    /// Diagnostic marker
-    Diagnostic(DiagnosticSeverity),
-    /// Git diff indicator
-    GitDiff(GitDiffKind),
-}
-
-/// Diagnostic severity level
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum DiagnosticSeverity {
-    Error,
-    Warning,
-    Info,
-    Hint,
-}
-
-/// Git diff kind
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum GitDiffKind {
-    Added,
-    Modified,
-    Deleted,
-}
-
 /// Highlight range for selections, search results, etc.
 #[derive(Debug, Clone)]
 pub struct HighlightRange {
@@ -138,3 +253,134 @@ pub enum HighlightKind {
     /// Custom highlight with color
     Custom(Srgb),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::{ BufferId, Selection };
+
+    #[test]
+    fn test_selection_highlight_spans_whole_selection() {
+        let text = "line\n".repeat(10);
+        let buffer = Buffer::from_text(BufferId::new(1), &text);
+        let selections = SelectionSet::new(
+            Selection::new(Position::new(0, 0), Position::new(10, 0))
+        );
+
+        let mut manager = DecorationManager::new();
+        manager.add_highlight(HighlightRange {
+            range: 0..4,
+            kind: HighlightKind::SearchResult,
+        });
+        manager.set_selection_highlights(&selections, &buffer);
+
+        let selection_highlights: Vec<_> = manager
+            .highlights()
+            .iter()
+            .filter(|h| matches!(h.kind, HighlightKind::Selection))
+            .collect();
+        assert_eq!(selection_highlights.len(), 1);
+        assert_eq!(selection_highlights[0].range, 0..text.len());
+
+        // Other highlights are left alone
+        assert_eq!(manager.highlights().len(), 2);
+    }
+
+    #[test]
+    fn test_selection_highlights_replace_previous() {
+        let buffer = Buffer::from_text(BufferId::new(1), "hello world");
+        let mut manager = DecorationManager::new();
+
+        let selections = SelectionSet::new(Selection::new(Position::new(0, 0), Position::new(0, 5)));
+        manager.set_selection_highlights(&selections, &buffer);
+
+        let selections = SelectionSet::new(Selection::new(Position::new(0, 6), Position::new(0, 11)));
+        manager.set_selection_highlights(&selections, &buffer);
+
+        assert_eq!(manager.highlights().len(), 1);
+        assert_eq!(manager.highlights()[0].range, 6..11);
+    }
+
+    #[test]
+    fn test_search_results_alongside_selection() {
+        let buffer = Buffer::from_text(BufferId::new(1), "foo bar\nfoo baz foo");
+        let mut manager = DecorationManager::new();
+        let selections = SelectionSet::new(Selection::new(Position::new(0, 4), Position::new(0, 7)));
+        manager.set_selection_highlights(&selections, &buffer);
+
+        let matches = [
+            (Position::new(0, 0), Position::new(0, 3)),
+            (Position::new(1, 0), Position::new(1, 3)),
+            (Position::new(1, 8), Position::new(1, 11)),
+            (Position::new(9, 0), Position::new(9, 3)),
+        ];
+        manager.set_search_results(&matches, &buffer);
+        assert_eq!(manager.search_matches(), &[0..3, 8..11, 16..19]);
+        assert_eq!(manager.highlights().len(), 4);
+
+        manager.set_current_match(Some(1));
+        let current: Vec<_> = manager
+            .highlights()
+            .iter()
+            .filter(|h| matches!(h.kind, HighlightKind::Custom(_)))
+            .map(|h| h.range.clone())
+            .collect();
+        assert_eq!(current, vec![8..11]);
+
+        // New results replace the old ones instead of piling up
+        manager.set_search_results(&matches[..1], &buffer);
+        assert_eq!(manager.highlights().len(), 2);
+        assert_eq!(manager.current_match(), None);
+    }
+
+    #[test]
+    fn test_clear_search_keeps_other_highlights() {
+        let buffer = Buffer::from_text(BufferId::new(1), "foo foo");
+        let mut manager = DecorationManager::new();
+        let selections = SelectionSet::new(Selection::new(Position::new(0, 0), Position::new(0, 3)));
+        manager.set_selection_highlights(&selections, &buffer);
+        manager.add_highlight(HighlightRange {
+            range: 4..7,
+            kind: HighlightKind::Custom(CURRENT_MATCH_COLOR),
+        });
+
+        manager.set_search_results(&[(Position::new(0, 4), Position::new(0, 7))], &buffer);
+        manager.set_current_match(Some(0));
+        manager.clear_search();
+
+        let kinds: Vec<_> = manager.highlights().iter().map(|h| h.kind.clone()).collect();
+        assert_eq!(kinds.len(), 2);
+        assert!(matches!(kinds[0], HighlightKind::Selection));
+        assert!(matches!(kinds[1], HighlightKind::Custom(_)));
+        assert!(manager.search_matches().is_empty());
+    }
+
+    #[test]
+    fn test_occurrence_highlights_replace_and_clear() {
+        let mut manager = DecorationManager::new();
+        manager.add_highlight(HighlightRange { range: 0..4, kind: HighlightKind::SearchResult });
+
+        manager.highlight_occurrences("x", &[13..14, 18..19], &[5..6, 30..31]);
+        manager.highlight_occurrences("x", &[13..14, 18..19], &[5..6, 30..31]);
+        assert_eq!(manager.occurrence_symbol(), Some("x"));
+
+        let count = |manager: &DecorationManager, read: bool| {
+            manager
+                .highlights()
+                .iter()
+                .filter(|h| match h.kind {
+                    HighlightKind::ReadOccurrence => read,
+                    HighlightKind::WriteOccurrence => !read,
+                    _ => false,
+                })
+                .count()
+        };
+        assert_eq!(count(&manager, true), 2);
+        assert_eq!(count(&manager, false), 2);
+
+        manager.clear_occurrences();
+        assert_eq!(manager.occurrence_symbol(), None);
+        assert_eq!(manager.highlights().len(), 1);
+        assert!(matches!(manager.highlights()[0].kind, HighlightKind::SearchResult));
+    }
+}
@@ -3,7 +3,11 @@
 //! Phase 3.1: Editor View Component Hierarchy
 
 use gpui::*;
+use bidi_text::FoldModel;
+use editor_core::diff_slices;
 use crate::decorations::{ GutterDecoration, GutterDecorationKind, GitDiffKind };
+use crate::syntax::highlighting::HighlightResult;
+use typst_syntax::{ SyntaxKind, SyntaxNode };
 
 /// Gutter component showing line numbers and decorations
 pub struct Gutter {
@@ -213,18 +217,65 @@ impl Default for LineNumbers {
 pub struct FoldingMarkers {
     /// Folded line ranges
     pub folded_ranges: Vec<(usize, usize)>,
+    /// Foldable line ranges, sorted by start line
+    regions: Vec<(usize, usize)>,
 }
 
 impl FoldingMarkers {
     pub fn new() -> Self {
         Self {
             folded_ranges: Vec::new(),
+            regions: Vec::new(),
         }
     }
 
+    /// Detect foldable regions from the syntax tree
+    ///
+    /// Code blocks, content blocks and function call argument lists that span
+    /// more than one line are foldable. `line_of_byte` maps a byte offset in
+    /// the source to its line.
+    pub fn compute_regions(&mut self, result: &HighlightResult, line_of_byte: impl Fn(usize) -> usize) {
+        let mut regions = Vec::new();
+        let mut stack: Vec<(&SyntaxNode, usize)> = vec![(&result.root, 0)];
+
+        while let Some((node, offset)) = stack.pop() {
+            let foldable = matches!(
+                node.kind(),
+                SyntaxKind::CodeBlock | SyntaxKind::ContentBlock | SyntaxKind::Args
+            );
+
+            if foldable && node.len() > 0 {
+                let start = line_of_byte(offset);
+                let end = line_of_byte(offset + node.len() - 1);
+                if end > start {
+                    regions.push((start, end));
+                }
+            }
+
+            let mut child_offset = offset;
+            for child in node.children() {
+                stack.push((child, child_offset));
+                child_offset += child.len();
+            }
+        }
+
+        // Outer regions first when several start on the same line
+        regions.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+        regions.dedup();
+
+        // Drop folds that no longer correspond to a region
+        self.folded_ranges.retain(|range| regions.contains(range));
+        self.regions = regions;
+    }
+
+    /// Get the foldable regions
+    pub fn regions(&self) -> &[(usize, usize)] {
+        &self.regions
+    }
+
     /// Check if a line can be folded
-    pub fn can_fold(&self, _line: usize) -> bool {
-        todo!("Check if line has foldable content")
+    pub fn can_fold(&self, line: usize) -> bool {
+        self.regions.iter().any(|(start, _)| *start == line)
     }
 
     /// Check if a line is folded
@@ -233,13 +284,42 @@ impl FoldingMarkers {
     }
 
     /// Toggle fold at line
-    pub fn toggle_fold(&mut self, _line: usize) {
-        todo!("Toggle fold state at line")
+    ///
+    /// Uses the outermost region starting at the line.
+    pub fn toggle_fold(&mut self, line: usize) {
+        let Some(region) = self.regions.iter().find(|(start, _)| *start == line).copied() else {
+            return;
+        };
+
+        if let Some(index) = self.folded_ranges.iter().position(|range| *range == region) {
+            self.folded_ranges.remove(index);
+        } else {
+            self.folded_ranges.push(region);
+        }
     }
 
     /// Fold all foldable regions
+    ///
+    /// Only top-level regions are folded; nested regions are hidden by them.
     pub fn fold_all(&mut self) {
-        todo!("Fold all foldable regions")
+        let top_level: Vec<(usize, usize)> = self.regions
+            .iter()
+            .filter(|(start, end)| {
+                !self.regions
+                    .iter()
+                    .any(|other| {
+                        other != &(*start, *end) && other.0 <= *start && *end <= other.1
+                    })
+            })
+            .copied()
+            .collect();
+
+        self.folded_ranges = top_level;
+    }
+
+    /// Folded regions as a model for fold-aware cursor movement
+    pub fn fold_model(&self) -> FoldModel {
+        FoldModel::new(self.folded_ranges.clone())
     }
 
     /// Unfold all regions
@@ -294,6 +374,31 @@ impl GitDiffIndicators {
         }
     }
 
+    /// Compute per-line diff kinds of `current` against `original`
+    ///
+    /// Lines of a changed region pair up as Modified; lines beyond the old
+    /// region's length are Added. A pure deletion marks the line after it as
+    /// Deleted, or the last line when the deletion is at the end of the file.
+    pub fn compute(&mut self, original: &str, current: &str) {
+        let old: Vec<&str> = original.lines().collect();
+        let new: Vec<&str> = current.lines().collect();
+
+        self.diffs.clear();
+        for hunk in diff_slices(&old, &new) {
+            if hunk.new.is_empty() {
+                let line = hunk.new.start.min(new.len().saturating_sub(1));
+                self.diffs.push((line, GitDiffKind::Deleted));
+                continue;
+            }
+
+            let modified = hunk.old.len().min(hunk.new.len());
+            for (offset, line) in hunk.new.enumerate() {
+                let kind = if offset < modified { GitDiffKind::Modified } else { GitDiffKind::Added };
+                self.diffs.push((line, kind));
+            }
+        }
+    }
+
     /// Set git diff information
     pub fn set_diffs(&mut self, diffs: Vec<(usize, GitDiffKind)>) {
         self.diffs = diffs;
@@ -312,9 +417,12 @@ impl GitDiffIndicators {
         self.diffs.clear();
     }
 
-    /// Render diff indicator for line
-    pub fn render(&self, _line: usize) {
-        todo!("Render git diff indicator")
+    /// Gutter decoration for the diff indicator on a line, if any
+    pub fn render(&self, line: usize) -> Option<GutterDecoration> {
+        self.get_diff(line).map(|kind| GutterDecoration {
+            line,
+            kind: GutterDecorationKind::GitDiff(kind),
+        })
     }
 }
 
@@ -325,4 +433,60 @@ impl Default for GitDiffIndicators {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::syntax::SyntaxHighlighter;
+
+    fn line_of_byte(text: &str) -> impl Fn(usize) -> usize + '_ {
+        move |offset| text[..offset].matches('\n').count()
+    }
+
+    #[test]
+    fn test_compute_folding_regions() {
+        let text =
+            "#let f(x) = {\n  let y = x\n  if y > 0 {\n    y\n  }\n}\n#block[\n  Some text\n]\n";
+        let result = SyntaxHighlighter::new().highlight(text);
+
+        let mut markers = FoldingMarkers::new();
+        markers.compute_regions(&result, line_of_byte(text));
+
+        assert_eq!(markers.regions(), &[(0, 5), (2, 4), (6, 8)]);
+        assert!(markers.can_fold(0));
+        assert!(markers.can_fold(2));
+        assert!(!markers.can_fold(1));
+
+        markers.toggle_fold(2);
+        assert!(markers.is_folded(3));
+        markers.toggle_fold(2);
+        assert!(!markers.is_folded(3));
+
+        markers.fold_all();
+        assert_eq!(markers.folded_ranges, vec![(0, 5), (6, 8)]);
+    }
+
+    #[test]
+    fn test_compute_git_diff() {
+        let original = "= Title\nintro\nold body\nkept\ntail one\ntail two\n";
+        let current = "= Title\nnew line\nintro\nnew body\nmore body\nkept\n";
+
+        let mut indicators = GitDiffIndicators::new();
+        indicators.compute(original, current);
+
+        assert_eq!(indicators.diffs, vec![
+            (1, GitDiffKind::Added),
+            (3, GitDiffKind::Modified),
+            (4, GitDiffKind::Added),
+            (5, GitDiffKind::Deleted)
+        ]);
+        assert!(indicators.render(0).is_none());
+        assert!(
+            matches!(
+                indicators.render(3).map(|decoration| decoration.kind),
+                Some(GutterDecorationKind::GitDiff(GitDiffKind::Modified))
+            )
+        );
+
+        indicators.compute(original, original);
+        assert!(indicators.diffs.is_empty());
+    }
+}
@@ -5,6 +5,7 @@
 use crate::rendering::line_layout::VisualLine;
 use crate::syntax::highlighting::HighlightToken;
 use crate::decorations::InlineDecoration;
+use std::ops::Range;
 
 /// Renders a single line of text with syntax highlighting
 pub struct LineRenderer {
@@ -62,6 +63,51 @@ impl LineRenderer {
         }
     }
 
+    /// Render uncached lines in a range, limited to `budget` lines per frame
+    ///
+    /// Cached lines do not count against the budget. `render` produces the
+    /// rendering for a line; the result is cached. When lines remain, the
+    /// caller should schedule another frame and call this again.
+    pub fn render_visible(
+        &mut self,
+        lines: Range<usize>,
+        budget: usize,
+        mut render: impl FnMut(usize) -> CachedLine
+    ) -> RenderStatus {
+        let mut rendered = 0;
+        let mut remaining = 0;
+
+        for line_number in lines {
+            if self.get_cached(line_number).is_some() {
+                continue;
+            }
+
+            if rendered < budget {
+                let cached = render(line_number);
+                self.insert_cached(line_number, cached);
+                rendered += 1;
+            } else {
+                remaining += 1;
+            }
+        }
+
+        if remaining == 0 {
+            RenderStatus::Complete { rendered }
+        } else {
+            RenderStatus::Pending { rendered, remaining }
+        }
+    }
+
+    /// Add a rendered line to the cache, evicting the oldest entry if full
+    fn insert_cached(&mut self, line_number: usize, cached: CachedLine) {
+        self.invalidate_line(line_number);
+        self.cache.push((line_number, cached));
+
+        if self.cache.len() > self.max_cache_size {
+            self.cache.remove(0);
+        }
+    }
+
     /// Get cached line rendering if available
     pub fn get_cached(&self, line_number: usize) -> Option<&CachedLine> {
         // Search cache for entry matching line number
@@ -96,6 +142,27 @@ impl Default for LineRenderer {
     }
 }
 
+/// Outcome of a budgeted render pass
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderStatus {
+    /// All requested lines are rendered
+    Complete {
+        rendered: usize,
+    },
+    /// The frame budget ran out before all lines were rendered
+    Pending {
+        rendered: usize,
+        remaining: usize,
+    },
+}
+
+impl RenderStatus {
+    /// Check if another frame is needed
+    pub fn has_remaining(&self) -> bool {
+        matches!(self, RenderStatus::Pending { .. })
+    }
+}
+
 /// Cached line rendering data
 pub struct CachedLine {
     /// Rendered texture or shape data
@@ -208,4 +275,44 @@ pub enum InlineWidgetKind {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    fn render(line_number: usize) -> CachedLine {
+        CachedLine {
+            data: Vec::new(),
+            version: line_number,
+        }
+    }
+
+    #[test]
+    fn test_render_budget_spreads_over_frames() {
+        let mut renderer = LineRenderer::new();
+        let mut calls = 0;
+
+        let status = renderer.render_visible(0..12, 5, |line| {
+            calls += 1;
+            render(line)
+        });
+        assert_eq!(status, RenderStatus::Pending { rendered: 5, remaining: 7 });
+        assert!(status.has_remaining());
+
+        let status = renderer.render_visible(0..12, 5, render);
+        assert_eq!(status, RenderStatus::Pending { rendered: 5, remaining: 2 });
+
+        let status = renderer.render_visible(0..12, 5, render);
+        assert_eq!(status, RenderStatus::Complete { rendered: 2 });
+        assert!(!status.has_remaining());
+        assert_eq!(calls, 5);
+    }
+
+    #[test]
+    fn test_cached_lines_are_free() {
+        let mut renderer = LineRenderer::new();
+        renderer.render_visible(0..10, 10, render);
+
+        // Scrolling by two lines only renders the two new lines
+        let status = renderer.render_visible(2..12, 5, render);
+        assert_eq!(status, RenderStatus::Complete { rendered: 2 });
+    }
+}
@@ -22,8 +22,10 @@ pub use cursor_renderer::{
     PrimaryCursor,
     SecondaryCursors,
 };
-pub use scrollbar::ScrollBar;
+pub use scrollbar::{ ScrollBar, MinimapModel };
 pub use overlays::Overlays;
+#[cfg(feature = "diagnostics")]
+pub use overlays::ToCodeActions;
 pub use status_bar::StatusBar;
 
 /// Editor view component - the main editor interface
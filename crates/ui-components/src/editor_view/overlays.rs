@@ -4,6 +4,9 @@
 
 use gpui::*;
 use editor_core::Position;
+pub use editor_core::signature::{ ParameterHints, ParameterInfo, SignatureInfo };
+#[cfg(feature = "diagnostics")]
+use typst_integration::diagnostics::Diagnostic;
 
 /// Overlay manager for popups and tooltips
 pub struct Overlays {
@@ -152,6 +155,7 @@ pub enum CompletionKind {
     Constant,
     Type,
     Module,
+    Label,
 }
 
 /// Hover information tooltip
@@ -169,50 +173,6 @@ impl HoverInfo {
     }
 }
 
-/// Parameter hints popup
-#[derive(Debug, Clone)]
-pub struct ParameterHints {
-    /// Hints position
-    pub position: Position,
-    /// Signatures
-    pub signatures: Vec<SignatureInfo>,
-    /// Active signature index
-    pub active_signature: usize,
-    /// Active parameter index
-    pub active_parameter: usize,
-}
-
-impl ParameterHints {
-    pub fn new(position: Position, signatures: Vec<SignatureInfo>) -> Self {
-        Self {
-            position,
-            signatures,
-            active_signature: 0,
-            active_parameter: 0,
-        }
-    }
-
-    /// Get active signature
-    pub fn get_active_signature(&self) -> Option<&SignatureInfo> {
-        self.signatures.get(self.active_signature)
-    }
-}
-
-/// Signature information
-#[derive(Debug, Clone)]
-pub struct SignatureInfo {
-    pub label: String,
-    pub parameters: Vec<ParameterInfo>,
-    pub documentation: Option<String>,
-}
-
-/// Parameter information
-#[derive(Debug, Clone)]
-pub struct ParameterInfo {
-    pub label: String,
-    pub documentation: Option<String>,
-}
-
 /// Quick fixes menu
 #[derive(Debug, Clone)]
 pub struct QuickFixesMenu {
@@ -233,6 +193,12 @@ impl QuickFixesMenu {
         }
     }
 
+    /// Menu of the quick fixes suggested by a diagnostic's hints
+    #[cfg(feature = "diagnostics")]
+    pub fn for_diagnostic(position: Position, diagnostic: &Diagnostic) -> Self {
+        Self::new(position, diagnostic.to_code_actions())
+    }
+
     /// Select next action
     pub fn select_next(&mut self) {
         if !self.actions.is_empty() {
@@ -258,7 +224,7 @@ impl QuickFixesMenu {
 }
 
 /// Code action
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct CodeAction {
     pub title: String,
     pub kind: CodeActionKind,
@@ -272,5 +238,50 @@ pub enum CodeActionKind {
     SourceAction,
 }
 
-#[cfg(test)]
-mod tests {}
+/// Code actions offered for a compiler diagnostic
+#[cfg(feature = "diagnostics")]
+pub trait ToCodeActions {
+    /// One quick fix per actionable hint, in hint order
+    fn to_code_actions(&self) -> Vec<CodeAction>;
+}
+
+#[cfg(feature = "diagnostics")]
+impl ToCodeActions for Diagnostic {
+    fn to_code_actions(&self) -> Vec<CodeAction> {
+        self.quick_fixes()
+            .into_iter()
+            .map(|title| CodeAction { title, kind: CodeActionKind::QuickFix })
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "diagnostics"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diagnostic_to_code_actions() {
+        let diagnostic = Diagnostic::error("unknown variable".to_string()).with_hints(
+            vec!["try importing `foo`".to_string(), "try defining `foo`".to_string()]
+        );
+        assert_eq!(diagnostic.to_code_actions(), vec![
+            CodeAction { title: "Try importing `foo`".to_string(), kind: CodeActionKind::QuickFix },
+            CodeAction { title: "Try defining `foo`".to_string(), kind: CodeActionKind::QuickFix }
+        ]);
+        assert!(Diagnostic::error("unknown variable".to_string()).to_code_actions().is_empty());
+    }
+
+    #[test]
+    fn test_quick_fixes_from_diagnostic() {
+        let diagnostic = Diagnostic::error("unclosed delimiter".to_string()).with_hints(
+            vec!["try adding a closing `]`".to_string(), "see the docs".to_string()]
+        );
+        let menu = QuickFixesMenu::for_diagnostic(Position::new(2, 4), &diagnostic);
+        let titles: Vec<&str> = menu.actions
+            .iter()
+            .map(|action| action.title.as_str())
+            .collect();
+        assert_eq!(titles, vec!["Try adding a closing `]`"]);
+        assert_eq!(menu.get_selected().map(|action| action.kind), Some(CodeActionKind::QuickFix));
+    }
+}
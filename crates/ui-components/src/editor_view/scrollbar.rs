@@ -3,6 +3,16 @@
 //! Phase 3.1: Editor View Component Hierarchy
 
 use gpui::*;
+use editor_core::{ Buffer, Position };
+use palette::Srgb;
+use crate::syntax::highlighting::HighlightToken;
+use crate::syntax::theme::Theme;
+
+/// Characters of source text summarized by one minimap cell
+const MINIMAP_CHARS_PER_CELL: usize = 4;
+
+/// Maximum number of characters sampled per minimap row
+const MINIMAP_MAX_COLUMNS: usize = 120;
 
 /// Scrollbar component
 pub struct ScrollBar {
@@ -78,6 +88,8 @@ pub struct VerticalScrollBar {
     pub minimap_mode: bool,
     /// Minimap scale (pixels per line)
     pub minimap_scale: f32,
+    /// Minimap height in pixels
+    pub minimap_height: f32,
 }
 
 impl VerticalScrollBar {
@@ -86,6 +98,7 @@ impl VerticalScrollBar {
             scrollbar: ScrollBar::new(ScrollBarOrientation::Vertical),
             minimap_mode: false,
             minimap_scale: 1.0,
+            minimap_height: 600.0,
         }
     }
 
@@ -99,6 +112,109 @@ impl VerticalScrollBar {
         self.minimap_scale = scale.max(0.1);
     }
 
+    /// Set minimap height in pixels
+    pub fn set_minimap_height(&mut self, height: f32) {
+        self.minimap_height = height.max(0.0);
+    }
+
+    /// Build the minimap data model for a buffer
+    ///
+    /// Each row summarizes one line as a strip of colored density cells. When
+    /// the buffer has more lines than fit in `minimap_height` at
+    /// `minimap_scale` pixels per line, lines are grouped and only the first
+    /// line of each group is sampled, so cost scales with the minimap height
+    /// rather than the buffer size.
+    ///
+    /// `tokens` are the highlighter's nested tokens in preorder; each cell
+    /// takes the color of the innermost token covering it.
+    pub fn build_minimap(
+        &self,
+        buffer: &Buffer,
+        tokens: &[HighlightToken],
+        theme: &Theme
+    ) -> MinimapModel {
+        let total_lines = buffer.len_lines();
+        let max_rows = ((self.minimap_height / self.minimap_scale).floor() as usize).max(1);
+        let lines_per_row = total_lines.div_ceil(max_rows).max(1);
+        let spans = innermost_spans(tokens);
+
+        let rows = (0..total_lines)
+            .step_by(lines_per_row)
+            .map(|line| Self::minimap_row(buffer, line, &spans, theme))
+            .collect();
+
+        MinimapModel {
+            rows,
+            lines_per_row,
+            row_height: self.minimap_scale,
+        }
+    }
+
+    /// Summarize a single line as a minimap row
+    ///
+    /// `tokens` must be disjoint and sorted, as from [`innermost_spans`].
+    fn minimap_row(
+        buffer: &Buffer,
+        line: usize,
+        tokens: &[HighlightToken],
+        theme: &Theme
+    ) -> MinimapRow {
+        let text = buffer.line(line).unwrap_or_default();
+        let line_start = buffer.position_to_byte_idx(Position::new(line, 0)).unwrap_or(0);
+
+        // Spans are disjoint and sorted, so skip straight to this line
+        let mut token_idx = tokens.partition_point(|token| token.end <= line_start);
+
+        let mut indent = 0;
+        let mut seen_content = false;
+        let mut cells = Vec::new();
+        let mut cell_color = None;
+        let mut cell_filled = 0;
+        let mut cell_len = 0;
+
+        for (offset, ch) in text.char_indices().take(MINIMAP_MAX_COLUMNS) {
+            if ch == '\n' || ch == '\r' {
+                break;
+            }
+
+            if ch.is_whitespace() {
+                if !seen_content {
+                    indent += 1;
+                }
+            } else {
+                seen_content = true;
+                cell_filled += 1;
+
+                if cell_color.is_none() {
+                    let byte = line_start + offset;
+                    while token_idx < tokens.len() && tokens[token_idx].end <= byte {
+                        token_idx += 1;
+                    }
+                    cell_color = Some(
+                        tokens
+                            .get(token_idx)
+                            .filter(|token| token.start <= byte)
+                            .map(|token| theme.color_for_token(token.token_type))
+                            .unwrap_or(theme.colors.foreground)
+                    );
+                }
+            }
+
+            cell_len += 1;
+            if cell_len == MINIMAP_CHARS_PER_CELL {
+                cells.push(MinimapCell::new(cell_color.take(), cell_filled, theme));
+                cell_filled = 0;
+                cell_len = 0;
+            }
+        }
+
+        if cell_len > 0 {
+            cells.push(MinimapCell::new(cell_color, cell_filled, theme));
+        }
+
+        MinimapRow { line, indent, cells }
+    }
+
     /// Render minimap
     pub fn render_minimap(&self) {
         todo!("Render code minimap")
@@ -116,6 +232,88 @@ impl Default for VerticalScrollBar {
     }
 }
 
+/// Flatten nested preorder tokens into disjoint, sorted spans
+///
+/// Each span takes the type of the innermost token covering it; bytes no
+/// token covers are left out.
+fn innermost_spans(tokens: &[HighlightToken]) -> Vec<HighlightToken> {
+    let mut spans = Vec::new();
+    let mut stack: Vec<&HighlightToken> = Vec::new();
+    let mut pos = 0;
+    let mut emit = |start: usize, end: usize, token: &HighlightToken| {
+        if start < end {
+            spans.push(HighlightToken { start, end, token_type: token.token_type });
+        }
+    };
+
+    for token in tokens {
+        // Close the tokens that end before this one starts
+        while let Some(top) = stack.last().copied().filter(|top| top.end <= token.start) {
+            emit(pos, top.end, top);
+            pos = pos.max(top.end);
+            stack.pop();
+        }
+        if let Some(top) = stack.last() {
+            emit(pos, token.start, top);
+        }
+        pos = pos.max(token.start);
+        stack.push(token);
+    }
+    while let Some(top) = stack.pop() {
+        emit(pos, top.end, top);
+        pos = pos.max(top.end);
+    }
+
+    spans
+}
+
+/// Minimap data model, ready to be drawn row by row
+#[derive(Debug, Clone)]
+pub struct MinimapModel {
+    /// One row per sampled line
+    pub rows: Vec<MinimapRow>,
+    /// Number of buffer lines summarized by each row
+    pub lines_per_row: usize,
+    /// Row height in pixels
+    pub row_height: f32,
+}
+
+impl MinimapModel {
+    /// Total height of the minimap in pixels
+    pub fn height(&self) -> f32 {
+        (self.rows.len() as f32) * self.row_height
+    }
+}
+
+/// A single minimap row
+#[derive(Debug, Clone)]
+pub struct MinimapRow {
+    /// Buffer line sampled for this row
+    pub line: usize,
+    /// Leading whitespace in characters
+    pub indent: usize,
+    /// Density cells, left to right
+    pub cells: Vec<MinimapCell>,
+}
+
+/// A cell summarizing a few characters of a line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MinimapCell {
+    /// Color of the dominant token
+    pub color: Srgb,
+    /// Fraction of non-whitespace characters (0.0 to 1.0)
+    pub density: f32,
+}
+
+impl MinimapCell {
+    fn new(color: Option<Srgb>, filled: usize, theme: &Theme) -> Self {
+        Self {
+            color: color.unwrap_or(theme.colors.background),
+            density: (filled as f32) / (MINIMAP_CHARS_PER_CELL as f32),
+        }
+    }
+}
+
 /// Horizontal scrollbar
 pub struct HorizontalScrollBar {
     /// Base scrollbar
@@ -162,4 +360,49 @@ impl Default for HorizontalScrollBar {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use crate::syntax::highlighting::{ SyntaxHighlighter, TokenType };
+    use editor_core::BufferId;
+
+    #[test]
+    fn test_minimap_downsamples_large_buffers() {
+        let text = "    let x = 1\n".repeat(100_000);
+        let buffer = Buffer::from_text(BufferId::new(1), &text);
+        let theme = Theme::default();
+
+        let mut scrollbar = VerticalScrollBar::new();
+        scrollbar.set_minimap_scale(2.0);
+        scrollbar.set_minimap_height(600.0);
+
+        // 100_001 lines into 300 rows of 2px: 334 lines per row
+        let model = scrollbar.build_minimap(&buffer, &[], &theme);
+        assert_eq!(model.lines_per_row, 334);
+        assert_eq!(model.rows.len(), 300);
+        assert!(model.height() <= 600.0);
+    }
+
+    #[test]
+    fn test_minimap_row_cells() {
+        let text = "a\n  #let x = 1";
+        let buffer = Buffer::from_text(BufferId::new(1), text);
+        let theme = Theme::default();
+        // Nested tokens, starting with a root markup token over everything
+        let result = SyntaxHighlighter::new().highlight(text);
+
+        let model = VerticalScrollBar::new().build_minimap(&buffer, &result.tokens, &theme);
+        assert_eq!(model.rows.len(), 2);
+        assert_eq!(model.lines_per_row, 1);
+
+        // Cells: "  #l", "et x", " = 1"; each takes its first character's
+        // innermost token
+        let row = &model.rows[1];
+        assert_eq!(row.indent, 2);
+        assert_eq!(row.cells.len(), 3);
+        assert_eq!(row.cells[0].color, theme.color_for_token(TokenType::Markup));
+        assert_eq!(row.cells[0].density, 0.5);
+        assert_eq!(row.cells[1].color, theme.color_for_token(TokenType::Keyword));
+        assert_eq!(row.cells[2].color, theme.color_for_token(TokenType::Operator));
+        assert_eq!(row.cells[2].density, 0.5);
+    }
+}
@@ -3,7 +3,10 @@
 //! Phase 3.1: Editor View Component Hierarchy
 
 use gpui::*;
-use editor_core::Position;
+use editor_core::{ Buffer, Position, SelectionSet };
+
+/// Separator between status line segments
+const SEGMENT_SEPARATOR: &str = "  |  ";
 
 /// Status bar at bottom of editor
 pub struct StatusBar {
@@ -28,8 +31,42 @@ impl StatusBar {
     }
 
     /// Update all components
-    pub fn update(&mut self, _position: &Position, _selection_size: usize) {
-        todo!("Update status bar components")
+    pub fn update(&mut self, position: &Position, selection: &SelectionSet, buffer: &Buffer) {
+        self.position.update(position);
+
+        let mut char_count = 0;
+        let mut line_count = 0;
+        for sel in selection.selections().iter().filter(|sel| !sel.is_collapsed()) {
+            let (start, end) = sel.range();
+            if
+                let (Ok(start_idx), Ok(end_idx)) = (
+                    buffer.position_to_char_idx(start),
+                    buffer.position_to_char_idx(end),
+                )
+            {
+                char_count += end_idx.saturating_sub(start_idx);
+                line_count += end.line - start.line + 1;
+            }
+        }
+        self.selection.update(char_count, line_count);
+
+        // Buffers are always held as UTF-8
+        self.encoding.set_encoding("UTF-8".to_string());
+
+        let extension = buffer
+            .file_path()
+            .and_then(|path| path.extension())
+            .and_then(|ext| ext.to_str());
+        self.language.set_language(LanguageMode::for_extension(extension).to_string());
+    }
+
+    /// Format all segments as a single text-mode status line
+    pub fn format_line(&self) -> String {
+        let mut segments = vec![self.position.format()];
+        segments.extend(self.selection.format());
+        segments.push(self.encoding.format());
+        segments.push(self.language.format());
+        segments.join(SEGMENT_SEPARATOR)
     }
 
     /// Render status bar
@@ -170,6 +207,19 @@ impl LanguageMode {
         }
     }
 
+    /// Language name for a file extension
+    pub fn for_extension(extension: Option<&str>) -> &'static str {
+        match extension.map(|ext| ext.to_ascii_lowercase()).as_deref() {
+            Some("typ") => "Typst",
+            Some("bib") => "BibTeX",
+            Some("md") => "Markdown",
+            Some("toml") => "TOML",
+            Some("yml" | "yaml") => "YAML",
+            Some("json") => "JSON",
+            _ => "Plain Text",
+        }
+    }
+
     /// Set language
     pub fn set_language(&mut self, language: String) {
         self.language = language;
@@ -188,4 +238,27 @@ impl Default for LanguageMode {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use editor_core::{ BufferId, Selection };
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_status_line_with_multiline_selection() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "= Title\nSome text\nMore");
+        buffer.set_file_path(PathBuf::from("notes.typ"));
+
+        let cursor = Position::new(1, 4);
+        let selection = SelectionSet::new(Selection::new(Position::new(0, 2), cursor));
+
+        let mut status = StatusBar::new();
+        status.update(&cursor, &selection, &buffer);
+
+        let line = status.format_line();
+        assert!(line.contains("Ln 2"));
+        assert!(line.contains("Col 5"));
+        assert!(line.contains("10 chars (2 lines)"));
+        assert!(line.contains("UTF-8"));
+        assert!(line.contains("Typst"));
+    }
+}
@@ -3,8 +3,11 @@
 //! Phase 3.1: Editor View Component Hierarchy
 
 use gpui::*;
-use editor_core::{ Buffer, Position, SelectionSet };
-use crate::rendering::Viewport;
+use bidi_text::wrap_graphemes;
+use editor_core::{ Buffer, BufferChange, Position, SelectionSet };
+use crate::rendering::{ LineIndex, Viewport };
+use super::scrollbar::HorizontalScrollBar;
+use unicode_segmentation::UnicodeSegmentation;
 
 /// Text content area - the main editor canvas
 pub struct TextContent {
@@ -18,6 +21,10 @@ pub struct TextContent {
     pub tab_size: usize,
     /// Word wrap enabled
     pub word_wrap: bool,
+    /// Visual rows of each logical line, used while word wrap is enabled
+    pub line_index: LineIndex,
+    /// Width of the widest line in pixels
+    pub content_width: f32,
 }
 
 impl TextContent {
@@ -28,6 +35,8 @@ impl TextContent {
             char_width: 8.0,
             tab_size: 4,
             word_wrap: false,
+            line_index: LineIndex::default(),
+            content_width: 0.0,
         }
     }
 
@@ -35,13 +44,11 @@ impl TextContent {
     pub fn render_visible_lines(&self, buffer: &Buffer, _selections: &SelectionSet) {
         let (padded_first_line, padded_last_line) = self.visible_lines();
 
-        for line in padded_first_line..=padded_last_line.min(buffer.len_lines().saturating_sub(1)) {
-            if let Ok(_line_text) = buffer.line(line as usize) {
-                // Rendering is driven by higher-level systems (LineRenderer, shaders).
-                // This method intentionally leaves rendering details to those systems.
-                // For now we simply iterate the visible lines to ensure callers can
-                // request line text and perform any per-line processing if needed.
-            }
+        for _line_text in buffer.lines_in_range(padded_first_line, padded_last_line + 1) {
+            // Rendering is driven by higher-level systems (LineRenderer, shaders).
+            // This method intentionally leaves rendering details to those systems.
+            // For now we simply iterate the visible lines to ensure callers can
+            // request line text and perform any per-line processing if needed.
         }
     }
 
@@ -72,43 +79,178 @@ impl TextContent {
     }
 
     /// Convert screen position to buffer position
-    pub fn screen_to_buffer_position(&self, screen_pos: Point<Pixels>) -> Position {
+    ///
+    /// Tabs count as reaching the next tab stop. Clicks land on the nearest
+    /// grapheme boundary, and clicks past the end of a line land at its end.
+    pub fn screen_to_buffer_position(
+        &self,
+        buffer: &Buffer,
+        screen_pos: Point<Pixels>
+    ) -> Position {
         let bounds = self.viewport.bounds;
 
-        // Convert screen coordinates to f32
+        // Convert screen coordinates to f32, relative to the viewport
         let screen_x: f32 = screen_pos.x.into();
         let screen_y: f32 = screen_pos.y.into();
+        let origin_x: f32 = bounds.origin.x.into();
+        let origin_y: f32 = bounds.origin.y.into();
+        let scroll_x: f32 = self.viewport.scroll_offset.x.into();
+        let x = (screen_x - origin_x + scroll_x).max(0.0);
+        let visual_row = ((screen_y - origin_y) / self.line_height).floor().max(0.0) as usize;
+
+        // Find the logical line, and the wrapped row within it
+        let line = if self.word_wrap {
+            let total = self.line_index.total_rows();
+            if visual_row < total {
+                self.line_index.logical_line_at(visual_row)
+            } else {
+                self.line_index.len() + (visual_row - total)
+            }
+        } else {
+            visual_row
+        };
+        let last_line = buffer.len_lines().saturating_sub(1);
+        if line > last_line {
+            let text = line_text(buffer, last_line);
+            return Position::new(last_line, text.graphemes(true).count());
+        }
 
-        // Subtract viewport offset
-        let adjusted_y: f32 = bounds.origin.y.into();
-        let adjusted_x: f32 = bounds.origin.x.into();
-
-        // Calculate line number
-        let line = ((screen_y - adjusted_y) / self.line_height).floor() as usize;
-
-        // Calculate column (for now, simple monospace calculation)
-        // TODO: Handle word wrap and bidirectional text
-        let column = ((screen_x - adjusted_x) / self.char_width).floor() as usize;
+        let text = line_text(buffer, line);
+        let cells = self.grapheme_cells(&text);
+        let rows = self.wrap_starts(&text, &cells);
+        let row = visual_row.saturating_sub(self.visual_row(line)).min(rows.len() - 1);
+        let row_start = rows[row];
+        let row_end = rows.get(row + 1).copied().unwrap_or(cells.len() - 1);
+
+        let cell_x = x / self.char_width;
+        for column in row_start..row_end {
+            let middle = ((cells[column] + cells[column + 1]) as f32) / 2.0;
+            if cell_x < middle - (cells[row_start] as f32) {
+                return Position::new(line, column);
+            }
+        }
 
+        // Past the end of the row; a wrapped row ends before its break
+        let column = if row + 1 < rows.len() { row_end - 1 } else { row_end };
         Position::new(line, column)
     }
 
     /// Convert buffer position to screen position
-    pub fn buffer_to_screen_position(&self, pos: &Position) -> Point<Pixels> {
+    ///
+    /// Columns past the end of the line are clamped to it.
+    pub fn buffer_to_screen_position(&self, buffer: &Buffer, pos: &Position) -> Point<Pixels> {
         let bounds = self.viewport.bounds;
 
-        // Calculate base Y coordinate: line * line_height
-        let y_pos = (pos.line as f32) * self.line_height;
+        let text = line_text(buffer, pos.line);
+        let cells = self.grapheme_cells(&text);
+        let column = pos.column.min(cells.len() - 1);
+        let rows = self.wrap_starts(&text, &cells);
+        let row = rows.partition_point(|start| *start <= column) - 1;
+
+        // Y from the line's first visual row plus the wrapped row
+        let y_pos = ((self.visual_row(pos.line) + row) as f32) * self.line_height;
 
-        // Calculate X coordinate: column * char_width
-        // TODO: Handle word wrap, variable-width fonts, and bidirectional text
-        let x_pos = (pos.column as f32) * self.char_width;
+        // X from the cells before the column within its row
+        // TODO: Handle variable-width fonts and bidirectional text
+        let x_pos = ((cells[column] - cells[rows[row]]) as f32) * self.char_width;
 
-        // Add viewport offset
+        // Add viewport offset, less the horizontal scroll
         let bounds_x: f32 = bounds.origin.x.into();
         let bounds_y: f32 = bounds.origin.y.into();
+        let scroll_x: f32 = self.viewport.scroll_offset.x.into();
+
+        point(px(x_pos + bounds_x - scroll_x), px(y_pos + bounds_y))
+    }
+
+    /// Recompute the visual rows of every line from the current wrap width
+    pub fn update_line_index(&mut self, buffer: &Buffer) {
+        let rows: Vec<usize> = (0..buffer.len_lines())
+            .map(|line| self.row_count(buffer, line))
+            .collect();
+        self.line_index = LineIndex::new(&rows);
+    }
+
+    /// Re-wrap just the lines touched by an edit to `buffer`
+    ///
+    /// Call after every change, with the buffer as it is once the change is
+    /// applied. Lines the index does not cover yet are left alone.
+    pub fn on_buffer_change(&mut self, buffer: &Buffer, change: &BufferChange) {
+        let start = change.old_range.start.line;
+        if start >= self.line_index.len() {
+            return;
+        }
+        let old_end = (change.old_range.end.line + 1).min(self.line_index.len());
+        let rows: Vec<usize> = (start..=change.new_range.end.line)
+            .map(|line| self.row_count(buffer, line))
+            .collect();
+
+        // Lines present before and after keep their slot, so only a change
+        // in line count shifts the index
+        let kept = (old_end - start).min(rows.len());
+        for (offset, &row_count) in rows[..kept].iter().enumerate() {
+            self.line_index.update(start + offset, row_count);
+        }
+        if rows.len() > kept {
+            self.line_index.insert(start + kept, &rows[kept..]);
+        } else {
+            self.line_index.remove(start + kept..old_end);
+        }
+    }
+
+    /// Visual rows of a logical line at the current wrap width
+    fn row_count(&self, buffer: &Buffer, line: usize) -> usize {
+        let text = line_text(buffer, line);
+        self.wrap_starts(&text, &self.grapheme_cells(&text)).len()
+    }
+
+    /// Cell where each grapheme of a line starts, plus the line's end
+    ///
+    /// Graphemes take one cell, except tabs, which reach the next tab stop.
+    fn grapheme_cells(&self, text: &str) -> Vec<usize> {
+        let tab_size = self.tab_size.max(1);
+        let mut cells = vec![0];
+        let mut cell = 0;
+        for grapheme in text.graphemes(true) {
+            cell = if grapheme == "\t" { (cell / tab_size + 1) * tab_size } else { cell + 1 };
+            cells.push(cell);
+        }
+        cells
+    }
+
+    /// Grapheme columns at which each visual row of a line starts
+    ///
+    /// Without word wrap a line is one row. With it, the line wraps to the
+    /// viewport width with [`wrap_graphemes`], measuring graphemes in cells.
+    fn wrap_starts(&self, text: &str, cells: &[usize]) -> Vec<usize> {
+        let width: f32 = self.viewport.bounds.size.width.into();
+        if !self.word_wrap || self.char_width <= 0.0 || width <= 0.0 {
+            return vec![0];
+        }
+        let max_cells = (width / self.char_width).floor().max(1.0);
+
+        let graphemes: Vec<(f32, bool)> = text
+            .graphemes(true)
+            .zip(cells.windows(2))
+            .map(|(grapheme, cell)| {
+                ((cell[1] - cell[0]) as f32, grapheme.chars().all(char::is_whitespace))
+            })
+            .collect();
+        wrap_graphemes(&graphemes, max_cells)
+            .into_iter()
+            .map(|row| row.start)
+            .collect()
+    }
 
-        point(px(x_pos + bounds_x), px(y_pos + bounds_y))
+    /// First visual row of a logical line
+    ///
+    /// Without word wrap every line is one row. Lines the index does not
+    /// cover yet are counted as one row each.
+    pub fn visual_row(&self, line: usize) -> usize {
+        if !self.word_wrap {
+            return line;
+        }
+        let indexed = self.line_index.len();
+        self.line_index.visual_row_of(line) + line.saturating_sub(indexed)
     }
 
     /// Set line height
@@ -124,6 +266,41 @@ impl TextContent {
     /// Toggle word wrap
     pub fn toggle_word_wrap(&mut self) {
         self.word_wrap = !self.word_wrap;
+        self.clamp_horizontal_scroll();
+    }
+
+    /// Recompute the content width from the buffer's widest line
+    pub fn update_content_width(&mut self, buffer: &Buffer) {
+        let columns = buffer.metrics_with_tab_size(self.tab_size).longest_line_display_columns;
+        self.content_width = (columns as f32) * self.char_width;
+        self.clamp_horizontal_scroll();
+    }
+
+    /// Largest horizontal scroll offset; 0 with word wrap, as nothing overflows
+    pub fn max_scroll_x(&self) -> f32 {
+        if self.word_wrap {
+            return 0.0;
+        }
+        let viewport_width: f32 = self.viewport.bounds.size.width.into();
+        (self.content_width - viewport_width).max(0.0)
+    }
+
+    /// Size and position a horizontal scrollbar's thumb for the current scroll
+    pub fn update_horizontal_scrollbar(&self, scrollbar: &mut HorizontalScrollBar) {
+        let viewport_width: f32 = self.viewport.bounds.size.width.into();
+        let content_width = if self.word_wrap { viewport_width } else { self.content_width };
+        scrollbar.set_dimensions(content_width, viewport_width);
+
+        let max_scroll = self.max_scroll_x();
+        let scroll_x: f32 = self.viewport.scroll_offset.x.into();
+        let position = if max_scroll > 0.0 { scroll_x / max_scroll } else { 0.0 };
+        scrollbar.scrollbar.set_position(position);
+    }
+
+    /// Keep the horizontal scroll offset within the content
+    fn clamp_horizontal_scroll(&mut self) {
+        let scroll_x: f32 = self.viewport.scroll_offset.x.into();
+        self.viewport.scroll_offset.x = px(scroll_x.clamp(0.0, self.max_scroll_x()));
     }
 
     /// Scroll to make position visible
@@ -154,11 +331,15 @@ impl TextContent {
     /// Handle scroll event
     pub fn handle_scroll(&mut self, delta: Point<Pixels>) {
         // Add delta to current scroll offset
+        let delta_x: f32 = delta.x.into();
         let delta_y: f32 = delta.y.into();
 
+        let current_x: f32 = self.viewport.scroll_offset.x.into();
         let current_y: f32 = self.viewport.scroll_offset.y.into();
 
-        // Update scroll offset (horizontal scrolling will be implemented later)
+        // Update scroll offset, keeping the horizontal offset within the content
+        self.viewport.scroll_offset.x = px(current_x + delta_x);
+        self.clamp_horizontal_scroll();
         self.viewport.scroll_offset.y = px((current_y + delta_y).max(0.0));
 
         // TODO: Add clamping based on content bounds
@@ -166,6 +347,14 @@ impl TextContent {
     }
 }
 
+/// Text of a line without its line ending, or empty past the end
+fn line_text(buffer: &Buffer, line: usize) -> String {
+    let mut text = buffer.line(line).unwrap_or_default();
+    let content_len = text.trim_end_matches(['\n', '\r']).len();
+    text.truncate(content_len);
+    text
+}
+
 impl Default for TextContent {
     fn default() -> Self {
         Self::new()
@@ -173,4 +362,166 @@ impl Default for TextContent {
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+    use editor_core::BufferId;
+
+    fn screen(content: &TextContent, buffer: &Buffer, line: usize, column: usize) -> (f32, f32) {
+        let point = content.buffer_to_screen_position(buffer, &Position::new(line, column));
+        (point.x.into(), point.y.into())
+    }
+
+    fn click(content: &TextContent, buffer: &Buffer, x: f32, y: f32) -> Position {
+        content.screen_to_buffer_position(buffer, point(px(x), px(y)))
+    }
+
+    fn assert_round_trips(content: &TextContent, buffer: &Buffer, line: usize) {
+        let text = line_text(buffer, line);
+        for column in 0..=text.graphemes(true).count() {
+            let (x, y) = screen(content, buffer, line, column);
+            assert_eq!(click(content, buffer, x, y), Position::new(line, column));
+        }
+    }
+
+    #[test]
+    fn test_wrapped_lines_shift_later_lines() {
+        let buffer = Buffer::from_text(BufferId::new(1), "a\nb\nc\nd\ne");
+        let mut content = TextContent::new();
+        content.line_index = LineIndex::new(&[1, 3, 1]);
+        assert_eq!(screen(&content, &buffer, 2, 0).1, 40.0);
+
+        content.toggle_word_wrap();
+        assert_eq!(screen(&content, &buffer, 1, 0).1, 20.0);
+        assert_eq!(screen(&content, &buffer, 2, 0).1, 80.0);
+        // Lines past the index take one row each
+        assert_eq!(screen(&content, &buffer, 4, 0).1, 120.0);
+
+        content.line_index.update(1, 2);
+        assert_eq!(screen(&content, &buffer, 2, 0).1, 60.0);
+    }
+
+    #[test]
+    fn test_tabs_expand_to_tab_stops() {
+        let buffer = Buffer::from_text(BufferId::new(1), "\tab\tc\nxy");
+        let content = TextContent::new();
+
+        // The tab reaches column 4, and the second tab column 8
+        assert_eq!(screen(&content, &buffer, 0, 1), (32.0, 0.0));
+        assert_eq!(screen(&content, &buffer, 0, 4), (64.0, 0.0));
+        assert_round_trips(&content, &buffer, 0);
+
+        // Clicks land on the nearest boundary and clamp past the line end
+        assert_eq!(click(&content, &buffer, 10.0, 5.0), Position::new(0, 0));
+        assert_eq!(click(&content, &buffer, 20.0, 5.0), Position::new(0, 1));
+        assert_eq!(click(&content, &buffer, 500.0, 25.0), Position::new(1, 2));
+        assert_eq!(click(&content, &buffer, 0.0, 500.0), Position::new(1, 2));
+        assert_eq!(screen(&content, &buffer, 1, 9), (16.0, 20.0));
+    }
+
+    #[test]
+    fn test_wrapped_line_positions() {
+        let buffer = Buffer::from_text(BufferId::new(1), "intro\nhello world again\nend");
+        let mut content = TextContent::new();
+        content.viewport.bounds.size.width = px(80.0);
+        content.toggle_word_wrap();
+        content.update_line_index(&buffer);
+
+        // Ten cells per row: "hello ", "world ", "again"
+        assert_eq!(content.line_index, LineIndex::new(&[1, 3, 1]));
+        assert_eq!(screen(&content, &buffer, 1, 8), (16.0, 40.0));
+        assert_eq!(screen(&content, &buffer, 1, 12), (0.0, 60.0));
+        assert_eq!(screen(&content, &buffer, 2, 0), (0.0, 80.0));
+        for line in 0..3 {
+            assert_round_trips(&content, &buffer, line);
+        }
+
+        // Past the end of a wrapped row stays on that row
+        assert_eq!(click(&content, &buffer, 79.0, 25.0), Position::new(1, 5));
+        assert_eq!(click(&content, &buffer, 79.0, 65.0), Position::new(1, 17));
+    }
+
+    #[test]
+    fn test_edits_rewrap_touched_lines() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "intro\nhello world again\nend");
+        let mut content = TextContent::new();
+        content.viewport.bounds.size.width = px(80.0);
+        content.toggle_word_wrap();
+        content.update_line_index(&buffer);
+
+        let changes = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = changes.clone();
+        buffer.subscribe(Box::new(move |change| sink.lock().unwrap().push(change.clone())));
+
+        let apply = |content: &mut TextContent, buffer: &Buffer| {
+            for change in changes.lock().unwrap().drain(..) {
+                content.on_buffer_change(buffer, &change);
+            }
+            let mut rebuilt = TextContent::new();
+            rebuilt.viewport.bounds.size.width = px(80.0);
+            rebuilt.toggle_word_wrap();
+            rebuilt.update_line_index(buffer);
+            assert_eq!(content.line_index, rebuilt.line_index, "{:?}", buffer.text());
+        };
+
+        // Same line count, then a split into more lines, then a join
+        buffer.insert(Position::new(0, 5), " and more words").unwrap();
+        apply(&mut content, &buffer);
+        buffer.insert(Position::new(1, 6), "\nnew\nlines ").unwrap();
+        apply(&mut content, &buffer);
+        buffer.delete(Position::new(0, 3), Position::new(3, 2)).unwrap();
+        apply(&mut content, &buffer);
+        assert_eq!(content.line_index.len(), buffer.len_lines());
+    }
+
+    fn scroll_x(content: &TextContent) -> f32 {
+        content.viewport.scroll_offset.x.into()
+    }
+
+    #[test]
+    fn test_horizontal_scroll_is_clamped() {
+        let buffer = Buffer::from_text(BufferId::new(1), &format!("short\n{}", "x".repeat(100)));
+        let mut content = TextContent::new();
+        content.viewport.bounds.size.width = px(300.0);
+        content.update_content_width(&buffer);
+        assert_eq!(content.content_width, 800.0);
+        assert_eq!(content.max_scroll_x(), 500.0);
+
+        content.handle_scroll(point(px(120.0), px(0.0)));
+        assert_eq!(scroll_x(&content), 120.0);
+        content.handle_scroll(point(px(1000.0), px(0.0)));
+        assert_eq!(scroll_x(&content), 500.0);
+        content.handle_scroll(point(px(-2000.0), px(0.0)));
+        assert_eq!(scroll_x(&content), 0.0);
+
+        // Positions move with the scroll offset
+        content.handle_scroll(point(px(80.0), px(0.0)));
+        assert_eq!(screen(&content, &buffer, 1, 20), (80.0, 20.0));
+        assert_eq!(click(&content, &buffer, 80.0, 25.0), Position::new(1, 20));
+
+        let mut scrollbar = HorizontalScrollBar::new();
+        content.update_horizontal_scrollbar(&mut scrollbar);
+        assert!(scrollbar.is_needed());
+        assert_eq!(scrollbar.scrollbar.thumb_size, 300.0 / 800.0);
+        assert_eq!(scrollbar.scrollbar.position, 80.0 / 500.0);
+    }
+
+    #[test]
+    fn test_word_wrap_pins_horizontal_scroll() {
+        let buffer = Buffer::from_text(BufferId::new(1), &"word ".repeat(100));
+        let mut content = TextContent::new();
+        content.viewport.bounds.size.width = px(300.0);
+        content.update_content_width(&buffer);
+        content.handle_scroll(point(px(200.0), px(0.0)));
+        assert_eq!(scroll_x(&content), 200.0);
+
+        content.toggle_word_wrap();
+        assert_eq!(scroll_x(&content), 0.0);
+        content.handle_scroll(point(px(200.0), px(0.0)));
+        assert_eq!(scroll_x(&content), 0.0);
+
+        let mut scrollbar = HorizontalScrollBar::new();
+        content.update_horizontal_scrollbar(&mut scrollbar);
+        assert!(!scrollbar.is_needed());
+        assert_eq!(scrollbar.scrollbar.position, 0.0);
+    }
+}
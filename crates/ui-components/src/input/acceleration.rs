@@ -0,0 +1,153 @@
+//! Key-repeat-aware movement acceleration
+//!
+//! Phase 3.4: Input Handling
+
+use std::time::{ Duration, Instant };
+
+/// Configuration for movement acceleration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AccelerationConfig {
+    /// Whether acceleration is enabled
+    pub enabled: bool,
+    /// Maximum gap between events still counted as a key repeat
+    pub repeat_gap: Duration,
+    /// How long a key must be held before movement accelerates
+    pub threshold: Duration,
+    /// Time after the threshold for each additional unit of movement
+    pub ramp_interval: Duration,
+    /// Upper bound on units moved per event
+    pub max_step: usize,
+}
+
+impl Default for AccelerationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            repeat_gap: Duration::from_millis(100),
+            threshold: Duration::from_millis(500),
+            ramp_interval: Duration::from_millis(250),
+            max_step: 8,
+        }
+    }
+}
+
+/// Computes how many units to move per movement event while a key repeats
+pub struct MovementAccelerator {
+    config: AccelerationConfig,
+    /// When the current run of repeated events started
+    sequence_start: Option<Instant>,
+    /// Time of the most recent event
+    last_event: Option<Instant>,
+}
+
+impl MovementAccelerator {
+    pub fn new(config: AccelerationConfig) -> Self {
+        Self {
+            config,
+            sequence_start: None,
+            last_event: None,
+        }
+    }
+
+    /// Get the configuration
+    pub fn config(&self) -> &AccelerationConfig {
+        &self.config
+    }
+
+    /// Replace the configuration
+    pub fn set_config(&mut self, config: AccelerationConfig) {
+        self.config = config;
+        self.reset();
+    }
+
+    /// Register a movement event and return the number of units to move
+    pub fn step(&mut self, now: Instant) -> usize {
+        let is_repeat = self.last_event.is_some_and(
+            |last| now.saturating_duration_since(last) <= self.config.repeat_gap
+        );
+
+        if !is_repeat {
+            self.sequence_start = Some(now);
+        }
+        self.last_event = Some(now);
+
+        if !self.config.enabled {
+            return 1;
+        }
+
+        let held = now.saturating_duration_since(self.sequence_start.unwrap_or(now));
+        if held < self.config.threshold {
+            return 1;
+        }
+
+        let ramp_ms = self.config.ramp_interval.as_millis().max(1);
+        let extra = ((held - self.config.threshold).as_millis() / ramp_ms) as usize;
+        (2 + extra).min(self.config.max_step.max(1))
+    }
+
+    /// Reset acceleration (e.g. when the key is released)
+    pub fn reset(&mut self) {
+        self.sequence_start = None;
+        self.last_event = None;
+    }
+}
+
+impl Default for MovementAccelerator {
+    fn default() -> Self {
+        Self::new(AccelerationConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_press_moves_one() {
+        let mut accelerator = MovementAccelerator::default();
+        assert_eq!(accelerator.step(Instant::now()), 1);
+    }
+
+    #[test]
+    fn test_sustained_repeat_accelerates() {
+        let mut accelerator = MovementAccelerator::default();
+        let start = Instant::now();
+
+        // Repeats every 30ms for 1.5s
+        let steps: Vec<usize> = (0..=50)
+            .map(|i| accelerator.step(start + Duration::from_millis(i * 30)))
+            .collect();
+
+        // Below the threshold every event moves by one
+        assert!(steps[..16].iter().all(|&s| s == 1));
+        // After the threshold movement accelerates, up to the cap
+        assert!(steps[17] > 1);
+        assert!(steps.last().copied().unwrap() > steps[17]);
+        assert!(steps.iter().all(|&s| s <= accelerator.config().max_step));
+    }
+
+    #[test]
+    fn test_gap_resets_acceleration() {
+        let mut accelerator = MovementAccelerator::default();
+        let start = Instant::now();
+
+        for i in 0..=30 {
+            accelerator.step(start + Duration::from_millis(i * 30));
+        }
+        let after_pause = accelerator.step(start + Duration::from_millis(2000));
+        assert_eq!(after_pause, 1);
+    }
+
+    #[test]
+    fn test_disabled_always_moves_one() {
+        let mut accelerator = MovementAccelerator::new(AccelerationConfig {
+            enabled: false,
+            ..Default::default()
+        });
+        let start = Instant::now();
+
+        for i in 0..=50 {
+            assert_eq!(accelerator.step(start + Duration::from_millis(i * 30)), 1);
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! Auto-closing and auto-surrounding of brackets and quotes
+
+use editor_core::{ Buffer, Position, Selection };
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Opening characters and the characters that close them
+///
+/// `$` delimits Typst math, so it pairs like a quote.
+const PAIRS: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('$', '$'),
+];
+
+/// Outcome of typing a character with auto-closing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditResult {
+    /// Selection after the edit
+    pub selection: Selection,
+    /// Whether the buffer was changed; typing over a closing character only
+    /// moves the cursor
+    pub edited: bool,
+}
+
+/// Type `typed` at `pos`, closing or surrounding where it makes sense
+///
+/// - With a non-empty `selection`, an opening character surrounds it and the
+///   surrounded text stays selected.
+/// - Typing a closing character right before the same character steps over
+///   it instead of inserting another one.
+/// - An opening character inserts its closing partner with the cursor
+///   between them, unless the next character is part of a word. Quotes and
+///   `$` also stay single after a word character, e.g. in `don"`.
+///
+/// Anything else is inserted as typed.
+pub fn auto_close(
+    buffer: &mut Buffer,
+    pos: Position,
+    typed: char,
+    selection: &Selection
+) -> editor_core::Result<EditResult> {
+    let pair = PAIRS.iter().find(|(open, _)| *open == typed).copied();
+
+    if let Some((open, close)) = pair {
+        if !selection.is_collapsed() {
+            let selection = buffer.wrap_selection(
+                selection,
+                &open.to_string(),
+                &close.to_string()
+            )?;
+            return Ok(EditResult { selection, edited: true });
+        }
+    }
+
+    let (before, after) = neighbors(buffer, pos)?;
+    let is_closer = PAIRS.iter().any(|(_, close)| *close == typed);
+    if is_closer && after == Some(typed) {
+        let cursor = Position::new(pos.line, pos.column + 1);
+        return Ok(EditResult { selection: Selection::collapsed(cursor), edited: false });
+    }
+
+    let is_word = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+    let text = match pair {
+        Some((open, close)) => {
+            let keep_single = is_word(after) || (open == close && is_word(before));
+            if keep_single { typed.to_string() } else { format!("{}{}", open, close) }
+        }
+        None => typed.to_string(),
+    };
+
+    buffer.insert(pos, &text)?;
+    let cursor = Position::new(pos.line, pos.column + 1);
+    Ok(EditResult { selection: Selection::collapsed(cursor), edited: true })
+}
+
+/// Characters just before and after a position on its line
+fn neighbors(buffer: &Buffer, pos: Position) -> editor_core::Result<(Option<char>, Option<char>)> {
+    let line = buffer.line(pos.line)?;
+    let content = line.trim_end_matches(['\n', '\r']);
+    let graphemes: Vec<&str> = content.graphemes(true).collect();
+
+    let before = pos.column
+        .checked_sub(1)
+        .and_then(|column| graphemes.get(column))
+        .and_then(|grapheme| grapheme.chars().last());
+    let after = graphemes.get(pos.column).and_then(|grapheme| grapheme.chars().next());
+    Ok((before, after))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::BufferId;
+
+    fn type_char(buffer: &mut Buffer, pos: Position, typed: char) -> EditResult {
+        auto_close(buffer, pos, typed, &Selection::collapsed(pos)).unwrap()
+    }
+
+    #[test]
+    fn test_insert_pair_between() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "#f");
+        let result = type_char(&mut buffer, Position::new(0, 2), '(');
+        assert_eq!(buffer.text(), "#f()");
+        assert_eq!(result.selection, Selection::collapsed(Position::new(0, 3)));
+
+        let result = type_char(&mut buffer, Position::new(0, 3), '$');
+        assert_eq!(buffer.text(), "#f($$)");
+        assert_eq!(result.selection.cursor.position, Position::new(0, 4));
+
+        // Before a word, and quotes after a word, stay single
+        let mut buffer = Buffer::from_text(BufferId::new(1), "word");
+        type_char(&mut buffer, Position::new(0, 0), '[');
+        type_char(&mut buffer, Position::new(0, 5), '"');
+        assert_eq!(buffer.text(), "[word\"");
+    }
+
+    #[test]
+    fn test_type_over_closing_char() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "");
+        type_char(&mut buffer, Position::new(0, 0), '"');
+        assert_eq!(buffer.text(), "\"\"");
+
+        let result = type_char(&mut buffer, Position::new(0, 1), '"');
+        assert!(!result.edited);
+        assert_eq!(buffer.text(), "\"\"");
+        assert_eq!(result.selection.cursor.position, Position::new(0, 2));
+
+        let mut buffer = Buffer::from_text(BufferId::new(1), "(x)");
+        let result = type_char(&mut buffer, Position::new(0, 2), ')');
+        assert!(!result.edited);
+        assert_eq!(buffer.text(), "(x)");
+    }
+
+    #[test]
+    fn test_surround_selection() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "a + b");
+        let selection = Selection::new(Position::new(0, 0), Position::new(0, 5));
+
+        let result = auto_close(&mut buffer, Position::new(0, 5), '$', &selection).unwrap();
+        assert_eq!(buffer.text(), "$a + b$");
+        assert_eq!(result.selection, Selection::new(Position::new(0, 1), Position::new(0, 6)));
+
+        let result = auto_close(&mut buffer, Position::new(0, 6), '[', &result.selection).unwrap();
+        assert_eq!(buffer.text(), "$[a + b]$");
+        assert!(result.edited);
+    }
+}
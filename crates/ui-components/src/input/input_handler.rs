@@ -2,21 +2,60 @@
 //!
 //! Phase 3.4: Input Handling
 
+use crate::decorations::{ InlineDecoration, InlineDecorationKind };
+use editor_core::{ Buffer, Position };
 use gpui::*;
+use super::key_bindings::{ Action, KeyBindings };
 
 /// Input handler for the editor
 pub struct InputHandler {
-    // TODO: Implement input handler state
+    bindings: KeyBindings,
+    ime: ImeState,
 }
 
 impl InputHandler {
     pub fn new() -> Self {
-        Self {}
+        Self::with_bindings(KeyBindings::load_defaults())
+    }
+
+    /// Create an input handler resolving keys through `bindings`
+    pub fn with_bindings(bindings: KeyBindings) -> Self {
+        Self {
+            bindings,
+            ime: ImeState::default(),
+        }
+    }
+
+    /// Key bindings used to resolve keyboard events
+    pub fn bindings(&self) -> &KeyBindings {
+        &self.bindings
+    }
+
+    /// Mutable access to the key bindings, e.g. to register user overrides
+    pub fn bindings_mut(&mut self) -> &mut KeyBindings {
+        &mut self.bindings
     }
 
     /// Handle keyboard input
-    pub fn handle_keyboard_event(&mut self, _event: &KeyDownEvent) {
-        todo!("Implement keyboard event handling")
+    ///
+    /// Bound keys resolve to their action. Unbound keys producing printable
+    /// text without ctrl, platform or function modifiers resolve to
+    /// [`Action::Insert`]; anything else is unhandled.
+    pub fn handle_keyboard_event(&mut self, event: &KeyDownEvent) -> Option<Action> {
+        if let Some(action) = self.bindings.find_action(event) {
+            return Some(action.clone());
+        }
+
+        let keystroke = &event.keystroke;
+        let modifiers = &keystroke.modifiers;
+        if modifiers.control || modifiers.platform || modifiers.function {
+            return None;
+        }
+
+        keystroke.key_char
+            .as_ref()
+            .filter(|text| !text.is_empty() && !text.chars().any(char::is_control))
+            .map(|text| Action::Insert(text.clone()))
     }
 
     /// Handle mouse input
@@ -25,8 +64,72 @@ impl InputHandler {
     }
 
     /// Handle text input (from IME or direct)
-    pub fn handle_text_input(&mut self, _text: &str) {
-        todo!("Implement text input handling")
+    ///
+    /// Committed text replaces any composition in progress, whose preedit is
+    /// discarded.
+    pub fn handle_text_input(&mut self, text: &str) -> Option<Action> {
+        self.ime.reset();
+        (!text.is_empty()).then(|| Action::Insert(text.to_string()))
+    }
+
+    /// Current IME state
+    pub fn ime_state(&self) -> &ImeState {
+        &self.ime
+    }
+
+    /// Update the IME preedit text
+    ///
+    /// `cursor` is a byte offset into `text`, clamped to a character
+    /// boundary. The buffer is not touched until the composition is
+    /// committed; an empty preedit ends the composition.
+    pub fn set_composition(&mut self, text: &str, cursor: usize) {
+        if text.is_empty() {
+            self.ime.reset();
+            return;
+        }
+
+        let mut cursor = cursor.min(text.len());
+        while !text.is_char_boundary(cursor) {
+            cursor -= 1;
+        }
+
+        self.ime.composing = true;
+        self.ime.composition = text.to_string();
+        self.ime.cursor_pos = cursor;
+    }
+
+    /// Insert the composed text at `pos` and end the composition
+    ///
+    /// The text is inserted as a single undo step. Returns the cursor
+    /// position after the inserted text, or `pos` if nothing was composing.
+    pub fn commit_composition(
+        &mut self,
+        buffer: &mut Buffer,
+        pos: Position
+    ) -> editor_core::Result<Position> {
+        if !self.ime.composing {
+            return Ok(pos);
+        }
+
+        let cursor = buffer.paste(pos, &self.ime.composition)?;
+        self.ime.reset();
+        Ok(cursor)
+    }
+
+    /// Discard the composition without touching the buffer
+    pub fn cancel_composition(&mut self) {
+        self.ime.reset();
+    }
+
+    /// Inline decoration showing the preedit at byte offset `at`
+    pub fn preedit_decoration(&self, at: usize) -> Option<InlineDecoration> {
+        self.ime.composing.then(|| InlineDecoration {
+            range: at..at,
+            kind: InlineDecorationKind::Preedit {
+                text: self.ime.composition.clone(),
+                cursor: self.ime.cursor_pos,
+            },
+        })
     }
 }
 
@@ -37,12 +140,20 @@ impl Default for InputHandler {
 }
 
 /// IME (Input Method Editor) state for CJK input
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ImeState {
     pub composing: bool,
     pub composition: String,
+    /// Caret position as a byte offset into `composition`
     pub cursor_pos: usize,
 }
 
+impl ImeState {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
 /// Mouse click type
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ClickType {
@@ -57,3 +168,109 @@ pub struct HoverState {
     pub position: Point<Pixels>,
     pub start_time: std::time::Instant,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::key_bindings::{ KeyBinding, Modifiers as BindingModifiers };
+    use editor_core::BufferId;
+
+    fn key_event(key: &str, key_char: Option<&str>, modifiers: Modifiers) -> KeyDownEvent {
+        KeyDownEvent {
+            keystroke: Keystroke {
+                modifiers,
+                key: key.to_string(),
+                key_char: key_char.map(str::to_string),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn handler() -> InputHandler {
+        let mut bindings = KeyBindings::new();
+        let ctrl = BindingModifiers { ctrl: true, ..Default::default() };
+        bindings.register(KeyBinding::new("c", ctrl), Action::Copy);
+        bindings.register(
+            KeyBinding::new("z", BindingModifiers { shift: true, ..ctrl }),
+            Action::Redo
+        );
+        InputHandler::with_bindings(bindings)
+    }
+
+    #[test]
+    fn test_ctrl_c_maps_to_copy() {
+        let event = key_event("c", None, Modifiers { control: true, ..Default::default() });
+        assert_eq!(handler().handle_keyboard_event(&event), Some(Action::Copy));
+    }
+
+    #[test]
+    fn test_ctrl_shift_z_maps_to_redo() {
+        let event = key_event("z", None, Modifiers {
+            control: true,
+            shift: true,
+            ..Default::default()
+        });
+        assert_eq!(handler().handle_keyboard_event(&event), Some(Action::Redo));
+    }
+
+    #[test]
+    fn test_plain_letter_inserts_text() {
+        let mut handler = handler();
+
+        let event = key_event("a", Some("a"), Modifiers::default());
+        assert_eq!(handler.handle_keyboard_event(&event), Some(Action::Insert("a".into())));
+
+        let shifted = key_event("a", Some("A"), Modifiers { shift: true, ..Default::default() });
+        assert_eq!(handler.handle_keyboard_event(&shifted), Some(Action::Insert("A".into())));
+
+        // Unbound chords never insert text
+        let chord = key_event("q", Some("q"), Modifiers { control: true, ..Default::default() });
+        assert_eq!(handler.handle_keyboard_event(&chord), None);
+    }
+
+    #[test]
+    fn test_composition_commits_as_one_undo_step() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "ab");
+        let mut handler = InputHandler::new();
+        buffer.insert(Position::new(0, 2), "c").unwrap();
+
+        handler.set_composition("n", 1);
+        handler.set_composition("に", 3);
+        handler.set_composition("にほ", 6);
+        handler.set_composition("日本", 6);
+        assert!(handler.ime_state().composing);
+        assert_eq!(buffer.text(), "abc");
+
+        let decoration = handler.preedit_decoration(3).unwrap();
+        assert_eq!(decoration.range, 3..3);
+        assert!(
+            matches!(decoration.kind, InlineDecorationKind::Preedit { ref text, cursor: 6 } if text == "日本")
+        );
+
+        let cursor = handler.commit_composition(&mut buffer, Position::new(0, 3)).unwrap();
+        assert_eq!(buffer.text(), "abc日本");
+        assert_eq!(cursor, Position::new(0, 5));
+        assert_eq!(handler.ime_state(), &ImeState::default());
+        assert!(handler.preedit_decoration(3).is_none());
+
+        // The composition undoes on its own, leaving the earlier typing
+        buffer.undo().unwrap();
+        assert_eq!(buffer.text(), "abc");
+    }
+
+    #[test]
+    fn test_cancelled_composition_leaves_buffer_untouched() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "ab");
+        let mut handler = InputHandler::new();
+
+        handler.set_composition("ㅎ", 3);
+        handler.set_composition("한", 3);
+        handler.cancel_composition();
+
+        assert!(!handler.ime_state().composing);
+        let cursor = handler.commit_composition(&mut buffer, Position::new(0, 2)).unwrap();
+        assert_eq!(cursor, Position::new(0, 2));
+        assert_eq!(buffer.text(), "ab");
+        assert!(!buffer.can_undo());
+    }
+}
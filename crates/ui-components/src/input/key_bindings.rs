@@ -5,6 +5,7 @@
 use gpui::*;
 use serde::{ Deserialize, Serialize };
 use std::collections::HashMap;
+use std::path::Path;
 
 /// Key binding manager
 pub struct KeyBindings {
@@ -20,17 +21,173 @@ impl KeyBindings {
 
     /// Load default key bindings for the current platform
     pub fn load_defaults() -> Self {
-        todo!("Implement default key bindings")
+        Self::defaults_for(cfg!(target_os = "macos"))
     }
 
-    /// Register a key binding
-    pub fn register(&mut self, _binding: KeyBinding, _action: Action) {
-        todo!("Implement key binding registration")
+    /// Default key bindings for macOS or for other platforms
+    ///
+    /// Shortcuts use cmd on macOS and ctrl elsewhere; word movement uses
+    /// alt on macOS and ctrl elsewhere.
+    pub fn defaults_for(macos: bool) -> Self {
+        let none = Modifiers::default();
+        let shift = Modifiers { shift: true, ..none };
+        let primary = if macos {
+            Modifiers { meta: true, ..none }
+        } else {
+            Modifiers { ctrl: true, ..none }
+        };
+        let primary_shift = Modifiers { shift: true, ..primary };
+        let word = if macos {
+            Modifiers { alt: true, ..none }
+        } else {
+            Modifiers { ctrl: true, ..none }
+        };
+
+        let mut bindings = Self::new();
+        let mut bind = |key: &str, modifiers: Modifiers, action: Action| {
+            bindings.register(KeyBinding::new(key, modifiers), action);
+        };
+
+        // Cursor movement and selection
+        bind("left", none, Action::MoveLeft);
+        bind("right", none, Action::MoveRight);
+        bind("up", none, Action::MoveUp);
+        bind("down", none, Action::MoveDown);
+        bind("left", shift, Action::SelectLeft);
+        bind("right", shift, Action::SelectRight);
+        bind("up", shift, Action::SelectUp);
+        bind("down", shift, Action::SelectDown);
+        bind("left", word, Action::MoveWordLeft);
+        bind("right", word, Action::MoveWordRight);
+        bind("home", none, Action::MoveLineStart);
+        bind("end", none, Action::MoveLineEnd);
+        bind("pageup", none, Action::MovePageUp);
+        bind("pagedown", none, Action::MovePageDown);
+        if macos {
+            bind("left", primary, Action::MoveLineStart);
+            bind("right", primary, Action::MoveLineEnd);
+            bind("up", primary, Action::MoveDocumentStart);
+            bind("down", primary, Action::MoveDocumentEnd);
+        } else {
+            bind("home", primary, Action::MoveDocumentStart);
+            bind("end", primary, Action::MoveDocumentEnd);
+        }
+        bind("a", primary, Action::SelectAll);
+
+        // Editing
+        bind("backspace", none, Action::Backspace);
+        bind("delete", none, Action::Delete);
+        bind("backspace", word, Action::DeleteWord);
+        bind("k", primary_shift, Action::DeleteLine);
+        bind("enter", none, Action::Newline);
+        bind("tab", none, Action::Indent);
+        bind("tab", shift, Action::Outdent);
+
+        // Clipboard and history
+        bind("c", primary, Action::Copy);
+        bind("x", primary, Action::Cut);
+        bind("v", primary, Action::Paste);
+        bind("z", primary, Action::Undo);
+        bind("z", primary_shift, Action::Redo);
+        if !macos {
+            bind("y", primary, Action::Redo);
+        }
+
+        // Files
+        bind("s", primary, Action::Save);
+        bind("s", primary_shift, Action::SaveAs);
+        bind("o", primary, Action::Open);
+        bind("w", primary, Action::Close);
+
+        // Search and multi-cursor
+        bind("f", primary, Action::Find);
+        bind("g", primary, Action::FindNext);
+        bind("g", primary_shift, Action::FindPrevious);
+        bind("h", primary, Action::Replace);
+        bind("d", primary, Action::SelectNextOccurrence);
+
+        bindings
+    }
+
+    /// Load key bindings from a JSON config file over the platform defaults
+    ///
+    /// The file holds a list of [`KeyBindingEntry`] values. Entries replace
+    /// the default action for their chord, and a `null` action unbinds it.
+    /// Two entries for the same chord are an error naming every conflict.
+    pub fn load_from_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let entries: Vec<KeyBindingEntry> = serde_json::from_str(&content)?;
+
+        let mut seen: HashMap<&KeyBinding, &Option<Action>> = HashMap::new();
+        let mut conflicts = Vec::new();
+        for entry in &entries {
+            if let Some(previous) = seen.insert(&entry.binding, &entry.action) {
+                conflicts.push(
+                    format!("{} is bound to both {:?} and {:?}", entry.binding, previous, entry.action)
+                );
+            }
+        }
+        if !conflicts.is_empty() {
+            return Err(
+                format!("Conflicting key bindings in {}: {}", path.display(), conflicts.join("; ")).into()
+            );
+        }
+
+        let mut bindings = Self::load_defaults();
+        for entry in entries {
+            match entry.action {
+                Some(action) => bindings.register(entry.binding, action),
+                None => {
+                    bindings.bindings.remove(&entry.binding);
+                }
+            }
+        }
+        Ok(bindings)
+    }
+
+    /// Save key bindings to a JSON config file
+    ///
+    /// Only differences from the platform defaults are written, with `null`
+    /// entries for unbound defaults, so the file loads back to the same map.
+    pub fn save_to_file(&self, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+        let defaults = Self::load_defaults();
+
+        let mut entries: Vec<KeyBindingEntry> = self.bindings
+            .iter()
+            .filter(|(binding, action)| defaults.lookup(binding) != Some(*action))
+            .map(|(binding, action)| KeyBindingEntry {
+                binding: binding.clone(),
+                action: Some(action.clone()),
+            })
+            .chain(
+                defaults.bindings
+                    .keys()
+                    .filter(|binding| !self.bindings.contains_key(*binding))
+                    .map(|binding| KeyBindingEntry {
+                        binding: binding.clone(),
+                        action: None,
+                    })
+            )
+            .collect();
+        entries.sort_by_key(|entry| entry.binding.to_string());
+
+        std::fs::write(path, serde_json::to_string_pretty(&entries)?)?;
+        Ok(())
+    }
+
+    /// Register a key binding, replacing any existing action for it
+    pub fn register(&mut self, binding: KeyBinding, action: Action) {
+        self.bindings.insert(binding, action);
     }
 
     /// Find action for a key event
-    pub fn find_action(&self, _event: &KeyDownEvent) -> Option<&Action> {
-        todo!("Implement key binding lookup")
+    pub fn find_action(&self, event: &KeyDownEvent) -> Option<&Action> {
+        self.lookup(&KeyBinding::from_keystroke(&event.keystroke))
+    }
+
+    /// Find action for a key binding
+    pub fn lookup(&self, binding: &KeyBinding) -> Option<&Action> {
+        self.bindings.get(binding)
     }
 }
 
@@ -44,11 +201,66 @@ impl Default for KeyBindings {
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 pub struct KeyBinding {
     pub key: String,
+    #[serde(default)]
     pub modifiers: Modifiers,
 }
 
+impl KeyBinding {
+    pub fn new(key: impl Into<String>, modifiers: Modifiers) -> Self {
+        Self {
+            key: key.into(),
+            modifiers,
+        }
+    }
+
+    /// Build a binding from a gpui keystroke
+    ///
+    /// Key names are lowercased so `"Z"` and `"z"` bind alike; shift is
+    /// carried by the modifiers. The platform key (cmd on macOS, super
+    /// elsewhere) maps to `meta`.
+    pub fn from_keystroke(keystroke: &Keystroke) -> Self {
+        let modifiers = &keystroke.modifiers;
+        Self {
+            key: keystroke.key.to_lowercase(),
+            modifiers: Modifiers {
+                ctrl: modifiers.control,
+                alt: modifiers.alt,
+                shift: modifiers.shift,
+                meta: modifiers.platform,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let modifiers = [
+            (self.modifiers.ctrl, "ctrl"),
+            (self.modifiers.alt, "alt"),
+            (self.modifiers.shift, "shift"),
+            (self.modifiers.meta, "meta"),
+        ];
+        for (_, name) in modifiers.iter().filter(|(held, _)| *held) {
+            write!(f, "{}-", name)?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+/// One entry of a key binding config file
+///
+/// Serialized as `{ "key": ..., "modifiers": {...}, "action": ... }`; a
+/// `null` action unbinds the chord.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct KeyBindingEntry {
+    #[serde(flatten)]
+    pub binding: KeyBinding,
+    pub action: Option<Action>,
+}
+
 /// Keyboard modifiers
-#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, Hash, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Modifiers {
     pub ctrl: bool,
     pub alt: bool,
@@ -118,3 +330,89 @@ pub enum Action {
     // Custom action
     Custom(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctrl() -> Modifiers {
+        Modifiers { ctrl: true, ..Default::default() }
+    }
+
+    fn cmd() -> Modifiers {
+        Modifiers { meta: true, ..Default::default() }
+    }
+
+    #[test]
+    fn test_defaults_bind_save() {
+        let bindings = KeyBindings::load_defaults();
+        let primary = if cfg!(target_os = "macos") { cmd() } else { ctrl() };
+
+        assert_eq!(bindings.lookup(&KeyBinding::new("s", primary)), Some(&Action::Save));
+    }
+
+    #[test]
+    fn test_copy_shortcut_differs_on_macos() {
+        let macos = KeyBindings::defaults_for(true);
+        let other = KeyBindings::defaults_for(false);
+
+        assert_eq!(macos.lookup(&KeyBinding::new("c", cmd())), Some(&Action::Copy));
+        assert_eq!(macos.lookup(&KeyBinding::new("c", ctrl())), None);
+        assert_eq!(other.lookup(&KeyBinding::new("c", ctrl())), Some(&Action::Copy));
+        assert_eq!(other.lookup(&KeyBinding::new("c", cmd())), None);
+    }
+
+    #[test]
+    fn test_config_override_replaces_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        let primary = KeyBindings::load_defaults()
+            .bindings
+            .iter()
+            .find(|(_, action)| **action == Action::Save)
+            .map(|(binding, _)| binding.modifiers)
+            .unwrap();
+        let config = serde_json::json!([
+            { "key": "s", "modifiers": primary, "action": { "Custom": "save-all" } },
+            { "key": "f", "modifiers": primary, "action": null },
+            { "key": "f5", "action": "Save" }
+        ]);
+        std::fs::write(&path, config.to_string()).unwrap();
+
+        let bindings = KeyBindings::load_from_file(&path).unwrap();
+        assert_eq!(
+            bindings.lookup(&KeyBinding::new("s", primary)),
+            Some(&Action::Custom("save-all".into()))
+        );
+        assert_eq!(bindings.lookup(&KeyBinding::new("f", primary)), None);
+        assert_eq!(bindings.lookup(&KeyBinding::new("f5", Modifiers::default())), Some(&Action::Save));
+        assert_eq!(bindings.lookup(&KeyBinding::new("c", primary)), Some(&Action::Copy));
+    }
+
+    #[test]
+    fn test_config_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        let mut bindings = KeyBindings::load_defaults();
+        bindings.register(KeyBinding::new("f6", ctrl()), Action::AddCursor);
+        bindings.bindings.remove(&KeyBinding::new("tab", Modifiers::default()));
+
+        bindings.save_to_file(&path).unwrap();
+        let loaded = KeyBindings::load_from_file(&path).unwrap();
+        assert_eq!(loaded.bindings, bindings.bindings);
+    }
+
+    #[test]
+    fn test_config_reports_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        let config = serde_json::json!([
+            { "key": "f5", "action": "Save" },
+            { "key": "f5", "action": "Open" }
+        ]);
+        std::fs::write(&path, config.to_string()).unwrap();
+
+        let error = KeyBindings::load_from_file(&path).err().unwrap().to_string();
+        assert!(error.contains("f5 is bound to both"));
+    }
+}
@@ -4,6 +4,14 @@
 
 pub mod input_handler;
 pub mod key_bindings;
+pub mod acceleration;
+pub mod vim;
+pub mod auto_close;
+pub mod on_type;
 
 pub use input_handler::{ InputHandler, ImeState, ClickType, HoverState };
-pub use key_bindings::{ KeyBindings, KeyBinding, Action, Modifiers };
+pub use key_bindings::{ KeyBindings, KeyBinding, KeyBindingEntry, Action, Modifiers };
+pub use acceleration::{ MovementAccelerator, AccelerationConfig };
+pub use vim::{ VimState, VimMode };
+pub use auto_close::{ auto_close, EditResult };
+pub use on_type::OnTypeFormatter;
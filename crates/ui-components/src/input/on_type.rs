@@ -0,0 +1,195 @@
+//! Formatting triggered by typing specific characters
+
+use editor_core::{ Buffer, Position, TextEdit };
+
+/// Adjusts the text around a character as it is typed
+///
+/// Currently re-indents a closing bracket typed at the start of a line to
+/// the indentation of the line holding its opening bracket.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OnTypeFormatter;
+
+impl OnTypeFormatter {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Type `typed` at `pos`, along with any formatting it triggers
+    ///
+    /// If formatting applies, the character and the formatting edits are
+    /// applied as one undo step and the formatting edits are returned, in
+    /// positions from before the keystroke. Otherwise the buffer is left
+    /// untouched and `None` is returned, so the caller inserts the character
+    /// as usual.
+    pub fn on_type(
+        &self,
+        buffer: &mut Buffer,
+        pos: Position,
+        typed: char
+    ) -> Option<Vec<TextEdit>> {
+        let edit = Self::reindent_closing(buffer, pos, typed)?;
+
+        // The keystroke lands at the end of the re-indented range, so both
+        // go in one replacement
+        let text = format!("{}{}", edit.text, typed);
+        buffer.replace_ranges(vec![(edit.range.clone(), text)]).ok()?;
+        Some(vec![edit])
+    }
+
+    /// Edit re-indenting a closing bracket typed after only whitespace
+    fn reindent_closing(buffer: &Buffer, pos: Position, typed: char) -> Option<TextEdit> {
+        let open = match typed {
+            ']' => '[',
+            '}' => '{',
+            ')' => '(',
+            _ => {
+                return None;
+            }
+        };
+
+        let line = buffer.line(pos.line).ok()?;
+        let before: String = line.chars().take(pos.column).collect();
+        let only_indent = before.chars().all(|c| c == ' ' || c == '\t');
+        if before.chars().count() != pos.column || !only_indent {
+            return None;
+        }
+
+        let open_line = unclosed_bracket_line(buffer, pos.line, open, typed)?;
+        let indent: String = buffer
+            .line(open_line)
+            .ok()?
+            .chars()
+            .take_while(|c| *c == ' ' || *c == '\t')
+            .collect();
+        if indent == before {
+            return None;
+        }
+
+        Some(TextEdit { range: Position::new(pos.line, 0)..pos, text: indent })
+    }
+}
+
+/// Lexical context of the bracket scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Span {
+    Text,
+    String,
+    Raw,
+    LineComment,
+    /// Block comments nest, so this tracks the depth
+    BlockComment(usize),
+}
+
+/// Line of the last unclosed `open` in the lines before `line`
+///
+/// Brackets in strings, raw text, comments and escapes are not counted.
+fn unclosed_bracket_line(buffer: &Buffer, line: usize, open: char, close: char) -> Option<usize> {
+    let mut unclosed = Vec::new();
+    let mut span = Span::Text;
+    for (line_idx, text) in buffer.lines_in_range(0, line).enumerate() {
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            let next = chars.peek().copied();
+            span = match (span, c) {
+                (Span::Text | Span::String, '\\') => {
+                    chars.next();
+                    span
+                }
+                (Span::Text, '"') => Span::String,
+                (Span::Text, '`') => Span::Raw,
+                (Span::Text, '/') if next == Some('/') => Span::LineComment,
+                (Span::Text, '/') if next == Some('*') => {
+                    chars.next();
+                    Span::BlockComment(1)
+                }
+                (Span::Text, c) => {
+                    if c == open {
+                        unclosed.push(line_idx);
+                    } else if c == close {
+                        unclosed.pop();
+                    }
+                    Span::Text
+                }
+                (Span::String, '"') | (Span::Raw, '`') => Span::Text,
+                (Span::BlockComment(depth), '*') if next == Some('/') => {
+                    chars.next();
+                    if depth == 1 { Span::Text } else { Span::BlockComment(depth - 1) }
+                }
+                (Span::BlockComment(depth), '/') if next == Some('*') => {
+                    chars.next();
+                    Span::BlockComment(depth + 1)
+                }
+                (span, _) => span,
+            };
+        }
+        if span == Span::LineComment {
+            span = Span::Text;
+        }
+    }
+    unclosed.pop()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::BufferId;
+
+    #[test]
+    fn test_closing_bracket_reindents() {
+        let text = "#block[\n  #list(\n    [a],\n        ";
+        let mut buffer = Buffer::from_text(BufferId::new(1), text);
+        let formatter = OnTypeFormatter::new();
+
+        let edits = formatter.on_type(&mut buffer, Position::new(3, 8), ')').unwrap();
+        assert_eq!(edits, vec![TextEdit {
+            range: Position::new(3, 0)..Position::new(3, 8),
+            text: "  ".to_string(),
+        }]);
+        assert_eq!(buffer.text(), "#block[\n  #list(\n    [a],\n  )");
+
+        // Nested pairs are skipped when finding the opening bracket
+        buffer.insert(Position::new(3, 3), "\n\t").unwrap();
+        formatter.on_type(&mut buffer, Position::new(4, 1), ']').unwrap();
+        assert_eq!(buffer.text(), "#block[\n  #list(\n    [a],\n  )\n]");
+
+        // The re-indent and the bracket are undone together
+        buffer.undo().unwrap();
+        assert_eq!(buffer.text(), "#block[\n  #list(\n    [a],\n  )\n\t");
+    }
+
+    #[test]
+    fn test_no_reindent_needed() {
+        let formatter = OnTypeFormatter::new();
+
+        // Text before the cursor, already correct indentation, or the opening
+        // bracket on the same line
+        let mut buffer = Buffer::from_text(BufferId::new(1), "#f(\n  x");
+        assert!(formatter.on_type(&mut buffer, Position::new(1, 3), ')').is_none());
+        let mut buffer = Buffer::from_text(BufferId::new(1), "  #f(\n  ");
+        assert!(formatter.on_type(&mut buffer, Position::new(1, 2), ')').is_none());
+        let mut buffer = Buffer::from_text(BufferId::new(1), "#f(");
+        assert!(formatter.on_type(&mut buffer, Position::new(0, 3), ')').is_none());
+
+        // Unmatched closers and other characters
+        let mut buffer = Buffer::from_text(BufferId::new(1), "x\n  ");
+        assert!(formatter.on_type(&mut buffer, Position::new(1, 2), ']').is_none());
+        assert!(formatter.on_type(&mut buffer, Position::new(1, 2), 'a').is_none());
+        assert_eq!(buffer.text(), "x\n  ");
+    }
+
+    #[test]
+    fn test_brackets_in_strings_and_comments_are_skipped() {
+        let formatter = OnTypeFormatter::new();
+        let text = concat!(
+            "#f(\n",
+            "  \"(\", `(`, \\(\n",
+            "  // (\n",
+            "  /* ( /* ) */ ) */\n",
+            "  #g(x)\n",
+            "    "
+        );
+        let mut buffer = Buffer::from_text(BufferId::new(1), text);
+        formatter.on_type(&mut buffer, Position::new(5, 4), ')').unwrap();
+        assert!(buffer.text().ends_with("\n)"));
+    }
+}
@@ -0,0 +1,538 @@
+//! Vim-style modal editing on top of the input handler
+//!
+//! Phase 3.4: Input Handling
+
+use editor_core::buffer::is_word_grapheme;
+use editor_core::{ Buffer, Position, Selection, SelectionSet };
+use gpui::*;
+use unicode_segmentation::UnicodeSegmentation;
+use super::input_handler::InputHandler;
+use super::key_bindings::Action;
+
+/// Vim editing mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VimMode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+}
+
+/// Operator waiting for a motion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Yank,
+    Change,
+}
+
+/// Cursor motion
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordForward,
+    WordBackward,
+    LineStart,
+    LineEnd,
+    /// `gg`: first line, or line `count`
+    DocumentStart,
+    /// `G`: last line, or line `count`
+    DocumentEnd,
+    /// Repeated operator (`dd`): the current line and `count - 1` below
+    CurrentLine,
+}
+
+impl Motion {
+    /// Whether an operator applied with this motion acts on whole lines
+    fn is_linewise(&self) -> bool {
+        matches!(
+            self,
+            Motion::Up |
+                Motion::Down |
+                Motion::DocumentStart |
+                Motion::DocumentEnd |
+                Motion::CurrentLine
+        )
+    }
+}
+
+/// Modal key interpreter
+///
+/// Motions move the primary selection directly. Operators select the text
+/// they act on and return the actions that consume the selection (`Cut` for
+/// `d` and `c`, `Copy` for `y`), which the editor applies in order. In insert
+/// mode keys go through a regular [`InputHandler`].
+pub struct VimState {
+    mode: VimMode,
+    /// Count typed before the next command
+    count: Option<usize>,
+    /// Operator waiting for a motion, with the count typed before it
+    operator: Option<(Operator, Option<usize>)>,
+    /// Whether `g` was typed and the next key completes a `g` command
+    pending_g: bool,
+    /// Handler for keys typed in insert mode
+    insert: InputHandler,
+}
+
+impl VimState {
+    pub fn new() -> Self {
+        Self::with_input_handler(InputHandler::new())
+    }
+
+    /// Create a vim state using `insert` for keys typed in insert mode
+    pub fn with_input_handler(insert: InputHandler) -> Self {
+        Self {
+            mode: VimMode::Normal,
+            count: None,
+            operator: None,
+            pending_g: false,
+            insert,
+        }
+    }
+
+    /// Current mode
+    pub fn mode(&self) -> VimMode {
+        self.mode
+    }
+
+    /// Interpret a key press
+    pub fn handle_key(
+        &mut self,
+        event: &KeyDownEvent,
+        buffer: &Buffer,
+        selections: &mut SelectionSet
+    ) -> Vec<Action> {
+        let keystroke = &event.keystroke;
+        if keystroke.key == "escape" {
+            self.escape(buffer, selections);
+            return Vec::new();
+        }
+
+        if self.mode == VimMode::Insert {
+            return self.insert.handle_keyboard_event(event).into_iter().collect();
+        }
+
+        let modifiers = &keystroke.modifiers;
+        if modifiers.control || modifiers.platform || modifiers.function {
+            self.reset_pending();
+            return Vec::new();
+        }
+
+        let key = keystroke.key_char.as_deref().unwrap_or(&keystroke.key);
+        self.handle_command_key(key, buffer, selections)
+    }
+
+    fn handle_command_key(
+        &mut self,
+        key: &str,
+        buffer: &Buffer,
+        selections: &mut SelectionSet
+    ) -> Vec<Action> {
+        // A selection left behind by a yank collapses to its start
+        if self.mode == VimMode::Normal && !selections.primary().is_collapsed() {
+            let (start, _) = selections.primary().range();
+            *selections.primary_mut() = Selection::collapsed(start);
+        }
+
+        if let Some(digit) = key.parse::<usize>().ok().filter(|_| key.len() == 1) {
+            if digit != 0 || self.count.is_some() {
+                self.count = Some(self.count.unwrap_or(0) * 10 + digit);
+                return Vec::new();
+            }
+        }
+
+        if std::mem::take(&mut self.pending_g) {
+            return match key {
+                "g" => self.apply_motion(Motion::DocumentStart, buffer, selections),
+                _ => {
+                    self.reset_pending();
+                    Vec::new()
+                }
+            };
+        }
+
+        match key {
+            "h" => self.apply_motion(Motion::Left, buffer, selections),
+            "l" => self.apply_motion(Motion::Right, buffer, selections),
+            "j" => self.apply_motion(Motion::Down, buffer, selections),
+            "k" => self.apply_motion(Motion::Up, buffer, selections),
+            "w" => self.apply_motion(Motion::WordForward, buffer, selections),
+            "b" => self.apply_motion(Motion::WordBackward, buffer, selections),
+            "0" => self.apply_motion(Motion::LineStart, buffer, selections),
+            "$" => self.apply_motion(Motion::LineEnd, buffer, selections),
+            "G" => self.apply_motion(Motion::DocumentEnd, buffer, selections),
+            "g" => {
+                self.pending_g = true;
+                Vec::new()
+            }
+            "d" => self.start_operator(Operator::Delete, buffer, selections),
+            "y" => self.start_operator(Operator::Yank, buffer, selections),
+            "c" => self.start_operator(Operator::Change, buffer, selections),
+            "i" if self.mode == VimMode::Normal && self.operator.is_none() => {
+                self.enter_insert();
+                Vec::new()
+            }
+            "a" if self.mode == VimMode::Normal && self.operator.is_none() => {
+                let position = selections.primary().cursor.position;
+                let column = (position.column + 1).min(line_len(buffer, position.line));
+                *selections.primary_mut() = Selection::collapsed(
+                    Position::new(position.line, column)
+                );
+                self.enter_insert();
+                Vec::new()
+            }
+            "v" if self.operator.is_none() => {
+                self.count = None;
+                if self.mode == VimMode::Visual {
+                    let cursor = selections.primary().cursor.position;
+                    *selections.primary_mut() = Selection::collapsed(cursor);
+                    self.mode = VimMode::Normal;
+                } else {
+                    self.mode = VimMode::Visual;
+                }
+                Vec::new()
+            }
+            _ => {
+                self.reset_pending();
+                Vec::new()
+            }
+        }
+    }
+
+    fn start_operator(
+        &mut self,
+        operator: Operator,
+        buffer: &Buffer,
+        selections: &mut SelectionSet
+    ) -> Vec<Action> {
+        if self.mode == VimMode::Visual {
+            // Visual selections include the character under the cursor
+            let (start, end) = selections.primary().range();
+            let end = Position::new(end.line, (end.column + 1).min(line_len(buffer, end.line)));
+            self.count = None;
+            self.mode = VimMode::Normal;
+            return self.apply_operator(operator, start, end, selections);
+        }
+
+        match self.operator {
+            Some((pending, _)) if pending == operator =>
+                self.apply_motion(Motion::CurrentLine, buffer, selections),
+            Some(_) => {
+                self.reset_pending();
+                Vec::new()
+            }
+            None => {
+                self.operator = Some((operator, self.count.take()));
+                Vec::new()
+            }
+        }
+    }
+
+    fn apply_motion(
+        &mut self,
+        motion: Motion,
+        buffer: &Buffer,
+        selections: &mut SelectionSet
+    ) -> Vec<Action> {
+        let operator = self.operator.take();
+        let count = match (operator.and_then(|(_, count)| count), self.count.take()) {
+            (None, None) => None,
+            (before, after) => Some(before.unwrap_or(1) * after.unwrap_or(1)),
+        };
+
+        let primary = selections.primary().clone();
+        let from = primary.cursor.position;
+        let target = motion_target(buffer, motion, from, count);
+
+        if let Some((operator, _)) = operator {
+            let (start, end) = if motion.is_linewise() {
+                line_range(buffer, from.line.min(target.line), from.line.max(target.line))
+            } else {
+                let (start, end) = if target < from { (target, from) } else { (from, target) };
+                // A motion ending at the start of a later line stops at the end of the line before
+                if end.line > start.line && end.column == 0 {
+                    (start, Position::new(end.line - 1, line_len(buffer, end.line - 1)))
+                } else {
+                    (start, end)
+                }
+            };
+            return self.apply_operator(operator, start, end, selections);
+        }
+
+        *selections.primary_mut() = match self.mode {
+            VimMode::Visual => Selection::new(primary.anchor, target),
+            _ => {
+                let last_column = line_len(buffer, target.line).saturating_sub(1);
+                Selection::collapsed(Position::new(target.line, target.column.min(last_column)))
+            }
+        };
+        Vec::new()
+    }
+
+    fn apply_operator(
+        &mut self,
+        operator: Operator,
+        start: Position,
+        end: Position,
+        selections: &mut SelectionSet
+    ) -> Vec<Action> {
+        *selections.primary_mut() = Selection::new(start, end);
+        match operator {
+            Operator::Delete => vec![Action::Cut],
+            Operator::Yank => vec![Action::Copy],
+            Operator::Change => {
+                self.enter_insert();
+                vec![Action::Cut]
+            }
+        }
+    }
+
+    fn escape(&mut self, buffer: &Buffer, selections: &mut SelectionSet) {
+        let cursor = selections.primary().cursor.position;
+        let cursor = match self.mode {
+            // Leaving insert mode steps back onto the last inserted character
+            VimMode::Insert => Position::new(cursor.line, cursor.column.saturating_sub(1)),
+            _ => cursor,
+        };
+        let last_column = line_len(buffer, cursor.line).saturating_sub(1);
+        *selections.primary_mut() = Selection::collapsed(
+            Position::new(cursor.line, cursor.column.min(last_column))
+        );
+
+        self.mode = VimMode::Normal;
+        self.reset_pending();
+    }
+
+    fn enter_insert(&mut self) {
+        self.mode = VimMode::Insert;
+        self.reset_pending();
+    }
+
+    fn reset_pending(&mut self) {
+        self.count = None;
+        self.operator = None;
+        self.pending_g = false;
+    }
+}
+
+impl Default for VimState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Position a motion moves to, repeated `count` times where that applies
+fn motion_target(
+    buffer: &Buffer,
+    motion: Motion,
+    from: Position,
+    count: Option<usize>
+) -> Position {
+    let times = count.unwrap_or(1);
+    let last = last_line(buffer);
+    // `gg` and `G` take a 1-based line number as their count
+    let counted_line = |default: usize| count.map_or(default, |line| line.saturating_sub(1)).min(last);
+
+    match motion {
+        Motion::Left => Position::new(from.line, from.column.saturating_sub(times)),
+        Motion::Right => {
+            let column = (from.column + times).min(line_len(buffer, from.line));
+            Position::new(from.line, column)
+        }
+        Motion::Up | Motion::Down => {
+            let line = if motion == Motion::Up {
+                from.line.saturating_sub(times)
+            } else {
+                (from.line + times).min(last)
+            };
+            Position::new(line, from.column.min(line_len(buffer, line)))
+        }
+        Motion::WordForward => (0..times).fold(from, |pos, _| next_word_start(buffer, pos)),
+        Motion::WordBackward => (0..times).fold(from, |pos, _| prev_word_start(buffer, pos)),
+        Motion::LineStart => Position::new(from.line, 0),
+        Motion::LineEnd => Position::new(from.line, line_len(buffer, from.line)),
+        Motion::DocumentStart => Position::new(counted_line(0), 0),
+        Motion::DocumentEnd => Position::new(counted_line(last), 0),
+        Motion::CurrentLine => Position::new((from.line + times - 1).min(last), 0),
+    }
+}
+
+/// Range covering whole lines `first..=last`, including one line break
+fn line_range(buffer: &Buffer, first: usize, last: usize) -> (Position, Position) {
+    if last + 1 < buffer.len_lines() {
+        (Position::new(first, 0), Position::new(last + 1, 0))
+    } else if first > 0 {
+        let start = Position::new(first - 1, line_len(buffer, first - 1));
+        (start, Position::new(last, line_len(buffer, last)))
+    } else {
+        (Position::new(first, 0), Position::new(last, line_len(buffer, last)))
+    }
+}
+
+/// Start of the next word, stopping at the end of the buffer
+fn next_word_start(buffer: &Buffer, from: Position) -> Position {
+    let mut pos = from;
+    loop {
+        let next = buffer.next_word_boundary(pos).unwrap_or(pos);
+        if next == pos {
+            return pos;
+        }
+        pos = next;
+        if is_word_start(buffer, pos) || (pos.column == 0 && line_len(buffer, pos.line) == 0) {
+            return pos;
+        }
+    }
+}
+
+/// Start of the previous word, stopping at the start of a line
+fn prev_word_start(buffer: &Buffer, from: Position) -> Position {
+    let mut pos = from;
+    loop {
+        let prev = buffer.prev_word_boundary(pos).unwrap_or(pos);
+        if prev == pos {
+            return pos;
+        }
+        pos = prev;
+        if is_word_start(buffer, pos) || pos.column == 0 {
+            return pos;
+        }
+    }
+}
+
+fn is_word_start(buffer: &Buffer, pos: Position) -> bool {
+    let is_word = is_word_grapheme;
+    let line = line_content(buffer, pos.line);
+    let graphemes: Vec<&str> = line.graphemes(true).collect();
+
+    match graphemes.get(pos.column) {
+        Some(grapheme) if is_word(grapheme) => {
+            pos.column == 0 || !is_word(graphemes[pos.column - 1])
+        }
+        _ => false,
+    }
+}
+
+/// Line text without its line break
+fn line_content(buffer: &Buffer, line: usize) -> String {
+    let mut content = buffer.line(line).unwrap_or_default();
+    let len = content.trim_end_matches(['\n', '\r']).len();
+    content.truncate(len);
+    content
+}
+
+/// Line length in graphemes, excluding the line break
+fn line_len(buffer: &Buffer, line: usize) -> usize {
+    line_content(buffer, line).graphemes(true).count()
+}
+
+/// Last line the cursor can reach; a trailing line break does not start a line
+fn last_line(buffer: &Buffer) -> usize {
+    let last = buffer.len_lines().saturating_sub(1);
+    if last > 0 && line_len(buffer, last) == 0 {
+        last - 1
+    } else {
+        last
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::BufferId;
+
+    fn key(text: &str) -> KeyDownEvent {
+        KeyDownEvent {
+            keystroke: Keystroke {
+                modifiers: Modifiers {
+                    shift: text.chars().any(char::is_uppercase),
+                    ..Default::default()
+                },
+                key: text.to_lowercase(),
+                key_char: Some(text.to_string()),
+            },
+            ..Default::default()
+        }
+    }
+
+    fn escape() -> KeyDownEvent {
+        KeyDownEvent {
+            keystroke: Keystroke {
+                modifiers: Modifiers::default(),
+                key: "escape".to_string(),
+                key_char: None,
+            },
+            ..Default::default()
+        }
+    }
+
+    fn type_keys(
+        vim: &mut VimState,
+        keys: &str,
+        buffer: &Buffer,
+        selections: &mut SelectionSet
+    ) -> Vec<Action> {
+        keys.chars()
+            .flat_map(|ch| vim.handle_key(&key(&ch.to_string()), buffer, selections))
+            .collect()
+    }
+
+    #[test]
+    fn test_dw_deletes_word_and_space() {
+        let buffer = Buffer::from_text(BufferId::new(1), "foo bar\nbaz");
+        let mut selections = SelectionSet::new(Selection::collapsed(Position::zero()));
+        let mut vim = VimState::new();
+
+        let actions = type_keys(&mut vim, "dw", &buffer, &mut selections);
+
+        assert_eq!(actions, vec![Action::Cut]);
+        assert_eq!(selections.primary().range(), (Position::new(0, 0), Position::new(0, 4)));
+        assert_eq!(vim.mode(), VimMode::Normal);
+
+        // On the last word of a line the line break is kept
+        let mut selections = SelectionSet::new(Selection::collapsed(Position::new(0, 4)));
+        type_keys(&mut vim, "dw", &buffer, &mut selections);
+        assert_eq!(selections.primary().range(), (Position::new(0, 4), Position::new(0, 7)));
+    }
+
+    #[test]
+    fn test_counted_motion() {
+        let buffer = Buffer::from_text(BufferId::new(1), "one\ntwo\nthree\nfour\nfive\n");
+        let mut selections = SelectionSet::new(Selection::collapsed(Position::new(0, 2)));
+        let mut vim = VimState::new();
+
+        assert!(type_keys(&mut vim, "3j", &buffer, &mut selections).is_empty());
+        assert_eq!(selections.primary().cursor.position, Position::new(3, 2));
+
+        // Counts stop at the last line
+        type_keys(&mut vim, "9j", &buffer, &mut selections);
+        assert_eq!(selections.primary().cursor.position, Position::new(4, 2));
+
+        type_keys(&mut vim, "gg", &buffer, &mut selections);
+        assert_eq!(selections.primary().cursor.position, Position::new(0, 0));
+    }
+
+    #[test]
+    fn test_enter_and_leave_insert_mode() {
+        let buffer = Buffer::from_text(BufferId::new(1), "abc");
+        let mut selections = SelectionSet::new(Selection::collapsed(Position::zero()));
+        let mut vim = VimState::new();
+
+        type_keys(&mut vim, "a", &buffer, &mut selections);
+        assert_eq!(vim.mode(), VimMode::Insert);
+        assert_eq!(selections.primary().cursor.position, Position::new(0, 1));
+
+        let actions = type_keys(&mut vim, "x", &buffer, &mut selections);
+        assert_eq!(actions, vec![Action::Insert("x".into())]);
+
+        vim.handle_key(&escape(), &buffer, &mut selections);
+        assert_eq!(vim.mode(), VimMode::Normal);
+        assert_eq!(selections.primary().cursor.position, Position::new(0, 0));
+
+        // In normal mode letters are commands, not text
+        assert!(type_keys(&mut vim, "l", &buffer, &mut selections).is_empty());
+        assert_eq!(selections.primary().cursor.position, Position::new(0, 1));
+    }
+}
@@ -16,12 +16,14 @@ pub mod rendering; // Phase 3.2: Text Rendering Pipeline
 pub mod syntax; // Phase 3.3: Syntax Highlighting
 pub mod input; // Phase 3.4: Input Handling
 pub mod decorations; // Phase 3.5: Decorations and Annotations
+pub mod commands;
+pub mod completion;
 
 // Re-export main components
 pub use editor_view::EditorView;
-pub use preview_pane::PreviewPane;
+pub use preview_pane::{ PreviewPane, PreviewStatus };
 pub use sidebar::Sidebar;
-pub use panels::Panel;
+pub use panels::{ Panel, PanelManager, DockPosition };
 
 pub use decorations::{
     DecorationManager,
@@ -33,5 +35,7 @@ pub use decorations::{
     HighlightKind,
 };
 pub use input::{ InputHandler, KeyBindings };
+pub use commands::{ Command, CommandRegistry };
+pub use completion::{ BuiltinCompleter, LabelCompleter, SymbolCompleter };
 pub use rendering::{ TextShaper, FontManager, LineLayout, Viewport };
 pub use syntax::{ SyntaxHighlighter, Theme, ThemeManager };
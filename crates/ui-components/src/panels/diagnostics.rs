@@ -0,0 +1,229 @@
+//! Problems panel listing compiler diagnostics
+
+use super::{ DockPosition, Panel };
+use std::cmp::Ordering;
+use typst_integration::diagnostics::{ Diagnostic, DiagnosticList, Severity, SourceLocation };
+
+/// Problems panel model
+///
+/// Entries are sorted by severity, then by file, line and column, with
+/// entries that have no location last. A severity filter narrows the list to
+/// a single severity.
+#[derive(Debug, Default)]
+pub struct DiagnosticsPanel {
+    diagnostics: DiagnosticList,
+    filter: Option<Severity>,
+    /// Index into the visible entries of the last navigation target
+    selected: Option<usize>,
+}
+
+impl DiagnosticsPanel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the listed diagnostics, clearing the navigation position
+    pub fn set_diagnostics(&mut self, diagnostics: DiagnosticList) {
+        self.diagnostics = diagnostics;
+        self.selected = None;
+    }
+
+    /// Get the listed diagnostics
+    pub fn diagnostics(&self) -> &DiagnosticList {
+        &self.diagnostics
+    }
+
+    /// Only show diagnostics of one severity
+    pub fn set_filter(&mut self, severity: Severity) {
+        self.filter = Some(severity);
+        self.selected = None;
+    }
+
+    /// Show diagnostics of every severity
+    pub fn show_all(&mut self) {
+        self.filter = None;
+        self.selected = None;
+    }
+
+    /// Get the active severity filter
+    pub fn filter(&self) -> Option<Severity> {
+        self.filter
+    }
+
+    /// Index of the selected entry in [`visible_entries`](Self::visible_entries)
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Diagnostics in display order, after filtering
+    pub fn visible_entries(&self) -> Vec<&Diagnostic> {
+        let mut entries: Vec<&Diagnostic> = self.diagnostics.diagnostics
+            .iter()
+            .filter(|diagnostic| self.filter.is_none_or(|severity| diagnostic.severity == severity))
+            .collect();
+        entries.sort_by(|a, b| compare_entries(a, b));
+        entries
+    }
+
+    /// Select the next entry with a location and return where to jump
+    ///
+    /// Wraps around to the first entry after the last one.
+    pub fn navigate_next(&mut self) -> Option<SourceLocation> {
+        let count = self.visible_entries().len();
+        let start = self.selected.map_or(0, |selected| selected + 1);
+        self.navigate((0..count).map(|step| (start + step) % count))
+    }
+
+    /// Select the previous entry with a location and return where to jump
+    ///
+    /// Wraps around to the last entry before the first one.
+    pub fn navigate_prev(&mut self) -> Option<SourceLocation> {
+        let count = self.visible_entries().len();
+        let start = self.selected.unwrap_or(0) + count;
+        self.navigate((1..=count).map(|step| (start - step) % count))
+    }
+
+    /// Select the first candidate index whose entry has a location
+    fn navigate(&mut self, candidates: impl Iterator<Item = usize>) -> Option<SourceLocation> {
+        let entries = self.visible_entries();
+        let (index, location) = candidates
+            .filter_map(|index| Some((index, entries[index].location.clone()?)))
+            .next()?;
+        self.selected = Some(index);
+        Some(location)
+    }
+}
+
+impl Panel for DiagnosticsPanel {
+    fn id(&self) -> &str {
+        "diagnostics"
+    }
+
+    fn title(&self) -> &str {
+        "Problems"
+    }
+
+    fn position(&self) -> DockPosition {
+        DockPosition::Bottom
+    }
+
+    fn render_lines(&self) -> Vec<String> {
+        self.visible_entries()
+            .into_iter()
+            .map(|diagnostic| {
+                let severity = match diagnostic.severity {
+                    Severity::Error => "error",
+                    Severity::Warning => "warning",
+                    Severity::Info => "info",
+                    Severity::Hint => "hint",
+                };
+                match &diagnostic.location {
+                    Some(location) =>
+                        format!(
+                            "{}: {} ({}:{}:{})",
+                            severity,
+                            diagnostic.message,
+                            location.file.display(),
+                            location.line,
+                            location.column
+                        ),
+                    None => format!("{}: {}", severity, diagnostic.message),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Most severe first
+fn severity_rank(severity: Severity) -> u8 {
+    match severity {
+        Severity::Error => 0,
+        Severity::Warning => 1,
+        Severity::Info => 2,
+        Severity::Hint => 3,
+    }
+}
+
+fn compare_entries(a: &Diagnostic, b: &Diagnostic) -> Ordering {
+    severity_rank(a.severity)
+        .cmp(&severity_rank(b.severity))
+        .then_with(|| match (&a.location, &b.location) {
+            (Some(a), Some(b)) => (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn at(line: usize) -> SourceLocation {
+        SourceLocation { file: PathBuf::from("main.typ"), line, column: 0 }
+    }
+
+    fn hint(message: &str) -> Diagnostic {
+        Diagnostic { severity: Severity::Hint, ..Diagnostic::warning(message.to_string()) }
+    }
+
+    fn panel() -> DiagnosticsPanel {
+        let mut list = DiagnosticList::new();
+        list.add(hint("hint 3").with_location(at(3)));
+        list.add(Diagnostic::warning("warning 9".to_string()).with_location(at(9)));
+        list.add(Diagnostic::error("error without location".to_string()));
+        list.add(Diagnostic::error("error 7".to_string()).with_location(at(7)));
+        list.add(Diagnostic::warning("warning 2".to_string()).with_location(at(2)));
+        list.add(Diagnostic::error("error 1".to_string()).with_location(at(1)));
+
+        let mut panel = DiagnosticsPanel::new();
+        panel.set_diagnostics(list);
+        panel
+    }
+
+    fn messages(panel: &DiagnosticsPanel) -> Vec<&str> {
+        panel
+            .visible_entries()
+            .into_iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_sorted_and_filtered_entries() {
+        let mut panel = panel();
+        assert_eq!(messages(&panel), vec![
+            "error 1",
+            "error 7",
+            "error without location",
+            "warning 2",
+            "warning 9",
+            "hint 3"
+        ]);
+
+        panel.set_filter(Severity::Warning);
+        assert_eq!(messages(&panel), vec!["warning 2", "warning 9"]);
+
+        panel.show_all();
+        assert_eq!(panel.visible_entries().len(), 6);
+    }
+
+    #[test]
+    fn test_navigation_skips_entries_without_location() {
+        let mut panel = panel();
+
+        assert_eq!(panel.navigate_next(), Some(at(1)));
+        assert_eq!(panel.navigate_next(), Some(at(7)));
+        assert_eq!(panel.navigate_next(), Some(at(2)));
+        assert_eq!(panel.selected(), Some(3));
+        assert_eq!(panel.navigate_prev(), Some(at(7)));
+
+        panel.set_filter(Severity::Hint);
+        assert_eq!(panel.navigate_next(), Some(at(3)));
+        assert_eq!(panel.navigate_next(), Some(at(3)));
+
+        panel.set_filter(Severity::Info);
+        assert_eq!(panel.navigate_next(), None);
+    }
+}
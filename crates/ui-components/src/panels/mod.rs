@@ -0,0 +1,234 @@
+//! Panel components
+
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+
+#[cfg(feature = "diagnostics")]
+pub use diagnostics::DiagnosticsPanel;
+
+use std::collections::HashMap;
+
+/// Panel type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PanelType {
+    Diagnostics,
+    Search,
+    Terminal,
+    Output,
+}
+
+/// Edge of the window a panel docks to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DockPosition {
+    Left,
+    Right,
+    Bottom,
+}
+
+/// A dockable panel such as the diagnostics list, search results or terminal
+pub trait Panel {
+    /// Unique identifier used to toggle the panel
+    fn id(&self) -> &str;
+
+    /// Title shown in the panel's header
+    fn title(&self) -> &str;
+
+    /// Dock position the panel opens in
+    fn position(&self) -> DockPosition;
+
+    /// Text-mode rendering of the panel's contents, one row per line
+    fn render_lines(&self) -> Vec<String>;
+}
+
+/// Tracks registered panels and the one visible in each dock position
+#[derive(Default)]
+pub struct PanelManager {
+    panels: Vec<Box<dyn Panel>>,
+    /// Id of the visible panel per dock position
+    active: HashMap<DockPosition, String>,
+}
+
+impl PanelManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a panel, replacing any panel with the same id
+    pub fn register(&mut self, panel: Box<dyn Panel>) {
+        match self.panels.iter_mut().find(|existing| existing.id() == panel.id()) {
+            Some(existing) => {
+                let old_position = existing.position();
+                if old_position != panel.position() &&
+                    self.active.get(&old_position).map(String::as_str) == Some(panel.id())
+                {
+                    self.active.remove(&old_position);
+                }
+                *existing = panel;
+            }
+            None => self.panels.push(panel),
+        }
+    }
+
+    /// Get a panel by id
+    pub fn get(&self, id: &str) -> Option<&dyn Panel> {
+        self.panels
+            .iter()
+            .find(|panel| panel.id() == id)
+            .map(|panel| panel.as_ref())
+    }
+
+    /// Panels docked at a position, in registration order
+    pub fn panels_at(&self, position: DockPosition) -> impl Iterator<Item = &dyn Panel> {
+        self.panels
+            .iter()
+            .filter(move |panel| panel.position() == position)
+            .map(|panel| panel.as_ref())
+    }
+
+    /// Show a panel, or hide it if it is already visible
+    ///
+    /// Showing a panel hides whichever panel was visible in the same dock
+    /// position. Returns whether the panel is visible afterwards; unknown ids
+    /// return `false`.
+    pub fn toggle(&mut self, id: &str) -> bool {
+        let Some(position) = self.get(id).map(|panel| panel.position()) else {
+            return false;
+        };
+
+        if self.is_visible(id) {
+            self.active.remove(&position);
+            false
+        } else {
+            self.active.insert(position, id.to_string());
+            true
+        }
+    }
+
+    /// Check whether a panel is the visible one in its dock position
+    pub fn is_visible(&self, id: &str) -> bool {
+        self.get(id).is_some_and(|panel| {
+            self.active.get(&panel.position()).map(String::as_str) == Some(id)
+        })
+    }
+
+    /// The visible panel at a dock position
+    pub fn active_for(&self, position: DockPosition) -> Option<&dyn Panel> {
+        self.active.get(&position).and_then(|id| self.get(id))
+    }
+}
+
+/// Visibility and size of a panel area
+pub struct PanelState {
+    /// Panel type
+    panel_type: PanelType,
+    /// Whether panel is visible
+    visible: bool,
+    /// Panel height
+    height: f32,
+}
+
+impl PanelState {
+    pub fn new(panel_type: PanelType) -> Self {
+        Self {
+            panel_type,
+            visible: false,
+            height: 200.0,
+        }
+    }
+
+    pub fn panel_type(&self) -> PanelType {
+        self.panel_type
+    }
+
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    pub fn set_height(&mut self, height: f32) {
+        self.height = height.max(100.0).min(600.0);
+    }
+
+    pub fn height(&self) -> f32 {
+        self.height
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestPanel {
+        id: &'static str,
+        position: DockPosition,
+    }
+
+    impl Panel for TestPanel {
+        fn id(&self) -> &str {
+            self.id
+        }
+
+        fn title(&self) -> &str {
+            self.id
+        }
+
+        fn position(&self) -> DockPosition {
+            self.position
+        }
+
+        fn render_lines(&self) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    fn manager() -> PanelManager {
+        let mut manager = PanelManager::new();
+        for (id, position) in [
+            ("diagnostics", DockPosition::Bottom),
+            ("terminal", DockPosition::Bottom),
+            ("search", DockPosition::Left),
+        ] {
+            manager.register(Box::new(TestPanel { id, position }));
+        }
+        manager
+    }
+
+    fn active_id(manager: &PanelManager, position: DockPosition) -> Option<&str> {
+        manager.active_for(position).map(|panel| panel.id())
+    }
+
+    #[test]
+    fn test_toggle_one_panel_per_position() {
+        let mut manager = manager();
+        assert_eq!(active_id(&manager, DockPosition::Bottom), None);
+
+        assert!(manager.toggle("diagnostics"));
+        assert!(manager.toggle("search"));
+        assert_eq!(active_id(&manager, DockPosition::Bottom), Some("diagnostics"));
+        assert_eq!(active_id(&manager, DockPosition::Left), Some("search"));
+
+        // Opening the terminal replaces diagnostics in the bottom dock
+        assert!(manager.toggle("terminal"));
+        assert_eq!(active_id(&manager, DockPosition::Bottom), Some("terminal"));
+        assert!(!manager.is_visible("diagnostics"));
+        assert_eq!(active_id(&manager, DockPosition::Left), Some("search"));
+
+        assert!(!manager.toggle("terminal"));
+        assert_eq!(active_id(&manager, DockPosition::Bottom), None);
+        assert!(!manager.toggle("missing"));
+    }
+
+    #[test]
+    fn test_panels_at_position() {
+        let manager = manager();
+        let bottom: Vec<&str> = manager
+            .panels_at(DockPosition::Bottom)
+            .map(|panel| panel.id())
+            .collect();
+        assert_eq!(bottom, vec!["diagnostics", "terminal"]);
+        assert_eq!(manager.panels_at(DockPosition::Right).count(), 0);
+    }
+}
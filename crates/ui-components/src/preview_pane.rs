@@ -1,14 +1,55 @@
 //! Preview pane component
 
+use preview::{ PageLayout, PreviewRenderer, Size, SyncManager, Viewport, ZoomLevel };
+use std::ops::Range;
+use std::path::PathBuf;
+#[cfg(feature = "diagnostics")]
+use typst_integration::compiler::CompileResult;
+#[cfg(feature = "diagnostics")]
+use typst_integration::diagnostics::Severity;
+
+/// Page size used until real page sizes are known (A4 in points)
+const DEFAULT_PAGE_SIZE: Size<f32> = Size { width: 595.0, height: 842.0 };
+
+/// Gap between pages, in unscaled points
+const PAGE_GAP: f32 = 16.0;
+
+/// Compilation state shown by the preview
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewStatus {
+    /// Waiting for a compile result
+    Loading,
+    /// A document is rendered
+    Rendered,
+    /// The last compile failed; the previous pages stay on screen
+    Error(String),
+}
+
 /// Preview pane component
 pub struct PreviewPane {
     /// Whether preview is visible
     visible: bool,
+    renderer: PreviewRenderer,
+    viewport: Viewport,
+    sync: SyncManager,
+    status: PreviewStatus,
+    /// Stacked pages of the last successful compile
+    layout: PageLayout,
+    /// Id of the newest compile result applied
+    last_result: Option<u64>,
 }
 
 impl PreviewPane {
     pub fn new() -> Self {
-        Self { visible: true }
+        Self {
+            visible: true,
+            renderer: PreviewRenderer::default(),
+            viewport: Viewport::default(),
+            sync: SyncManager::new(),
+            status: PreviewStatus::Loading,
+            layout: PageLayout::new(Vec::new(), PAGE_GAP),
+            last_result: None,
+        }
     }
 
     pub fn set_visible(&mut self, visible: bool) {
@@ -18,6 +59,169 @@ impl PreviewPane {
     pub fn is_visible(&self) -> bool {
         self.visible
     }
+
+    /// Get compilation status
+    pub fn status(&self) -> &PreviewStatus {
+        &self.status
+    }
+
+    /// Message of the last compile error, if the preview is in the error state
+    pub fn current_error(&self) -> Option<&str> {
+        match &self.status {
+            PreviewStatus::Error(message) => Some(message),
+            _ => None,
+        }
+    }
+
+    /// Mark a compile as in flight
+    pub fn on_compile_started(&mut self) {
+        if self.status != PreviewStatus::Rendered {
+            self.status = PreviewStatus::Loading;
+        }
+    }
+
+    /// Apply a finished compile
+    ///
+    /// Results older than the newest one applied are ignored. A compile
+    /// without a loadable document keeps the previous pages and switches to
+    /// the error state, showing `error` if given.
+    pub fn on_compile_finished(
+        &mut self,
+        id: u64,
+        document: Option<PathBuf>,
+        error: Option<String>
+    ) {
+        if self.last_result.is_some_and(|last| id < last) {
+            return;
+        }
+        self.last_result = Some(id);
+
+        let loaded = document.and_then(|path| {
+            self.renderer.load_document(path).ok()?;
+            self.renderer.page_count().ok()
+        });
+
+        match loaded {
+            Some(page_count) => {
+                let current = self.current_page();
+                self.layout = PageLayout::new(vec![DEFAULT_PAGE_SIZE; page_count], PAGE_GAP);
+                self.status = PreviewStatus::Rendered;
+                self.goto_page(current.min(page_count.saturating_sub(1)));
+            }
+            None => {
+                let message = error.unwrap_or_else(|| "Compilation failed".to_string());
+                self.status = PreviewStatus::Error(message);
+            }
+        }
+    }
+
+    /// Apply a finished compile from the Typst compiler
+    ///
+    /// A failed compile shows its first error.
+    #[cfg(feature = "diagnostics")]
+    pub fn on_compile_result(&mut self, result: CompileResult) {
+        let error = result.diagnostics.diagnostics
+            .iter()
+            .find(|diagnostic| diagnostic.severity == Severity::Error)
+            .map(|diagnostic| diagnostic.message.clone());
+        let document = result.document.filter(|_| result.success);
+        self.on_compile_finished(result.id, document, error);
+    }
+
+    /// Replace the page sizes once the rendered document reports them
+    pub fn set_page_sizes(&mut self, pages: Vec<Size<f32>>) {
+        let current = self.current_page();
+        self.layout = PageLayout::new(pages, PAGE_GAP);
+        self.goto_page(current);
+    }
+
+    /// Get the page layout
+    pub fn layout(&self) -> &PageLayout {
+        &self.layout
+    }
+
+    /// Get the viewport
+    pub fn viewport(&self) -> &Viewport {
+        &self.viewport
+    }
+
+    /// Resize the viewport, keeping the current page in view
+    pub fn set_size(&mut self, width: f32, height: f32) {
+        let current = self.current_page();
+        self.viewport.set_size(width, height);
+        self.goto_page(current);
+    }
+
+    /// Change the zoom level, keeping the current page in view
+    pub fn set_zoom(&mut self, zoom: ZoomLevel) {
+        let current = self.current_page();
+        self.viewport.set_zoom(zoom);
+        self.goto_page(current);
+    }
+
+    /// Scroll so a page's top edge is at the top of the viewport
+    pub fn goto_page(&mut self, page: usize) {
+        let y = self.layout.scroll_for_page(page) * self.scale();
+        self.viewport.scroll_to(self.viewport.scroll_x, y);
+    }
+
+    /// Scale from page points to viewport pixels
+    ///
+    /// Fit modes fit the largest page, so every page fits the same way.
+    pub fn scale(&self) -> f32 {
+        let (width, height) = (0..self.layout.page_count())
+            .filter_map(|page| self.layout.page_size(page))
+            .fold((0.0f32, 0.0f32), |(width, height), size| {
+                (width.max(size.width), height.max(size.height))
+            });
+        if width == 0.0 {
+            return self.viewport.current_scale(DEFAULT_PAGE_SIZE.width, DEFAULT_PAGE_SIZE.height);
+        }
+        self.viewport.current_scale(width, height)
+    }
+
+    /// Page at the top of the viewport
+    pub fn current_page(&self) -> usize {
+        self.visible_pages().start
+    }
+
+    /// Pages that intersect the viewport and need rasterizing
+    ///
+    /// Empty unless a document is rendered or still shown after an error.
+    pub fn visible_pages(&self) -> Range<usize> {
+        let page_count = self.layout.page_count();
+        if page_count == 0 || self.status == PreviewStatus::Loading {
+            return 0..0;
+        }
+
+        let scale = self.scale().max(f32::EPSILON);
+        let top = self.viewport.scroll_y / scale;
+        let bottom = (self.viewport.scroll_y + self.viewport.height) / scale;
+
+        let (mut first, offset) = self.layout.page_at_scroll(top);
+        // A top edge in the gap below a page means that page is scrolled past
+        let first_bottom = self.layout.scroll_for_page(first) + offset;
+        if top > first_bottom && first + 1 < page_count {
+            first += 1;
+        }
+        let (last, _) = self.layout.page_at_scroll(bottom);
+        first..last.max(first) + 1
+    }
+
+    /// Get the renderer
+    pub fn renderer(&self) -> &PreviewRenderer {
+        &self.renderer
+    }
+
+    /// Get the source/preview sync state
+    pub fn sync(&self) -> &SyncManager {
+        &self.sync
+    }
+
+    /// Get mutable source/preview sync state
+    pub fn sync_mut(&mut self) -> &mut SyncManager {
+        &mut self.sync
+    }
 }
 
 impl Default for PreviewPane {
@@ -25,3 +229,77 @@ impl Default for PreviewPane {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "diagnostics")]
+    use typst_integration::diagnostics::{ Diagnostic, DiagnosticList };
+
+    #[cfg(feature = "diagnostics")]
+    fn success(id: u64) -> CompileResult {
+        CompileResult {
+            id,
+            success: true,
+            diagnostics: DiagnosticList::new(),
+            document: Some(PathBuf::from("main.pdf")),
+        }
+    }
+
+    #[cfg(feature = "diagnostics")]
+    fn failure(id: u64, message: &str) -> CompileResult {
+        let mut diagnostics = DiagnosticList::new();
+        diagnostics.add(Diagnostic::warning("unused".to_string()));
+        diagnostics.add(Diagnostic::error(message.to_string()));
+        CompileResult { id, success: false, diagnostics, document: None }
+    }
+
+    #[test]
+    #[cfg(feature = "diagnostics")]
+    fn test_compile_result_transitions() {
+        let mut pane = PreviewPane::new();
+        assert_eq!(*pane.status(), PreviewStatus::Loading);
+        assert_eq!(pane.visible_pages(), 0..0);
+
+        pane.on_compile_result(success(1));
+        assert_eq!(*pane.status(), PreviewStatus::Rendered);
+        assert_eq!(pane.current_error(), None);
+
+        pane.on_compile_result(failure(2, "unknown variable: x"));
+        assert_eq!(pane.current_error(), Some("unknown variable: x"));
+        // The last good render stays visible
+        assert_eq!(pane.visible_pages(), 0..1);
+
+        // A stale success does not hide the newer error
+        pane.on_compile_result(success(1));
+        assert_eq!(pane.current_error(), Some("unknown variable: x"));
+
+        pane.on_compile_result(success(3));
+        assert_eq!(*pane.status(), PreviewStatus::Rendered);
+    }
+
+    #[test]
+    fn test_visible_pages_follow_zoom_and_navigation() {
+        let mut pane = PreviewPane::new();
+        pane.on_compile_finished(1, Some(PathBuf::from("main.pdf")), None);
+        pane.set_page_sizes(vec![Size { width: 500.0, height: 500.0 }; 4]);
+        pane.set_size(500.0, 400.0);
+
+        // Fit width: 1px per point, one page plus part of the next in view
+        pane.set_zoom(ZoomLevel::FitWidth);
+        assert_eq!(pane.scale(), 1.0);
+        assert_eq!(pane.visible_pages(), 0..1);
+
+        pane.goto_page(2);
+        assert_eq!(pane.current_page(), 2);
+        assert_eq!(pane.visible_pages(), 2..3);
+
+        // Zooming out keeps page 2 at the top and brings more pages in view
+        pane.set_zoom(ZoomLevel::Custom(0.25));
+        assert_eq!(pane.current_page(), 2);
+        assert_eq!(pane.visible_pages(), 2..4);
+
+        pane.goto_page(0);
+        assert_eq!(pane.visible_pages(), 0..4);
+    }
+}
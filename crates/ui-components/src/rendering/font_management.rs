@@ -4,28 +4,147 @@
 
 use fontdb;
 use ttf_parser;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{ Arc, Mutex };
 
 /// Font manager for loading and caching fonts
 pub struct FontManager {
     database: fontdb::Database,
+    /// Preferred families, consulted before any other face
+    chain: FontFallbackChain,
+    /// Loaded faces by database ID
+    loaded: Mutex<HashMap<fontdb::ID, Arc<FontData>>>,
+    /// Resolved fallback face per script
+    fallbacks: Mutex<HashMap<Script, Option<fontdb::ID>>>,
 }
 
 impl FontManager {
     pub fn new() -> Self {
         let mut database = fontdb::Database::new();
         database.load_system_fonts();
-        Self { database }
+        Self::with_database(database)
+    }
+
+    /// Create a font manager over an existing font database
+    pub fn with_database(database: fontdb::Database) -> Self {
+        Self {
+            database,
+            chain: FontFallbackChain::default(),
+            loaded: Mutex::new(HashMap::new()),
+            fallbacks: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Set the preferred font families
+    pub fn set_fallback_chain(&mut self, chain: FontFallbackChain) {
+        self.chain = chain;
+        self.fallbacks.lock().unwrap().clear();
+    }
+
+    /// Get the preferred font families
+    pub fn fallback_chain(&self) -> &FontFallbackChain {
+        &self.chain
     }
 
     /// Load a font by family name
-    pub fn load_font(&self, _family: &str) -> Option<Arc<FontData>> {
-        todo!("Implement font loading")
+    pub fn load_font(&self, family: &str) -> Option<Arc<FontData>> {
+        let id = self.query_family(family)?;
+        self.load_face(id)
     }
 
     /// Get fallback font for a script
-    pub fn get_fallback(&self, _script: Script) -> Option<Arc<FontData>> {
-        todo!("Implement font fallback")
+    ///
+    /// Families in the fallback chain are tried first, in order; otherwise the
+    /// first face in the database covering the script's representative code
+    /// points is used.
+    pub fn get_fallback(&self, script: Script) -> Option<Arc<FontData>> {
+        let cached = self.fallbacks.lock().unwrap().get(&script).copied();
+        let id = match cached {
+            Some(id) => id,
+            None => {
+                let id = self.find_fallback(script);
+                self.fallbacks.lock().unwrap().insert(script, id);
+                id
+            }
+        };
+
+        self.load_face(id?)
+    }
+
+    /// Resolve one font per script run in `text`
+    ///
+    /// Runs are those returned by [`Script::runs`]. Runs whose script has no
+    /// covering face use the default (`Script::Other`) face instead.
+    pub fn fallback_chain_for(&self, text: &str) -> Vec<Arc<FontData>> {
+        Script::runs(text)
+            .into_iter()
+            .filter_map(|(_, script)| {
+                self.get_fallback(script).or_else(|| self.get_fallback(Script::Other))
+            })
+            .collect()
+    }
+
+    fn find_fallback(&self, script: Script) -> Option<fontdb::ID> {
+        let families = std::iter::once(&self.chain.primary).chain(self.chain.fallbacks.iter());
+        let preferred = families
+            .filter_map(|family| self.query_family(family))
+            .find(|id| self.covers(*id, script));
+
+        preferred.or_else(|| {
+            self.database
+                .faces()
+                .map(|face| face.id)
+                .find(|id| self.covers(*id, script))
+        })
+    }
+
+    fn query_family(&self, family: &str) -> Option<fontdb::ID> {
+        self.database.query(
+            &(fontdb::Query {
+                families: &[fontdb::Family::Name(family)],
+                ..Default::default()
+            })
+        )
+    }
+
+    /// Check whether a face has glyphs for all of a script's sample characters
+    fn covers(&self, id: fontdb::ID, script: Script) -> bool {
+        self.database
+            .with_face_data(id, |data, index| {
+                ttf_parser::Face
+                    ::parse(data, index)
+                    .map(|face| {
+                        script
+                            .sample_chars()
+                            .iter()
+                            .all(|ch| face.glyph_index(*ch).is_some())
+                    })
+                    .unwrap_or(false)
+            })
+            .unwrap_or(false)
+    }
+
+    fn load_face(&self, id: fontdb::ID) -> Option<Arc<FontData>> {
+        if let Some(font) = self.loaded.lock().unwrap().get(&id) {
+            return Some(font.clone());
+        }
+
+        let family = self.database
+            .face(id)?
+            .families.first()
+            .map(|(name, _)| name.clone())
+            .unwrap_or_default();
+        let (data, index) = self.database.with_face_data(id, |data, index| (data.to_vec(), index))?;
+
+        let font = Arc::new(FontData {
+            id,
+            family,
+            data: Arc::new(data),
+            index,
+        });
+        self.loaded.lock().unwrap().insert(id, font.clone());
+        Some(font)
     }
 }
 
@@ -36,11 +155,27 @@ impl Default for FontManager {
 }
 
 /// Font data wrapper
+#[derive(Debug)]
 pub struct FontData {
-    // TODO: Implement font data storage
+    /// Face ID in the font database
+    pub id: fontdb::ID,
+    /// Family name
+    pub family: String,
+    /// Raw font file data
+    pub data: Arc<Vec<u8>>,
+    /// Face index within the font file
+    pub index: u32,
+}
+
+impl FontData {
+    /// Parse the face for glyph and metric queries
+    pub fn face(&self) -> Option<ttf_parser::Face<'_>> {
+        ttf_parser::Face::parse(&self.data, self.index).ok()
+    }
 }
 
 /// Script identifier for font selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Script {
     Latin,
     Arabic,
@@ -48,8 +183,116 @@ pub enum Script {
     Other,
 }
 
+impl Script {
+    /// Classify a character, or `None` for script-neutral characters
+    /// (digits, whitespace, punctuation) that join the surrounding run
+    pub fn of(ch: char) -> Option<Script> {
+        match ch {
+            'a'..='z' | 'A'..='Z' | '\u{00C0}'..='\u{024F}' | '\u{1E00}'..='\u{1EFF}' =>
+                Some(Script::Latin),
+            '\u{0590}'..='\u{05FF}' | '\u{FB1D}'..='\u{FB4F}' => Some(Script::Hebrew),
+            | '\u{0600}'..='\u{06FF}'
+            | '\u{0750}'..='\u{077F}'
+            | '\u{08A0}'..='\u{08FF}'
+            | '\u{FB50}'..='\u{FDFF}'
+            | '\u{FE70}'..='\u{FEFF}' => Some(Script::Arabic),
+            _ if ch.is_alphabetic() => Some(Script::Other),
+            _ => None,
+        }
+    }
+
+    /// Split text into byte ranges of a single script
+    ///
+    /// Neutral characters attach to the run before them; leading neutrals
+    /// attach to the first run. Text with no classified characters forms a
+    /// single `Script::Latin` run.
+    pub fn runs(text: &str) -> Vec<(Range<usize>, Script)> {
+        let mut runs: Vec<(Range<usize>, Script)> = Vec::new();
+
+        for (offset, ch) in text.char_indices() {
+            let end = offset + ch.len_utf8();
+            match (Script::of(ch), runs.last_mut()) {
+                (Some(script), Some(last)) if last.1 != script => runs.push((offset..end, script)),
+                (Some(script), None) => runs.push((0..end, script)),
+                (_, Some(last)) => last.0.end = end,
+                (None, None) => {}
+            }
+        }
+
+        if runs.is_empty() && !text.is_empty() {
+            runs.push((0..text.len(), Script::Latin));
+        }
+        runs
+    }
+
+    /// Characters a face must cover to render this script
+    fn sample_chars(&self) -> &'static [char] {
+        match self {
+            Script::Latin => &['a', 'Z', 'é'],
+            Script::Arabic => &['\u{0627}', '\u{0644}', '\u{0645}'],
+            Script::Hebrew => &['\u{05D0}', '\u{05E9}', '\u{05EA}'],
+            Script::Other => &[],
+        }
+    }
+}
+
 /// Font fallback chain configuration
+#[derive(Debug, Clone, Default)]
 pub struct FontFallbackChain {
     pub primary: String,
     pub fallbacks: Vec<String>,
 }
+
+#[cfg(test)]
+impl FontManager {
+    /// Font manager over the fonts vendored for tests, independent of the
+    /// fonts installed on the system
+    pub(crate) fn with_test_fonts() -> Self {
+        let mut database = fontdb::Database::new();
+        database.load_font_data(
+            include_bytes!("../../tests/data/fonts/NotoSans-Regular.ttf").to_vec()
+        );
+        database.load_font_data(
+            include_bytes!("../../tests/data/fonts/NotoSansHebrew-Regular.ttf").to_vec()
+        );
+        Self::with_database(database)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_script_runs() {
+        let runs = Script::runs("Hello שלום, world");
+        let scripts: Vec<Script> = runs
+            .iter()
+            .map(|(_, script)| *script)
+            .collect();
+        assert_eq!(scripts, vec![Script::Latin, Script::Hebrew, Script::Latin]);
+        assert_eq!(runs[0].0, 0..6);
+        assert_eq!(runs[1].0, 6..16);
+    }
+
+    #[test]
+    fn test_hebrew_fallback_differs_from_latin() {
+        // Noto Sans has no Hebrew, so Hebrew falls back to Noto Sans Hebrew
+        let mut manager = FontManager::with_test_fonts();
+        manager.set_fallback_chain(FontFallbackChain {
+            primary: "Noto Sans".to_string(),
+            fallbacks: Vec::new(),
+        });
+
+        let latin = manager.get_fallback(Script::Latin).unwrap();
+        let hebrew = manager.get_fallback(Script::Hebrew).unwrap();
+        assert_eq!(latin.family, "Noto Sans");
+        assert_eq!(hebrew.family, "Noto Sans Hebrew");
+        assert_ne!(latin.id, hebrew.id);
+        assert!(hebrew.face().unwrap().glyph_index('\u{05D0}').is_some());
+
+        let chain = manager.fallback_chain_for("Typst שלום");
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[1].id, hebrew.id);
+    }
+}
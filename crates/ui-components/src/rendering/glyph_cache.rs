@@ -2,28 +2,165 @@
 //!
 //! Phase 3.2: Text Rendering Pipeline
 
+use super::font_management::FontData;
 use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Default number of glyphs kept in the cache
+const DEFAULT_CAPACITY: usize = 4096;
+
+/// Default atlas texture size in pixels (square)
+const DEFAULT_ATLAS_SIZE: u32 = 1024;
 
 /// Cache for positioned glyphs
+///
+/// Glyphs are rasterized on first use into a shared coverage atlas and kept
+/// until they are the least recently used entry of a full cache. Glyphs of
+/// fonts that were never added render as empty placeholders.
 pub struct GlyphCache {
-    cache: HashMap<GlyphCacheKey, CachedGlyph>,
+    cache: HashMap<GlyphCacheKey, CacheEntry>,
+    fonts: HashMap<usize, Arc<FontData>>,
+    atlas: GlyphAtlas,
+    capacity: usize,
+    /// Monotonic use counter for LRU ordering
+    tick: u64,
+    /// Number of glyphs rasterized so far
+    render_count: usize,
+}
+
+struct CacheEntry {
+    glyph: CachedGlyph,
+    last_used: u64,
 }
 
 impl GlyphCache {
     pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Create a cache holding at most `capacity` glyphs
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
             cache: HashMap::new(),
+            fonts: HashMap::new(),
+            atlas: GlyphAtlas::new(DEFAULT_ATLAS_SIZE, DEFAULT_ATLAS_SIZE),
+            capacity: capacity.max(1),
+            tick: 0,
+            render_count: 0,
         }
     }
 
+    /// Register a font under the ID used in cache keys
+    pub fn add_font(&mut self, font_id: usize, font: Arc<FontData>) {
+        self.fonts.insert(font_id, font);
+        self.cache.retain(|key, _| key.font_id != font_id);
+    }
+
+    /// Set the maximum number of cached glyphs, evicting as needed
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity.max(1);
+        while self.cache.len() > self.capacity {
+            self.evict_lru();
+        }
+    }
+
+    /// Maximum number of cached glyphs
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Number of cached glyphs
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Check if the cache is empty
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+
+    /// Check if a glyph is cached, without touching its LRU position
+    pub fn contains(&self, key: &GlyphCacheKey) -> bool {
+        self.cache.contains_key(key)
+    }
+
+    /// Number of glyphs rasterized since the cache was created
+    pub fn render_count(&self) -> usize {
+        self.render_count
+    }
+
+    /// Coverage atlas the cached texture coordinates refer to
+    pub fn atlas(&self) -> &GlyphAtlas {
+        &self.atlas
+    }
+
     /// Get or render a glyph
-    pub fn get_or_render(&mut self, _key: GlyphCacheKey) -> &CachedGlyph {
-        todo!("Implement glyph cache lookup")
+    pub fn get_or_render(&mut self, key: GlyphCacheKey) -> &CachedGlyph {
+        self.tick += 1;
+        let tick = self.tick;
+
+        if !self.cache.contains_key(&key) {
+            if self.cache.len() >= self.capacity {
+                self.evict_lru();
+            }
+            let glyph = self.render(key);
+            self.cache.insert(key, CacheEntry { glyph, last_used: tick });
+        }
+
+        let entry = self.cache.get_mut(&key).unwrap();
+        entry.last_used = tick;
+        &entry.glyph
     }
 
     /// Clear the cache
     pub fn clear(&mut self) {
         self.cache.clear();
+        self.atlas.clear();
+    }
+
+    fn evict_lru(&mut self) {
+        let oldest = self.cache
+            .iter()
+            .min_by_key(|(_, entry)| entry.last_used)
+            .map(|(key, _)| *key);
+
+        if let Some(key) = oldest {
+            self.cache.remove(&key);
+        }
+    }
+
+    fn render(&mut self, key: GlyphCacheKey) -> CachedGlyph {
+        self.render_count += 1;
+
+        let Some(bitmap) = self.fonts
+            .get(&key.font_id)
+            .and_then(|font| rasterize(font, key.glyph_id, key.size as f32)) else {
+            return CachedGlyph::empty();
+        };
+
+        // Evicted glyphs leave holes in the atlas; start over once it is full
+        let rect = match self.atlas.allocate(bitmap.width, bitmap.height) {
+            Some(rect) => Some(rect),
+            None => {
+                self.cache.clear();
+                self.atlas.clear();
+                self.atlas.allocate(bitmap.width, bitmap.height)
+            }
+        };
+
+        let texture_coords = match rect {
+            Some((x, y)) => {
+                self.atlas.write(x, y, &bitmap);
+                self.atlas.normalized(x, y, bitmap.width, bitmap.height)
+            }
+            None => (0.0, 0.0, 0.0, 0.0),
+        };
+
+        CachedGlyph {
+            texture_coords,
+            bitmap_size: (bitmap.width, bitmap.height),
+            metrics: bitmap.metrics,
+        }
     }
 }
 
@@ -34,22 +171,371 @@ impl Default for GlyphCache {
 }
 
 /// Key for glyph cache lookups
-#[derive(Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct GlyphCacheKey {
     pub glyph_id: u32,
     pub font_id: usize,
+    /// Font size in pixels per em
     pub size: u32,
 }
 
 /// Cached glyph data
 pub struct CachedGlyph {
+    /// Normalized atlas rectangle (u0, v0, u1, v1)
     pub texture_coords: (f32, f32, f32, f32),
+    /// Bitmap width and height in pixels
+    pub bitmap_size: (u32, u32),
     pub metrics: GlyphMetrics,
 }
 
+impl CachedGlyph {
+    fn empty() -> Self {
+        Self {
+            texture_coords: (0.0, 0.0, 0.0, 0.0),
+            bitmap_size: (0, 0),
+            metrics: GlyphMetrics {
+                advance: 0.0,
+                bearing_x: 0.0,
+                bearing_y: 0.0,
+            },
+        }
+    }
+}
+
 /// Glyph metrics
+///
+/// Bearings place the bitmap's top-left corner relative to the pen position
+/// on the baseline, with `bearing_y` measured upwards.
 pub struct GlyphMetrics {
     pub advance: f32,
     pub bearing_x: f32,
     pub bearing_y: f32,
 }
+
+/// Single-channel coverage texture packed with glyph bitmaps in shelves
+pub struct GlyphAtlas {
+    pub width: u32,
+    pub height: u32,
+    pixels: Vec<u8>,
+    cursor_x: u32,
+    cursor_y: u32,
+    row_height: u32,
+}
+
+impl GlyphAtlas {
+    fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![0; (width * height) as usize],
+            cursor_x: 0,
+            cursor_y: 0,
+            row_height: 0,
+        }
+    }
+
+    /// Coverage values, row-major
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    fn clear(&mut self) {
+        self.pixels.fill(0);
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.row_height = 0;
+    }
+
+    /// Reserve space for a bitmap, returning its top-left corner
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.width || height > self.height {
+            return None;
+        }
+
+        if self.cursor_x + width > self.width {
+            self.cursor_x = 0;
+            self.cursor_y += self.row_height;
+            self.row_height = 0;
+        }
+        if self.cursor_y + height > self.height {
+            return None;
+        }
+
+        let origin = (self.cursor_x, self.cursor_y);
+        self.cursor_x += width;
+        self.row_height = self.row_height.max(height);
+        Some(origin)
+    }
+
+    fn write(&mut self, x: u32, y: u32, bitmap: &GlyphBitmap) {
+        for row in 0..bitmap.height {
+            let src = (row * bitmap.width) as usize;
+            let dst = ((y + row) * self.width + x) as usize;
+            self.pixels[dst..dst + bitmap.width as usize].copy_from_slice(
+                &bitmap.coverage[src..src + bitmap.width as usize]
+            );
+        }
+    }
+
+    fn normalized(&self, x: u32, y: u32, width: u32, height: u32) -> (f32, f32, f32, f32) {
+        let (w, h) = (self.width as f32, self.height as f32);
+        ((x as f32) / w, (y as f32) / h, ((x + width) as f32) / w, ((y + height) as f32) / h)
+    }
+}
+
+/// Rasterized glyph coverage
+struct GlyphBitmap {
+    width: u32,
+    height: u32,
+    coverage: Vec<u8>,
+    metrics: GlyphMetrics,
+}
+
+/// Rasterize a glyph outline at the given pixel size
+///
+/// Returns `None` if the face cannot be parsed. Glyphs without an outline
+/// (such as spaces) produce an empty bitmap that still carries an advance.
+fn rasterize(font: &FontData, glyph_id: u32, size: f32) -> Option<GlyphBitmap> {
+    let face = font.face()?;
+    let glyph = ttf_parser::GlyphId(u16::try_from(glyph_id).ok()?);
+    let scale = size / (face.units_per_em() as f32);
+    let advance = (face.glyph_hor_advance(glyph).unwrap_or(0) as f32) * scale;
+
+    let mut outline = OutlineFlattener::default();
+    let Some(bbox) = face.outline_glyph(glyph, &mut outline) else {
+        return Some(GlyphBitmap {
+            width: 0,
+            height: 0,
+            coverage: Vec::new(),
+            metrics: GlyphMetrics { advance, bearing_x: 0.0, bearing_y: 0.0 },
+        });
+    };
+
+    // One pixel of padding on each side keeps antialiased edges intact
+    let left = (bbox.x_min as f32) * scale - 1.0;
+    let top = (bbox.y_max as f32) * scale + 1.0;
+    let width = (((bbox.x_max - bbox.x_min) as f32) * scale).ceil() as u32 + 2;
+    let height = (((bbox.y_max - bbox.y_min) as f32) * scale).ceil() as u32 + 2;
+
+    let mut rasterizer = Rasterizer::new(width as usize, height as usize);
+    for (p0, p1) in outline.lines {
+        let to_pixels = |(x, y): (f32, f32)| (x * scale - left, top - y * scale);
+        rasterizer.draw_line(to_pixels(p0), to_pixels(p1));
+    }
+
+    Some(GlyphBitmap {
+        width,
+        height,
+        coverage: rasterizer.coverage(),
+        metrics: GlyphMetrics { advance, bearing_x: left, bearing_y: top },
+    })
+}
+
+/// Number of line segments used to approximate each curve
+const CURVE_SEGMENTS: usize = 8;
+
+/// Collects a glyph outline as line segments in font units
+#[derive(Default)]
+struct OutlineFlattener {
+    lines: Vec<((f32, f32), (f32, f32))>,
+    start: (f32, f32),
+    current: (f32, f32),
+}
+
+impl ttf_parser::OutlineBuilder for OutlineFlattener {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.start = (x, y);
+        self.current = (x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.lines.push((self.current, (x, y)));
+        self.current = (x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let p0 = self.current;
+        for i in 1..=CURVE_SEGMENTS {
+            let t = (i as f32) / (CURVE_SEGMENTS as f32);
+            let mt = 1.0 - t;
+            let px = mt * mt * p0.0 + 2.0 * mt * t * x1 + t * t * x;
+            let py = mt * mt * p0.1 + 2.0 * mt * t * y1 + t * t * y;
+            self.line_to(px, py);
+        }
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let p0 = self.current;
+        for i in 1..=CURVE_SEGMENTS {
+            let t = (i as f32) / (CURVE_SEGMENTS as f32);
+            let mt = 1.0 - t;
+            let px =
+                mt * mt * mt * p0.0 + 3.0 * mt * mt * t * x1 + 3.0 * mt * t * t * x2 + t * t * t * x;
+            let py =
+                mt * mt * mt * p0.1 + 3.0 * mt * mt * t * y1 + 3.0 * mt * t * t * y2 + t * t * t * y;
+            self.line_to(px, py);
+        }
+    }
+
+    fn close(&mut self) {
+        if self.current != self.start {
+            self.lines.push((self.current, self.start));
+        }
+        self.current = self.start;
+    }
+}
+
+/// Signed-area accumulation rasterizer producing antialiased coverage
+struct Rasterizer {
+    width: usize,
+    height: usize,
+    accumulation: Vec<f32>,
+}
+
+impl Rasterizer {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            // Padding absorbs writes one cell past the right edge
+            accumulation: vec![0.0; width * height + 4],
+        }
+    }
+
+    fn add(&mut self, index: isize, value: f32) {
+        if let Some(cell) = usize::try_from(index).ok().and_then(|i| self.accumulation.get_mut(i)) {
+            *cell += value;
+        }
+    }
+
+    /// Accumulate the signed area covered to the right of a line segment
+    fn draw_line(&mut self, p0: (f32, f32), p1: (f32, f32)) {
+        if (p0.1 - p1.1).abs() <= f32::EPSILON {
+            return;
+        }
+        let (dir, p0, p1) = if p0.1 < p1.1 { (1.0, p0, p1) } else { (-1.0, p1, p0) };
+        let dxdy = (p1.0 - p0.0) / (p1.1 - p0.1);
+
+        let mut x = p0.0;
+        if p0.1 < 0.0 {
+            x -= p0.1 * dxdy;
+        }
+
+        let y_start = p0.1.max(0.0) as usize;
+        let y_end = self.height.min(p1.1.ceil() as usize);
+        for y in y_start..y_end {
+            let line_start = (y * self.width) as isize;
+            let dy = ((y + 1) as f32).min(p1.1) - (y as f32).max(p0.1);
+            let x_next = x + dxdy * dy;
+            let d = dy * dir;
+
+            let (x0, x1) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            let x0_floor = x0.floor();
+            let x0i = x0_floor as isize;
+            let x1_ceil = x1.ceil();
+            let x1i = x1_ceil as isize;
+
+            if x1i <= x0i + 1 {
+                let xmf = 0.5 * (x + x_next) - x0_floor;
+                self.add(line_start + x0i, d - d * xmf);
+                self.add(line_start + x0i + 1, d * xmf);
+            } else {
+                let s = (x1 - x0).recip();
+                let x0f = x0 - x0_floor;
+                let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+                let x1f = x1 - x1_ceil + 1.0;
+                let am = 0.5 * s * x1f * x1f;
+
+                self.add(line_start + x0i, d * a0);
+                if x1i == x0i + 2 {
+                    self.add(line_start + x0i + 1, d * (1.0 - a0 - am));
+                } else {
+                    let a1 = s * (1.5 - x0f);
+                    self.add(line_start + x0i + 1, d * (a1 - a0));
+                    for xi in x0i + 2..x1i - 1 {
+                        self.add(line_start + xi, d * s);
+                    }
+                    let a2 = a1 + ((x1i - x0i - 3) as f32) * s;
+                    self.add(line_start + x1i - 1, d * (1.0 - a2 - am));
+                }
+                self.add(line_start + x1i, d * am);
+            }
+
+            x = x_next;
+        }
+    }
+
+    /// Integrate accumulated areas into 8-bit coverage
+    fn coverage(&self) -> Vec<u8> {
+        let mut sum = 0.0f32;
+        self.accumulation[..self.width * self.height]
+            .iter()
+            .map(|value| {
+                sum += value;
+                (sum.abs().min(1.0) * 255.0).round() as u8
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::font_management::FontManager;
+
+    fn key(glyph_id: u32) -> GlyphCacheKey {
+        GlyphCacheKey { glyph_id, font_id: 0, size: 16 }
+    }
+
+    #[test]
+    fn test_same_key_renders_once() {
+        let mut cache = GlyphCache::new();
+
+        cache.get_or_render(key(1));
+        cache.get_or_render(key(1));
+
+        assert_eq!(cache.render_count(), 1);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = GlyphCache::with_capacity(2);
+
+        cache.get_or_render(key(1));
+        cache.get_or_render(key(2));
+        // Touch 1 so 2 becomes least recently used
+        cache.get_or_render(key(1));
+        cache.get_or_render(key(3));
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.contains(&key(1)));
+        assert!(!cache.contains(&key(2)));
+        assert!(cache.contains(&key(3)));
+        assert_eq!(cache.render_count(), 3);
+    }
+
+    #[test]
+    fn test_rasterizes_outline() {
+        let font = FontManager::with_test_fonts().load_font("Noto Sans").unwrap();
+        let glyph_id = font.face().unwrap().glyph_index('A').unwrap().0 as u32;
+
+        let mut cache = GlyphCache::new();
+        cache.add_font(0, font);
+        let glyph = cache.get_or_render(GlyphCacheKey { glyph_id, font_id: 0, size: 32 });
+
+        assert!(glyph.metrics.advance > 0.0);
+        let (width, height) = glyph.bitmap_size;
+        assert!(width > 2 && height > 2);
+        let (u0, v0, u1, v1) = glyph.texture_coords;
+        assert!(u1 > u0 && v1 > v0);
+
+        let covered = cache
+            .atlas()
+            .pixels()
+            .iter()
+            .filter(|value| **value > 128)
+            .count();
+        assert!(covered > 0);
+    }
+}
@@ -0,0 +1,196 @@
+//! Mapping between logical lines and visual rows
+//!
+//! With word wrap, a logical line spans a variable number of visual rows.
+//! The index keeps per-line row counts in a Fenwick tree so both directions
+//! of the mapping, and updating a line after it is re-wrapped, take log time.
+
+use std::ops::Range;
+
+/// Cumulative visual-row counts of logical lines
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LineIndex {
+    /// Visual rows of each logical line
+    rows: Vec<usize>,
+    /// Fenwick tree over `rows`, 1-based
+    tree: Vec<usize>,
+}
+
+impl LineIndex {
+    /// Build an index from the visual row count of each logical line
+    pub fn new(row_counts: &[usize]) -> Self {
+        Self {
+            rows: row_counts.to_vec(),
+            tree: build_tree(row_counts),
+        }
+    }
+
+    /// Number of logical lines
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Visual rows of a logical line, or 0 past the end
+    pub fn row_count(&self, logical_line: usize) -> usize {
+        self.rows.get(logical_line).copied().unwrap_or(0)
+    }
+
+    /// Total visual rows of all lines
+    pub fn total_rows(&self) -> usize {
+        self.prefix_sum(self.rows.len())
+    }
+
+    /// First visual row of a logical line
+    ///
+    /// Lines past the end start at the total row count.
+    pub fn visual_row_of(&self, logical_line: usize) -> usize {
+        self.prefix_sum(logical_line.min(self.rows.len()))
+    }
+
+    /// Logical line shown at a visual row
+    ///
+    /// Rows past the end map to the last line.
+    pub fn logical_line_at(&self, visual_row: usize) -> usize {
+        // Descend the tree for the number of lines ending at or before the row
+        let mut line = 0;
+        let mut remaining = visual_row;
+        let mut step = self.rows.len().checked_next_power_of_two().unwrap_or(0);
+        while step > 0 {
+            let next = line + step;
+            if next < self.tree.len() && self.tree[next] <= remaining {
+                line = next;
+                remaining -= self.tree[next];
+            }
+            step /= 2;
+        }
+        line.min(self.rows.len().saturating_sub(1))
+    }
+
+    /// Change the visual row count of a logical line
+    pub fn update(&mut self, logical_line: usize, new_row_count: usize) {
+        let Some(rows) = self.rows.get_mut(logical_line) else {
+            return;
+        };
+        let old_row_count = std::mem::replace(rows, new_row_count);
+
+        let mut node = logical_line + 1;
+        while node < self.tree.len() {
+            self.tree[node] = self.tree[node] + new_row_count - old_row_count;
+            node += lowest_bit(node);
+        }
+    }
+
+    /// Insert logical lines with the given row counts before `logical_line`
+    ///
+    /// Later lines shift down. Rebuilding the tree is linear in the line
+    /// count but needs no re-wrapping.
+    pub fn insert(&mut self, logical_line: usize, row_counts: &[usize]) {
+        let at = logical_line.min(self.rows.len());
+        self.rows.splice(at..at, row_counts.iter().copied());
+        self.tree = build_tree(&self.rows);
+    }
+
+    /// Remove a range of logical lines; later lines shift up
+    pub fn remove(&mut self, lines: Range<usize>) {
+        let end = lines.end.min(self.rows.len());
+        let start = lines.start.min(end);
+        self.rows.drain(start..end);
+        self.tree = build_tree(&self.rows);
+    }
+
+    /// Visual rows of the first `lines` logical lines
+    fn prefix_sum(&self, lines: usize) -> usize {
+        let mut sum = 0;
+        let mut node = lines;
+        while node > 0 {
+            sum += self.tree[node];
+            node -= lowest_bit(node);
+        }
+        sum
+    }
+}
+
+/// Fenwick tree over per-line row counts, built in linear time
+fn build_tree(row_counts: &[usize]) -> Vec<usize> {
+    let mut tree = vec![0; row_counts.len() + 1];
+    for (i, &rows) in row_counts.iter().enumerate() {
+        let node = i + 1;
+        tree[node] += rows;
+        let parent = node + lowest_bit(node);
+        if parent < tree.len() {
+            tree[parent] += tree[node];
+        }
+    }
+    tree
+}
+
+fn lowest_bit(node: usize) -> usize {
+    node & node.wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forward_and_inverse_lookups() {
+        // Rows: line 0 -> 0, line 1 -> 1..4, line 2 -> 4, line 3 -> 5..7
+        let index = LineIndex::new(&[1, 3, 1, 2]);
+        let starts: Vec<usize> = (0..=4).map(|line| index.visual_row_of(line)).collect();
+        assert_eq!(starts, vec![0, 1, 4, 5, 7]);
+        assert_eq!(index.total_rows(), 7);
+
+        let lines: Vec<usize> = (0..8).map(|row| index.logical_line_at(row)).collect();
+        assert_eq!(lines, vec![0, 1, 1, 1, 2, 3, 3, 3]);
+    }
+
+    #[test]
+    fn test_update_row_count() {
+        let mut index = LineIndex::new(&[1; 10]);
+        index.update(2, 4);
+        assert_eq!(index.visual_row_of(3), 6);
+        assert_eq!(index.logical_line_at(5), 2);
+        assert_eq!(index.logical_line_at(6), 3);
+
+        // Shrinking a line moves later lines back up
+        index.update(2, 2);
+        assert_eq!(index.visual_row_of(9), 10);
+        assert_eq!(index.logical_line_at(4), 3);
+        assert_eq!(index.total_rows(), 11);
+
+        // Every row maps back to the line that starts at or before it
+        for row in 0..index.total_rows() {
+            let line = index.logical_line_at(row);
+            assert!(index.visual_row_of(line) <= row);
+            assert!(row < index.visual_row_of(line + 1));
+        }
+    }
+
+    #[test]
+    fn test_insert_and_remove_lines() {
+        let mut index = LineIndex::new(&[1, 2, 3]);
+        index.insert(1, &[4, 5]);
+        assert_eq!(index, LineIndex::new(&[1, 4, 5, 2, 3]));
+        assert_eq!(index.visual_row_of(3), 10);
+
+        index.remove(0..2);
+        assert_eq!(index, LineIndex::new(&[5, 2, 3]));
+        assert_eq!(index.logical_line_at(6), 1);
+
+        // Out-of-range edits clamp to the end
+        index.insert(10, &[1]);
+        index.remove(2..10);
+        assert_eq!(index, LineIndex::new(&[5, 2]));
+    }
+
+    #[test]
+    fn test_empty_index() {
+        let index = LineIndex::default();
+        assert_eq!(index.visual_row_of(3), 0);
+        assert_eq!(index.logical_line_at(3), 0);
+        assert_eq!(index.total_rows(), 0);
+    }
+}
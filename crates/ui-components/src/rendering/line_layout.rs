@@ -2,22 +2,194 @@
 //!
 //! Phase 3.2: Text Rendering Pipeline
 
-use bidi_text::*;
+use bidi_text::{ wrap_graphemes, BidiInfo };
+use super::font_management::FontData;
+use super::text_shaping::{ Font, ShapedGlyph, TextShaper };
 use std::ops::Range;
+use std::sync::Arc;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Advance of one grapheme, relative to the font size, when no font is set
+const FALLBACK_ADVANCE: f32 = 0.6;
+
+/// Ascent relative to the font size when no font is set
+const FALLBACK_ASCENT: f32 = 0.8;
 
 /// Line layout calculator
 pub struct LineLayout {
-    // TODO: Implement line layout state
+    /// Font used to shape and measure text; monospace metrics when unset
+    font: Option<Arc<FontData>>,
+    shaper: TextShaper,
+    /// Line height in pixels
+    pub line_height: f32,
+    /// Style applied to every run
+    pub style: TextStyle,
 }
 
 impl LineLayout {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            font: None,
+            shaper: TextShaper::new(),
+            line_height: 20.0,
+            style: TextStyle {
+                color: palette::Srgb::new(0.0, 0.0, 0.0),
+                font_family: String::new(),
+                font_size: 14.0,
+            },
+        }
+    }
+
+    /// Set the font used for shaping and measurement
+    pub fn set_font(&mut self, font: Option<Arc<FontData>>) {
+        if let Some(font) = &font {
+            self.style.font_family = font.family.clone();
+        }
+        self.font = font;
     }
 
     /// Calculate visual lines from a logical line
-    pub fn calculate_visual_lines(&self, _text: &str, _max_width: f32) -> Vec<VisualLine> {
-        todo!("Implement line layout calculation")
+    ///
+    /// Lines wrap with [`wrap_graphemes`], breaking after whitespace where
+    /// possible and mid-word only when a word is wider than `max_width`;
+    /// breaks always fall on grapheme boundaries. Whitespace at a break hangs
+    /// past the edge and is not counted in `pixel_width`. The returned lines
+    /// have `logical_line` 0; callers laying out a document set it.
+    pub fn calculate_visual_lines(&self, text: &str, max_width: f32) -> Vec<VisualLine> {
+        let graphemes = self.measure_graphemes(text);
+
+        let widths: Vec<(f32, bool)> = graphemes
+            .iter()
+            .map(|grapheme| (grapheme.width, grapheme.whitespace))
+            .collect();
+        let breaks = wrap_graphemes(&widths, max_width);
+
+        let bidi = BidiInfo::new(text, None);
+        let ascent = self.ascent();
+
+        breaks
+            .into_iter()
+            .enumerate()
+            .map(|(index, range)| {
+                let line = &graphemes[range.clone()];
+                let byte_range = match (line.first(), line.last()) {
+                    (Some(first), Some(last)) => first.bytes.start..last.bytes.end,
+                    _ => text.len()..text.len(),
+                };
+                let char_start = text[..byte_range.start].chars().count();
+                let char_end = char_start + text[byte_range.clone()].chars().count();
+
+                let pixel_width = line
+                    .iter()
+                    .rev()
+                    .skip_while(|g| g.whitespace)
+                    .map(|g| g.width)
+                    .sum();
+
+                VisualLine {
+                    logical_line: 0,
+                    visual_line_index: index,
+                    char_range: char_start..char_end,
+                    pixel_width,
+                    baseline_y: (index as f32) * self.line_height + ascent,
+                    bidi_runs: self.visual_runs(text, &bidi, byte_range, &graphemes),
+                }
+            })
+            .collect()
+    }
+
+    /// Split text into graphemes with their advances
+    fn measure_graphemes(&self, text: &str) -> Vec<MeasuredGrapheme> {
+        let mut graphemes: Vec<MeasuredGrapheme> = text
+            .grapheme_indices(true)
+            .map(|(start, grapheme)| MeasuredGrapheme {
+                bytes: start..start + grapheme.len(),
+                width: 0.0,
+                whitespace: grapheme.chars().all(char::is_whitespace),
+            })
+            .collect();
+
+        match self.shaping_font() {
+            Some(font) => {
+                // Attribute each glyph's advance to the grapheme holding its cluster
+                let shaped = self.shaper.shape(text, &font);
+                for glyph in &shaped.glyphs {
+                    let index = graphemes.partition_point(|g| g.bytes.end <= glyph.cluster);
+                    if let Some(grapheme) = graphemes.get_mut(index) {
+                        grapheme.width += glyph.x_advance;
+                    }
+                }
+            }
+            None => {
+                let advance = self.style.font_size * FALLBACK_ADVANCE;
+                for grapheme in &mut graphemes {
+                    grapheme.width = advance;
+                }
+            }
+        }
+
+        graphemes
+    }
+
+    /// Build visually ordered runs for one wrapped line
+    fn visual_runs(
+        &self,
+        text: &str,
+        bidi: &BidiInfo,
+        byte_range: Range<usize>,
+        graphemes: &[MeasuredGrapheme]
+    ) -> Vec<VisualTextRun> {
+        if byte_range.is_empty() {
+            return Vec::new();
+        }
+
+        let mut runs = bidi.visual_runs(byte_range);
+        reorder_runs(&mut runs);
+
+        let font = self.shaping_font();
+        let mut x_offset = 0.0;
+
+        runs.into_iter()
+            .map(|run| {
+                let direction = if run.direction.is_rtl() { Direction::RTL } else { Direction::LTR };
+                let run_text = &text[run.logical_range.clone()];
+                let width: f32 = graphemes
+                    .iter()
+                    .filter(|g| run.logical_range.contains(&g.bytes.start))
+                    .map(|g| g.width)
+                    .sum();
+
+                let glyphs: Vec<ShapedGlyph> = match &font {
+                    Some(font) =>
+                        self.shaper.shape_with_hints(run_text, font, Some(direction), None).glyphs,
+                    None => Vec::new(),
+                };
+
+                let visual_run = VisualTextRun {
+                    text: run_text.to_string(),
+                    direction,
+                    x_offset,
+                    glyphs,
+                    style: self.style.clone(),
+                };
+                x_offset += width;
+                visual_run
+            })
+            .collect()
+    }
+
+    fn shaping_font(&self) -> Option<Font<'_>> {
+        self.font.as_deref().and_then(|font| Font::from_font_data(font, self.style.font_size))
+    }
+
+    fn ascent(&self) -> f32 {
+        match self.shaping_font() {
+            Some(font) => {
+                let face = font.face();
+                (face.ascender() as f32) * self.style.font_size / (face.units_per_em() as f32)
+            }
+            None => self.style.font_size * FALLBACK_ASCENT,
+        }
     }
 }
 
@@ -27,13 +199,54 @@ impl Default for LineLayout {
     }
 }
 
+/// Reorder runs from logical to visual order (UAX #9 rule L2)
+///
+/// From the highest embedding level down to the lowest odd level, every
+/// maximal sequence of runs at that level or higher is reversed.
+fn reorder_runs(runs: &mut [bidi_text::algorithm::VisualRun]) {
+    let Some(max_level) = runs.iter().map(|run| run.level).max() else {
+        return;
+    };
+    let Some(min_odd) = runs
+        .iter()
+        .map(|run| run.level)
+        .filter(|level| level % 2 == 1)
+        .min() else {
+        return;
+    };
+
+    for level in (min_odd..=max_level).rev() {
+        let mut i = 0;
+        while i < runs.len() {
+            if runs[i].level >= level {
+                let start = i;
+                while i < runs.len() && runs[i].level >= level {
+                    i += 1;
+                }
+                runs[start..i].reverse();
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// A grapheme with its byte range and advance
+struct MeasuredGrapheme {
+    bytes: Range<usize>,
+    width: f32,
+    whitespace: bool,
+}
+
 /// A visual line (may be part of a wrapped logical line)
 pub struct VisualLine {
     pub logical_line: usize,
     pub visual_line_index: usize,
+    /// Character range within the logical line
     pub char_range: Range<usize>,
     pub pixel_width: f32,
     pub baseline_y: f32,
+    /// Runs in visual (left-to-right display) order
     pub bidi_runs: Vec<VisualTextRun>,
 }
 
@@ -54,8 +267,74 @@ pub enum Direction {
 }
 
 /// Text style for a run
+#[derive(Debug, Clone)]
 pub struct TextStyle {
     pub color: palette::Srgb,
     pub font_family: String,
     pub font_size: f32,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Layout with monospace fallback metrics: 6px per grapheme
+    fn layout() -> LineLayout {
+        let mut layout = LineLayout::new();
+        layout.style.font_size = 10.0;
+        layout
+    }
+
+    #[test]
+    fn test_wraps_ltr_at_whitespace() {
+        let lines = layout().calculate_visual_lines("The quick brown fox jumps", 60.0);
+
+        let ranges: Vec<Range<usize>> = lines
+            .iter()
+            .map(|line| line.char_range.clone())
+            .collect();
+        assert_eq!(ranges, vec![0..10, 10..20, 20..25]);
+
+        // Trailing spaces hang past the edge
+        assert_eq!(lines[0].pixel_width, 54.0);
+        assert_eq!(lines[2].pixel_width, 30.0);
+        assert_eq!(lines[1].baseline_y, 28.0);
+
+        assert_eq!(lines[0].bidi_runs.len(), 1);
+        assert_eq!(lines[0].bidi_runs[0].text, "The quick ");
+        assert_eq!(lines[0].bidi_runs[0].direction, Direction::LTR);
+    }
+
+    #[test]
+    fn test_wraps_mid_word_without_whitespace() {
+        let lines = layout().calculate_visual_lines("abcdefghijklmnopqrstuvwxy", 60.0);
+
+        let ranges: Vec<Range<usize>> = lines
+            .iter()
+            .map(|line| line.char_range.clone())
+            .collect();
+        assert_eq!(ranges, vec![0..10, 10..20, 20..25]);
+        assert!(lines.iter().all(|line| line.pixel_width <= 60.0));
+    }
+
+    #[test]
+    fn test_never_splits_grapheme_clusters() {
+        // Each "e\u{301}" is one grapheme of two chars
+        let text = "e\u{301}".repeat(12);
+        let lines = layout().calculate_visual_lines(&text, 60.0);
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].char_range, 0..20);
+        assert_eq!(lines[1].char_range, 20..24);
+    }
+
+    #[test]
+    fn test_runs_in_visual_order() {
+        let lines = layout().calculate_visual_lines("abc שלום def", 1000.0);
+        let runs = &lines[0].bidi_runs;
+
+        assert_eq!(runs.len(), 3);
+        assert_eq!(runs[1].direction, Direction::RTL);
+        assert!(runs[0].x_offset < runs[1].x_offset && runs[1].x_offset < runs[2].x_offset);
+    }
+}
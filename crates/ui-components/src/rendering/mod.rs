@@ -6,10 +6,12 @@ pub mod text_shaping;
 pub mod font_management;
 pub mod glyph_cache;
 pub mod line_layout;
+pub mod line_index;
 pub mod viewport;
 
-pub use text_shaping::{ TextShaper, ShapedText, ShapedGlyph };
-pub use font_management::{ FontManager, FontData, Script };
+pub use text_shaping::{ TextShaper, ShapedText, ShapedGlyph, Font };
+pub use font_management::{ FontManager, FontData, FontFallbackChain, Script };
 pub use glyph_cache::{ GlyphCache, GlyphCacheKey };
 pub use line_layout::{ LineLayout, VisualLine, VisualTextRun };
+pub use line_index::LineIndex;
 pub use viewport::{ Viewport, ScrollAnchor };
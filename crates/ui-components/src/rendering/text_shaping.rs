@@ -4,20 +4,74 @@
 
 use rustybuzz;
 use ttf_parser;
+use super::font_management::{ FontData, Script };
+use super::line_layout::Direction;
 
 /// Text shaping service for complex script support
 pub struct TextShaper {
-    // TODO: Implement text shaping state
+    /// OpenType features applied to every run
+    features: Vec<rustybuzz::Feature>,
 }
 
 impl TextShaper {
     pub fn new() -> Self {
-        Self {}
+        Self { features: Vec::new() }
+    }
+
+    /// Set OpenType features applied to every run (e.g. disabling `liga`)
+    pub fn set_features(&mut self, features: Vec<rustybuzz::Feature>) {
+        self.features = features;
     }
 
     /// Shape a text run with the given font and features
-    pub fn shape(&self, _text: &str, _font: &Font) -> ShapedText {
-        todo!("Implement text shaping")
+    ///
+    /// Direction and script are guessed from the text.
+    pub fn shape(&self, text: &str, font: &Font) -> ShapedText {
+        self.shape_with_hints(text, font, None, None)
+    }
+
+    /// Shape a text run, overriding the guessed direction and script
+    ///
+    /// Hints matter for runs the guess gets wrong, such as an RTL run
+    /// produced by the bidi pipeline that starts with neutral characters.
+    pub fn shape_with_hints(
+        &self,
+        text: &str,
+        font: &Font,
+        direction: Option<Direction>,
+        script: Option<Script>
+    ) -> ShapedText {
+        let mut buffer = rustybuzz::UnicodeBuffer::new();
+        buffer.push_str(text);
+
+        if let Some(direction) = direction {
+            buffer.set_direction(match direction {
+                Direction::LTR => rustybuzz::Direction::LeftToRight,
+                Direction::RTL => rustybuzz::Direction::RightToLeft,
+            });
+        }
+        if let Some(script) = script.and_then(buzz_script) {
+            buffer.set_script(script);
+        }
+
+        let output = rustybuzz::shape(&font.face, &self.features, buffer);
+        let scale = font.scale();
+
+        let glyphs = output
+            .glyph_infos()
+            .iter()
+            .zip(output.glyph_positions())
+            .map(|(info, pos)| ShapedGlyph {
+                glyph_id: info.glyph_id,
+                cluster: info.cluster as usize,
+                x_offset: (pos.x_offset as f32) * scale,
+                y_offset: (pos.y_offset as f32) * scale,
+                x_advance: (pos.x_advance as f32) * scale,
+                y_advance: (pos.y_advance as f32) * scale,
+            })
+            .collect();
+
+        ShapedText { glyphs }
     }
 }
 
@@ -27,14 +81,39 @@ impl Default for TextShaper {
     }
 }
 
+/// Map a font selection script to a HarfBuzz script tag
+fn buzz_script(script: Script) -> Option<rustybuzz::Script> {
+    match script {
+        Script::Latin => Some(rustybuzz::script::LATIN),
+        Script::Arabic => Some(rustybuzz::script::ARABIC),
+        Script::Hebrew => Some(rustybuzz::script::HEBREW),
+        Script::Other => None,
+    }
+}
+
 /// Shaped text result
 pub struct ShapedText {
     pub glyphs: Vec<ShapedGlyph>,
 }
 
+impl ShapedText {
+    /// Total horizontal advance in pixels
+    pub fn width(&self) -> f32 {
+        self.glyphs
+            .iter()
+            .map(|glyph| glyph.x_advance)
+            .sum()
+    }
+}
+
 /// A single shaped glyph with positioning
+///
+/// Offsets and advances are in pixels at the font's size, with y pointing up
+/// as in font coordinates.
 pub struct ShapedGlyph {
     pub glyph_id: u32,
+    /// Byte offset of the first character this glyph was shaped from
+    pub cluster: usize,
     pub x_offset: f32,
     pub y_offset: f32,
     pub x_advance: f32,
@@ -42,6 +121,74 @@ pub struct ShapedGlyph {
 }
 
 /// Font reference for shaping
-pub struct Font {
-    // TODO: Implement font data
+pub struct Font<'a> {
+    face: rustybuzz::Face<'a>,
+    /// Font size in pixels per em
+    pub size: f32,
+}
+
+impl<'a> Font<'a> {
+    /// Create a font from raw font file data and a face index
+    pub fn from_data(data: &'a [u8], index: u32, size: f32) -> Option<Self> {
+        let face = rustybuzz::Face::from_slice(data, index)?;
+        Some(Self { face, size })
+    }
+
+    /// Create a font from a face loaded by the font manager
+    pub fn from_font_data(font: &'a FontData, size: f32) -> Option<Self> {
+        Self::from_data(&font.data, font.index, size)
+    }
+
+    /// Underlying face for glyph and metric queries
+    pub fn face(&self) -> &ttf_parser::Face<'a> {
+        &self.face
+    }
+
+    /// Pixels per font unit
+    fn scale(&self) -> f32 {
+        self.size / (self.face.units_per_em() as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rendering::font_management::FontManager;
+
+    #[test]
+    fn test_shape_fi_ligature() {
+        // Noto Sans ships an fi ligature
+        let data = FontManager::with_test_fonts().load_font("Noto Sans").unwrap();
+        let font = Font::from_font_data(&data, 16.0).unwrap();
+        let shaper = TextShaper::new();
+
+        let shaped = shaper.shape("fi", &font);
+        assert!(shaped.glyphs.len() < "fi".chars().count());
+        assert!(shaped.width() > 0.0);
+
+        // The ligature can be turned off through features
+        let mut plain = TextShaper::new();
+        plain.set_features(
+            vec![rustybuzz::Feature::new(ttf_parser::Tag::from_bytes(b"liga"), 0, ..)]
+        );
+        assert_eq!(plain.shape("fi", &font).glyphs.len(), 2);
+    }
+
+    #[test]
+    fn test_shape_rtl_hint_reverses_clusters() {
+        let data = FontManager::with_test_fonts().get_fallback(Script::Hebrew).unwrap();
+        let font = Font::from_font_data(&data, 16.0).unwrap();
+
+        let shaped = TextShaper::new().shape_with_hints(
+            "שלום",
+            &font,
+            Some(Direction::RTL),
+            Some(Script::Hebrew)
+        );
+        let clusters: Vec<usize> = shaped.glyphs
+            .iter()
+            .map(|glyph| glyph.cluster)
+            .collect();
+        assert_eq!(clusters, vec![6, 4, 2, 0]);
+    }
 }
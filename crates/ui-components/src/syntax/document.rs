@@ -0,0 +1,82 @@
+//! Shared parse of the active document
+//!
+//! Phase 3.3: Syntax Highlighting
+
+use super::highlighting::{ HighlightResult, SyntaxHighlighter };
+use editor_core::Buffer;
+use std::sync::Arc;
+
+/// Latest parse of a buffer, shared by folding, outline, bracket matching and
+/// highlighting
+///
+/// Parses come from the highlighter's cache, which is keyed by buffer ID and
+/// version, so features asking for the same buffer state get the same `Arc`
+/// without reparsing.
+pub struct DocumentModel {
+    highlighter: SyntaxHighlighter,
+}
+
+impl DocumentModel {
+    pub fn new() -> Self {
+        Self::with_highlighter(SyntaxHighlighter::new())
+    }
+
+    /// Create a document model that parses with the given highlighter
+    pub fn with_highlighter(highlighter: SyntaxHighlighter) -> Self {
+        Self { highlighter }
+    }
+
+    /// Get the parse for the buffer's current version, reparsing if stale
+    pub fn get(&mut self, buffer: &Buffer) -> Arc<HighlightResult> {
+        self.highlighter.highlight_for(buffer)
+    }
+
+    /// The highlighter whose cache the model shares
+    pub fn highlighter(&self) -> &SyntaxHighlighter {
+        &self.highlighter
+    }
+}
+
+impl Default for DocumentModel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use editor_core::{ BufferId, Position };
+
+    #[test]
+    fn test_same_version_shares_parse() {
+        let buffer = Buffer::from_text(BufferId::new(1), "#let x = 1");
+        let mut model = DocumentModel::new();
+
+        let first = model.get(&buffer);
+        let second = model.get(&buffer);
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+
+    #[test]
+    fn test_version_bump_reparses() {
+        let mut buffer = Buffer::from_text(BufferId::new(1), "#let x = 1");
+        let mut model = DocumentModel::new();
+
+        let before = model.get(&buffer);
+        buffer.insert(Position::new(0, 10), "0").unwrap();
+        let after = model.get(&buffer);
+
+        assert!(!Arc::ptr_eq(&before, &after));
+        assert_eq!(after.source.text(), "#let x = 10");
+    }
+
+    #[test]
+    fn test_shares_the_highlighter_cache() {
+        let buffer = Buffer::from_text(BufferId::new(1), "= Title");
+        let mut model = DocumentModel::new();
+
+        let parse = model.get(&buffer);
+        assert!(Arc::ptr_eq(&parse, &model.highlighter().highlight_for(&buffer)));
+    }
+}
@@ -2,51 +2,499 @@
 //!
 //! Phase 3.3: Syntax Highlighting
 
-use typst_syntax::{ parse, SyntaxNode, SyntaxKind };
-use std::sync::Arc;
+use super::theme::Theme;
+use crate::decorations::{ InlineDecoration, InlineDecorationKind };
+use editor_core::{ Buffer, BufferId, Version };
+use palette::Srgb;
+use typst_syntax::{ LinkedNode, SyntaxNode, SyntaxKind, Source };
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::sync::{ Arc, Mutex };
+
+/// Number of buffer versions whose highlighting is kept
+const CACHED_VERSIONS: usize = 8;
 
 /// Syntax highlighter using Typst's parser
 pub struct SyntaxHighlighter {
-    // Typst parser is stateless, no need to store state
+    /// Results of recent `highlight_for` calls, most recent first
+    cache: Mutex<VecDeque<(BufferId, Version, Arc<HighlightResult>)>>,
 }
 
 impl SyntaxHighlighter {
     pub fn new() -> Self {
-        Self {}
+        Self {
+            cache: Mutex::new(VecDeque::with_capacity(CACHED_VERSIONS)),
+        }
+    }
+
+    /// Highlight a buffer, reusing the result for a version seen recently
+    ///
+    /// A new version whose text matches a cached one, as after undo or redo,
+    /// reuses that result too.
+    pub fn highlight_for(&self, buffer: &Buffer) -> Arc<HighlightResult> {
+        let key = (buffer.id(), buffer.version());
+        let mut cache = self.cache.lock().unwrap();
+        if let Some(index) = cache.iter().position(|(id, version, _)| (*id, *version) == key) {
+            let entry = cache.remove(index).expect("index found above");
+            let result = entry.2.clone();
+            cache.push_front(entry);
+            return result;
+        }
+
+        let text = buffer.text();
+        let same_text = cache
+            .iter()
+            .find(|(id, _, result)| *id == key.0 && result.source.text() == text)
+            .map(|(_, _, result)| result.clone());
+        let result = same_text.unwrap_or_else(|| self.highlight(&text));
+
+        cache.push_front((key.0, key.1, result.clone()));
+        cache.truncate(CACHED_VERSIONS);
+        result
     }
 
     /// Parse and highlight Typst text
     pub fn highlight(&self, text: &str) -> Arc<HighlightResult> {
-        let root = parse(text);
+        Self::build_result(Source::detached(text))
+    }
+
+    /// Re-highlight after an edit, reusing the previous parse where possible
+    ///
+    /// `edit` is `(start, end, replacement)` in byte offsets of the previous
+    /// text. Typst's incremental reparser only re-parses the affected subtree
+    /// and falls back to a full parse when the edit changes nesting (e.g. an
+    /// unclosed bracket). Tokens are likewise only extracted for the
+    /// reparsed range and spliced into the previous ones. The result is
+    /// equivalent to `highlight(new_text)`.
+    pub fn highlight_incremental(
+        &self,
+        prev: &HighlightResult,
+        edit: (usize, usize, &str),
+        new_text: &str
+    ) -> Arc<HighlightResult> {
+        let (start, end, replacement) = edit;
+        let old_text = prev.source.text();
+
+        let valid_edit =
+            start <= end &&
+            end <= old_text.len() &&
+            old_text.is_char_boundary(start) &&
+            old_text.is_char_boundary(end);
+        if !valid_edit {
+            return self.highlight(new_text);
+        }
+
+        let mut source = prev.source.clone();
+        let reparsed = source.edit(start..end, replacement);
+
+        // The edit does not describe how we got to `new_text`
+        if source.text() != new_text {
+            return self.highlight(new_text);
+        }
+
+        let grown = (replacement.len() as isize) - ((end - start) as isize);
+        let shift = |offset: usize| ((offset as isize) + grown) as usize;
+        let old_reparsed = reparsed.start..shift(reparsed.end).max(reparsed.start);
+        if reparsed.is_empty() || old_reparsed.is_empty() {
+            return Self::build_result(source);
+        }
+
+        // Nodes outside the reparsed range are unchanged, so only tokens
+        // overlapping it are extracted again. The rest are reused, shifted
+        // past the edit when they come after it.
+        let root = source.root().clone();
+        let mut changed = Self::extract_tokens_in(&root, &reparsed).into_iter().peekable();
+        let before_end = prev.tokens.partition_point(|token| token.start < old_reparsed.start);
+        let after_start = prev.tokens.partition_point(|token| token.start < old_reparsed.end);
+
+        // Tokens are in preorder, which for non-empty ranges is by start and
+        // then by descending end
+        let order = |token: &HighlightToken| (token.start, std::cmp::Reverse(token.end));
+        let before = prev.tokens[..before_end]
+            .iter()
+            .filter(|token| token.end <= old_reparsed.start);
+        let mut tokens = Vec::with_capacity(prev.tokens.len());
+        for token in before {
+            while let Some(next) = changed.next_if(|next| order(next) < order(token)) {
+                tokens.push(next);
+            }
+            tokens.push(token.clone());
+        }
+        tokens.extend(changed);
+        tokens.extend(
+            prev.tokens[after_start..].iter().map(|token| HighlightToken {
+                start: shift(token.start),
+                end: shift(token.end),
+                token_type: token.token_type,
+            })
+        );
+
+        Arc::new(HighlightResult { root, tokens, source })
+    }
+
+    /// Resolve token colors against a theme
+    ///
+    /// Spans follow token order, so nested tokens come after (and are drawn
+    /// over) the tokens that contain them.
+    pub fn styled_spans(result: &HighlightResult, theme: &Theme) -> Vec<(Range<usize>, Srgb)> {
+        result.tokens
+            .iter()
+            .map(|token| (token.start..token.end, theme.color_for_token(token.token_type)))
+            .collect()
+    }
+
+    /// Find the bracket matching the one next to a cursor
+    ///
+    /// Checks the character after the cursor first, then the one before it.
+    /// Brackets are matched through the syntax tree, so bracket characters
+    /// inside strings, comments, or plain markup text never match. Returns the
+    /// byte offset of the matching bracket, or `None` if it is unmatched.
+    pub fn matching_bracket(&self, result: &HighlightResult, byte_offset: usize) -> Option<usize> {
+        let root = LinkedNode::new(&result.root);
+
+        [Some(byte_offset), byte_offset.checked_sub(1)]
+            .into_iter()
+            .flatten()
+            .filter_map(|offset| Self::leaf_at(&root, offset))
+            .find(|leaf| Self::bracket_pair(leaf.kind()).is_some())
+            .and_then(|leaf| Self::find_match(&leaf))
+    }
+
+    /// Find delimiters the parser could not pair up
+    ///
+    /// Covers `(`, `[`, `{` and `$`. Delimiters are read from the syntax tree,
+    /// so those inside strings, comments, raw text or plain markup text are
+    /// ignored. Returns each delimiter's byte offset, in source order.
+    pub fn unbalanced_delimiters(&self, result: &HighlightResult) -> Vec<(usize, DelimiterError)> {
+        let mut errors = Vec::new();
+        let mut stack = vec![LinkedNode::new(&result.root)];
+
+        while let Some(node) = stack.pop() {
+            if node.kind() == SyntaxKind::Error {
+                let error = match node.text().as_str() {
+                    "(" | "[" | "{" | "$" => Some(DelimiterError::UnclosedOpen),
+                    ")" | "]" | "}" => Some(DelimiterError::UnexpectedClose),
+                    _ => None,
+                };
+                if let Some(error) = error {
+                    errors.push((node.offset(), error));
+                }
+            }
+
+            let children: Vec<_> = node.children().collect();
+            stack.extend(children.into_iter().rev());
+        }
+
+        errors
+    }
+
+    /// Error squiggles under unbalanced delimiters, for feedback before the
+    /// compiler reports
+    pub fn delimiter_decorations(&self, result: &HighlightResult) -> Vec<InlineDecoration> {
+        self.unbalanced_delimiters(result)
+            .into_iter()
+            .map(|(offset, _)| InlineDecoration {
+                range: offset..offset + 1,
+                kind: InlineDecorationKind::ErrorSquiggle,
+            })
+            .collect()
+    }
+
+    /// Whether the byte at `byte_offset` is inside math
+    ///
+    /// The `$` delimiters themselves are not, and neither is content nested
+    /// in math, such as the body of `#box[..]` inside an equation.
+    pub fn is_in_math(&self, result: &HighlightResult, byte_offset: usize) -> bool {
+        let root = LinkedNode::new(&result.root);
+        let Some(leaf) = Self::leaf_at(&root, byte_offset) else {
+            return false;
+        };
+        std::iter::successors(Some(leaf), |node| node.parent().cloned())
+            .map(|node| node.kind())
+            .find(|kind| matches!(kind, SyntaxKind::Math | SyntaxKind::Markup))
+            == Some(SyntaxKind::Math)
+    }
+
+    /// Byte ranges inside math, in order and with adjacent ranges merged
+    ///
+    /// Follows the same rules as [`is_in_math`](Self::is_in_math), but walks
+    /// the leaves once, so word motion can test every grapheme of a line
+    /// without descending from the root each time.
+    pub fn math_ranges(&self, result: &HighlightResult) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut stack = vec![(LinkedNode::new(&result.root), false)];
+
+        while let Some((node, in_math)) = stack.pop() {
+            let in_math = match node.kind() {
+                SyntaxKind::Math => true,
+                SyntaxKind::Markup => false,
+                _ => in_math,
+            };
+
+            if node.get().children().len() > 0 {
+                let children: Vec<_> = node.children().collect();
+                stack.extend(children.into_iter().rev().map(|child| (child, in_math)));
+            } else if in_math && !node.range().is_empty() {
+                match ranges.last_mut() {
+                    Some(last) if last.end == node.offset() => {
+                        last.end = node.range().end;
+                    }
+                    _ => ranges.push(node.range()),
+                }
+            }
+        }
+
+        ranges
+    }
+
+    /// Collect the document's headings in source order
+    ///
+    /// Headings nested in content blocks are included. The level is the
+    /// number of `=` in the heading marker, so a panel can rebuild nesting
+    /// from consecutive entries.
+    pub fn outline(&self, result: &HighlightResult, source: &str) -> Vec<OutlineEntry> {
+        let mut entries = Vec::new();
+        let mut stack = vec![LinkedNode::new(&result.root)];
+
+        while let Some(node) = stack.pop() {
+            if node.kind() == SyntaxKind::Heading {
+                let level = node
+                    .children()
+                    .find(|child| child.kind() == SyntaxKind::HeadingMarker)
+                    .map_or(1, |marker| marker.len());
+                let title = node
+                    .children()
+                    .find(|child| child.kind() == SyntaxKind::Markup)
+                    .and_then(|body| source.get(body.range()))
+                    .unwrap_or_default()
+                    .trim()
+                    .to_string();
+
+                entries.push(OutlineEntry { level, title, range: node.range() });
+            }
+
+            let children: Vec<_> = node.children().collect();
+            stack.extend(children.into_iter().rev());
+        }
+
+        entries
+    }
+
+    /// Names of the labels defined in the document, in source order
+    ///
+    /// Each name appears once, without its angle brackets. Labels are read
+    /// from the syntax tree, so text like `<x>` in comments, strings or raw
+    /// blocks does not count.
+    pub fn collect_labels(&self, result: &HighlightResult, source: &str) -> Vec<String> {
+        let mut labels: Vec<String> = Vec::new();
+        let mut stack = vec![LinkedNode::new(&result.root)];
+
+        while let Some(node) = stack.pop() {
+            if node.kind() == SyntaxKind::Label {
+                let name = source
+                    .get(node.range())
+                    .unwrap_or_default()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>');
+                if !name.is_empty() && !labels.iter().any(|label| label == name) {
+                    labels.push(name.to_string());
+                }
+            }
+
+            let children: Vec<_> = node.children().collect();
+            stack.extend(children.into_iter().rev());
+        }
+
+        labels
+    }
+
+    /// Name of the identifier at a cursor, checking the byte after the cursor
+    /// first and then the one before it
+    pub fn identifier_at(&self, result: &HighlightResult, byte_offset: usize) -> Option<String> {
+        let root = LinkedNode::new(&result.root);
+
+        [Some(byte_offset), byte_offset.checked_sub(1)]
+            .into_iter()
+            .flatten()
+            .filter_map(|offset| Self::leaf_at(&root, offset))
+            .find(|leaf| matches!(leaf.kind(), SyntaxKind::Ident | SyntaxKind::MathIdent))
+            .map(|leaf| leaf.text().to_string())
+    }
+
+    /// Find the byte ranges where an identifier is read and written
+    ///
+    /// Writes are the names a binding introduces: `#let` targets (including
+    /// destructuring and function names), closure parameters and `for` loop
+    /// patterns. Every other use is a read. Named-argument keys and field
+    /// names after `.` are not occurrences of the variable.
+    pub fn occurrences(&self, result: &HighlightResult, name: &str) -> SymbolOccurrences {
+        let mut occurrences = SymbolOccurrences::default();
+        let mut stack = vec![LinkedNode::new(&result.root)];
+
+        while let Some(node) = stack.pop() {
+            let is_name = matches!(node.kind(), SyntaxKind::Ident | SyntaxKind::MathIdent) &&
+                node.text().as_str() == name;
+            if is_name {
+                match Self::occurrence_kind(&node) {
+                    Some(true) => occurrences.writes.push(node.range()),
+                    Some(false) => occurrences.reads.push(node.range()),
+                    None => {}
+                }
+            }
+
+            let children: Vec<_> = node.children().collect();
+            stack.extend(children.into_iter().rev());
+        }
+
+        occurrences
+    }
+
+    /// Classify an identifier as a write (`true`), a read (`false`), or not a
+    /// variable occurrence at all
+    fn occurrence_kind(ident: &LinkedNode) -> Option<bool> {
+        let Some(parent) = ident.parent() else {
+            return Some(false);
+        };
+        let precedes = |kind: SyntaxKind| {
+            std::iter::successors(ident.next_sibling(), |node| node.next_sibling())
+                .any(|node| node.kind() == kind)
+        };
+        let is_first_child = ident.prev_sibling().is_none();
+
+        match parent.kind() {
+            SyntaxKind::LetBinding => Some(precedes(SyntaxKind::Eq)),
+            SyntaxKind::ForLoop => Some(precedes(SyntaxKind::In)),
+            SyntaxKind::Params | SyntaxKind::Destructuring => Some(true),
+            // The function name in `#let f(x) = ..`
+            SyntaxKind::Closure => Some(is_first_child && precedes(SyntaxKind::Params)),
+            SyntaxKind::Spread | SyntaxKind::Named => {
+                let grandparent = parent.parent().map(|node| node.kind());
+                let in_pattern = matches!(
+                    grandparent,
+                    Some(SyntaxKind::Params | SyntaxKind::Destructuring)
+                );
+                match (parent.kind(), is_first_child) {
+                    // Keys in `(key: value)` name a field, not a variable
+                    (SyntaxKind::Named, true) =>
+                        (grandparent == Some(SyntaxKind::Params)).then_some(true),
+                    _ => Some(in_pattern),
+                }
+            }
+            SyntaxKind::FieldAccess if !is_first_child => None,
+            _ => Some(false),
+        }
+    }
+
+    /// Find the leaf covering the byte at `offset`
+    fn leaf_at<'a>(root: &LinkedNode<'a>, offset: usize) -> Option<LinkedNode<'a>> {
+        let mut node = root.clone();
+        loop {
+            if node.children().next().is_none() {
+                return Some(node);
+            }
+            node = node.children().find(|child| {
+                child.offset() <= offset && offset < child.offset() + child.len()
+            })?;
+        }
+    }
+
+    /// Get the (left, right) kinds of the bracket pair a kind belongs to
+    fn bracket_pair(kind: SyntaxKind) -> Option<(SyntaxKind, SyntaxKind)> {
+        match kind {
+            SyntaxKind::LeftParen | SyntaxKind::RightParen =>
+                Some((SyntaxKind::LeftParen, SyntaxKind::RightParen)),
+            SyntaxKind::LeftBracket | SyntaxKind::RightBracket =>
+                Some((SyntaxKind::LeftBracket, SyntaxKind::RightBracket)),
+            SyntaxKind::LeftBrace | SyntaxKind::RightBrace =>
+                Some((SyntaxKind::LeftBrace, SyntaxKind::RightBrace)),
+            _ => None,
+        }
+    }
+
+    /// Search the bracket's siblings for its counterpart
+    fn find_match(bracket: &LinkedNode) -> Option<usize> {
+        let (left, right) = Self::bracket_pair(bracket.kind())?;
+        let parent = bracket.parent()?;
+        let siblings: Vec<LinkedNode> = parent.children().collect();
+
+        let forward = bracket.kind() == left;
+        let (open, close) = if forward { (left, right) } else { (right, left) };
+
+        let candidates: Box<dyn Iterator<Item = &LinkedNode>> = if forward {
+            Box::new(siblings[bracket.index() + 1..].iter())
+        } else {
+            Box::new(siblings[..bracket.index()].iter().rev())
+        };
+
+        let mut depth = 0;
+        for sibling in candidates {
+            if sibling.kind() == open {
+                depth += 1;
+            } else if sibling.kind() == close {
+                if depth == 0 {
+                    return Some(sibling.offset());
+                }
+                depth -= 1;
+            }
+        }
+
+        None
+    }
+
+    /// Build a highlight result from a parsed source
+    fn build_result(source: Source) -> Arc<HighlightResult> {
+        let root = source.root().clone();
         let tokens = Self::extract_tokens(&root);
 
         Arc::new(HighlightResult {
             root,
             tokens,
+            source,
         })
     }
 
     /// Extract tokens from the syntax tree for highlighting
-    /// Uses iterative approach to avoid stack overflow on deep trees
     fn extract_tokens(node: &SyntaxNode) -> Vec<HighlightToken> {
+        Self::extract_tokens_in(node, &(0..node.len()))
+    }
+
+    /// Extract tokens of the nodes overlapping `region`, in preorder
+    ///
+    /// Subtrees outside the region are not visited. Empty nodes color
+    /// nothing, so they get no token. Uses an iterative approach to avoid
+    /// stack overflow on deep trees.
+    fn extract_tokens_in(node: &SyntaxNode, region: &Range<usize>) -> Vec<HighlightToken> {
         let mut tokens = Vec::new();
-        let mut stack = vec![node];
+        // Each entry carries the byte offset at which its node starts
+        let mut stack = vec![(node, 0)];
 
-        while let Some(current) = stack.pop() {
-            let token_type = Self::syntax_kind_to_token_type(current.kind());
+        while let Some((current, offset)) = stack.pop() {
+            let end = offset + current.len();
+            if offset >= region.end || end <= region.start {
+                continue;
+            }
 
-            if let Some(token_type) = token_type {
+            let token_type = Self::syntax_kind_to_token_type(current.kind());
+            if let Some(token_type) = token_type.filter(|_| end > offset) {
                 tokens.push(HighlightToken {
-                    start: 0, // TODO: Calculate actual byte offset in Phase 3 implementation
-                    end: 0, // TODO: Calculate actual byte offset in Phase 3 implementation
+                    start: offset,
+                    end,
                     token_type,
                 });
             }
 
-            // Push children in reverse order to process in correct order
-            for child in current.children().rev() {
-                stack.push(child);
-            }
+            // Compute child offsets in source order, then push them in reverse
+            // so they are popped (and emitted) in the correct order
+            let mut child_offset = offset;
+            let children: Vec<_> = current
+                .children()
+                .map(|child| {
+                    let start = child_offset;
+                    child_offset += child.len();
+                    (child, start)
+                })
+                .collect();
+            stack.extend(children.into_iter().rev());
         }
 
         tokens
@@ -126,11 +574,42 @@ impl Default for SyntaxHighlighter {
 pub struct HighlightResult {
     pub root: SyntaxNode,
     pub tokens: Vec<HighlightToken>,
+    /// Parsed source, kept for incremental re-highlighting
+    pub source: Source,
+}
+
+/// Where a symbol is read and written, as byte ranges in source order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SymbolOccurrences {
+    pub reads: Vec<Range<usize>>,
+    pub writes: Vec<Range<usize>>,
+}
+
+/// Why a delimiter is unbalanced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DelimiterError {
+    /// An opening delimiter that is never closed
+    UnclosedOpen,
+    /// A closing delimiter with nothing open to close
+    UnexpectedClose,
+}
+
+/// A heading in the document outline
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineEntry {
+    /// Heading depth, starting at 1 for `=`
+    pub level: usize,
+    pub title: String,
+    /// Byte range of the whole heading
+    pub range: Range<usize>,
 }
 
 /// A highlighted token
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct HighlightToken {
+    /// Byte offset where the token starts
     pub start: usize,
+    /// Byte offset where the token ends (exclusive)
     pub end: usize,
     pub token_type: TokenType,
 }
@@ -151,3 +630,281 @@ pub enum TokenType {
     Label,
     Reference,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_byte_offsets() {
+        let source = "#let x = 1";
+        let result = SyntaxHighlighter::new().highlight(source);
+
+        let keyword = result.tokens
+            .iter()
+            .find(|t| t.token_type == TokenType::Keyword)
+            .expect("let keyword token");
+        assert_eq!(keyword.start..keyword.end, 1..4);
+        assert_eq!(&source[keyword.start..keyword.end], "let");
+
+        let constant = result.tokens
+            .iter()
+            .find(|t| t.token_type == TokenType::Constant)
+            .expect("integer token");
+        assert_eq!(&source[constant.start..constant.end], "1");
+    }
+
+    #[test]
+    fn test_keyword_resolves_to_theme_color() {
+        let theme = Theme::default_dark();
+        let result = SyntaxHighlighter::new().highlight("#let x = 1");
+
+        let spans = SyntaxHighlighter::styled_spans(&result, &theme);
+        assert_eq!(spans.len(), result.tokens.len());
+
+        let (_, color) = spans
+            .iter()
+            .find(|(range, _)| *range == (1..4))
+            .expect("keyword span");
+        assert_eq!(*color, theme.colors.keyword);
+        assert_eq!(theme.color_for_token(TokenType::Math), theme.colors.math);
+    }
+
+    #[test]
+    fn test_matching_nested_brackets() {
+        let highlighter = SyntaxHighlighter::new();
+        let result = highlighter.highlight("#f(a, (b + c))[x]");
+
+        assert_eq!(highlighter.matching_bracket(&result, 2), Some(13));
+        assert_eq!(highlighter.matching_bracket(&result, 6), Some(12));
+        assert_eq!(highlighter.matching_bracket(&result, 12), Some(6));
+        // Between `)` and `[` the bracket after the cursor wins
+        assert_eq!(highlighter.matching_bracket(&result, 14), Some(16));
+        assert_eq!(highlighter.matching_bracket(&result, 16), Some(14));
+    }
+
+    #[test]
+    fn test_bracket_in_string_does_not_match() {
+        let highlighter = SyntaxHighlighter::new();
+        let result = highlighter.highlight("#let s = \"(\" + str(1)");
+
+        assert_eq!(highlighter.matching_bracket(&result, 10), None);
+        assert_eq!(highlighter.matching_bracket(&result, 11), None);
+        assert_eq!(highlighter.matching_bracket(&result, 18), Some(20));
+    }
+
+    #[test]
+    fn test_outline_levels_and_titles() {
+        let highlighter = SyntaxHighlighter::new();
+        let source = "= A\nIntro text\n\n== B\n#block[\n= C\n]\n";
+        let result = highlighter.highlight(source);
+
+        let outline = highlighter.outline(&result, source);
+        let summary: Vec<(usize, &str)> = outline
+            .iter()
+            .map(|entry| (entry.level, entry.title.as_str()))
+            .collect();
+        assert_eq!(summary, vec![(1, "A"), (2, "B"), (1, "C")]);
+        assert_eq!(&source[outline[1].range.clone()], "== B");
+    }
+
+    #[test]
+    fn test_occurrences_read_and_write() {
+        let highlighter = SyntaxHighlighter::new();
+        let source = "#let x = 1; #x + #x";
+        let result = highlighter.highlight(source);
+
+        assert_eq!(highlighter.identifier_at(&result, 13).as_deref(), Some("x"));
+        let occurrences = highlighter.occurrences(&result, "x");
+        assert_eq!(occurrences.writes.len(), 1);
+        assert_eq!(occurrences.writes[0], 5..6);
+        assert_eq!(occurrences.reads, vec![13..14, 18..19]);
+    }
+
+    #[test]
+    fn test_occurrences_skip_named_keys_and_fields() {
+        let highlighter = SyntaxHighlighter::new();
+        let source = "#let f(x, y: 2) = x\n#f(1, y: x.y)";
+        let result = highlighter.highlight(source);
+
+        let x = highlighter.occurrences(&result, "x");
+        assert_eq!(x.writes.len(), 1);
+        assert_eq!(x.writes[0], 7..8);
+        assert_eq!(x.reads, vec![18..19, 29..30]);
+
+        let y = highlighter.occurrences(&result, "y");
+        assert_eq!(y.writes.len(), 1);
+        assert_eq!(y.writes[0], 10..11);
+        assert!(y.reads.is_empty());
+    }
+
+    #[test]
+    fn test_unmatched_bracket() {
+        let highlighter = SyntaxHighlighter::new();
+        let result = highlighter.highlight("#f(a");
+
+        assert_eq!(highlighter.matching_bracket(&result, 2), None);
+    }
+
+    #[test]
+    fn test_unclosed_content_block() {
+        let highlighter = SyntaxHighlighter::new();
+        let source = "#block[Some #\"[\" text // ( [\n and $x$";
+        let result = highlighter.highlight(source);
+
+        // Delimiters in strings and comments are ignored
+        let errors = highlighter.unbalanced_delimiters(&result);
+        assert_eq!(errors, vec![(6, DelimiterError::UnclosedOpen)]);
+
+        let decorations = highlighter.delimiter_decorations(&result);
+        assert_eq!(decorations.len(), 1);
+        assert_eq!(decorations[0].range, 6..7);
+        assert!(matches!(decorations[0].kind, InlineDecorationKind::ErrorSquiggle));
+    }
+
+    #[test]
+    fn test_extra_closing_paren() {
+        let highlighter = SyntaxHighlighter::new();
+        let source = "#{ calc.max(1, 2)) }";
+        let result = highlighter.highlight(source);
+
+        let errors = highlighter.unbalanced_delimiters(&result);
+        assert_eq!(errors, vec![(17, DelimiterError::UnexpectedClose)]);
+
+        let balanced = highlighter.highlight("#f(a)[b] $x$ #{ (1, 2) }");
+        assert!(highlighter.unbalanced_delimiters(&balanced).is_empty());
+    }
+
+    #[test]
+    fn test_highlight_cache_by_version() {
+        use editor_core::{ Buffer, BufferId, Position };
+
+        let highlighter = SyntaxHighlighter::new();
+        let mut buffer = Buffer::from_text(BufferId::new(1), "#let x = 1");
+        let first = highlighter.highlight_for(&buffer);
+        assert!(Arc::ptr_eq(&first, &highlighter.highlight_for(&buffer)));
+
+        // An edit re-parses
+        buffer.insert(Position::new(0, 10), "0").unwrap();
+        let edited = highlighter.highlight_for(&buffer);
+        assert!(!Arc::ptr_eq(&first, &edited));
+        assert_eq!(edited.source.text(), "#let x = 10");
+        assert!(Arc::ptr_eq(&edited, &highlighter.highlight_for(&buffer)));
+
+        // Undo returns to text that is still cached
+        buffer.undo().unwrap();
+        assert!(Arc::ptr_eq(&first, &highlighter.highlight_for(&buffer)));
+
+        // Another buffer at the same version is highlighted separately
+        let other = Buffer::from_text(BufferId::new(2), "= Title");
+        assert_eq!(other.version(), Buffer::from_text(BufferId::new(1), "").version());
+        assert_eq!(highlighter.highlight_for(&other).source.text(), "= Title");
+    }
+
+    #[test]
+    fn test_math_detection_and_word_stops() {
+        use editor_core::{ Buffer, BufferId, Position };
+
+        let highlighter = SyntaxHighlighter::new();
+        let source = "Let $a + b/c$ hold";
+        let result = highlighter.highlight(source);
+
+        assert!(!highlighter.is_in_math(&result, 0));
+        assert!(!highlighter.is_in_math(&result, 4));
+        assert!(highlighter.is_in_math(&result, 5));
+        assert!(highlighter.is_in_math(&result, 9));
+        assert!(highlighter.is_in_math(&result, 11));
+        assert!(!highlighter.is_in_math(&result, 12));
+        assert!(!highlighter.is_in_math(&result, 15));
+
+        // The bulk ranges agree with the single-offset query
+        let math = highlighter.math_ranges(&result);
+        let in_math = |offset: usize| math.iter().any(|range| range.contains(&offset));
+        for offset in 0..source.len() {
+            assert_eq!(in_math(offset), highlighter.is_in_math(&result, offset), "{}", offset);
+        }
+
+        // Inside math, `/` separates identifiers as a word stop
+        let buffer = Buffer::from_text(BufferId::new(1), source);
+        let next = |column| buffer.next_word_boundary_with(Position::new(0, column), in_math);
+        assert_eq!(next(5).unwrap(), Position::new(0, 6));
+        assert_eq!(next(9).unwrap(), Position::new(0, 10));
+        assert_eq!(next(10).unwrap(), Position::new(0, 11));
+        assert_eq!(next(11).unwrap(), Position::new(0, 12));
+
+        // `_` splits words only inside math
+        let source = "$x_i$ x_i";
+        let result = highlighter.highlight(source);
+        let buffer = Buffer::from_text(BufferId::new(1), source);
+        let math = highlighter.math_ranges(&result);
+        let in_math = |offset: usize| math.iter().any(|range| range.contains(&offset));
+        let next = |column| buffer.next_word_boundary_with(Position::new(0, column), in_math);
+        assert_eq!(next(1).unwrap(), Position::new(0, 2));
+        assert_eq!(next(6).unwrap(), Position::new(0, 9));
+    }
+
+    #[test]
+    fn test_labels_offered_after_at() {
+        use crate::completion::LabelCompleter;
+
+        let highlighter = SyntaxHighlighter::new();
+        let source = concat!(
+            "#figure(image(\"a.png\"), caption: [A]) <fig:first>\n",
+            "// #figure[old] <fig:stale>\n",
+            "#figure(table[x], caption: [B]) <fig:second>\n",
+            "See @fig:first and `<fig:raw>`. <intro>\n"
+        );
+        let result = highlighter.highlight(source);
+
+        let labels = highlighter.collect_labels(&result, source);
+        assert_eq!(labels, vec!["fig:first", "fig:second", "intro"]);
+
+        let completer = LabelCompleter::new(labels);
+        let offered: Vec<String> = completer
+            .complete("@fig")
+            .into_iter()
+            .map(|item| item.label)
+            .collect();
+        assert_eq!(offered, vec!["fig:first", "fig:second"]);
+    }
+
+    #[test]
+    fn test_incremental_matches_full_highlight() {
+        let highlighter = SyntaxHighlighter::new();
+        let original = "= Heading\n#let x = 1\nSome *strong* text with $x + 1$.\n";
+
+        let edits: &[(usize, usize, &str)] = &[
+            // Typing inside markup
+            (21, 25, "Other"),
+            // Changing a literal
+            (19, 20, "42"),
+            // Opening an unclosed bracket changes nesting
+            (22, 22, "#f["),
+            // Deleting across node boundaries
+            (5, 25, ""),
+            // Appending at the end
+            (original.len(), original.len(), "// done"),
+            // Editing inside math and inside a strong span
+            (50, 51, "y^2"),
+            (29, 33, "bold"),
+            // Editing code before later markup
+            (15, 16, "total"),
+        ];
+
+        let prev = highlighter.highlight(original);
+        for &(start, end, replacement) in edits {
+            let mut new_text = original.to_string();
+            new_text.replace_range(start..end, replacement);
+
+            let incremental = highlighter.highlight_incremental(
+                &prev,
+                (start, end, replacement),
+                &new_text
+            );
+            let full = highlighter.highlight(&new_text);
+
+            assert_eq!(incremental.tokens, full.tokens, "edit {:?}", (start, end, replacement));
+            assert_eq!(incremental.source.text(), new_text);
+        }
+    }
+}
@@ -4,6 +4,15 @@
 
 pub mod highlighting;
 pub mod theme;
+pub mod document;
 
-pub use highlighting::{ SyntaxHighlighter, HighlightResult, TokenType };
+pub use highlighting::{
+    SyntaxHighlighter,
+    HighlightResult,
+    DelimiterError,
+    OutlineEntry,
+    SymbolOccurrences,
+    TokenType,
+};
 pub use theme::{ Theme, ThemeManager, ThemeVariant, ColorScheme };
+pub use document::DocumentModel;
@@ -2,7 +2,9 @@
 //!
 //! Phase 3.3: Syntax Highlighting
 
-use palette::Srgb;
+use super::highlighting::TokenType;
+use palette::{ Mix, Srgb };
+use preview::PreviewStyle;
 use serde::{ Deserialize, Serialize };
 use std::collections::HashMap;
 
@@ -36,6 +38,10 @@ impl Theme {
                 comment: Srgb::new(0.4, 0.4, 0.4),
                 type_name: Srgb::new(0.4, 0.2, 0.7),
                 operator: Srgb::new(0.5, 0.5, 0.5),
+                markup: Srgb::new(0.0, 0.0, 0.0),
+                math: Srgb::new(0.0, 0.45, 0.55),
+                label: Srgb::new(0.55, 0.35, 0.0),
+                reference: Srgb::new(0.1, 0.4, 0.6),
                 error: Srgb::new(1.0, 0.0, 0.0),
                 warning: Srgb::new(1.0, 0.6, 0.0),
                 info: Srgb::new(0.0, 0.5, 0.9),
@@ -81,6 +87,10 @@ impl Theme {
                 comment: Srgb::new(0.5, 0.5, 0.5),
                 type_name: Srgb::new(0.6, 0.4, 0.8),
                 operator: Srgb::new(0.7, 0.7, 0.7),
+                markup: Srgb::new(0.9, 0.9, 0.9),
+                math: Srgb::new(0.4, 0.8, 0.8),
+                label: Srgb::new(0.9, 0.7, 0.4),
+                reference: Srgb::new(0.5, 0.7, 0.9),
                 error: Srgb::new(1.0, 0.3, 0.3),
                 warning: Srgb::new(1.0, 0.7, 0.3),
                 info: Srgb::new(0.3, 0.7, 1.0),
@@ -108,6 +118,201 @@ impl Theme {
     }
 }
 
+impl Theme {
+    /// Resolve the color used to draw a syntax token
+    pub fn color_for_token(&self, token: TokenType) -> Srgb {
+        let colors = &self.colors;
+        match token {
+            TokenType::Keyword => colors.keyword,
+            TokenType::Function => colors.function,
+            TokenType::Variable => colors.variable,
+            TokenType::Constant => colors.constant,
+            TokenType::String => colors.string,
+            TokenType::Comment => colors.comment,
+            TokenType::Type => colors.type_name,
+            TokenType::Operator => colors.operator,
+            TokenType::Markup => colors.markup,
+            TokenType::Math => colors.math,
+            TokenType::Label => colors.label,
+            TokenType::Reference => colors.reference,
+        }
+    }
+
+    /// Preview chrome styling derived from this theme
+    ///
+    /// Shadows are only drawn on light themes, where they stay visible.
+    pub fn preview_style(&self) -> PreviewStyle {
+        PreviewStyle {
+            background: self.colors.panel_background,
+            page_shadow: self.variant == ThemeVariant::Light,
+            page_gap: self.spacing.panel_padding * 2.0,
+        }
+    }
+}
+
+impl Theme {
+    /// Convert a VS Code (TextMate) color theme to a theme
+    ///
+    /// Reads the `colors` map for UI colors and `tokenColors` rules for syntax
+    /// colors. A rule applies to a token category when its scope is a prefix of
+    /// the category's scope; the most specific rule wins. Categories with no
+    /// matching rule use the foreground color.
+    pub fn from_vscode_json(value: &serde_json::Value) -> Result<Theme, Box<dyn std::error::Error>> {
+        let object = value.as_object().ok_or("VS Code theme must be a JSON object")?;
+
+        let variant = match object.get("type").and_then(|t| t.as_str()) {
+            Some("light") | Some("hc-light") => ThemeVariant::Light,
+            _ => ThemeVariant::Dark,
+        };
+        let mut theme = match variant {
+            ThemeVariant::Light => Theme::default_light(),
+            ThemeVariant::Dark => Theme::default_dark(),
+        };
+        if let Some(name) = object.get("name").and_then(|n| n.as_str()) {
+            theme.name = name.to_string();
+        }
+
+        if let Some(colors) = object.get("colors").and_then(|c| c.as_object()) {
+            for (key, field) in VSCODE_UI_COLORS {
+                if let Some(color) = colors.get(*key).and_then(|c| c.as_str()).and_then(parse_hex_color) {
+                    *field(&mut theme.colors) = color;
+                }
+            }
+        }
+
+        // Most specific matching rule per token category: (scope length, color)
+        let mut matches: Vec<Option<(usize, Srgb)>> = vec![None; VSCODE_TOKEN_SCOPES.len()];
+        let rules = object
+            .get("tokenColors")
+            .and_then(|t| t.as_array())
+            .map(|a| a.as_slice())
+            .unwrap_or(&[]);
+
+        for rule in rules {
+            let Some(color) = rule
+                .pointer("/settings/foreground")
+                .and_then(|c| c.as_str())
+                .and_then(parse_hex_color) else {
+                continue;
+            };
+
+            let scopes: Vec<&str> = match rule.get("scope") {
+                Some(serde_json::Value::String(scope)) => scope.split(',').map(str::trim).collect(),
+                Some(serde_json::Value::Array(scopes)) =>
+                    scopes
+                        .iter()
+                        .filter_map(|s| s.as_str())
+                        .collect(),
+                _ => continue,
+            };
+
+            for scope in scopes {
+                for (i, (category_scope, _)) in VSCODE_TOKEN_SCOPES.iter().enumerate() {
+                    let applies =
+                        *category_scope == scope ||
+                        category_scope
+                            .strip_prefix(scope)
+                            .is_some_and(|rest| rest.starts_with('.'));
+                    // Later rules win ties, as in VS Code
+                    if applies && matches[i].is_none_or(|(len, _)| scope.len() >= len) {
+                        matches[i] = Some((scope.len(), color));
+                    }
+                }
+            }
+        }
+
+        let foreground = theme.colors.foreground;
+        for ((_, field), matched) in VSCODE_TOKEN_SCOPES.iter().zip(matches) {
+            *field(&mut theme.colors) = matched.map_or(foreground, |(_, color)| color);
+        }
+
+        Ok(theme)
+    }
+}
+
+/// Accessor for a color in a scheme
+type ColorField = fn(&mut ColorScheme) -> &mut Srgb;
+
+/// VS Code `colors` keys mapped to UI colors
+const VSCODE_UI_COLORS: &[(&str, ColorField)] = &[
+    ("editor.background", |c| &mut c.background),
+    ("editor.foreground", |c| &mut c.foreground),
+    ("editorGroup.border", |c| &mut c.border),
+    ("editor.selectionBackground", |c| &mut c.selection),
+    ("editorCursor.foreground", |c| &mut c.cursor),
+    ("editor.lineHighlightBackground", |c| &mut c.current_line),
+    ("editorError.foreground", |c| &mut c.error),
+    ("editorWarning.foreground", |c| &mut c.warning),
+    ("editorInfo.foreground", |c| &mut c.info),
+    ("editorHint.foreground", |c| &mut c.hint),
+    ("button.background", |c| &mut c.button_background),
+    ("button.hoverBackground", |c| &mut c.button_hover),
+    ("input.background", |c| &mut c.input_background),
+    ("panel.background", |c| &mut c.panel_background),
+    ("sideBar.background", |c| &mut c.sidebar_background),
+    ("statusBar.background", |c| &mut c.statusbar_background),
+];
+
+/// TextMate scopes representing each token color
+const VSCODE_TOKEN_SCOPES: &[(&str, ColorField)] = &[
+    ("keyword.control", |c| &mut c.keyword),
+    ("keyword.operator", |c| &mut c.operator),
+    ("entity.name.function", |c| &mut c.function),
+    ("variable.other", |c| &mut c.variable),
+    ("constant.numeric", |c| &mut c.constant),
+    ("string.quoted", |c| &mut c.string),
+    ("comment.line", |c| &mut c.comment),
+    ("entity.name.type", |c| &mut c.type_name),
+    ("markup.bold", |c| &mut c.markup),
+    ("markup.math", |c| &mut c.math),
+    ("entity.name.label", |c| &mut c.label),
+    ("markup.other.reference", |c| &mut c.reference),
+];
+
+/// Parse a `#rgb`, `#rrggbb` or `#rrggbbaa` color (alpha is ignored)
+fn parse_hex_color(hex: &str) -> Option<Srgb> {
+    let hex = hex.strip_prefix('#').filter(|hex| hex.is_ascii())?;
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok().map(|v| (v as f32) / 255.0);
+
+    match hex.len() {
+        3 => {
+            let expand = |i: usize| channel(&hex[i..i + 1].repeat(2));
+            Some(Srgb::new(expand(0)?, expand(1)?, expand(2)?))
+        }
+        6 | 8 => Some(Srgb::new(channel(&hex[0..2])?, channel(&hex[2..4])?, channel(&hex[4..6])?)),
+        _ => None,
+    }
+}
+
+impl Theme {
+    /// Interpolate between two themes for animated transitions
+    ///
+    /// Colors and numeric typography/spacing are interpolated; names, fonts
+    /// and the variant switch to the target at the midpoint.
+    pub fn lerp(&self, other: &Theme, t: f32) -> Theme {
+        let t = t.clamp(0.0, 1.0);
+        let discrete = if t < 0.5 { self } else { other };
+
+        Theme {
+            name: discrete.name.clone(),
+            variant: discrete.variant,
+            colors: self.colors.lerp(&other.colors, t),
+            typography: Typography {
+                editor_font: discrete.typography.editor_font.clone(),
+                editor_size: lerp_f32(self.typography.editor_size, other.typography.editor_size, t),
+                ui_font: discrete.typography.ui_font.clone(),
+                ui_size: lerp_f32(self.typography.ui_size, other.typography.ui_size, t),
+                line_height: lerp_f32(self.typography.line_height, other.typography.line_height, t),
+            },
+            spacing: Spacing {
+                gutter_width: lerp_f32(self.spacing.gutter_width, other.spacing.gutter_width, t),
+                line_padding: lerp_f32(self.spacing.line_padding, other.spacing.line_padding, t),
+                panel_padding: lerp_f32(self.spacing.panel_padding, other.spacing.panel_padding, t),
+            },
+        }
+    }
+}
+
 impl Default for Theme {
     fn default() -> Self {
         Self::default_light()
@@ -155,6 +360,14 @@ pub struct ColorScheme {
     pub type_name: Srgb,
     #[serde(with = "serde_srgb")]
     pub operator: Srgb,
+    #[serde(with = "serde_srgb")]
+    pub markup: Srgb,
+    #[serde(with = "serde_srgb")]
+    pub math: Srgb,
+    #[serde(with = "serde_srgb")]
+    pub label: Srgb,
+    #[serde(with = "serde_srgb")]
+    pub reference: Srgb,
 
     // Semantic colors
     #[serde(with = "serde_srgb")]
@@ -181,6 +394,63 @@ pub struct ColorScheme {
     pub statusbar_background: Srgb,
 }
 
+impl ColorScheme {
+    /// Interpolate between two color schemes
+    ///
+    /// Colors are mixed in linear light, so midpoints match perceived
+    /// brightness. `t` is clamped to `0.0..=1.0`.
+    pub fn lerp(&self, other: &ColorScheme, t: f32) -> ColorScheme {
+        let t = t.clamp(0.0, 1.0);
+        ColorScheme {
+            background: lerp_color(self.background, other.background, t),
+            foreground: lerp_color(self.foreground, other.foreground, t),
+            border: lerp_color(self.border, other.border, t),
+            selection: lerp_color(self.selection, other.selection, t),
+            cursor: lerp_color(self.cursor, other.cursor, t),
+            current_line: lerp_color(self.current_line, other.current_line, t),
+            keyword: lerp_color(self.keyword, other.keyword, t),
+            function: lerp_color(self.function, other.function, t),
+            variable: lerp_color(self.variable, other.variable, t),
+            constant: lerp_color(self.constant, other.constant, t),
+            string: lerp_color(self.string, other.string, t),
+            comment: lerp_color(self.comment, other.comment, t),
+            type_name: lerp_color(self.type_name, other.type_name, t),
+            operator: lerp_color(self.operator, other.operator, t),
+            markup: lerp_color(self.markup, other.markup, t),
+            math: lerp_color(self.math, other.math, t),
+            label: lerp_color(self.label, other.label, t),
+            reference: lerp_color(self.reference, other.reference, t),
+            error: lerp_color(self.error, other.error, t),
+            warning: lerp_color(self.warning, other.warning, t),
+            info: lerp_color(self.info, other.info, t),
+            hint: lerp_color(self.hint, other.hint, t),
+            button_background: lerp_color(self.button_background, other.button_background, t),
+            button_hover: lerp_color(self.button_hover, other.button_hover, t),
+            input_background: lerp_color(self.input_background, other.input_background, t),
+            panel_background: lerp_color(self.panel_background, other.panel_background, t),
+            sidebar_background: lerp_color(self.sidebar_background, other.sidebar_background, t),
+            statusbar_background: lerp_color(self.statusbar_background, other.statusbar_background, t),
+        }
+    }
+}
+
+/// Mix two sRGB colors in linear space
+fn lerp_color(from: Srgb, to: Srgb, t: f32) -> Srgb {
+    // Avoid round-trip error at the endpoints
+    if t <= 0.0 {
+        return from;
+    }
+    if t >= 1.0 {
+        return to;
+    }
+    Srgb::from_linear(from.into_linear().mix(to.into_linear(), t))
+}
+
+/// Linearly interpolate a number
+fn lerp_f32(from: f32, to: f32, t: f32) -> f32 {
+    from + (to - from) * t
+}
+
 /// Typography settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Typography {
@@ -254,9 +524,32 @@ impl ThemeManager {
         }
     }
 
-    pub fn load_theme(&mut self, _path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        // TODO: Implement theme loading from file
-        Ok(())
+    /// Load a theme from a JSON file and add it to the available themes
+    ///
+    /// Accepts either our native theme format or a VS Code color theme
+    /// (detected by its `tokenColors` or `colors` keys). The theme is
+    /// registered under its lowercased name, which is returned.
+    pub fn load_theme(&mut self, path: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let content = std::fs::read_to_string(path)?;
+        let value: serde_json::Value = serde_json::from_str(&content)?;
+
+        let is_native = value.get("variant").is_some();
+        let theme = if is_native {
+            serde_json::from_value::<Theme>(value)?
+        } else if value.get("tokenColors").is_some() || value.get("colors").is_some() {
+            Theme::from_vscode_json(&value)?
+        } else {
+            return Err(format!("Unrecognized theme format: {}", path).into());
+        };
+
+        let key = theme.name.to_lowercase();
+        self.themes.insert(key.clone(), theme);
+        Ok(key)
+    }
+
+    /// Check if a theme is available
+    pub fn has_theme(&self, name: &str) -> bool {
+        self.themes.contains_key(name)
     }
 }
 
@@ -265,3 +558,111 @@ impl Default for ThemeManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vscode_theme_conversion() {
+        let json =
+            serde_json::json!({
+            "name": "Tiny",
+            "type": "dark",
+            "colors": {
+                "editor.background": "#1e1e1e",
+                "editor.foreground": "#d4d4d4"
+            },
+            "tokenColors": [
+                { "scope": "comment", "settings": { "foreground": "#6a9955" } },
+                { "scope": ["string", "string.quoted.double"], "settings": { "foreground": "#ce9178" } },
+                { "scope": "keyword", "settings": { "foreground": "#569cd6" } },
+                { "scope": "keyword.operator", "settings": { "foreground": "#d4d4d4" } },
+                { "scope": "entity.name.function", "settings": { "foreground": "#dcdcaa" } },
+                { "scope": "meta.unknown", "settings": { "foreground": "#ff0000" } }
+            ]
+        });
+
+        let theme = Theme::from_vscode_json(&json).unwrap();
+        assert_eq!(theme.name, "Tiny");
+        assert_eq!(theme.variant, ThemeVariant::Dark);
+        assert_eq!(theme.colors.background, parse_hex_color("#1e1e1e").unwrap());
+        assert_eq!(theme.colors.keyword, parse_hex_color("#569cd6").unwrap());
+        assert_eq!(theme.colors.string, parse_hex_color("#ce9178").unwrap());
+        assert_eq!(theme.colors.comment, parse_hex_color("#6a9955").unwrap());
+        assert_eq!(theme.colors.function, parse_hex_color("#dcdcaa").unwrap());
+
+        // Categories without a matching rule fall back to the foreground
+        assert_eq!(theme.colors.variable, theme.colors.foreground);
+        assert_eq!(theme.colors.label, theme.colors.foreground);
+    }
+
+    #[test]
+    fn test_parse_hex_color_rejects_malformed() {
+        assert_eq!(parse_hex_color("#fff"), Some(Srgb::new(1.0, 1.0, 1.0)));
+        assert_eq!(parse_hex_color("#é12"), None);
+        assert_eq!(parse_hex_color("#12é456"), None);
+        assert_eq!(parse_hex_color("#12345"), None);
+        assert_eq!(parse_hex_color("123456"), None);
+    }
+
+    #[test]
+    fn test_lerp_endpoints() {
+        let light = Theme::default_light();
+        let dark = Theme::default_dark();
+
+        let start = light.lerp(&dark, 0.0);
+        assert_eq!(start.colors.background, light.colors.background);
+        assert_eq!(start.colors.keyword, light.colors.keyword);
+        assert_eq!(start.name, light.name);
+
+        let end = light.lerp(&dark, 1.0);
+        assert_eq!(end.colors.background, dark.colors.background);
+        assert_eq!(end.colors.keyword, dark.colors.keyword);
+        assert_eq!(end.variant, dark.variant);
+
+        // Out-of-range values are clamped
+        let clamped = light.lerp(&dark, 2.0);
+        assert_eq!(clamped.colors.foreground, dark.colors.foreground);
+    }
+
+    #[test]
+    fn test_lerp_mixes_in_linear_space() {
+        let mut black = Theme::default_dark().colors;
+        black.background = Srgb::new(0.0, 0.0, 0.0);
+        let mut white = Theme::default_light().colors;
+        white.background = Srgb::new(1.0, 1.0, 1.0);
+
+        let mid = black.lerp(&white, 0.5).background;
+        // Naive sRGB averaging would give 0.5; linear-light mixing gives ~0.735
+        assert!((mid.red - 0.735).abs() < 0.005, "got {}", mid.red);
+        assert_eq!(mid.red, mid.green);
+        assert_eq!(mid.green, mid.blue);
+    }
+
+    #[test]
+    fn test_preview_style_from_theme() {
+        let dark = Theme::default_dark();
+        let style = dark.preview_style();
+        assert_eq!(style.background, dark.colors.panel_background);
+        assert!(!style.page_shadow);
+        assert!(Theme::default_light().preview_style().page_shadow);
+    }
+
+    #[test]
+    fn test_load_native_theme() {
+        let mut theme = Theme::default_dark();
+        theme.name = "Midnight".to_string();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("midnight.json");
+        std::fs::write(&path, serde_json::to_string(&theme).unwrap()).unwrap();
+
+        let mut manager = ThemeManager::new();
+        let key = manager.load_theme(path.to_str().unwrap()).unwrap();
+
+        assert_eq!(key, "midnight");
+        manager.set_active_theme(key);
+        assert_eq!(manager.get_active_theme().name, "Midnight");
+    }
+}
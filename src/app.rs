@@ -1,62 +1,309 @@
 //! Main application structure
 
 use crate::state::{ ApplicationState, WindowState };
-use editor_core::{ Buffer, BufferId };
-use std::path::PathBuf;
+use crate::watch::{ FileWatcher, WatchEvent, DEFAULT_DEBOUNCE };
+use editor_core::selection::{ Cursor, Position, Selection, SelectionSet };
+use editor_core::registry::{ read_buffer, write_buffer };
+use editor_core::{ Buffer, BufferChange, BufferHandle, BufferId, BufferRegistry };
+use editor_core::{ EditorError, UndoLink };
+use lsp_client::{ workspace_edit_changes, ApplyTextEdits };
+use lsp_types::WorkspaceEdit;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex };
 
 /// The main Typst Editor application
 pub struct TypstEditor {
     /// Application state
     pub state: ApplicationState,
     /// Buffer registry
-    buffers: std::collections::HashMap<BufferId, Buffer>,
-    /// Next buffer ID
-    next_buffer_id: u64,
+    buffers: BufferRegistry,
+    /// Watches the files of open buffers; `None` if watching is unavailable
+    watcher: Option<FileWatcher>,
 }
 
 impl TypstEditor {
     pub fn new() -> Self {
         Self {
             state: ApplicationState::new(),
-            buffers: std::collections::HashMap::new(),
-            next_buffer_id: 1,
+            buffers: BufferRegistry::new(),
+            watcher: FileWatcher::new(DEFAULT_DEBOUNCE)
+                .map_err(|err| tracing::warn!("File watching unavailable: {}", err))
+                .ok(),
         }
     }
 
     /// Create a new buffer
     pub fn create_buffer(&mut self, text: &str) -> BufferId {
-        let id = BufferId::new(self.next_buffer_id);
-        self.next_buffer_id += 1;
-
-        let buffer = Buffer::from_text(id, text);
-        self.buffers.insert(id, buffer);
-
-        id
+        self.buffers.create(text)
     }
 
-    /// Get a buffer by ID
-    pub fn get_buffer(&self, id: BufferId) -> Option<&Buffer> {
-        self.buffers.get(&id)
+    /// Get a shared handle to a buffer by ID
+    pub fn get_buffer(&self, id: BufferId) -> Option<BufferHandle> {
+        self.buffers.get(id)
     }
 
-    /// Get a mutable buffer by ID
-    pub fn get_buffer_mut(&mut self, id: BufferId) -> Option<&mut Buffer> {
-        self.buffers.get_mut(&id)
+    /// Get the buffer registry
+    pub fn buffers(&self) -> &BufferRegistry {
+        &self.buffers
     }
 
     /// Open a file
     pub fn open_file(&mut self, path: PathBuf) -> Result<BufferId, std::io::Error> {
-        let id = BufferId::new(self.next_buffer_id);
-        self.next_buffer_id += 1;
-
-        let buffer = Buffer::from_file(id, path.clone())?;
-        self.buffers.insert(id, buffer);
+        let id = self.buffers.open(path.clone())?;
+        self.watch_file(&path);
 
         self.state.add_recent_file(path);
 
         Ok(id)
     }
 
+    /// Find the buffer showing a file, loading it if no buffer has it open
+    ///
+    /// Buffers loaded here are not added to the recent files.
+    fn buffer_for_path(&mut self, path: &Path) -> Result<BufferId, std::io::Error> {
+        if let Some(id) = self.buffers.find_by_path(path) {
+            return Ok(id);
+        }
+        let id = self.buffers.open(path.to_path_buf())?;
+        self.watch_file(path);
+        Ok(id)
+    }
+
+    /// Save a buffer to its file
+    ///
+    /// The write is not reported as an external change.
+    pub fn save_buffer(&mut self, id: BufferId) -> std::io::Result<()> {
+        let handle = self.buffers
+            .get(id)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "No such buffer"))?;
+        let path = {
+            let mut buffer = write_buffer(&handle);
+            buffer.save()?;
+            buffer.file_path().cloned()
+        };
+
+        if let (Some(watcher), Some(path)) = (&self.watcher, path) {
+            watcher.mark_saved(&path);
+        }
+        Ok(())
+    }
+
+    /// Close a buffer and stop watching its file
+    ///
+    /// Returns false if there is no such buffer.
+    pub fn close_buffer(&mut self, id: BufferId) -> bool {
+        let Some(handle) = self.buffers.remove(id) else {
+            return false;
+        };
+        let path = read_buffer(&handle).file_path().cloned();
+
+        if let (Some(watcher), Some(path)) = (&mut self.watcher, path) {
+            if self.buffers.find_by_path(&path).is_none() {
+                if let Err(err) = watcher.unwatch(&path) {
+                    tracing::warn!("Failed to stop watching {}: {}", path.display(), err);
+                }
+            }
+        }
+        true
+    }
+
+    /// Changes to open files made outside the editor since the last call
+    pub fn file_events(&self) -> Vec<WatchEvent> {
+        match &self.watcher {
+            Some(watcher) => watcher.events().try_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    fn watch_file(&mut self, path: &Path) {
+        if let Some(watcher) = &mut self.watcher {
+            if let Err(err) = watcher.watch(path) {
+                tracing::warn!("Failed to watch {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    /// Apply a workspace edit, such as the result of a rename
+    ///
+    /// Files without a buffer are loaded first. The undo steps of all changed
+    /// buffers are linked, so [`undo`](Self::undo) in any of them reverts the
+    /// whole edit. If any document's edits fail, the documents already
+    /// changed are reverted. Cursors and selections of the editors showing a
+    /// changed buffer move with the text around them.
+    pub fn apply_workspace_edit(&mut self, edit: &WorkspaceEdit) -> anyhow::Result<()> {
+        let mut documents = Vec::new();
+        for (uri, edits) in workspace_edit_changes(edit)? {
+            let path = uri
+                .to_file_path()
+                .map_err(|_| anyhow::anyhow!("Cannot edit non-file URI {}", uri))?;
+            let id = self.buffer_for_path(&path)?;
+            let handle = self.buffers.get(id).expect("buffer was just looked up");
+            documents.push((id, handle, edits));
+        }
+
+        let link = UndoLink::new();
+        let mut applied: Vec<BufferHandle> = Vec::new();
+        for (id, handle, edits) in documents {
+            let (outcome, changes) = {
+                let mut buffer = write_buffer(&handle);
+                let changes = Arc::new(Mutex::new(Vec::new()));
+                let sink = changes.clone();
+                let subscription = buffer.subscribe(
+                    Box::new(move |change: &BufferChange| sink.lock().unwrap().push(change.clone()))
+                );
+                let outcome = buffer.apply_text_edits(&edits);
+                buffer.unsubscribe(subscription);
+
+                let changes = std::mem::take(&mut *changes.lock().unwrap());
+                if outcome.is_ok() && !changes.is_empty() {
+                    buffer.link_last_undo(link);
+                }
+                (outcome, changes)
+            };
+            if let Err(err) = outcome {
+                for handle in applied.iter().rev() {
+                    write_buffer(handle).undo()?;
+                }
+                return Err(err.into());
+            }
+            if !changes.is_empty() {
+                self.move_editor_selections(id, &changes);
+                applied.push(handle);
+            }
+        }
+        Ok(())
+    }
+
+    /// Undo the last edit of a buffer
+    ///
+    /// If the edit is part of a workspace edit, it is reverted in every
+    /// buffer it changed. When one of those buffers has been edited since,
+    /// nothing is reverted and an error names it. Returns false if there is
+    /// nothing to undo.
+    pub fn undo(&mut self, id: BufferId) -> anyhow::Result<bool> {
+        self.step_history(id, Buffer::undo_link, Buffer::undo)
+    }
+
+    /// Redo the last undone edit of a buffer
+    ///
+    /// Workspace edits are redone in every buffer they changed, with the same
+    /// rules as [`undo`](Self::undo).
+    pub fn redo(&mut self, id: BufferId) -> anyhow::Result<bool> {
+        self.step_history(id, Buffer::redo_link, Buffer::redo)
+    }
+
+    /// Undo or redo in a buffer and in the buffers its next step is linked to
+    fn step_history(
+        &mut self,
+        id: BufferId,
+        next_link: fn(&Buffer) -> Option<UndoLink>,
+        step: fn(&mut Buffer) -> editor_core::Result<Position>
+    ) -> anyhow::Result<bool> {
+        let handle = self.buffers.get(id).ok_or_else(|| anyhow::anyhow!("No such buffer"))?;
+        let link = next_link(&read_buffer(&handle));
+        let Some(link) = link else {
+            return match step(&mut write_buffer(&handle)) {
+                Ok(_) => Ok(true),
+                Err(EditorError::UndoHistoryExhausted | EditorError::RedoHistoryExhausted) =>
+                    Ok(false),
+                Err(err) => Err(err.into()),
+            };
+        };
+
+        // Check every buffer before changing any, so a refused step leaves
+        // them all as they were
+        let mut linked = Vec::new();
+        for other in self.buffers.ids() {
+            let Some(handle) = self.buffers.get(other) else {
+                continue;
+            };
+            let (next, blocked) = {
+                let buffer = read_buffer(&handle);
+                let next = next_link(&buffer) == Some(link);
+                let blocked = !next && buffer.has_undo_link(link);
+                (next, blocked.then(|| buffer_name(&buffer)))
+            };
+            if let Some(name) = blocked {
+                anyhow::bail!("{} has changed since the workspace edit", name);
+            }
+            if next {
+                linked.push(handle);
+            }
+        }
+
+        for handle in linked {
+            step(&mut write_buffer(&handle))?;
+        }
+        Ok(true)
+    }
+
+    /// Move cursors and selections of editors showing a buffer after an edit
+    fn move_editor_selections(&mut self, id: BufferId, changes: &[BufferChange]) {
+        let map = |position: Position| {
+            changes.iter().fold(position, |position, change| change.map_position(position))
+        };
+        let editors = self.state.windows
+            .iter_mut()
+            .flat_map(|window| window.workspace.editors_mut())
+            .filter(|editor| editor.buffer_id == id);
+        for editor in editors {
+            for cursor in &mut editor.cursors {
+                *cursor = Cursor::with_affinity(map(cursor.position), cursor.affinity);
+            }
+
+            let move_selection = |selection: &Selection| {
+                let mut moved = selection.clone();
+                moved.anchor = map(selection.anchor);
+                moved.cursor.position = map(selection.cursor.position);
+                moved
+            };
+            let primary = editor.selections.primary().clone();
+            let mut selections = SelectionSet::new(move_selection(&primary));
+            for selection in editor.selections.selections() {
+                if *selection != primary {
+                    selections.add_selection(move_selection(selection));
+                }
+            }
+            editor.selections = selections;
+        }
+    }
+
+    /// Restore a saved session and reopen its editors' files
+    ///
+    /// Editors whose file can no longer be opened are dropped.
+    pub fn restore_session(&mut self, path: &Path) -> anyhow::Result<()> {
+        let settings = self.state.settings.clone();
+        let mut state = ApplicationState::load_session(path, settings)?;
+
+        for window in &mut state.windows {
+            // Sessions restore into a single pane
+            let pane = window.workspace.active_pane_mut();
+            let active = pane.active_editor.take();
+            let mut kept = Vec::new();
+
+            for (index, mut editor) in pane.editors.drain(..).enumerate() {
+                let Some(file) = editor.file_path.clone() else {
+                    continue;
+                };
+                match self.buffers.open(file.clone()) {
+                    Ok(id) => {
+                        self.watch_file(&file);
+                        editor.buffer_id = id;
+                        if active == Some(index) {
+                            pane.active_editor = Some(kept.len());
+                        }
+                        kept.push(editor);
+                    }
+                    Err(err) => tracing::warn!("Failed to reopen {:?}: {}", editor.file_path, err),
+                }
+            }
+            pane.editors = kept;
+        }
+
+        self.state = state;
+        Ok(())
+    }
+
     /// Create a new window
     pub fn new_window(&mut self) -> usize {
         let window_id = self.state.windows.len();
@@ -76,6 +323,14 @@ impl TypstEditor {
     }
 }
 
+/// Name of a buffer for messages: its file, or its id if it has none
+fn buffer_name(buffer: &Buffer) -> String {
+    match buffer.file_path() {
+        Some(path) => path.display().to_string(),
+        None => format!("{:?}", buffer.id()),
+    }
+}
+
 impl Default for TypstEditor {
     fn default() -> Self {
         Self::new()
@@ -86,6 +341,10 @@ impl Default for TypstEditor {
 mod tests {
     use super::*;
 
+    fn buffer_text(app: &TypstEditor, id: BufferId) -> String {
+        app.get_buffer(id).unwrap().read().unwrap().text()
+    }
+
     #[test]
     fn test_app_creation() {
         let app = TypstEditor::new();
@@ -98,7 +357,7 @@ mod tests {
         let id = app.create_buffer("Hello World");
 
         let buffer = app.get_buffer(id).unwrap();
-        assert_eq!(buffer.text(), "Hello World");
+        assert_eq!(buffer.read().unwrap().text(), "Hello World");
     }
 
     #[test]
@@ -108,4 +367,90 @@ mod tests {
         assert_eq!(window_id, 0);
         assert_eq!(app.state.windows.len(), 1);
     }
+
+    #[test]
+    fn test_apply_workspace_edit_across_files() {
+        use crate::state::EditorState;
+        use lsp_types::{ OptionalVersionedTextDocumentIdentifier, OneOf, TextDocumentEdit };
+        use lsp_types::{ TextEdit, Url };
+
+        let dir = tempfile::tempdir().unwrap();
+        let main = dir.path().join("main.typ");
+        let lib = dir.path().join("lib.typ");
+        std::fs::write(&main, "#import \"lib.typ\": title\n#title(\"A\") #title\n").unwrap();
+        std::fs::write(&lib, "#let title(body) = heading(body)\n").unwrap();
+
+        // main.typ is open in the active editor with the cursor after the
+        // second `title`; lib.typ is not open yet
+        let mut app = TypstEditor::new();
+        let main_id = app.open_file(main.clone()).unwrap();
+        let window = app.new_window();
+        let mut editor = EditorState::new(main_id);
+        editor.cursors = vec![Cursor::new(Position::new(1, 18))];
+        editor.selections = SelectionSet::new(Selection::collapsed(Position::new(1, 18)));
+        app.state.windows[window].workspace.add_editor(editor);
+
+        let rename = |line: u32, start: u32| TextEdit {
+            range: lsp_types::Range::new(
+                lsp_types::Position::new(line, start),
+                lsp_types::Position::new(line, start + 5)
+            ),
+            new_text: "chapter".to_string(),
+        };
+        let document_edit = |path: &Path, edits: Vec<TextEdit>| TextDocumentEdit {
+            text_document: OptionalVersionedTextDocumentIdentifier {
+                uri: Url::from_file_path(path).unwrap(),
+                version: None,
+            },
+            edits: edits.into_iter().map(OneOf::Left).collect(),
+        };
+        let edit = WorkspaceEdit {
+            document_changes: Some(
+                lsp_types::DocumentChanges::Edits(
+                    vec![
+                        document_edit(&main, vec![rename(1, 13), rename(0, 19), rename(1, 1)]),
+                        document_edit(&lib, vec![rename(0, 5)])
+                    ]
+                )
+            ),
+            ..Default::default()
+        };
+        app.apply_workspace_edit(&edit).unwrap();
+
+        let lib_id = app.buffer_for_path(&lib).unwrap();
+        assert_eq!(
+            buffer_text(&app, main_id),
+            "#import \"lib.typ\": chapter\n#chapter(\"A\") #chapter\n"
+        );
+        assert_eq!(
+            buffer_text(&app, lib_id),
+            "#let chapter(body) = heading(body)\n"
+        );
+
+        let editor = &app.state.windows[window].workspace.active_pane().editors[0];
+        assert_eq!(editor.cursors[0].position, Position::new(1, 22));
+        assert_eq!(editor.selections.primary().cursor.position, Position::new(1, 22));
+
+        // Typing after the rename is undone on its own, and until it is the
+        // rename cannot be undone from the other file
+        let handle = app.get_buffer(main_id).unwrap();
+        write_buffer(&handle).insert(Position::new(2, 0), "more").unwrap();
+        assert!(app.undo(lib_id).is_err());
+        assert!(buffer_text(&app, main_id).ends_with("#chapter\nmore"));
+        assert_eq!(buffer_text(&app, lib_id), "#let chapter(body) = heading(body)\n");
+
+        assert!(app.undo(main_id).unwrap());
+        assert!(buffer_text(&app, main_id).ends_with("#chapter\n"));
+        assert_eq!(buffer_text(&app, lib_id), "#let chapter(body) = heading(body)\n");
+
+        // One undo in either file reverts the rename in both
+        assert!(app.undo(lib_id).unwrap());
+        assert!(buffer_text(&app, main_id).contains("#title(\"A\") #title"));
+        assert_eq!(buffer_text(&app, lib_id), "#let title(body) = heading(body)\n");
+        assert!(!app.undo(lib_id).unwrap());
+
+        assert!(app.redo(main_id).unwrap());
+        assert_eq!(buffer_text(&app, lib_id), "#let chapter(body) = heading(body)\n");
+        assert!(buffer_text(&app, main_id).ends_with("#chapter(\"A\") #chapter\n"));
+    }
 }
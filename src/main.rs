@@ -2,6 +2,8 @@
 
 mod state;
 mod app;
+mod watch;
+mod workspace;
 
 use tracing_subscriber;
 
@@ -1,7 +1,9 @@
 //! Application state management
 
+use anyhow::Result;
 use editor_core::BufferId;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
 use serde::{ Deserialize, Serialize };
 
 /// Global application state
@@ -17,10 +19,26 @@ pub struct ApplicationState {
 }
 
 impl ApplicationState {
+    /// Create the application state with settings from the user's config
+    ///
+    /// Falls back to default settings if the file cannot be read or parsed.
     pub fn new() -> Self {
+        let settings = match Settings::default_path() {
+            Some(path) =>
+                Settings::load(&path).unwrap_or_else(|err| {
+                    tracing::warn!("Failed to load settings from {}: {}", path.display(), err);
+                    Settings::default()
+                }),
+            None => Settings::default(),
+        };
+        Self::with_settings(settings)
+    }
+
+    /// Create the application state with the given settings
+    pub fn with_settings(settings: Settings) -> Self {
         Self {
             windows: Vec::new(),
-            settings: Settings::default(),
+            settings,
             recent_files: Vec::new(),
             theme: "dark".to_string(),
         }
@@ -38,6 +56,46 @@ impl ApplicationState {
         // Keep only last 20
         self.recent_files.truncate(20);
     }
+
+    /// Save recent files, theme and open editors to a JSON session file
+    ///
+    /// Editors without a file path are not saved.
+    pub fn save_session(&self, path: &Path) -> Result<()> {
+        let session = Session {
+            recent_files: self.recent_files.clone(),
+            theme: self.theme.clone(),
+            windows: self.windows.iter().map(WindowSession::from_window).collect(),
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, serde_json::to_string_pretty(&session)?)?;
+        Ok(())
+    }
+
+    /// Restore application state from a session file on top of `settings`
+    ///
+    /// Sessions do not store settings, so callers pass the ones already
+    /// loaded. Recent files that no longer exist are dropped. Restored
+    /// editors get placeholder buffer IDs, numbered from 1 in window order,
+    /// until the caller opens their files.
+    pub fn load_session(path: &Path, settings: Settings) -> Result<ApplicationState> {
+        let session: Session = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+
+        let mut state = Self::with_settings(settings);
+        state.recent_files = session.recent_files
+            .into_iter()
+            .filter(|file| file.exists())
+            .collect();
+        state.theme = session.theme;
+
+        let mut next_buffer_id = 1;
+        for window in session.windows {
+            state.windows.push(window.into_window(&mut next_buffer_id));
+        }
+        Ok(state)
+    }
 }
 
 impl Default for ApplicationState {
@@ -67,24 +125,286 @@ impl WindowState {
 pub struct WorkspaceState {
     /// Project root directory
     pub root: Option<PathBuf>,
-    /// Open editors
-    pub editors: Vec<EditorState>,
-    /// Active editor index
-    pub active_editor: Option<usize>,
+    /// Editor panes and how they are split
+    pub layout: PaneNode,
+    /// Pane receiving editor commands
+    pub active_pane: PaneId,
     /// Sidebar visibility
     pub sidebar_visible: bool,
     /// Panel visibility
     pub panel_visible: bool,
+    next_pane_id: usize,
 }
 
 impl WorkspaceState {
     pub fn new() -> Self {
         Self {
             root: None,
-            editors: Vec::new(),
-            active_editor: None,
+            layout: PaneNode::Pane(Pane::new(PaneId(0))),
+            active_pane: PaneId(0),
             sidebar_visible: true,
             panel_visible: false,
+            next_pane_id: 1,
+        }
+    }
+
+    pub fn active_pane(&self) -> &Pane {
+        self.layout.find(self.active_pane).expect("active pane is in the layout")
+    }
+
+    pub fn active_pane_mut(&mut self) -> &mut Pane {
+        self.layout.find_mut(self.active_pane).expect("active pane is in the layout")
+    }
+
+    pub fn pane(&self, id: PaneId) -> Option<&Pane> {
+        self.layout.find(id)
+    }
+
+    /// All panes, in layout order
+    pub fn panes(&self) -> Vec<&Pane> {
+        let mut panes = Vec::new();
+        self.layout.collect_panes(&mut panes);
+        panes
+    }
+
+    /// Editors of all panes, for updates that apply wherever a buffer is shown
+    pub fn editors_mut(&mut self) -> Vec<&mut EditorState> {
+        let mut panes = Vec::new();
+        self.layout.collect_panes_mut(&mut panes);
+        panes
+            .into_iter()
+            .flat_map(|pane| pane.editors.iter_mut())
+            .collect()
+    }
+
+    /// Add an editor to the active pane and focus it
+    pub fn add_editor(&mut self, editor: EditorState) -> usize {
+        self.active_pane_mut().add_editor(editor)
+    }
+
+    pub fn active_editor_mut(&mut self) -> Option<&mut EditorState> {
+        self.active_pane_mut().active_editor_mut()
+    }
+
+    /// Focus the active pane's editor for a buffer, opening one if needed
+    pub fn open_or_focus(&mut self, buffer_id: BufferId) -> usize {
+        self.active_pane_mut().open_or_focus(buffer_id)
+    }
+
+    /// Close an editor of the active pane, returning it
+    ///
+    /// A pane left without editors is removed unless it is the only one;
+    /// its sibling takes over its space and focus.
+    pub fn close_editor(&mut self, index: usize) -> Option<EditorState> {
+        let editor = self.active_pane_mut().close_editor(index)?;
+        if self.active_pane().editors.is_empty() {
+            if let Some(focus) = self.layout.remove(self.active_pane) {
+                self.active_pane = focus;
+            }
+        }
+        Some(editor)
+    }
+
+    pub fn next_tab(&mut self) {
+        self.active_pane_mut().next_tab();
+    }
+
+    pub fn prev_tab(&mut self) {
+        self.active_pane_mut().prev_tab();
+    }
+
+    pub fn move_tab(&mut self, from: usize, to: usize) {
+        self.active_pane_mut().move_tab(from, to);
+    }
+
+    /// Split the active pane in two and focus the new pane
+    ///
+    /// The new pane shows the active editor's buffer, if there is one, and
+    /// is placed right of or below the active pane.
+    pub fn split_active(&mut self, orientation: Orientation) -> PaneId {
+        let id = PaneId(self.next_pane_id);
+        self.next_pane_id += 1;
+
+        let mut pane = Pane::new(id);
+        let active = self.active_pane();
+        if let Some(editor) = active.active_editor.and_then(|index| active.editors.get(index)) {
+            pane.add_editor(editor.clone());
+        }
+        self.layout.split(self.active_pane, orientation, pane);
+        self.active_pane = id;
+        id
+    }
+
+    /// Focus a pane; returns false if there is no such pane
+    pub fn focus_pane(&mut self, id: PaneId) -> bool {
+        if self.layout.find(id).is_none() {
+            return false;
+        }
+        self.active_pane = id;
+        true
+    }
+
+    /// Resize the split directly holding a pane
+    ///
+    /// `ratio` is the share of the split's first child and is clamped to
+    /// [`MIN_SPLIT_RATIO`]`..=`[`MAX_SPLIT_RATIO`]. Returns false if the pane
+    /// is not in a split.
+    pub fn set_split_ratio(&mut self, id: PaneId, ratio: f32) -> bool {
+        self.layout.set_ratio(id, ratio)
+    }
+}
+
+impl Default for WorkspaceState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Smallest share of a split either child can have
+pub const MIN_SPLIT_RATIO: f32 = 0.1;
+/// Largest share of a split either child can have
+pub const MAX_SPLIT_RATIO: f32 = 0.9;
+
+/// Identifies a pane within its workspace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PaneId(usize);
+
+/// Direction in which a split places its children
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    /// Side by side, left to right
+    Horizontal,
+    /// Stacked, top to bottom
+    Vertical,
+}
+
+/// A node of the pane layout
+pub enum PaneNode {
+    Pane(Pane),
+    /// Two nodes sharing space; `ratio` is the first child's share
+    Split {
+        orientation: Orientation,
+        children: Vec<PaneNode>,
+        ratio: f32,
+    },
+}
+
+impl PaneNode {
+    fn find(&self, id: PaneId) -> Option<&Pane> {
+        match self {
+            PaneNode::Pane(pane) => (pane.id == id).then_some(pane),
+            PaneNode::Split { children, .. } => children.iter().find_map(|child| child.find(id)),
+        }
+    }
+
+    fn find_mut(&mut self, id: PaneId) -> Option<&mut Pane> {
+        match self {
+            PaneNode::Pane(pane) => (pane.id == id).then_some(pane),
+            PaneNode::Split { children, .. } =>
+                children.iter_mut().find_map(|child| child.find_mut(id)),
+        }
+    }
+
+    fn is_pane(&self, id: PaneId) -> bool {
+        matches!(self, PaneNode::Pane(pane) if pane.id == id)
+    }
+
+    fn first_pane(&self) -> &Pane {
+        match self {
+            PaneNode::Pane(pane) => pane,
+            PaneNode::Split { children, .. } => children[0].first_pane(),
+        }
+    }
+
+    fn collect_panes<'a>(&'a self, panes: &mut Vec<&'a Pane>) {
+        match self {
+            PaneNode::Pane(pane) => panes.push(pane),
+            PaneNode::Split { children, .. } => {
+                for child in children {
+                    child.collect_panes(panes);
+                }
+            }
+        }
+    }
+
+    fn collect_panes_mut<'a>(&'a mut self, panes: &mut Vec<&'a mut Pane>) {
+        match self {
+            PaneNode::Pane(pane) => panes.push(pane),
+            PaneNode::Split { children, .. } => {
+                for child in children {
+                    child.collect_panes_mut(panes);
+                }
+            }
+        }
+    }
+
+    /// Replace pane `id` with a split of it and `pane`
+    fn split(&mut self, id: PaneId, orientation: Orientation, pane: Pane) -> bool {
+        match self {
+            PaneNode::Pane(existing) if existing.id == id => {
+                let placeholder = PaneNode::Split { orientation, children: Vec::new(), ratio: 0.5 };
+                let existing = std::mem::replace(self, placeholder);
+                if let PaneNode::Split { children, .. } = self {
+                    children.extend([existing, PaneNode::Pane(pane)]);
+                }
+                true
+            }
+            PaneNode::Pane(_) => false,
+            PaneNode::Split { children, .. } => {
+                match children.iter_mut().find(|child| child.find(id).is_some()) {
+                    Some(child) => child.split(id, orientation, pane),
+                    None => false,
+                }
+            }
+        }
+    }
+
+    /// Remove pane `id`, collapsing the split holding it into its sibling
+    ///
+    /// Returns the pane to focus instead, or `None` if the pane is not found
+    /// or is the root.
+    fn remove(&mut self, id: PaneId) -> Option<PaneId> {
+        let PaneNode::Split { children, .. } = self else {
+            return None;
+        };
+        let Some(index) = children.iter().position(|child| child.is_pane(id)) else {
+            return children.iter_mut().find_map(|child| child.remove(id));
+        };
+
+        children.remove(index);
+        let sibling = children.remove(0);
+        let focus = sibling.first_pane().id;
+        *self = sibling;
+        Some(focus)
+    }
+
+    fn set_ratio(&mut self, id: PaneId, new_ratio: f32) -> bool {
+        let PaneNode::Split { children, ratio, .. } = self else {
+            return false;
+        };
+        if children.iter().any(|child| child.is_pane(id)) {
+            *ratio = new_ratio.clamp(MIN_SPLIT_RATIO, MAX_SPLIT_RATIO);
+            return true;
+        }
+        children.iter_mut().any(|child| child.set_ratio(id, new_ratio))
+    }
+}
+
+/// A group of editors shown as tabs, one at a time
+pub struct Pane {
+    pub id: PaneId,
+    /// Open editors
+    pub editors: Vec<EditorState>,
+    /// Active editor index
+    pub active_editor: Option<usize>,
+}
+
+impl Pane {
+    pub fn new(id: PaneId) -> Self {
+        Self {
+            id,
+            editors: Vec::new(),
+            active_editor: None,
         }
     }
 
@@ -98,18 +418,93 @@ impl WorkspaceState {
     pub fn active_editor_mut(&mut self) -> Option<&mut EditorState> {
         self.active_editor.and_then(|idx| self.editors.get_mut(idx))
     }
-}
 
-impl Default for WorkspaceState {
-    fn default() -> Self {
-        Self::new()
+    /// Focus the editor showing a buffer, opening one if there is none
+    ///
+    /// Returns the index of the focused editor.
+    pub fn open_or_focus(&mut self, buffer_id: BufferId) -> usize {
+        match self.editors.iter().position(|editor| editor.buffer_id == buffer_id) {
+            Some(index) => {
+                self.active_editor = Some(index);
+                index
+            }
+            None => self.add_editor(EditorState::new(buffer_id)),
+        }
+    }
+
+    /// Close the editor at `index`, returning it
+    ///
+    /// Closing the active editor focuses the one that takes its place, or
+    /// the previous one if it was the last. Out of range indices are ignored.
+    pub fn close_editor(&mut self, index: usize) -> Option<EditorState> {
+        if index >= self.editors.len() {
+            return None;
+        }
+        let editor = self.editors.remove(index);
+
+        self.active_editor = match self.active_editor {
+            _ if self.editors.is_empty() => None,
+            Some(active) if active > index => Some(active - 1),
+            Some(active) if active == index => Some(index.min(self.editors.len() - 1)),
+            active => active,
+        };
+        Some(editor)
+    }
+
+    /// Focus the next editor, wrapping around to the first
+    pub fn next_tab(&mut self) {
+        self.cycle_tab(1);
+    }
+
+    /// Focus the previous editor, wrapping around to the last
+    pub fn prev_tab(&mut self) {
+        self.cycle_tab(self.editors.len().saturating_sub(1));
+    }
+
+    fn cycle_tab(&mut self, step: usize) {
+        if self.editors.is_empty() {
+            return;
+        }
+        let len = self.editors.len();
+        self.active_editor = Some(match self.active_editor {
+            Some(active) => (active + step) % len,
+            None => 0,
+        });
+    }
+
+    /// Move the editor at `from` to `to`, keeping the same editor active
+    ///
+    /// `to` is clamped to the last position; an out of range `from` is
+    /// ignored.
+    pub fn move_tab(&mut self, from: usize, to: usize) {
+        if from >= self.editors.len() {
+            return;
+        }
+        let to = to.min(self.editors.len() - 1);
+        let editor = self.editors.remove(from);
+        self.editors.insert(to, editor);
+
+        self.active_editor = self.active_editor.map(|active| {
+            if active == from {
+                to
+            } else if from < active && active <= to {
+                active - 1
+            } else if to <= active && active < from {
+                active + 1
+            } else {
+                active
+            }
+        });
     }
 }
 
 /// Editor state for a single buffer
+#[derive(Clone)]
 pub struct EditorState {
     /// Buffer ID
     pub buffer_id: BufferId,
+    /// File shown in the editor, if saved
+    pub file_path: Option<PathBuf>,
     /// Scroll position
     pub scroll_x: f32,
     pub scroll_y: f32,
@@ -123,6 +518,7 @@ impl EditorState {
     pub fn new(buffer_id: BufferId) -> Self {
         Self {
             buffer_id,
+            file_path: None,
             scroll_x: 0.0,
             scroll_y: 0.0,
             cursors: vec![
@@ -133,6 +529,102 @@ impl EditorState {
     }
 }
 
+/// Serialized form of a session
+#[derive(Debug, Serialize, Deserialize)]
+struct Session {
+    recent_files: Vec<PathBuf>,
+    theme: String,
+    windows: Vec<WindowSession>,
+}
+
+/// Serialized form of a window's workspace
+#[derive(Debug, Serialize, Deserialize)]
+struct WindowSession {
+    id: usize,
+    root: Option<PathBuf>,
+    editors: Vec<EditorSession>,
+    active_editor: Option<usize>,
+    sidebar_visible: bool,
+    panel_visible: bool,
+}
+
+impl WindowSession {
+    /// Split layouts are not saved: the editors of all panes are saved as
+    /// one list, in layout order and without repeating files.
+    fn from_window(window: &WindowState) -> Self {
+        let workspace = &window.workspace;
+        let active = workspace.active_pane();
+        let active_path = active.active_editor
+            .and_then(|index| active.editors.get(index))
+            .and_then(|editor| editor.file_path.clone());
+
+        let mut editors: Vec<EditorSession> = Vec::new();
+        let panes = workspace.panes();
+        for editor in panes.iter().flat_map(|pane| pane.editors.iter()) {
+            let Some(path) = editor.file_path.clone() else {
+                continue;
+            };
+            if editors.iter().any(|saved| saved.path == path) {
+                continue;
+            }
+            editors.push(EditorSession {
+                path,
+                scroll_x: editor.scroll_x,
+                scroll_y: editor.scroll_y,
+                cursors: editor.cursors.clone(),
+                selections: editor.selections.clone(),
+            });
+        }
+
+        // Editors without paths are skipped, so find the active one by path
+        let active_editor = active_path.and_then(|path| {
+            editors.iter().position(|editor| editor.path == path)
+        });
+
+        Self {
+            id: window.id,
+            root: workspace.root.clone(),
+            editors,
+            active_editor,
+            sidebar_visible: workspace.sidebar_visible,
+            panel_visible: workspace.panel_visible,
+        }
+    }
+
+    fn into_window(self, next_buffer_id: &mut u64) -> WindowState {
+        let mut window = WindowState::new(self.id);
+        let workspace = &mut window.workspace;
+        workspace.root = self.root;
+        workspace.sidebar_visible = self.sidebar_visible;
+        workspace.panel_visible = self.panel_visible;
+
+        let pane = workspace.active_pane_mut();
+        for editor in self.editors {
+            let mut state = EditorState::new(BufferId::new(*next_buffer_id));
+            *next_buffer_id += 1;
+            state.file_path = Some(editor.path);
+            state.scroll_x = editor.scroll_x;
+            state.scroll_y = editor.scroll_y;
+            state.cursors = editor.cursors;
+            state.selections = editor.selections;
+            pane.editors.push(state);
+        }
+        pane.active_editor = self.active_editor.filter(|index| *index < pane.editors.len());
+
+        window
+    }
+}
+
+/// Serialized form of an editor
+#[derive(Debug, Serialize, Deserialize)]
+struct EditorSession {
+    path: PathBuf,
+    scroll_x: f32,
+    scroll_y: f32,
+    cursors: Vec<editor_core::selection::Cursor>,
+    selections: editor_core::selection::SelectionSet,
+}
+
 /// Preview state
 pub struct PreviewState {
     /// Rendered document path
@@ -168,7 +660,11 @@ impl Default for PreviewState {
 }
 
 /// Global settings
+///
+/// Every field falls back to its default when absent from the settings file,
+/// and unknown keys are ignored so older versions can read newer files.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     /// Editor settings
     pub editor: EditorSettings,
@@ -176,6 +672,69 @@ pub struct Settings {
     pub preview: PreviewSettings,
     /// LSP settings
     pub lsp: LspSettings,
+    /// Editor settings overriding `editor` per language, keyed by language
+    /// name as shown in the status bar (e.g. `Typst`)
+    pub language_overrides: HashMap<String, EditorSettingsOverride>,
+}
+
+impl Settings {
+    /// Load settings from a TOML file, using defaults if it does not exist
+    pub fn load(path: &Path) -> Result<Settings> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(toml::from_str(&content)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Settings::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Save settings to a TOML file, creating its directory if needed
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(path, toml::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Editor settings for a language, with its overrides applied
+    ///
+    /// Language names match case-insensitively.
+    pub fn editor_for_language(&self, lang: &str) -> EditorSettings {
+        let overrides = self.language_overrides
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(lang))
+            .map(|(_, overrides)| overrides);
+
+        match overrides {
+            Some(overrides) => overrides.apply(&self.editor),
+            None => self.editor.clone(),
+        }
+    }
+
+    /// Settings file in the platform config directory
+    pub fn default_path() -> Option<PathBuf> {
+        config_dir().map(|dir| dir.join("typst-editor").join("settings.toml"))
+    }
+}
+
+/// Platform config directory
+///
+/// `%APPDATA%` on Windows, `~/Library/Application Support` on macOS and
+/// `$XDG_CONFIG_HOME` (or `~/.config`) elsewhere.
+fn config_dir() -> Option<PathBuf> {
+    let from_env = |name: &str| {
+        std::env::var_os(name)
+            .filter(|value| !value.is_empty())
+            .map(PathBuf::from)
+    };
+
+    if cfg!(target_os = "windows") {
+        from_env("APPDATA")
+    } else if cfg!(target_os = "macos") {
+        from_env("HOME").map(|home| home.join("Library").join("Application Support"))
+    } else {
+        from_env("XDG_CONFIG_HOME").or_else(|| from_env("HOME").map(|home| home.join(".config")))
+    }
 }
 
 impl Default for Settings {
@@ -184,12 +743,14 @@ impl Default for Settings {
             editor: EditorSettings::default(),
             preview: PreviewSettings::default(),
             lsp: LspSettings::default(),
+            language_overrides: HashMap::new(),
         }
     }
 }
 
 /// Editor settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct EditorSettings {
     pub font_family: String,
     pub font_size: f32,
@@ -214,8 +775,44 @@ impl Default for EditorSettings {
     }
 }
 
+/// Partial editor settings; fields left unset inherit the base settings
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EditorSettingsOverride {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_family: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub font_size: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line_height: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tab_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub insert_spaces: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub word_wrap: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub show_line_numbers: Option<bool>,
+}
+
+impl EditorSettingsOverride {
+    /// Apply the set fields on top of `base`
+    pub fn apply(&self, base: &EditorSettings) -> EditorSettings {
+        EditorSettings {
+            font_family: self.font_family.clone().unwrap_or_else(|| base.font_family.clone()),
+            font_size: self.font_size.unwrap_or(base.font_size),
+            line_height: self.line_height.unwrap_or(base.line_height),
+            tab_size: self.tab_size.unwrap_or(base.tab_size),
+            insert_spaces: self.insert_spaces.unwrap_or(base.insert_spaces),
+            word_wrap: self.word_wrap.unwrap_or(base.word_wrap),
+            show_line_numbers: self.show_line_numbers.unwrap_or(base.show_line_numbers),
+        }
+    }
+}
+
 /// Preview settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PreviewSettings {
     pub default_zoom: String,
     pub sync_scroll: bool,
@@ -234,6 +831,7 @@ impl Default for PreviewSettings {
 
 /// LSP settings
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct LspSettings {
     pub enable: bool,
     pub diagnostic_delay: u64,
@@ -249,3 +847,257 @@ impl Default for LspSettings {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_settings_take_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(
+            &path,
+            "[editor]\ntab_size = 4\nfuture_option = true\n\n[experimental]\nenabled = true\n"
+        ).unwrap();
+
+        let settings = Settings::load(&path).unwrap();
+        assert_eq!(settings.editor.tab_size, 4);
+        assert_eq!(settings.editor.font_size, EditorSettings::default().font_size);
+        assert!(settings.editor.insert_spaces);
+        assert_eq!(settings.preview.default_zoom, "fit_width");
+        assert_eq!(settings.lsp.diagnostic_delay, 500);
+    }
+
+    #[test]
+    fn test_settings_round_trip_and_missing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config").join("settings.toml");
+        assert_eq!(Settings::load(&path).unwrap().editor.tab_size, 2);
+
+        let mut settings = Settings::default();
+        settings.editor.word_wrap = true;
+        settings.lsp.enable = false;
+        settings.save(&path).unwrap();
+
+        let loaded = Settings::load(&path).unwrap();
+        assert!(loaded.editor.word_wrap);
+        assert!(!loaded.lsp.enable);
+    }
+
+    #[test]
+    fn test_language_override_inherits_base() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.toml");
+        std::fs::write(
+            &path,
+            "[editor]\ntab_size = 2\nfont_size = 16.0\n\n[language_overrides.Typst]\ntab_size = 4\n"
+        ).unwrap();
+
+        let settings = Settings::load(&path).unwrap();
+        let typst = settings.editor_for_language("typst");
+        assert_eq!(typst.tab_size, 4);
+        assert_eq!(typst.font_size, 16.0);
+        assert_eq!(typst.insert_spaces, settings.editor.insert_spaces);
+
+        let plain = settings.editor_for_language("Plain Text");
+        assert_eq!(plain.tab_size, 2);
+    }
+
+    #[test]
+    fn test_session_prunes_missing_recent_files() {
+        use editor_core::selection::{ Position, Selection, SelectionSet };
+
+        let dir = tempfile::tempdir().unwrap();
+        let kept = dir.path().join("kept.typ");
+        let deleted = dir.path().join("deleted.typ");
+        let session_path = dir.path().join("session.json");
+        std::fs::write(&kept, "= Kept").unwrap();
+        std::fs::write(&deleted, "= Deleted").unwrap();
+
+        let mut state = ApplicationState::with_settings(Settings::default());
+        state.add_recent_file(deleted.clone());
+        state.add_recent_file(kept.clone());
+        state.theme = "light".to_string();
+
+        let mut window = WindowState::new(0);
+        window.workspace.add_editor(EditorState::new(BufferId::new(7)));
+        let mut editor = EditorState::new(BufferId::new(8));
+        editor.file_path = Some(kept.clone());
+        editor.scroll_y = 240.0;
+        editor.selections = SelectionSet::new(
+            Selection::new(Position::new(2, 1), Position::new(3, 4))
+        );
+        window.workspace.add_editor(editor);
+        state.add_window(window);
+
+        state.save_session(&session_path).unwrap();
+        std::fs::remove_file(&deleted).unwrap();
+
+        let restored = ApplicationState::load_session(&session_path, Settings::default()).unwrap();
+        assert_eq!(restored.recent_files, vec![kept.clone()]);
+        assert_eq!(restored.theme, "light");
+
+        // The untitled editor is not saved
+        let pane = restored.windows[0].workspace.active_pane();
+        assert_eq!(pane.editors.len(), 1);
+        assert_eq!(pane.active_editor, Some(0));
+
+        let editor = &pane.editors[0];
+        assert_eq!(editor.file_path.as_ref(), Some(&kept));
+        assert_eq!(editor.scroll_y, 240.0);
+        assert_eq!(
+            editor.selections.primary().range(),
+            (Position::new(2, 1), Position::new(3, 4))
+        );
+    }
+
+    fn pane_with_tabs(count: u64) -> Pane {
+        let mut pane = Pane::new(PaneId(0));
+        for id in 1..=count {
+            pane.open_or_focus(BufferId::new(id));
+        }
+        pane
+    }
+
+    fn tab_ids(pane: &Pane) -> Vec<BufferId> {
+        pane.editors
+            .iter()
+            .map(|editor| editor.buffer_id)
+            .collect()
+    }
+
+    #[test]
+    fn test_open_or_focus_reuses_editors() {
+        let mut pane = pane_with_tabs(3);
+        assert_eq!(pane.active_editor, Some(2));
+
+        assert_eq!(pane.open_or_focus(BufferId::new(1)), 0);
+        assert_eq!(pane.editors.len(), 3);
+        assert_eq!(pane.active_editor, Some(0));
+
+        pane.prev_tab();
+        assert_eq!(pane.active_editor, Some(2));
+        pane.next_tab();
+        pane.next_tab();
+        assert_eq!(pane.active_editor, Some(1));
+    }
+
+    #[test]
+    fn test_closing_tabs_keeps_active_editor_in_bounds() {
+        let mut pane = pane_with_tabs(4);
+
+        // Closing the active middle tab focuses the tab to its right
+        pane.active_editor = Some(1);
+        let closed = pane.close_editor(1).unwrap();
+        assert_eq!(closed.buffer_id, BufferId::new(2));
+        assert_eq!(pane.active_editor, Some(1));
+        assert_eq!(pane.editors[1].buffer_id, BufferId::new(3));
+
+        // Closing a tab before the active one keeps the same editor active
+        pane.close_editor(0);
+        assert_eq!(pane.active_editor, Some(0));
+        assert_eq!(pane.editors[0].buffer_id, BufferId::new(3));
+
+        // Closing the active last tab focuses the one before it
+        pane.active_editor = Some(1);
+        pane.close_editor(1);
+        assert_eq!(pane.active_editor, Some(0));
+
+        assert!(pane.close_editor(5).is_none());
+        pane.close_editor(0);
+        assert!(pane.editors.is_empty());
+        assert_eq!(pane.active_editor, None);
+        pane.next_tab();
+        assert_eq!(pane.active_editor, None);
+    }
+
+    #[test]
+    fn test_move_tab_follows_active_editor() {
+        let mut pane = pane_with_tabs(4);
+        let ids = |ids: &[u64]| ids.iter().map(|id| BufferId::new(*id)).collect::<Vec<_>>();
+
+        // Moving the active tab
+        pane.active_editor = Some(0);
+        pane.move_tab(0, 2);
+        assert_eq!(tab_ids(&pane), ids(&[2, 3, 1, 4]));
+        assert_eq!(pane.active_editor, Some(2));
+
+        // Moving other tabs across the active one
+        pane.move_tab(3, 0);
+        assert_eq!(tab_ids(&pane), ids(&[4, 2, 3, 1]));
+        assert_eq!(pane.active_editor, Some(3));
+        pane.move_tab(0, 10);
+        assert_eq!(tab_ids(&pane), ids(&[2, 3, 1, 4]));
+        assert_eq!(pane.active_editor, Some(2));
+    }
+
+    #[test]
+    fn test_split_and_focus_panes() {
+        let mut workspace = WorkspaceState::new();
+        let left = workspace.active_pane;
+        workspace.open_or_focus(BufferId::new(1));
+        workspace.open_or_focus(BufferId::new(2));
+
+        // The new pane shows the active editor's buffer and takes focus
+        let right = workspace.split_active(Orientation::Horizontal);
+        assert_eq!(workspace.active_pane, right);
+        assert_eq!(tab_ids(workspace.active_pane()), vec![BufferId::new(2)]);
+        match &workspace.layout {
+            PaneNode::Split { orientation, children, ratio } => {
+                assert_eq!(*orientation, Orientation::Horizontal);
+                assert_eq!(children.len(), 2);
+                assert_eq!(*ratio, 0.5);
+            }
+            PaneNode::Pane(_) => panic!("expected a split"),
+        }
+
+        // Tabs are per pane
+        workspace.open_or_focus(BufferId::new(3));
+        assert!(workspace.focus_pane(left));
+        assert_eq!(tab_ids(workspace.active_pane()), vec![BufferId::new(1), BufferId::new(2)]);
+        assert_eq!(workspace.active_pane().active_editor, Some(1));
+        assert_eq!(workspace.editors_mut().len(), 4);
+
+        assert!(!workspace.focus_pane(PaneId(99)));
+        assert_eq!(workspace.active_pane, left);
+
+        // Ratios stay within bounds
+        let root_ratio = |workspace: &WorkspaceState| match workspace.layout {
+            PaneNode::Split { ratio, .. } => ratio,
+            PaneNode::Pane(_) => panic!("expected a split"),
+        };
+        assert!(workspace.set_split_ratio(right, 0.95));
+        assert_eq!(root_ratio(&workspace), MAX_SPLIT_RATIO);
+        workspace.set_split_ratio(left, 0.0);
+        assert_eq!(root_ratio(&workspace), MIN_SPLIT_RATIO);
+    }
+
+    #[test]
+    fn test_closing_last_editor_collapses_pane() {
+        let mut workspace = WorkspaceState::new();
+        let top = workspace.active_pane;
+        workspace.open_or_focus(BufferId::new(1));
+        let right = workspace.split_active(Orientation::Horizontal);
+        let bottom_right = workspace.split_active(Orientation::Vertical);
+        assert_eq!(workspace.panes().len(), 3);
+        assert!(!workspace.set_split_ratio(PaneId(99), 0.3));
+
+        // The emptied pane's sibling takes its place and focus
+        workspace.close_editor(0);
+        assert_eq!(workspace.panes().len(), 2);
+        assert!(workspace.pane(bottom_right).is_none());
+        assert_eq!(workspace.active_pane, right);
+
+        workspace.close_editor(0);
+        assert_eq!(workspace.panes().len(), 1);
+        assert!(matches!(workspace.layout, PaneNode::Pane(_)));
+        assert_eq!(workspace.active_pane, top);
+
+        // The last pane stays, even without editors
+        workspace.close_editor(0);
+        assert_eq!(workspace.panes().len(), 1);
+        assert!(workspace.active_pane().editors.is_empty());
+        assert_eq!(workspace.active_pane().active_editor, None);
+    }
+}
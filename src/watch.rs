@@ -0,0 +1,322 @@
+//! Watching open files for changes made outside the editor
+
+use notify::{ Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher };
+use std::collections::{ HashMap, HashSet };
+use std::path::{ Path, PathBuf };
+use std::sync::mpsc::{ self, Receiver, RecvTimeoutError, Sender };
+use std::sync::{ Arc, Mutex };
+use std::thread;
+use std::time::{ Duration, Instant, SystemTime };
+
+/// How long a file must stay quiet before its change is reported
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A watched file changed on disk
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    Modified(PathBuf),
+    Removed(PathBuf),
+}
+
+/// Size and modification time of a file, to recognize our own saves
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: SystemTime,
+    len: u64,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<Self> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(Self { modified: metadata.modified().ok()?, len: metadata.len() })
+    }
+}
+
+/// Watches files and reports debounced changes over a channel
+///
+/// Parent directories are watched rather than the files themselves, so
+/// editors that save by writing a new file and renaming it over the old one
+/// are still noticed. Bursts of events for a file are reported once, after
+/// the file has been quiet for the debounce delay, as `Removed` if it no
+/// longer exists and `Modified` otherwise.
+pub struct FileWatcher {
+    watcher: RecommendedWatcher,
+    /// Watched files, shared with the debounce thread
+    files: Arc<Mutex<HashSet<PathBuf>>>,
+    /// Watched directories and how many watched files each holds
+    dirs: HashMap<PathBuf, usize>,
+    /// Stamps of files as the editor last saved them
+    saved: Arc<Mutex<HashMap<PathBuf, FileStamp>>>,
+    events: Receiver<WatchEvent>,
+}
+
+impl FileWatcher {
+    pub fn new(debounce: Duration) -> notify::Result<Self> {
+        let (raw_tx, raw_rx) = mpsc::channel();
+        let watcher = notify::recommended_watcher(move |result: notify::Result<Event>| {
+            match result {
+                Ok(event) => {
+                    let _ = raw_tx.send(event);
+                }
+                Err(err) => tracing::warn!("File watcher error: {}", err),
+            }
+        })?;
+
+        let files = Arc::new(Mutex::new(HashSet::new()));
+        let saved = Arc::new(Mutex::new(HashMap::new()));
+        let (tx, events) = mpsc::channel();
+        let debouncer = Debouncer {
+            delay: debounce,
+            files: files.clone(),
+            saved: saved.clone(),
+            pending: HashMap::new(),
+        };
+        thread::Builder
+            ::new()
+            .name("file-watcher".to_string())
+            .spawn(move || debouncer.run(raw_rx, tx))
+            .map_err(notify::Error::io)?;
+
+        Ok(Self {
+            watcher,
+            files,
+            dirs: HashMap::new(),
+            saved,
+            events,
+        })
+    }
+
+    /// Start watching a file; watching it again does nothing
+    pub fn watch(&mut self, path: &Path) -> notify::Result<()> {
+        let path = normalize(path);
+        let Some(dir) = path.parent().map(Path::to_path_buf) else {
+            return Ok(());
+        };
+        if !self.files.lock().unwrap().insert(path.clone()) {
+            return Ok(());
+        }
+
+        let count = self.dirs.entry(dir.clone()).or_insert(0);
+        if *count == 0 {
+            if let Err(err) = self.watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                self.dirs.remove(&dir);
+                self.files.lock().unwrap().remove(&path);
+                return Err(err);
+            }
+        }
+        *count += 1;
+        Ok(())
+    }
+
+    /// Stop watching a file
+    pub fn unwatch(&mut self, path: &Path) -> notify::Result<()> {
+        let path = normalize(path);
+        if !self.files.lock().unwrap().remove(&path) {
+            return Ok(());
+        }
+        self.saved.lock().unwrap().remove(&path);
+
+        let Some(dir) = path.parent() else {
+            return Ok(());
+        };
+        if let Some(count) = self.dirs.get_mut(dir) {
+            *count -= 1;
+            if *count == 0 {
+                self.dirs.remove(dir);
+                self.watcher.unwatch(dir)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check if a file is watched
+    pub fn is_watching(&self, path: &Path) -> bool {
+        self.files.lock().unwrap().contains(&normalize(path))
+    }
+
+    /// Record that the editor just wrote a file
+    ///
+    /// Changes are not reported while the file still has the size and
+    /// modification time it had when this was called.
+    pub fn mark_saved(&self, path: &Path) {
+        let path = normalize(path);
+        if let Some(stamp) = FileStamp::of(&path) {
+            self.saved.lock().unwrap().insert(path, stamp);
+        }
+    }
+
+    /// Debounced change events
+    pub fn events(&self) -> &Receiver<WatchEvent> {
+        &self.events
+    }
+}
+
+/// Absolute form of a path as the watcher reports it
+///
+/// The parent directory is canonicalized, since that is what gets watched;
+/// the file itself may not exist.
+fn normalize(path: &Path) -> PathBuf {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    let parent = if parent.as_os_str().is_empty() { Path::new(".") } else { parent };
+    parent
+        .canonicalize()
+        .unwrap_or_else(|_| parent.to_path_buf())
+        .join(name)
+}
+
+/// Collects raw events and reports each file once it has been quiet
+struct Debouncer {
+    delay: Duration,
+    files: Arc<Mutex<HashSet<PathBuf>>>,
+    saved: Arc<Mutex<HashMap<PathBuf, FileStamp>>>,
+    /// Files with unreported events and when to report them
+    pending: HashMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    /// Run until the watcher or the event receiver is dropped
+    fn run(mut self, raw: Receiver<Event>, tx: Sender<WatchEvent>) {
+        loop {
+            let now = Instant::now();
+            let timeout = self.pending
+                .values()
+                .min()
+                .map_or(Duration::from_secs(60), |due| due.saturating_duration_since(now));
+
+            match raw.recv_timeout(timeout) {
+                Ok(event) => self.record(event, Instant::now()),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => {
+                    return;
+                }
+            }
+
+            for event in self.take_due(Instant::now()) {
+                if tx.send(event).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Note an event that happened at `now`, delaying its file's report
+    fn record(&mut self, event: Event, now: Instant) {
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+
+        let files = self.files.lock().unwrap();
+        let due = now + self.delay;
+        for path in event.paths {
+            if files.contains(&path) {
+                self.pending.insert(path, due);
+            }
+        }
+    }
+
+    /// Events for files that have been quiet long enough at `now`
+    fn take_due(&mut self, now: Instant) -> Vec<WatchEvent> {
+        let due: Vec<PathBuf> = self.pending
+            .iter()
+            .filter(|(_, at)| **at <= now)
+            .map(|(path, _)| path.clone())
+            .collect();
+
+        let files = self.files.lock().unwrap();
+        let saved = self.saved.lock().unwrap();
+        due.into_iter()
+            .filter_map(|path| {
+                self.pending.remove(&path);
+                if !files.contains(&path) {
+                    return None;
+                }
+                match FileStamp::of(&path) {
+                    None => Some(WatchEvent::Removed(path)),
+                    Some(stamp) if saved.get(&path) == Some(&stamp) => None,
+                    Some(_) => Some(WatchEvent::Modified(path)),
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        std::fs::write(&path, "= Draft\n").unwrap();
+        path
+    }
+
+    #[test]
+    fn test_debouncer_reports_once_quiet() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = normalize(&temp_file(dir.path(), "draft.typ"));
+        let mut debouncer = Debouncer {
+            delay: Duration::from_millis(100),
+            files: Arc::new(Mutex::new(HashSet::from([path.clone()]))),
+            saved: Arc::default(),
+            pending: HashMap::new(),
+        };
+        let modified = |path: &Path| Event::new(EventKind::Any).add_path(path.to_path_buf());
+        let start = Instant::now();
+        let after = |millis: u64| start + Duration::from_millis(millis);
+
+        // Each event in a burst pushes the report back
+        for step in 0..3 {
+            debouncer.record(modified(&path), after(20 * step));
+        }
+        debouncer.record(modified(&dir.path().join("unwatched.typ")), after(40));
+        assert!(debouncer.take_due(after(139)).is_empty());
+        assert_eq!(debouncer.take_due(after(140)), vec![WatchEvent::Modified(path.clone())]);
+        assert!(debouncer.take_due(after(1000)).is_empty());
+
+        std::fs::remove_file(&path).unwrap();
+        debouncer.record(modified(&path), after(1000));
+        assert_eq!(debouncer.take_due(after(1100)), vec![WatchEvent::Removed(path)]);
+    }
+
+    #[test]
+    fn test_external_writes_are_debounced() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = temp_file(dir.path(), "external.typ");
+        // Much longer than the burst of writes, so they are reported once
+        let mut watcher = FileWatcher::new(Duration::from_millis(500)).unwrap();
+        watcher.watch(&path).unwrap();
+        assert!(watcher.is_watching(&path));
+
+        // An editor saving in several steps
+        for step in 0..3 {
+            std::fs::write(&path, format!("= Draft {}\n", step)).unwrap();
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        let event = watcher.events().recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, WatchEvent::Modified(normalize(&path)));
+        assert!(watcher.events().recv_timeout(Duration::from_millis(300)).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+        let event = watcher.events().recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(event, WatchEvent::Removed(normalize(&path)));
+    }
+
+    #[test]
+    fn test_own_saves_are_not_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = temp_file(dir.path(), "own.typ");
+        let mut watcher = FileWatcher::new(Duration::from_millis(100)).unwrap();
+        watcher.watch(&path).unwrap();
+
+        std::fs::write(&path, "= Saved by us\n").unwrap();
+        watcher.mark_saved(&path);
+        assert!(watcher.events().recv_timeout(Duration::from_millis(500)).is_err());
+
+        watcher.unwatch(&path).unwrap();
+        std::fs::write(&path, "= Changed after closing\n").unwrap();
+        assert!(watcher.events().recv_timeout(Duration::from_millis(500)).is_err());
+    }
+}
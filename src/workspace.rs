@@ -0,0 +1,529 @@
+//! Finding files in the project and searching their contents
+
+use ignore::{ WalkBuilder, WalkState };
+use regex::{ Regex, RegexBuilder };
+use std::ops::Range;
+use std::path::{ Path, PathBuf };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::sync::mpsc::{ self, Receiver };
+use std::sync::Arc;
+use std::thread;
+use ui_components::commands::fuzzy_score;
+
+/// How much more a match in the file name counts than one in the full path
+const FILE_NAME_WEIGHT: i32 = 2;
+/// How many leading bytes are checked for NUL to detect binary files
+const BINARY_CHECK_LEN: usize = 8192;
+
+/// A file matching a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScoredPath {
+    /// Path relative to the project root
+    pub path: PathBuf,
+    pub score: i32,
+}
+
+/// Fuzzy finder over the files of a project
+///
+/// The file list is collected once, skipping files excluded by `.gitignore`
+/// and similar ignore files, and kept until `refresh`. When a query extends
+/// the previous one, only the files that matched before are scored again.
+pub struct FileFinder {
+    root: PathBuf,
+    /// Paths relative to the root, with `/` separators, sorted
+    files: Vec<String>,
+    /// Last query and the indices of the files it matched
+    last: Option<(String, Vec<usize>)>,
+}
+
+impl FileFinder {
+    /// Collect the files under `root`
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        let mut finder = Self {
+            root: root.into(),
+            files: Vec::new(),
+            last: None,
+        };
+        finder.refresh();
+        finder
+    }
+
+    /// Collect the file list again, e.g. after files were created
+    pub fn refresh(&mut self) {
+        let walker = WalkBuilder::new(&self.root)
+            // Honor .gitignore even outside of git repositories
+            .require_git(false)
+            .build();
+
+        self.files = walker
+            .flatten()
+            .filter(|entry| entry.file_type().is_some_and(|ty| ty.is_file()))
+            .filter_map(|entry| {
+                let relative = entry.path().strip_prefix(&self.root).ok()?;
+                Some(relative.to_string_lossy().replace('\\', "/"))
+            })
+            .collect();
+        self.files.sort();
+        self.last = None;
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Get number of files found
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Check if no files were found
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Files matching `query`, best match first
+    ///
+    /// Matches in the file name outweigh matches in its directories. Ties
+    /// are broken by shorter, then alphabetically earlier paths.
+    pub fn search(&mut self, query: &str) -> Vec<ScoredPath> {
+        // A file matching a query also matches every prefix of it
+        let candidates: Vec<usize> = match self.last.take() {
+            Some((last, matched)) if query.starts_with(last.as_str()) => matched,
+            _ => (0..self.files.len()).collect(),
+        };
+
+        let mut scored: Vec<(i32, usize)> = candidates
+            .into_iter()
+            .filter_map(|index| score_path(query, &self.files[index]).map(|score| (score, index)))
+            .collect();
+        self.last = Some((
+            query.to_string(),
+            scored
+                .iter()
+                .map(|(_, index)| *index)
+                .collect(),
+        ));
+
+        scored.sort_by(|(a_score, a), (b_score, b)| {
+            b_score
+                .cmp(a_score)
+                .then_with(|| self.files[*a].len().cmp(&self.files[*b].len()))
+                .then_with(|| a.cmp(b))
+        });
+        scored
+            .into_iter()
+            .map(|(score, index)| ScoredPath { path: PathBuf::from(&self.files[index]), score })
+            .collect()
+    }
+}
+
+/// Score of a relative path, weighting matches in its file name
+fn score_path(query: &str, path: &str) -> Option<i32> {
+    let path_score = fuzzy_score(query, path)?;
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let name_score = fuzzy_score(query, name).unwrap_or(0);
+    Some(path_score + name_score * FILE_NAME_WEIGHT)
+}
+
+/// How a project search matches text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub case_sensitive: bool,
+    /// Only match whole words
+    pub whole_word: bool,
+    /// Treat the pattern as a regular expression rather than literal text
+    pub regex: bool,
+    /// Lines of context kept before and after each matching line
+    pub context_lines: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self {
+            case_sensitive: false,
+            whole_word: false,
+            regex: false,
+            context_lines: 2,
+        }
+    }
+}
+
+/// A line containing matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMatch {
+    /// Zero-based line number
+    pub line: usize,
+    pub text: String,
+    /// Byte ranges of the matches within `text`
+    pub ranges: Vec<Range<usize>>,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// Matches in one file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub matches: Vec<LineMatch>,
+}
+
+/// Matches of a finished project search
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchResults {
+    /// Files with matches, sorted by path
+    pub files: Vec<FileMatches>,
+    /// Files that could not be searched because they are unreadable or not
+    /// UTF-8, sorted
+    pub skipped: Vec<PathBuf>,
+}
+
+/// Outcome of scanning one file
+enum FileScan {
+    Matches(FileMatches),
+    /// No matches, or a binary file
+    Nothing,
+    /// The file could not be read as UTF-8 text
+    Skipped(PathBuf),
+}
+
+/// A search of the files under a directory
+///
+/// Files are scanned in parallel on background threads, skipping ignored
+/// and binary files. Iterating yields each file with matches as soon as it
+/// has been scanned, in no particular order; files that could not be read
+/// are collected in [`ProjectSearch::skipped`]. Dropping the search cancels
+/// it.
+pub struct ProjectSearch {
+    results: Receiver<FileScan>,
+    cancelled: Arc<AtomicBool>,
+    skipped: Vec<PathBuf>,
+}
+
+impl ProjectSearch {
+    /// Search the files under `root` and collect all matches
+    pub fn search(
+        root: &Path,
+        pattern: &str,
+        opts: SearchOptions
+    ) -> Result<SearchResults, regex::Error> {
+        let mut search = Self::start(root, pattern, opts)?;
+        let mut files: Vec<FileMatches> = search.by_ref().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let mut skipped = std::mem::take(&mut search.skipped);
+        skipped.sort();
+        Ok(SearchResults { files, skipped })
+    }
+
+    /// Start searching the files under `root`
+    ///
+    /// Fails if `pattern` is an invalid regular expression.
+    pub fn start(root: &Path, pattern: &str, opts: SearchOptions) -> Result<Self, regex::Error> {
+        let regex = search_regex(pattern, &opts)?;
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let (tx, results) = mpsc::channel();
+
+        let walker = WalkBuilder::new(root).require_git(false).build_parallel();
+        let flag = cancelled.clone();
+        let context_lines = opts.context_lines;
+        thread::spawn(move || {
+            walker.run(|| {
+                let tx = tx.clone();
+                let regex = regex.clone();
+                let flag = flag.clone();
+                Box::new(move |entry| {
+                    if flag.load(Ordering::Relaxed) {
+                        return WalkState::Quit;
+                    }
+                    let Ok(entry) = entry else {
+                        return WalkState::Continue;
+                    };
+                    if !entry.file_type().is_some_and(|ty| ty.is_file()) {
+                        return WalkState::Continue;
+                    }
+                    let scan = search_file(entry.path(), &regex, context_lines, &flag);
+                    if matches!(scan, FileScan::Nothing) {
+                        return WalkState::Continue;
+                    }
+                    // The receiver is gone once the search is dropped
+                    if tx.send(scan).is_err() { WalkState::Quit } else { WalkState::Continue }
+                })
+            });
+        });
+
+        Ok(Self { results, cancelled, skipped: Vec::new() })
+    }
+
+    /// Stop the search; files not scanned yet are skipped
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Flag that cancels the search when set, for use from other threads
+    pub fn cancel_flag(&self) -> Arc<AtomicBool> {
+        self.cancelled.clone()
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Files found so far that could not be read as UTF-8 text
+    pub fn skipped(&self) -> &[PathBuf] {
+        &self.skipped
+    }
+}
+
+impl Iterator for ProjectSearch {
+    type Item = FileMatches;
+
+    fn next(&mut self) -> Option<FileMatches> {
+        loop {
+            if self.is_cancelled() {
+                return None;
+            }
+            match self.results.recv().ok()? {
+                FileScan::Matches(found) => {
+                    return Some(found);
+                }
+                FileScan::Skipped(path) => self.skipped.push(path),
+                FileScan::Nothing => {}
+            }
+        }
+    }
+}
+
+impl Drop for ProjectSearch {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+fn search_regex(pattern: &str, opts: &SearchOptions) -> Result<Regex, regex::Error> {
+    let pattern = if opts.regex { pattern.to_string() } else { regex::escape(pattern) };
+    let pattern = if opts.whole_word { format!(r"\b(?:{})\b", pattern) } else { pattern };
+    RegexBuilder::new(&pattern).case_insensitive(!opts.case_sensitive).build()
+}
+
+/// Scan one file for matches
+///
+/// The cancellation flag is checked on every line, so a cancelled search
+/// stops within large files too.
+fn search_file(
+    path: &Path,
+    regex: &Regex,
+    context_lines: usize,
+    cancelled: &AtomicBool
+) -> FileScan {
+    let Ok(bytes) = std::fs::read(path) else {
+        return FileScan::Skipped(path.to_path_buf());
+    };
+    if bytes[..bytes.len().min(BINARY_CHECK_LEN)].contains(&0) {
+        return FileScan::Nothing;
+    }
+    let Ok(text) = String::from_utf8(bytes) else {
+        return FileScan::Skipped(path.to_path_buf());
+    };
+    let lines: Vec<&str> = text.lines().collect();
+
+    let context = |range: Range<usize>| -> Vec<String> {
+        lines[range]
+            .iter()
+            .map(|line| line.to_string())
+            .collect()
+    };
+    let matches: Vec<LineMatch> = lines
+        .iter()
+        .enumerate()
+        .take_while(|_| !cancelled.load(Ordering::Relaxed))
+        .filter_map(|(index, line)| {
+            let ranges: Vec<Range<usize>> = regex
+                .find_iter(line)
+                .map(|found| found.range())
+                .filter(|range| !range.is_empty())
+                .collect();
+            if ranges.is_empty() {
+                return None;
+            }
+            Some(LineMatch {
+                line: index,
+                text: line.to_string(),
+                ranges,
+                before: context(index.saturating_sub(context_lines)..index),
+                after: context(index + 1..(index + 1 + context_lines).min(lines.len())),
+            })
+        })
+        .collect();
+
+    if matches.is_empty() || cancelled.load(Ordering::Relaxed) {
+        FileScan::Nothing
+    } else {
+        FileScan::Matches(FileMatches { path: path.to_path_buf(), matches })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        let files = [
+            ".gitignore",
+            "main.typ",
+            "chapters/intro.typ",
+            "chapters/methods.typ",
+            "intro/notes.typ",
+            "build/main.pdf",
+            "debug.log",
+        ];
+        for file in files {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, "").unwrap();
+        }
+        std::fs::write(root.join(".gitignore"), "build/\n*.log\n").unwrap();
+        dir
+    }
+
+    fn paths(results: &[ScoredPath]) -> Vec<&str> {
+        results
+            .iter()
+            .map(|result| result.path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_search_prefers_file_names() {
+        let dir = project();
+        let root = dir.path();
+        let mut finder = FileFinder::new(root);
+
+        // Matching the file name beats matching a directory
+        let results = finder.search("intro");
+        assert_eq!(paths(&results), vec!["chapters/intro.typ", "intro/notes.typ"]);
+
+        let results = finder.search("meth");
+        assert_eq!(paths(&results)[0], "chapters/methods.typ");
+
+        // Growing and shrinking the query gives the same results as a fresh search
+        for query in ["m", "ma", "mai", "main", "ma"] {
+            let fresh = FileFinder::new(root).search(query);
+            assert_eq!(finder.search(query), fresh, "query {:?}", query);
+        }
+    }
+
+    #[test]
+    fn test_gitignored_files_are_excluded() {
+        let dir = project();
+        let root = dir.path();
+        let mut finder = FileFinder::new(root);
+
+        assert!(finder.search("debug").is_empty());
+        assert_eq!(paths(&finder.search("main")), vec!["main.typ"]);
+        assert_eq!(finder.len(), 4);
+
+        std::fs::write(root.join("appendix.typ"), "").unwrap();
+        assert!(finder.search("appendix").is_empty());
+        finder.refresh();
+        assert_eq!(paths(&finder.search("appendix")), vec!["appendix.typ"]);
+    }
+
+    fn search_tree() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        std::fs::create_dir_all(root.join("chapters")).unwrap();
+        std::fs::write(
+            root.join("main.typ"),
+            "= Title\nHello world\n#include \"chapters/intro.typ\"\nhello again\n"
+        ).unwrap();
+        std::fs::write(root.join("chapters/intro.typ"), "Othello\r\nsays HELLO\r\n").unwrap();
+        std::fs::write(root.join("logo.bin"), b"hello\0\x89PNG").unwrap();
+        std::fs::write(root.join("latin1.typ"), b"caf\xe9 hello\n").unwrap();
+        dir
+    }
+
+    fn lines(results: &[FileMatches], file: &str) -> Vec<usize> {
+        results
+            .iter()
+            .find(|result| result.path.ends_with(file))
+            .map(|result| {
+                result.matches
+                    .iter()
+                    .map(|found| found.line)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn test_project_search_options() {
+        let dir = search_tree();
+        let root = dir.path();
+
+        let results = ProjectSearch::search(root, "hello", SearchOptions::default()).unwrap();
+        assert_eq!(results.skipped, vec![root.join("latin1.typ")]);
+        let results = results.files;
+        assert_eq!(results.len(), 2, "binary files are skipped");
+        assert_eq!(lines(&results, "main.typ"), vec![1, 3]);
+        assert_eq!(lines(&results, "intro.typ"), vec![0, 1]);
+
+        // Ranges and context lines, with line endings stripped
+        let intro = results
+            .iter()
+            .find(|result| result.path.ends_with("intro.typ"))
+            .unwrap();
+        assert_eq!(intro.matches[0].ranges, vec![2..7]);
+        assert_eq!(intro.matches[0].after, vec!["says HELLO"]);
+        let main = results
+            .iter()
+            .find(|result| result.path.ends_with("main.typ"))
+            .unwrap();
+        assert_eq!(main.matches[0].before, vec!["= Title"]);
+        assert_eq!(main.matches[0].after, vec!["#include \"chapters/intro.typ\"", "hello again"]);
+
+        let opts = SearchOptions { whole_word: true, ..Default::default() };
+        let results = ProjectSearch::search(root, "hello", opts).unwrap().files;
+        assert_eq!(lines(&results, "intro.typ"), vec![1]);
+
+        let opts = SearchOptions { case_sensitive: true, ..Default::default() };
+        let results = ProjectSearch::search(root, "Hello", opts).unwrap().files;
+        assert_eq!(lines(&results, "main.typ"), vec![1]);
+        assert!(lines(&results, "intro.typ").is_empty());
+
+        // Literal patterns are escaped; regex patterns are not
+        let results = ProjectSearch::search(root, "h.llo", SearchOptions::default()).unwrap().files;
+        assert!(results.is_empty());
+        let opts = SearchOptions { regex: true, context_lines: 0, ..Default::default() };
+        let results = ProjectSearch::search(root, r"h.llo\s+\w+", opts.clone()).unwrap().files;
+        assert_eq!(lines(&results, "main.typ"), vec![1, 3]);
+        assert!(results[0].matches[0].before.is_empty());
+        assert!(ProjectSearch::search(root, "(", opts).is_err());
+    }
+
+    #[test]
+    fn test_cancelled_search_stops_streaming() {
+        let dir = search_tree();
+        let opts = SearchOptions::default();
+        let mut search = ProjectSearch::start(dir.path(), "hello", opts).unwrap();
+        assert!(search.next().is_some());
+
+        let flag = search.cancel_flag();
+        flag.store(true, Ordering::Relaxed);
+        assert!(search.is_cancelled());
+        assert!(search.next().is_none());
+    }
+
+    #[test]
+    fn test_cancellation_stops_within_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("long.typ");
+        std::fs::write(&path, "hello\n".repeat(1000)).unwrap();
+        let regex = search_regex("hello", &SearchOptions::default()).unwrap();
+
+        let scan = search_file(&path, &regex, 0, &AtomicBool::new(false));
+        assert!(matches!(scan, FileScan::Matches(found) if found.matches.len() == 1000));
+        let scan = search_file(&path, &regex, 0, &AtomicBool::new(true));
+        assert!(matches!(scan, FileScan::Nothing));
+    }
+}